@@ -0,0 +1,41 @@
+//! 构建脚本
+//!
+//! 生成 src 下全部源文件的列表（`klog` 的测试用它检查串口宏只在允许的模块中使用），
+//! 新增模块不需要手动登记
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let src = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("src");
+    let mut files = Vec::new();
+    collect(&src, "", &mut files);
+    files.sort();
+
+    // 每项是 (相对 src 的路径, include_str!(绝对路径))
+    let mut list = String::from("&[\n");
+    for name in &files {
+        let path = src.join(name);
+        list += &format!("    ({:?}, include_str!({:?})),\n", name, path.display().to_string());
+    }
+    list += "]\n";
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("kernel_sources.rs");
+    fs::write(out, list).unwrap();
+    println!("cargo:rerun-if-changed=src");
+}
+
+/// 收集 `dir` 下的 .rs 文件（递归），路径用 `/` 分隔
+fn collect(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.file_name().into_string().unwrap();
+        let path = entry.path();
+        if path.is_dir() {
+            collect(&path, &format!("{}{}/", prefix, name), files);
+        } else if name.ends_with(".rs") {
+            files.push(format!("{}{}", prefix, name));
+        }
+    }
+}
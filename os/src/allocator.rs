@@ -7,7 +7,7 @@
  *
  * 堆配置：
  * - 起始地址：0x8040_0000（物理内存中的某个位置）
- * - 大小：1 MB
+ * - 大小：1 MB（grow_heap 可以在运行时扩展）
  * ============================================
  */
 
@@ -28,8 +28,11 @@ pub const HEAP_SIZE: usize = 1024 * 1024;
 pub mod bump;
 pub mod linked_list;
 pub mod fixed_size_block;
+pub mod slab;
 
-use fixed_size_block::FixedSizeBlockAllocator;
+use core::sync::atomic::{AtomicBool, Ordering};
+use fixed_size_block::{FixedSizeBlockAllocator, BLOCK_SIZES};
+pub use fixed_size_block::HeapStats;
 
 /// 互斥锁包装器
 pub struct Locked<A> {
@@ -48,11 +51,34 @@ impl<A> Locked<A> {
     }
 }
 
+/// 固定大小块分配器的各大小类（从小到大，单位字节）
+///
+/// # 说明
+/// 分配的大小（和对齐）向上取整到第一个不小于它的大小类；
+/// 超过最大一类的分配由后备分配器处理
+pub fn block_sizes() -> &'static [usize] {
+    BLOCK_SIZES
+}
+
 /// 全局分配器实例
 #[global_allocator]
 static ALLOCATOR: Locked<FixedSizeBlockAllocator> =
     Locked::new(FixedSizeBlockAllocator::new());
 
+/// 全局分配器是否已经初始化（`init_heap_simple` / `init_heap` 设置）
+static HEAP_READY: AtomicBool = AtomicBool::new(false);
+
+/// 堆是否可以分配
+pub fn heap_ready() -> bool {
+    HEAP_READY.load(Ordering::Acquire)
+}
+
+/// 持有全局分配器的锁（测试用：持有期间的任何堆分配都会死锁）
+#[cfg(test)]
+pub(crate) fn hold_lock() -> spin::MutexGuard<'static, FixedSizeBlockAllocator> {
+    ALLOCATOR.lock()
+}
+
 /// 对齐地址到指定边界
 ///
 /// # 参数
@@ -61,10 +87,22 @@ static ALLOCATOR: Locked<FixedSizeBlockAllocator> =
 ///
 /// # 返回
 /// 对齐后的地址
-fn align_up(addr: usize, align: usize) -> usize {
+pub(crate) fn align_up(addr: usize, align: usize) -> usize {
     (addr + align - 1) & !(align - 1)
 }
 
+/// 简单堆占用的物理地址范围
+///
+/// # 说明
+/// 堆位于内核结束地址之后（对齐到 4KB），大小为 `HEAP_SIZE`
+///
+/// # 参数
+/// - `kernel_end_addr`: 内核结束地址
+pub fn simple_heap_range(kernel_end_addr: usize) -> core::ops::Range<usize> {
+    let heap_start = align_up(kernel_end_addr, 4096);
+    heap_start..heap_start + HEAP_SIZE
+}
+
 /// 初始化堆分配器（简单版本，不需要虚拟内存）
 ///
 /// # 功能
@@ -76,55 +114,43 @@ fn align_up(addr: usize, align: usize) -> usize {
 pub fn init_heap_simple(
     kernel_end_addr: usize,
 ) -> Result<(), &'static str> {
-    use crate::serial_println;
+    use crate::klog;
 
     // 将堆起始地址设置为内核结束地址之后，对齐到 4KB
-    let heap_start = align_up(kernel_end_addr, 4096);
+    let heap_start = simple_heap_range(kernel_end_addr).start;
 
-    serial_println!("[ALLOCATOR] Initializing heap at {:#x}", heap_start);
-    serial_println!("[ALLOCATOR] Heap size: {} bytes", HEAP_SIZE);
+    klog!("[ALLOCATOR] Initializing heap at {:#x}", heap_start);
+    klog!("[ALLOCATOR] Heap size: {} bytes", HEAP_SIZE);
 
     // 初始化分配器
     unsafe {
         ALLOCATOR.lock().init(heap_start, HEAP_SIZE);
     }
+    HEAP_READY.store(true, Ordering::Release);
 
-    serial_println!("[ALLOCATOR] Heap initialized successfully");
+    klog!("[ALLOCATOR] Heap initialized successfully");
     Ok(())
 }
 
-/// 初始化堆分配器（完整版本，需要虚拟内存）
+/// 初始化堆分配器（完整版本）
 ///
 /// # 功能
 /// - 为堆区域分配物理帧
-/// - 映射堆区域到虚拟地址空间
 /// - 初始化全局分配器
 ///
 /// # 参数
 /// - `frame_allocator`: 物理帧分配器
-///
-/// # 注意
-/// 此函数需要虚拟内存模块支持，当前已禁用
-#[allow(dead_code)]
-pub fn init_heap(
-    #[allow(unused_variables)] frame_allocator: &mut (),
-) -> Result<(), &'static str> {
-    Err("Virtual memory not implemented")
-}
-
-/*
-// 原始的 init_heap 实现（需要虚拟内存）
 pub fn init_heap(
     frame_allocator: &mut crate::memory::SimpleFrameAllocator,
 ) -> Result<(), &'static str> {
-    use crate::{serial_println, memory::PAGE_SIZE};
+    use crate::{klog, memory::PAGE_SIZE};
 
-    serial_println!("[ALLOCATOR] Initializing heap at {:#x}", HEAP_START);
-    serial_println!("[ALLOCATOR] Heap size: {} bytes", HEAP_SIZE);
+    klog!("[ALLOCATOR] Initializing heap at {:#x}", HEAP_START);
+    klog!("[ALLOCATOR] Heap size: {} bytes", HEAP_SIZE);
 
     // 计算需要的页数
     let page_count = (HEAP_SIZE + PAGE_SIZE - 1) / PAGE_SIZE;
-    serial_println!("[ALLOCATOR] Allocating {} pages for heap", page_count);
+    klog!("[ALLOCATOR] Allocating {} pages for heap", page_count);
 
     // 分配物理帧
     for _i in 0..page_count {
@@ -144,11 +170,216 @@ pub fn init_heap(
     unsafe {
         ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
     }
+    HEAP_READY.store(true, Ordering::Release);
 
-    serial_println!("[ALLOCATOR] Heap initialized successfully");
+    klog!("[ALLOCATOR] Heap initialized successfully");
     Ok(())
 }
-*/
+
+// ============================================
+// 扩展堆
+// ============================================
+
+/// 扩展内核堆
+///
+/// # 参数
+/// - `additional_bytes`: 增加的字节数（向上取整到页）
+/// - `frame_allocator`: 物理帧分配器（分配物理连续的页帧）
+///
+/// # 返回
+/// - `Err`: 没有足够的连续页帧，或堆的不相连区域已达上限（页帧已归还）
+///
+/// # 说明
+/// 内核通过恒等映射访问全部物理内存，新页帧不需要另外建立映射；
+/// 页帧紧接在当前堆末尾时直接延长堆，否则作为一块新的区域加入
+pub fn grow_heap(
+    additional_bytes: usize,
+    frame_allocator: &mut crate::memory::SimpleFrameAllocator,
+) -> Result<(), &'static str> {
+    grow(&ALLOCATOR, additional_bytes, frame_allocator)
+}
+
+/// 扩展指定的分配器（见 `grow_heap`）
+fn grow(
+    heap: &Locked<FixedSizeBlockAllocator>,
+    additional_bytes: usize,
+    frame_allocator: &mut crate::memory::SimpleFrameAllocator,
+) -> Result<(), &'static str> {
+    use crate::memory::PAGE_SIZE;
+
+    let count = additional_bytes.div_ceil(PAGE_SIZE);
+    let first = frame_allocator
+        .allocate_contiguous(count, PAGE_SIZE)
+        .ok_or("Not enough contiguous frames to grow the heap")?;
+    let start = first.start_address().as_usize();
+    // 页帧不会再被帧分配器交给别人，可以交给堆
+    let result = crate::interrupts::without_interrupts(|| unsafe {
+        heap.lock().extend(start, count * PAGE_SIZE)
+    });
+    if result.is_err() {
+        frame_allocator.deallocate_contiguous(first, count);
+    }
+    result
+}
+
+// ============================================
+// 堆使用统计
+// ============================================
+
+/// 全局分配器的使用统计
+pub fn heap_stats() -> HeapStats {
+    crate::interrupts::without_interrupts(|| ALLOCATOR.lock().stats())
+}
+
+/// 打印堆使用统计
+///
+/// # 说明
+/// 内存不足处理函数也调用它，因此走串口通道而不是控制台
+pub fn print_heap_stats() {
+    use crate::fmt::Elided;
+    use crate::serial_println;
+
+    let stats = heap_stats();
+    serial_println!("┌──────────────────────────────────────┐");
+    serial_println!("│ 堆使用情况                           │");
+    serial_println!("├──────────────────┬───────────────────┤");
+    serial_println!("│ Allocated        │ {:>11} bytes │", Elided(stats.allocated_bytes));
+    serial_println!("│ Free             │ {:>11} bytes │", Elided(stats.free_bytes));
+    serial_println!("│ Live allocations │ {:>17} │", Elided(stats.live_allocations));
+    serial_println!("├──────────────────┼───────────────────┤");
+    serial_println!("│ 块大小           │ 空闲块数          │");
+    serial_println!("├──────────────────┼───────────────────┤");
+    for (size, free) in block_sizes().iter().zip(stats.free_blocks) {
+        serial_println!("│ {:>10} bytes │ {:>17} │", size, Elided(free));
+    }
+    serial_println!("└──────────────────┴───────────────────┘");
+}
+
+/// 检查全局分配器的空闲链表（见 `FixedSizeBlockAllocator::check_free_lists`）
+pub fn check_heap() -> Result<usize, &'static str> {
+    crate::interrupts::without_interrupts(|| ALLOCATOR.lock().check_free_lists())
+}
+
+/// 一次泄漏检查发现的差额
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapLeak {
+    /// 检查的名称
+    pub tag: &'static str,
+    /// 多出来的未释放分配次数
+    pub allocations: isize,
+    /// 多占用的字节数
+    pub bytes: isize,
+}
+
+/// 作用域内的堆泄漏检查（见 `leak_check`）
+pub struct LeakCheck {
+    tag: &'static str,
+    before: HeapStats,
+}
+
+/// 开始一次泄漏检查
+///
+/// # 说明
+/// 离开作用域时分配次数或占用字节数比开始时多，打印带 `tag` 的报告；
+/// `leaked` 可以随时查看当前的差额（释放了作用域之前的分配时为负数）。
+/// 期间其他执行流（中断处理等）的分配同样计入，需要准确结果时关闭中断
+///
+/// # 示例
+/// ```ignore
+/// let _check = allocator::leak_check("rc-cycle");
+/// ```
+pub fn leak_check(tag: &'static str) -> LeakCheck {
+    LeakCheck { tag, before: heap_stats() }
+}
+
+impl LeakCheck {
+    /// 与开始时相比的差额（没有差额时为 None）
+    pub fn leaked(&self) -> Option<HeapLeak> {
+        let now = heap_stats();
+        let leak = HeapLeak {
+            tag: self.tag,
+            allocations: now.live_allocations as isize - self.before.live_allocations as isize,
+            bytes: now.allocated_bytes as isize - self.before.allocated_bytes as isize,
+        };
+        (leak.allocations != 0 || leak.bytes != 0).then_some(leak)
+    }
+}
+
+impl Drop for LeakCheck {
+    fn drop(&mut self) {
+        let leak = self.leaked().filter(|leak| leak.allocations > 0 || leak.bytes > 0);
+        if let Some(leak) = leak {
+            crate::klog!(
+                "[HEAP] leak in {}: {} allocations, {} bytes",
+                leak.tag,
+                leak.allocations,
+                leak.bytes
+            );
+        }
+    }
+}
+
+// ============================================
+// 内存不足处理
+// ============================================
+
+/// 内存不足时调用的处理函数
+static OOM_HANDLER: spin::Mutex<fn(core::alloc::Layout) -> !> =
+    spin::Mutex::new(default_oom_handler);
+
+/// 设置内存不足时的处理函数
+///
+/// # 说明
+/// 不可失败的分配（`Box::new`、`Vec::push` 等）失败时调用；
+/// `try_reserve` 等可失败的分配只返回错误，不会调用
+pub fn set_oom_handler(handler: fn(core::alloc::Layout) -> !) {
+    *OOM_HANDLER.lock() = handler;
+}
+
+/// 默认的内存不足处理：打印请求的大小和堆使用统计，然后停机
+///
+/// # 说明
+/// 堆还没有初始化时（见 `alloc_before_init`）直接停机
+pub fn default_oom_handler(layout: core::alloc::Layout) -> ! {
+    use crate::serial_println;
+
+    if !heap_ready() {
+        crate::hlt_loop();
+    }
+
+    serial_println!(
+        "[ALLOCATOR] Out of memory: size={} align={}",
+        layout.size(),
+        layout.align()
+    );
+    print_heap_stats();
+    crate::hlt_loop();
+}
+
+/// 堆初始化之前的分配（由全局分配器调用）
+///
+/// # 功能
+/// 直接写串口报告请求的大小，然后交给内存不足处理函数（默认停机）
+///
+/// # 说明
+/// 这时分配器的空闲链表还是空的，继续分配只会得到难以理解的错误；
+/// 报告不经过 SERIAL1 的锁和输出捕获，本身不分配内存
+pub(crate) fn alloc_before_init(layout: core::alloc::Layout) -> ! {
+    crate::serial::_print_raw(format_args!(
+        "[ALLOCATOR] Allocation before heap init: size={} align={}\n\
+         [ALLOCATOR] Call allocator::init_heap_simple before using Box, Vec or String\n",
+        layout.size(),
+        layout.align()
+    ));
+    handle_oom(layout)
+}
+
+/// 分配失败的入口（由 `alloc_error_handler` 调用）
+pub fn handle_oom(layout: core::alloc::Layout) -> ! {
+    // 先取出处理函数再调用，处理函数中可以再次 set_oom_handler
+    let handler = *OOM_HANDLER.lock();
+    handler(layout)
+}
 
 // ============================================
 // 测试
@@ -182,4 +413,303 @@ mod tests {
             assert_eq!(*x, i);
         }
     }
+
+    #[test_case]
+    fn test_grow_heap() {
+        use crate::memory::{test_frame_allocator, PAGE_SIZE};
+        use core::alloc::{GlobalAlloc, Layout};
+
+        // 在独立的分配器上测试，以免测试结束后全局堆仍在使用测试帧分配器的页帧
+        let mut frames = test_frame_allocator();
+        let heap = Locked::new(FixedSizeBlockAllocator::new());
+        let initial = frames.allocate_contiguous(4, PAGE_SIZE).unwrap();
+        unsafe { heap.lock().init(initial.start_address().as_usize(), 4 * PAGE_SIZE) };
+
+        // 填满初始的 16KB
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+        let mut blocks = Vec::new();
+        loop {
+            let ptr = unsafe { heap.alloc(layout) };
+            if ptr.is_null() {
+                break;
+            }
+            blocks.push(ptr);
+        }
+        assert!((1..=4).contains(&blocks.len()));
+
+        // 紧接着分配到的页帧：延长原来的区域
+        grow(&heap, 2 * PAGE_SIZE, &mut frames).expect("grow failed");
+        assert_eq!(heap.lock().region_count(), 1);
+        for _ in 0..2 {
+            let ptr = unsafe { heap.alloc(layout) };
+            assert!(!ptr.is_null());
+            blocks.push(ptr);
+        }
+
+        // 中间隔着别的页帧：成为新的区域
+        let spacer = frames.allocate().unwrap();
+        grow(&heap, PAGE_SIZE + 1, &mut frames).expect("grow failed");
+        assert_eq!(heap.lock().region_count(), 2);
+        let stats = heap.lock().stats();
+        assert_eq!(stats.allocated_bytes + stats.free_bytes, 8 * PAGE_SIZE);
+        let ptr = unsafe { heap.alloc(layout) };
+        assert!(!ptr.is_null());
+        blocks.push(ptr);
+
+        for ptr in blocks {
+            unsafe { heap.dealloc(ptr, layout) };
+        }
+        assert_eq!(heap.lock().stats().live_allocations, 0);
+        frames.deallocate(spacer);
+    }
+
+    #[test_case]
+    fn test_size_classes_select_free_list() {
+        use crate::memory::{test_frame_allocator, PAGE_SIZE};
+        use core::alloc::{GlobalAlloc, Layout};
+
+        let sizes = block_sizes();
+        assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(sizes.last(), Some(&4096));
+
+        let mut frames = test_frame_allocator();
+        let heap = Locked::new(FixedSizeBlockAllocator::new());
+        let region = frames.allocate_contiguous(8, PAGE_SIZE).unwrap();
+        unsafe { heap.lock().init(region.start_address().as_usize(), 8 * PAGE_SIZE) };
+
+        // 每个大小类的块释放后进入自己的空闲链表，再次分配时取回同一块
+        for (index, &size) in sizes.iter().enumerate() {
+            let layout = Layout::from_size_align(size, 8).unwrap();
+            let ptr = unsafe { heap.alloc(layout) };
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % size, 0);
+            unsafe { heap.dealloc(ptr, layout) };
+            let free = heap.lock().stats().free_blocks;
+            assert_eq!(free[index], 1, "size {} not cached in its class", size);
+            assert_eq!(free.iter().sum::<usize>(), index + 1);
+            assert_eq!(unsafe { heap.alloc(layout) }, ptr);
+            unsafe { heap.dealloc(ptr, layout) };
+        }
+
+        // 介于两类之间的大小向上取整：24 字节取回 32 字节类的块
+        let class_32 = sizes.iter().position(|&size| size == 32).unwrap();
+        let ptr = unsafe { heap.alloc(Layout::from_size_align(24, 8).unwrap()) };
+        assert_eq!(heap.lock().stats().free_blocks[class_32], 0);
+        unsafe { heap.dealloc(ptr, Layout::from_size_align(24, 8).unwrap()) };
+        assert_eq!(heap.lock().stats().free_blocks[class_32], 1);
+
+        // 超过最大一类：走后备分配器，不进入任何空闲链表
+        let layout = Layout::from_size_align(4097, 8).unwrap();
+        let ptr = unsafe { heap.alloc(layout) };
+        assert!(!ptr.is_null());
+        unsafe { heap.dealloc(ptr, layout) };
+        assert_eq!(heap.lock().stats().free_blocks.iter().sum::<usize>(), sizes.len());
+        frames.deallocate_contiguous(region, 8);
+    }
+
+    #[test_case]
+    fn test_alloc_failure_is_reported() {
+        use crate::faultinject::{arm, Policy};
+
+        // 小块和后备分配器两条路径都会失败，不会 panic
+        let mut small: Vec<u8> = Vec::new();
+        let mut large: Vec<u8> = Vec::new();
+        // 关闭中断，以免中断处理程序的分配也失败
+        crate::interrupts::without_interrupts(|| {
+            let _fault = arm("heap_alloc", Policy::Always);
+            assert!(small.try_reserve(16).is_err());
+            assert!(large.try_reserve(64 * 1024).is_err());
+        });
+        assert!(small.try_reserve(16).is_ok());
+        assert!(large.try_reserve(64 * 1024).is_ok());
+    }
+
+    #[test_case]
+    fn test_weak_keeps_allocation_until_dropped() {
+        use alloc::rc::Rc;
+
+        crate::interrupts::without_interrupts(|| {
+            let check = leak_check("weak");
+            let strong = Rc::new([7u8; 100]);
+            let weak = Rc::downgrade(&strong);
+            assert_eq!((Rc::strong_count(&strong), Rc::weak_count(&strong)), (1, 1));
+            assert_eq!(weak.upgrade().as_deref(), Some(&[7u8; 100]));
+
+            // 值已经析构，但计数和存储一起留到最后一个 Weak 被丢弃
+            drop(strong);
+            assert!(weak.upgrade().is_none());
+            assert_eq!(check.leaked().map(|leak| leak.allocations), Some(1));
+            drop(weak);
+            assert_eq!(check.leaked(), None);
+            assert!(check_heap().is_ok());
+        });
+    }
+
+    #[test_case]
+    fn test_rc_cycle_is_reported() {
+        use alloc::rc::Rc;
+        use alloc::vec;
+        use core::cell::RefCell;
+
+        struct Node {
+            _buffer: Vec<u8>,
+            next: RefCell<Option<Rc<Node>>>,
+        }
+
+        crate::interrupts::without_interrupts(|| {
+            let output = crate::console::capture();
+            let check = leak_check("rc-cycle");
+            let node = |fill, next| Rc::new(Node { _buffer: vec![fill; 1024], next });
+            let a = node(0, RefCell::new(None));
+            let b = node(1, RefCell::new(Some(a.clone())));
+            *a.next.borrow_mut() = Some(b.clone());
+            let weak = Rc::downgrade(&a);
+            drop((a, b));
+
+            // 两个节点互相持有：节点和缓冲区都没有释放
+            let leak = check.leaked().expect("cycle not detected");
+            assert_eq!(leak.allocations, 4);
+            assert!(leak.bytes >= 2048);
+            drop(check);
+            assert!(output.take().contains("[HEAP] leak in rc-cycle: 4 allocations"));
+
+            // 通过 Weak 打断环之后全部释放
+            let check = leak_check("rc-cycle-broken");
+            let a = weak.upgrade().expect("cycle already freed");
+            a.next.borrow_mut().take();
+            drop((a, weak));
+            assert_eq!(check.leaked().map(|leak| leak.allocations), Some(-4));
+            assert!(check_heap().is_ok());
+        });
+    }
+
+    #[test_case]
+    fn test_arc_dropped_on_another_task() {
+        use crate::task::{budget, executor::Executor, Task};
+        use alloc::rc::Rc;
+        use alloc::sync::Arc;
+        use core::cell::RefCell;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        /// 正在运行的任务（1：创建者，2：接收者）
+        static RUNNING: AtomicUsize = AtomicUsize::new(0);
+        /// 析构时正在运行的任务
+        static DROPPED_IN: AtomicUsize = AtomicUsize::new(0);
+
+        struct Shared {
+            buffer: Vec<u8>,
+        }
+
+        impl Drop for Shared {
+            fn drop(&mut self) {
+                DROPPED_IN.store(RUNNING.load(Ordering::Relaxed), Ordering::Relaxed);
+            }
+        }
+
+        async fn yield_now() {
+            budget::request_yield();
+            budget::consume().await;
+        }
+
+        crate::interrupts::without_interrupts(|| {
+            let check = leak_check("arc-tasks");
+            let mut executor = Executor::new();
+            let slot: Rc<RefCell<Option<Arc<Shared>>>> = Rc::new(RefCell::new(None));
+
+            let creator_slot = slot.clone();
+            executor.spawn(Task::with_name("creator", async move {
+                RUNNING.store(1, Ordering::Relaxed);
+                let shared = Arc::new(Shared { buffer: alloc::vec![0xa5; 4096] });
+                *creator_slot.borrow_mut() = Some(shared.clone());
+                yield_now().await;
+                RUNNING.store(1, Ordering::Relaxed);
+                assert_eq!(Arc::strong_count(&shared), 2);
+                drop(shared);
+            }));
+            executor.spawn(Task::with_name("receiver", async move {
+                let shared = loop {
+                    RUNNING.store(2, Ordering::Relaxed);
+                    if let Some(shared) = slot.borrow_mut().take() {
+                        break shared;
+                    }
+                    yield_now().await;
+                };
+                // 等创建者先放掉它的引用
+                while Arc::strong_count(&shared) > 1 {
+                    yield_now().await;
+                }
+                RUNNING.store(2, Ordering::Relaxed);
+                assert!(shared.buffer.iter().all(|&byte| byte == 0xa5));
+                drop(shared);
+            }));
+            executor.run_until_idle();
+            drop(executor);
+
+            assert_eq!(DROPPED_IN.load(Ordering::Relaxed), 2);
+            assert_eq!(check.leaked(), None);
+            assert!(check_heap().is_ok());
+        });
+    }
+
+    #[test_case]
+    fn test_check_heap_detects_bad_free_list() {
+        use core::alloc::{GlobalAlloc, Layout};
+
+        /// 堆外的一块按 64 字节对齐的内存
+        #[repr(align(64))]
+        struct Outside([u8; 64]);
+
+        let mut outside = Outside([0; 64]);
+        let region = Vec::<u8>::with_capacity(4096);
+        let heap = Locked::new(FixedSizeBlockAllocator::new());
+        unsafe { heap.lock().init(region.as_ptr() as usize, region.capacity()) };
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = unsafe { heap.alloc(layout) };
+        unsafe { heap.dealloc(ptr, layout) };
+        assert_eq!(heap.lock().check_free_lists(), Ok(1));
+
+        // 释放后写坏链表指针：指向堆外、没有对齐
+        let next = ptr as *mut usize;
+        unsafe { next.write(outside.0.as_mut_ptr() as usize) };
+        assert_eq!(heap.lock().check_free_lists(), Err("Free block outside the heap"));
+        unsafe { next.write(ptr as usize + 8) };
+        assert_eq!(heap.lock().check_free_lists(), Err("Free block misaligned"));
+        // 指回自己：链表成环
+        unsafe { next.write(ptr as usize) };
+        assert_eq!(heap.lock().check_free_lists(), Err("Free list length mismatch"));
+        unsafe { next.write(0) };
+        assert_eq!(heap.lock().check_free_lists(), Ok(1));
+        assert_eq!(outside.0[0], 0);
+    }
+}
+
+/// 在普通内核镜像中也运行的测试（builtin_tests 特性，见 `selftest`）；
+/// 其余测试只在 cargo test 中运行
+#[cfg(any(test, feature = "builtin_tests"))]
+mod selftests {
+    use super::*;
+    use crate::selftest::builtin_tests;
+    use alloc::{boxed::Box, vec::Vec};
+
+    builtin_tests! {
+        #[test_case]
+        fn test_heap_stats_track_live_allocations() {
+            // 关闭中断，以免中断处理程序的分配干扰计数
+            crate::interrupts::without_interrupts(|| {
+                let before = heap_stats();
+                let boxes: Vec<Box<[u8; 24]>> = (0..10).map(|i| Box::new([i; 24])).collect();
+                let during = heap_stats();
+                // 10 个 32 字节块加上 Vec 本身
+                assert_eq!(during.live_allocations, before.live_allocations + 11);
+                assert!(during.allocated_bytes >= before.allocated_bytes + 10 * 32);
+
+                drop(boxes);
+                let after = heap_stats();
+                assert_eq!(after.live_allocations, before.live_allocations);
+                // 释放的小块留在 32 字节大小类的空闲链表中
+                assert!(after.free_blocks[2] >= 10);
+                assert!(after.allocated_bytes + 10 * 32 <= during.allocated_bytes);
+            });
+        }
+    }
 }
@@ -46,6 +46,7 @@ unsafe impl GlobalAlloc for Locked<BumpAllocator> {
         }
     }
 
+    /// 只减少计数；最后一个分配释放时把 next 退回堆起始处，整块内存重新可用
     unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
         let mut bump = self.lock(); // 获取可变引用
 
@@ -58,4 +59,51 @@ unsafe impl GlobalAlloc for Locked<BumpAllocator> {
 
 fn align_up(addr: usize, align: usize) -> usize {
     (addr + align - 1) & !(align - 1)
-}
\ No newline at end of file
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// 测试用的小堆
+    #[repr(align(16))]
+    struct Arena([u8; 1024]);
+
+    #[test_case]
+    fn test_bump_resets_after_last_dealloc() {
+        let mut arena = Arena([0; 1024]);
+        let start = arena.0.as_mut_ptr() as usize;
+        let bump = Locked::new(BumpAllocator::new());
+        unsafe { bump.lock().init(start, arena.0.len()) };
+
+        let layout = Layout::from_size_align(24, 8).unwrap();
+        let first: Vec<*mut u8> = (0..5).map(|_| unsafe { bump.alloc(layout) }).collect();
+        assert!(first.iter().all(|p| !p.is_null()));
+        assert_eq!(first[0] as usize, start);
+        assert_eq!(bump.lock().allocations, 5);
+        assert_eq!(bump.lock().next, start + 5 * 24);
+
+        // 释放一部分：指针不回退
+        for &ptr in &first[..4] {
+            unsafe { bump.dealloc(ptr, layout) };
+        }
+        assert_eq!(bump.lock().next, start + 5 * 24);
+
+        // 释放最后一个：指针回到起点，之后的分配复用同一块内存
+        unsafe { bump.dealloc(first[4], layout) };
+        assert_eq!(bump.lock().allocations, 0);
+        assert_eq!(bump.lock().next, start);
+        let again = unsafe { bump.alloc(layout) };
+        assert_eq!(again, first[0]);
+        unsafe { bump.dealloc(again, layout) };
+
+        // 超出容量时返回空指针
+        let huge = Layout::from_size_align(2048, 8).unwrap();
+        assert!(unsafe { bump.alloc(huge) }.is_null());
+    }
+}
@@ -1,10 +1,61 @@
 struct ListNode{
     next: Option<&'static mut ListNode>,
 }
-const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+/// 各大小类的块大小
+///
+/// # 说明
+/// 修改时必须保持严格递增、每项都是 2 的幂（块按自身大小对齐），
+/// 并且最小的块能放下一个 ListNode；不满足时编译失败。
+/// 大于最后一项的分配交给后备分配器
+pub const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048, 4096];
+
+const _: () = assert!(valid_block_sizes(BLOCK_SIZES), "Invalid BLOCK_SIZES");
+
+/// 检查大小类是否可用（见 `BLOCK_SIZES`）
+const fn valid_block_sizes(sizes: &[usize]) -> bool {
+    if sizes.is_empty()
+        || sizes[0] < mem::size_of::<ListNode>()
+        || sizes[0] < mem::align_of::<ListNode>()
+    {
+        return false;
+    }
+    let mut i = 0;
+    while i < sizes.len() {
+        if !sizes[i].is_power_of_two() || (i > 0 && sizes[i] <= sizes[i - 1]) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// 初始堆之外最多扩展出的不相连区域数（与上一块区域相连的扩展不占名额）
+const MAX_EXTRA_REGIONS: usize = 7;
+
 pub struct FixedSizeBlockAllocator {
     list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
     fallback_allocator: linked_list_allocator::Heap,
+    /// extend 加入的、与之前的区域不相连的内存区域
+    extra_regions: [linked_list_allocator::Heap; MAX_EXTRA_REGIONS],
+    /// extra_regions 中已使用的个数
+    extra_count: usize,
+    /// 各大小类空闲链表中的块数
+    free_blocks: [usize; BLOCK_SIZES.len()],
+    /// 尚未释放的分配次数
+    live_allocations: usize,
+}
+
+/// 堆使用统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    /// 已分配的字节数（按块大小或后备分配器实际占用计算）
+    pub allocated_bytes: usize,
+    /// 空闲字节数（后备分配器的空闲空间加上空闲链表中的块）
+    pub free_bytes: usize,
+    /// 尚未释放的分配次数
+    pub live_allocations: usize,
+    /// 各大小类空闲链表中的块数（与 BLOCK_SIZES 对应）
+    pub free_blocks: [usize; BLOCK_SIZES.len()],
 }
 impl FixedSizeBlockAllocator {
     /// 创建一个空的FixedSizeBlockAllocator。
@@ -13,6 +64,24 @@ impl FixedSizeBlockAllocator {
         FixedSizeBlockAllocator {
             list_heads: [EMPTY; BLOCK_SIZES.len()],
             fallback_allocator: linked_list_allocator::Heap::empty(),
+            extra_regions: [const { linked_list_allocator::Heap::empty() }; MAX_EXTRA_REGIONS],
+            extra_count: 0,
+            free_blocks: [0; BLOCK_SIZES.len()],
+            live_allocations: 0,
+        }
+    }
+
+    /// 堆使用统计
+    pub fn stats(&self) -> HeapStats {
+        let cached: usize =
+            self.free_blocks.iter().zip(BLOCK_SIZES).map(|(n, size)| n * size).sum();
+        let free_bytes = self.regions().map(|heap| heap.free()).sum::<usize>() + cached;
+        let size: usize = self.regions().map(|heap| heap.size()).sum();
+        HeapStats {
+            allocated_bytes: size - free_bytes,
+            free_bytes,
+            live_allocations: self.live_allocations,
+            free_blocks: self.free_blocks,
         }
     }
 
@@ -23,17 +92,101 @@ impl FixedSizeBlockAllocator {
     pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
         unsafe { self.fallback_allocator.init(heap_start as *mut u8, heap_size); }
     }
+
+    /// 把 [start, start + size) 加入堆
+    ///
+    /// # 返回
+    /// - `Err`: 不相连的区域已达上限
+    ///
+    /// # 说明
+    /// 紧接在最后一块区域之后时直接延长那块区域，否则作为新的区域
+    ///
+    /// # Safety
+    /// 这段内存必须有效、未被使用，并且在分配器的整个生命周期内保持可用
+    pub unsafe fn extend(&mut self, start: usize, size: usize) -> Result<(), &'static str> {
+        let last = match self.extra_count {
+            0 => &mut self.fallback_allocator,
+            n => &mut self.extra_regions[n - 1],
+        };
+        if last.top() as usize == start {
+            unsafe { last.extend(size) };
+            return Ok(());
+        }
+        if self.extra_count == MAX_EXTRA_REGIONS {
+            return Err("Too many heap regions");
+        }
+        unsafe { self.extra_regions[self.extra_count].init(start as *mut u8, size) };
+        self.extra_count += 1;
+        Ok(())
+    }
+
+    /// 堆由几块不相连的区域组成
+    pub fn region_count(&self) -> usize {
+        1 + self.extra_count
+    }
+
+    /// 全部区域（初始堆在前）
+    fn regions(&self) -> impl Iterator<Item = &linked_list_allocator::Heap> {
+        core::iter::once(&self.fallback_allocator).chain(&self.extra_regions[..self.extra_count])
+    }
+
+    /// 检查各大小类的空闲链表
+    ///
+    /// # 返回
+    /// - `Ok(n)`: 链表完好，共 `n` 个空闲块
+    /// - `Err`: 某个块不在堆中或没有按块大小对齐（释放后被写坏），
+    ///   或链表长度与计数不符（重复释放、链表成环）
+    pub fn check_free_lists(&self) -> Result<usize, &'static str> {
+        let mut total = 0;
+        for (index, head) in self.list_heads.iter().enumerate() {
+            let mut count = 0;
+            let mut node = head.as_deref();
+            while let Some(current) = node {
+                let addr = current as *const ListNode as usize;
+                if !addr.is_multiple_of(BLOCK_SIZES[index]) {
+                    return Err("Free block misaligned");
+                }
+                let inside = |heap: &linked_list_allocator::Heap| {
+                    (heap.bottom() as usize..heap.top() as usize).contains(&addr)
+                };
+                if !self.regions().any(inside) {
+                    return Err("Free block outside the heap");
+                }
+                count += 1;
+                if count > self.free_blocks[index] {
+                    break;
+                }
+                node = current.next.as_deref();
+            }
+            if count != self.free_blocks[index] {
+                return Err("Free list length mismatch");
+            }
+            total += count;
+        }
+        Ok(total)
+    }
 }
 use alloc::alloc::Layout;
 use core::{mem, ptr::NonNull,ptr};
 
 impl FixedSizeBlockAllocator {
-    /// 使用后备分配器分配
+    /// 使用后备分配器分配（依次尝试每块区域）
     fn fallback_alloc(&mut self, layout: Layout) -> *mut u8 {
-        match self.fallback_allocator.allocate_first_fit(layout) {
-            Ok(ptr) => ptr.as_ptr(),
-            Err(_) => ptr::null_mut(),
-        }
+        let extra = &mut self.extra_regions[..self.extra_count];
+        core::iter::once(&mut self.fallback_allocator)
+            .chain(extra)
+            .find_map(|heap| heap.allocate_first_fit(layout).ok())
+            .map_or(ptr::null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    /// 归还给后备分配器中包含 `ptr` 的区域
+    unsafe fn fallback_dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let extra = &mut self.extra_regions[..self.extra_count];
+        let heap = core::iter::once(&mut self.fallback_allocator)
+            .chain(extra)
+            .find(|heap| (heap.bottom()..heap.top()).contains(&ptr.as_ptr()))
+            .expect("Freeing memory outside the heap");
+        unsafe { heap.deallocate(ptr, layout) };
     }
 }
 fn list_index(layout: &Layout) -> Option<usize> {
@@ -41,22 +194,32 @@ fn list_index(layout: &Layout) -> Option<usize> {
     BLOCK_SIZES.iter().position(|&s| s >= required_block_size)
 }
 use super::Locked;
+use crate::interrupts::InterruptGuard;
 use alloc::alloc::GlobalAlloc;
 
 unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    if !super::heap_ready() {
+        super::alloc_before_init(layout);
+    }
+    if crate::fault_point!("heap_alloc") {
+        return ptr::null_mut();
+    }
+    // 守卫先于锁创建、后于锁释放：持有锁期间不会被中断
+    let _irq = InterruptGuard::new();
     let mut allocator = self.lock();
-    match list_index(&layout) {
+    let ptr = match list_index(&layout) {
         Some(index) => {
             match allocator.list_heads[index].take() {
                 Some(node) => {
                     allocator.list_heads[index] = node.next.take();
+                    allocator.free_blocks[index] -= 1;
                     node as *mut ListNode as *mut u8
                 }
                 None => {
                     // 没有块存在于列表中 => 分配新块
                     let block_size = BLOCK_SIZES[index];
-                    // 只有当所有块大小都是 2 的幂时才有效
+                    // 所有块大小都是 2 的幂（编译期已检查）
                     let block_align = block_size;
                     let layout = Layout::from_size_align(block_size, block_align)
                         .unwrap();
@@ -65,30 +228,35 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
             }
         }
         None => allocator.fallback_alloc(layout),
+    };
+    if !ptr.is_null() {
+        allocator.live_allocations += 1;
     }
+    ptr
 }
 
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    let _irq = InterruptGuard::new();
     let mut allocator = self.lock();
+    allocator.live_allocations -= 1;
     match list_index(&layout) {
         Some(index) => {
             let new_node = ListNode {
                 next: allocator.list_heads[index].take(),
             };
-            // 验证块是否满足存储节点所需的大小和对齐方式要求
-            assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
-            assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
+            // 最小的块也能存放节点（编译期已检查）
             let new_node_ptr = ptr as *mut ListNode;
             unsafe {
                 new_node_ptr.write(new_node);
                 allocator.list_heads[index] = Some(&mut *new_node_ptr);
             }
+            allocator.free_blocks[index] += 1;
         }
         None => {
             let ptr = NonNull::new(ptr).unwrap();
             unsafe {
-                allocator.fallback_allocator.deallocate(ptr, layout);
+                allocator.fallback_dealloc(ptr, layout);
             }
         }
     }
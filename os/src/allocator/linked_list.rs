@@ -42,19 +42,55 @@ use super::align_up;
 use core::mem;
 
 impl LinkedListAllocator {
-    /// 将给定的内存区域添加到链表前端。
+    /// 将给定的内存区域按地址顺序插入链表，并与相邻的空闲区域合并。
+    ///
+    /// 链表始终按起始地址排序：释放时只需找到插入位置，
+    /// 再检查前一个和后一个节点是否与新区域首尾相接，O(n)。
     unsafe fn add_free_region(&mut self, addr: usize, size: usize) {
         // 确保给定的内存区域足以存储 ListNode
         assert_eq!(align_up(addr, mem::align_of::<ListNode>()), addr);
         assert!(size >= mem::size_of::<ListNode>());
 
-        // 创建一个新的 ListNode 并将其添加到链表前端
+        // 找到最后一个起始地址小于 addr 的节点（没有则为链表头）
+        let mut current = &mut self.head;
+        while let Some(ref next) = current.next {
+            if next.start_addr() > addr {
+                break;
+            }
+            current = current.next.as_mut().unwrap();
+        }
+
+        // 与后一个空闲区域相接：吸收它
+        let mut size = size;
+        let mut next = current.next.take();
+        debug_assert!(
+            next.as_ref().is_none_or(|node| addr + size <= node.start_addr()),
+            "freed region overlaps a free region"
+        );
+        if next.as_ref().is_some_and(|node| addr + size == node.start_addr()) {
+            let node = next.take().unwrap();
+            size += node.size;
+            next = node.next.take();
+        }
+
+        // 与前一个空闲区域相接：直接扩大前一个节点（链表头的大小为 0，不参与合并）
+        if current.size != 0 && current.end_addr() == addr {
+            current.size += size;
+            current.next = next;
+            return;
+        }
+        debug_assert!(
+            current.size == 0 || current.end_addr() <= addr,
+            "freed region overlaps a free region"
+        );
+
+        // 否则在 current 之后插入新节点
         let mut node = ListNode::new(size);
-        node.next = self.head.next.take();
+        node.next = next;
         let node_ptr = addr as *mut ListNode;
         unsafe {
             node_ptr.write(node);
-            self.head.next = Some(&mut *node_ptr)
+            current.next = Some(&mut *node_ptr)
         }
     }
 }
@@ -155,4 +191,67 @@ impl LinkedListAllocator {
         let size = layout.size().max(mem::size_of::<ListNode>());
         (size, layout.align())
     }
-}
\ No newline at end of file
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// 测试用的小堆
+    #[repr(align(16))]
+    struct Arena([u8; 1024]);
+
+    /// 空闲链表中的 (起始地址, 大小)
+    fn free_regions(allocator: &Locked<LinkedListAllocator>) -> Vec<(usize, usize)> {
+        let guard = allocator.lock();
+        let mut regions = Vec::new();
+        let mut current = &guard.head;
+        while let Some(ref node) = current.next {
+            regions.push((node.start_addr(), node.size));
+            current = node;
+        }
+        regions
+    }
+
+    #[test_case]
+    fn test_adjacent_frees_coalesce() {
+        let mut arena = Arena([0; 1024]);
+        let start = arena.0.as_mut_ptr() as usize;
+        let heap = Locked::new(LinkedListAllocator::new());
+        unsafe { heap.lock().init(start, arena.0.len()) };
+
+        // 三个相邻的块，再用一个块占满剩余空间
+        let block = Layout::from_size_align(64, 8).unwrap();
+        let rest = Layout::from_size_align(1024 - 3 * 64, 8).unwrap();
+        let [a, b, c, d] = [block, block, block, rest].map(|layout| unsafe { heap.alloc(layout) });
+        assert_eq!([a, b, c].map(|p| p as usize), [start, start + 64, start + 128]);
+        assert_eq!(d as usize, start + 192);
+        assert!(free_regions(&heap).is_empty());
+
+        // 先释放两端，再释放中间：中间的块同时与前后合并
+        unsafe {
+            heap.dealloc(a, block);
+            heap.dealloc(c, block);
+        }
+        assert_eq!(free_regions(&heap), [(start, 64), (start + 128, 64)]);
+        unsafe { heap.dealloc(b, block) };
+        assert_eq!(free_regions(&heap), [(start, 192)]);
+
+        // 跨越三个块的分配成功
+        let big = Layout::from_size_align(192, 8).unwrap();
+        let spanning = unsafe { heap.alloc(big) };
+        assert_eq!(spanning as usize, start);
+
+        // 全部释放后恢复为一整块
+        unsafe {
+            heap.dealloc(d, rest);
+            heap.dealloc(spanning, big);
+        }
+        assert_eq!(free_regions(&heap), [(start, 1024)]);
+    }
+}
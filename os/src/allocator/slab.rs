@@ -0,0 +1,260 @@
+/*
+ * ============================================
+ * Slab 分配器
+ * ============================================
+ * 功能：为大量同类型的内核对象（页表节点、将来的进程控制块等）分配内存
+ *
+ * 每个 SlabCache<T> 从帧分配器申请整页，切成 size_of::<T>() 大小的槽：
+ * | 页头（下一页） | 槽 0 | 槽 1 | ... |
+ * 空闲槽通过槽内的指针串成链表（侵入式空闲链表），分配和释放都是 O(1)；
+ * 空闲链表为空时再申请一页
+ *
+ * 与全局堆相比：没有按大小类取整的浪费，也不经过全局分配器的锁
+ * ============================================
+ */
+
+use super::align_up;
+use crate::memory::{PhysAddr, PhysFrame, SimpleFrameAllocator, PAGE_SIZE};
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ptr::NonNull;
+
+/// 空闲槽（存放在槽内部）
+struct FreeSlot {
+    next: Option<NonNull<FreeSlot>>,
+}
+
+/// 页头（存放在每一页开头）
+struct SlabPage {
+    next: Option<NonNull<SlabPage>>,
+}
+
+/// 同类型对象的 slab 缓存
+pub struct SlabCache<T> {
+    /// 空闲槽链表
+    free: Option<NonNull<FreeSlot>>,
+    /// 已申请的页（通过页头串成链表）
+    pages: Option<NonNull<SlabPage>>,
+    /// 已申请的页数
+    page_count: usize,
+    /// 尚未释放的对象数
+    live: usize,
+    _marker: PhantomData<T>,
+}
+
+// 页和槽只通过 &mut self 访问
+unsafe impl<T: Send> Send for SlabCache<T> {}
+
+impl<T> SlabCache<T> {
+    /// 槽的对齐（至少能放下空闲链表指针）
+    const SLOT_ALIGN: usize = if align_of::<T>() > align_of::<FreeSlot>() {
+        align_of::<T>()
+    } else {
+        align_of::<FreeSlot>()
+    };
+
+    /// 每个槽的字节数
+    pub const SLOT_SIZE: usize = {
+        let size = if size_of::<T>() > size_of::<FreeSlot>() {
+            size_of::<T>()
+        } else {
+            size_of::<FreeSlot>()
+        };
+        (size + Self::SLOT_ALIGN - 1) & !(Self::SLOT_ALIGN - 1)
+    };
+
+    /// 第一个槽在页内的偏移（页头之后）
+    const FIRST_SLOT: usize =
+        (size_of::<SlabPage>() + Self::SLOT_ALIGN - 1) & !(Self::SLOT_ALIGN - 1);
+
+    /// 每页的槽数
+    pub const SLOTS_PER_PAGE: usize =
+        PAGE_SIZE.saturating_sub(Self::FIRST_SLOT) / Self::SLOT_SIZE;
+
+    /// 创建空缓存（第一次分配时才申请页）
+    pub const fn new() -> Self {
+        SlabCache { free: None, pages: None, page_count: 0, live: 0, _marker: PhantomData }
+    }
+
+    /// 分配一个对象并写入 `value`
+    ///
+    /// # 参数
+    /// - `value`: 对象的初始值
+    /// - `frames`: 空闲槽用完时从这里申请新页
+    ///
+    /// # 返回
+    /// - `Some(ptr)`: 对象地址，用 dealloc 释放
+    /// - `None`: 没有空闲槽，也申请不到新页
+    pub fn alloc(&mut self, value: T, frames: &mut SimpleFrameAllocator) -> Option<NonNull<T>> {
+        const { assert!(Self::SLOTS_PER_PAGE > 0, "object too large for a slab page") };
+
+        if self.free.is_none() {
+            self.grow(frames)?;
+        }
+        let slot = self.free?;
+        unsafe {
+            self.free = slot.as_ref().next;
+            let ptr = slot.cast::<T>();
+            ptr.as_ptr().write(value);
+            self.live += 1;
+            Some(ptr)
+        }
+    }
+
+    /// 释放对象（先 drop，再把槽放回空闲链表）
+    ///
+    /// # Safety
+    /// `ptr` 必须是本缓存 alloc 返回、尚未释放的指针
+    pub unsafe fn dealloc(&mut self, ptr: NonNull<T>) {
+        ptr.as_ptr().drop_in_place();
+        let slot = ptr.cast::<FreeSlot>();
+        slot.as_ptr().write(FreeSlot { next: self.free });
+        self.free = Some(slot);
+        self.live -= 1;
+    }
+
+    /// 申请一页，把其中的槽全部加入空闲链表
+    fn grow(&mut self, frames: &mut SimpleFrameAllocator) -> Option<()> {
+        let frame = frames.allocate()?;
+        let base = frame.start_address().as_usize();
+        let first = base + Self::FIRST_SLOT;
+        debug_assert_eq!(align_up(first, Self::SLOT_ALIGN), first);
+
+        unsafe {
+            let page = base as *mut SlabPage;
+            page.write(SlabPage { next: self.pages });
+            self.pages = NonNull::new(page);
+
+            // 倒序入链，分配时按地址从低到高取出
+            for index in (0..Self::SLOTS_PER_PAGE).rev() {
+                let slot = (first + index * Self::SLOT_SIZE) as *mut FreeSlot;
+                slot.write(FreeSlot { next: self.free });
+                self.free = NonNull::new(slot);
+            }
+        }
+        self.page_count += 1;
+        Some(())
+    }
+
+    /// 尚未释放的对象数
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    /// 已申请的页数
+    pub fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// 不申请新页时最多容纳的对象数
+    pub fn capacity(&self) -> usize {
+        self.page_count * Self::SLOTS_PER_PAGE
+    }
+
+    /// 把所有页还给帧分配器
+    ///
+    /// # 返回
+    /// - `Ok(n)`: 归还的页数
+    /// - `Err`: 还有对象没有释放（缓存保持不变）
+    pub fn release_pages(
+        &mut self,
+        frames: &mut SimpleFrameAllocator,
+    ) -> Result<usize, &'static str> {
+        if self.live != 0 {
+            return Err("Slab objects still allocated");
+        }
+        let released = self.page_count;
+        while let Some(page) = self.pages {
+            unsafe { self.pages = page.as_ref().next };
+            frames.deallocate(PhysFrame::from_addr(PhysAddr::new(page.as_ptr() as usize)));
+        }
+        self.free = None;
+        self.page_count = 0;
+        Ok(released)
+    }
+}
+
+impl<T> Default for SlabCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::test_frame_allocator;
+    use alloc::vec::Vec;
+
+    /// 测试用的小对象
+    #[derive(Debug, PartialEq)]
+    struct Node {
+        id: u32,
+        parent: u64,
+    }
+
+    #[test_case]
+    fn test_slab_alloc_free_reuse() {
+        const COUNT: usize = 5000;
+        let mut frames = test_frame_allocator();
+        let used_before = frames.stats().used_frames;
+        let mut cache = SlabCache::<Node>::new();
+        assert_eq!(SlabCache::<Node>::SLOT_SIZE, 16);
+
+        let nodes: Vec<NonNull<Node>> = (0..COUNT)
+            .map(|i| cache.alloc(Node { id: i as u32, parent: !(i as u64) }, &mut frames).unwrap())
+            .collect();
+        assert_eq!(cache.live(), COUNT);
+        let pages = COUNT.div_ceil(SlabCache::<Node>::SLOTS_PER_PAGE);
+        assert_eq!(cache.page_count(), pages);
+        assert_eq!(frames.stats().used_frames, used_before + pages);
+        for (i, node) in nodes.iter().enumerate() {
+            assert_eq!(unsafe { node.as_ref() }, &Node { id: i as u32, parent: !(i as u64) });
+        }
+
+        // 释放后再分配：复用原来的槽，不再申请新页（后释放的先分配）
+        for node in &nodes {
+            unsafe { cache.dealloc(*node) };
+        }
+        assert_eq!(cache.live(), 0);
+        let again: Vec<NonNull<Node>> = (0..COUNT)
+            .map(|i| cache.alloc(Node { id: i as u32, parent: 0 }, &mut frames).unwrap())
+            .collect();
+        assert_eq!(again[0], nodes[COUNT - 1]);
+        assert_eq!(cache.page_count(), pages);
+        let mut sorted_old: Vec<usize> = nodes.iter().map(|p| p.as_ptr() as usize).collect();
+        let mut sorted_new: Vec<usize> = again.iter().map(|p| p.as_ptr() as usize).collect();
+        sorted_old.sort_unstable();
+        sorted_new.sort_unstable();
+        assert_eq!(sorted_old, sorted_new);
+
+        // 还有对象时不能归还页；全部释放后页帧回到帧分配器
+        assert!(cache.release_pages(&mut frames).is_err());
+        for node in again {
+            unsafe { cache.dealloc(node) };
+        }
+        assert_eq!(cache.release_pages(&mut frames), Ok(pages));
+        assert_eq!(frames.stats().used_frames, used_before);
+        assert_eq!(cache.capacity(), 0);
+    }
+
+    #[test_case]
+    fn test_slab_grow_failure() {
+        use crate::faultinject::{arm, Policy};
+
+        let mut frames = test_frame_allocator();
+        let mut cache = SlabCache::<u64>::new();
+        {
+            let _fault = arm("frame_alloc", Policy::Always);
+            assert!(cache.alloc(1, &mut frames).is_none());
+        }
+        let ptr = cache.alloc(7, &mut frames).unwrap();
+        assert_eq!(unsafe { *ptr.as_ref() }, 7);
+        unsafe { cache.dealloc(ptr) };
+        assert_eq!(cache.release_pages(&mut frames), Ok(1));
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * ============================================
+ * 性能测量辅助
+ * ============================================
+ * 功能：在没有时钟中断干扰的情况下测量代码
+ *
+ * 用法：
+ *   let (result, cycles) = bench::without_timer(|| {
+ *       let start = bench::cycles();
+ *       let r = work();
+ *       (r, bench::cycles() - start)
+ *   });
+ * ============================================
+ */
+
+use crate::interrupts::{self, InterruptSource};
+
+/// 读取 cycle 计数器
+pub fn cycles() -> u64 {
+    riscv::register::cycle::read64()
+}
+
+/// 屏蔽时钟中断并执行闭包
+///
+/// # 功能
+/// - 通过 `interrupts::disable_source` 屏蔽时钟中断
+/// - 执行闭包
+/// - 恢复原来的时钟中断状态，返回闭包结果
+///
+/// # 注意
+/// 闭包执行期间时钟不走：`uptime_ticks()` 不增加，键盘轮询、
+/// 依赖时钟的睡眠和调度都不会推进，因此闭包内不要等待这些事件。
+/// 屏蔽期间到期的时钟中断在恢复后立即触发
+pub fn without_timer<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let enabled = interrupts::is_source_enabled(InterruptSource::Timer);
+    if enabled {
+        interrupts::disable_source(InterruptSource::Timer);
+    }
+
+    let ret = f();
+
+    if enabled {
+        interrupts::enable_source(InterruptSource::Timer);
+    }
+
+    ret
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::uptime_ticks;
+    use riscv::register::time;
+
+    /// 忙等若干个 time 计数（10MHz）
+    fn spin_for(ticks: u64) {
+        let end = time::read64() + ticks;
+        while time::read64() < end {
+            core::hint::spin_loop();
+        }
+    }
+
+    #[test_case]
+    fn test_without_timer_stops_ticks() {
+        // 足够跨越多个时钟中断间隔（100ms）
+        const SPIN: u64 = 3_000_000;
+
+        let (before, after) = without_timer(|| {
+            let before = uptime_ticks();
+            spin_for(SPIN);
+            (before, uptime_ticks())
+        });
+        assert_eq!(before, after);
+        assert!(interrupts::is_source_enabled(InterruptSource::Timer));
+
+        // 恢复后时钟继续推进
+        let resumed = uptime_ticks();
+        let deadline = time::read64() + SPIN;
+        while uptime_ticks() == resumed && time::read64() < deadline {
+            core::hint::spin_loop();
+        }
+        assert!(uptime_ticks() > resumed);
+    }
+}
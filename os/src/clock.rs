@@ -0,0 +1,215 @@
+/*
+ * ============================================
+ * 时钟源
+ * ============================================
+ * 功能：为时钟中断计数、睡眠定时器和轮询预算提供统一的"当前时间"
+ * 包括：
+ * - ClockSource：时钟源接口
+ * - RealClock：读取 time CSR（默认）
+ * - TestClock：测试用的虚拟时钟，advance(n) 同步推进 n 个时钟中断
+ * - timebase：time 计数器的频率（来自设备树 /cpus 的 timebase-frequency）
+ *
+ * 每个时钟中断的处理逻辑在 interrupts::process_tick 中，
+ * 真实的时钟中断和 TestClock::advance 调用的是同一个函数
+ *
+ * 安装虚拟时钟期间，真实的时钟中断只重新设置定时器，
+ * 不再推进时间相关的状态，测试结果与真实时间无关
+ * ============================================
+ */
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use riscv::register::time;
+use spin::Mutex;
+
+/// time 计数器频率（QEMU virt：10MHz；设备树没有给出频率时使用）
+pub const TIME_FREQ: u64 = 10_000_000;
+
+/// 时钟中断间隔（time 计数，100ms）
+pub const TICK_INTERVAL: u64 = 1_000_000;
+
+/// 时钟源
+pub trait ClockSource: Sync {
+    /// 当前时间（time 计数）
+    fn now(&self) -> u64;
+
+    /// 是否为虚拟时钟
+    fn is_virtual(&self) -> bool {
+        false
+    }
+}
+
+/// 真实时钟：读取 time CSR
+pub struct RealClock;
+
+impl ClockSource for RealClock {
+    fn now(&self) -> u64 {
+        time::read64()
+    }
+}
+
+/// 当前使用的时钟源
+static CLOCK: Mutex<&'static dyn ClockSource> = Mutex::new(&RealClock);
+
+/// 当前使用的时钟源
+fn source() -> &'static dyn ClockSource {
+    crate::interrupts::without_interrupts(|| *CLOCK.lock())
+}
+
+/// 当前时间（time 计数）
+pub fn now() -> u64 {
+    source().now()
+}
+
+/// 当前是否安装了虚拟时钟
+pub fn is_virtual() -> bool {
+    source().is_virtual()
+}
+
+// ============================================
+// 计数器频率
+// ============================================
+
+/// time 计数器频率（Hz）
+static TIMEBASE: AtomicU64 = AtomicU64::new(TIME_FREQ);
+
+/// time 计数器频率（Hz）
+pub fn timebase() -> u64 {
+    TIMEBASE.load(Ordering::Relaxed)
+}
+
+/// 从设备树读取 time 计数器频率（/cpus 的 timebase-frequency）
+///
+/// # 返回
+/// - `Some(freq)`: 已更新的频率
+/// - `None`: 设备树没有给出（或为 0），保持原来的值
+pub fn set_timebase_from(fdt: &crate::device::Fdt) -> Option<u64> {
+    let freq = fdt.find("/cpus")?.prop_u32("timebase-frequency").filter(|&f| f != 0)? as u64;
+    TIMEBASE.store(freq, Ordering::Relaxed);
+    Some(freq)
+}
+
+/// time 计数换算为微秒
+pub fn ticks_to_micros(ticks: u64) -> u64 {
+    (ticks as u128 * 1_000_000 / timebase() as u128) as u64
+}
+
+/// 纳秒换算为 time 计数（向上取整，不足一个计数的时间也至少等一个计数）
+pub fn nanos_to_ticks(nanos: u64) -> u64 {
+    (nanos as u128 * timebase() as u128).div_ceil(1_000_000_000).min(u64::MAX as u128) as u64
+}
+
+/// 安装时钟源
+///
+/// # 返回
+/// 守卫对象，离开作用域时恢复之前的时钟源
+///
+/// # 示例
+/// ```ignore
+/// static CLOCK: TestClock = TestClock::new();
+/// let _clock = clock::install(&CLOCK);
+/// CLOCK.advance(3);
+/// ```
+pub fn install(clock: &'static dyn ClockSource) -> ClockGuard {
+    let previous = crate::interrupts::without_interrupts(|| {
+        core::mem::replace(&mut *CLOCK.lock(), clock)
+    });
+    ClockGuard { previous }
+}
+
+/// 时钟源守卫（见 `install`）
+pub struct ClockGuard {
+    previous: &'static dyn ClockSource,
+}
+
+impl Drop for ClockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous;
+        crate::interrupts::without_interrupts(|| *CLOCK.lock() = previous);
+    }
+}
+
+// ============================================
+// 虚拟时钟
+// ============================================
+
+/// 测试用的虚拟时钟
+///
+/// # 说明
+/// 时间只在调用 `advance` 时前进，每前进一个时钟中断间隔
+/// 就同步执行一次时钟中断的处理逻辑
+pub struct TestClock {
+    now: AtomicU64,
+}
+
+impl TestClock {
+    /// 创建从 0 开始的虚拟时钟
+    pub const fn new() -> Self {
+        TestClock {
+            now: AtomicU64::new(0),
+        }
+    }
+
+    /// 前进 `ticks` 个时钟中断
+    pub fn advance(&self, ticks: u64) {
+        for _ in 0..ticks {
+            self.now.fetch_add(TICK_INTERVAL, Ordering::Relaxed);
+            // 与真实时钟中断一样在关中断的状态下处理
+            crate::interrupts::without_interrupts(|| {
+                crate::interrupts::in_interrupt_context(crate::interrupts::process_tick)
+            });
+        }
+    }
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockSource for TestClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::Relaxed)
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::uptime_ticks;
+
+    #[test_case]
+    fn test_test_clock_drives_tick_processing() {
+        static CLOCK: TestClock = TestClock::new();
+
+        {
+            let _clock = install(&CLOCK);
+            assert!(is_virtual());
+            assert_eq!(now(), 0);
+
+            let before = uptime_ticks();
+            CLOCK.advance(3);
+            assert_eq!(uptime_ticks(), before + 3);
+            assert_eq!(now(), 3 * TICK_INTERVAL);
+        }
+
+        assert!(!is_virtual());
+    }
+
+    #[test_case]
+    fn test_timebase_from_device_tree() {
+        let fdt = crate::device::Fdt::parse(crate::device::SAMPLE_DTB).expect("parse failed");
+        assert_eq!(set_timebase_from(&fdt), Some(10_000_000));
+        assert_eq!(timebase(), 10_000_000);
+        assert_eq!(ticks_to_micros(TIME_FREQ), 1_000_000);
+        assert_eq!(ticks_to_micros(TICK_INTERVAL + 9), 100_000);
+    }
+}
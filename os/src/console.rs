@@ -6,6 +6,16 @@
  * 实现：通过串口输出（RISC-V 没有 VGA 设备）
  *
  * 在 RISC-V 环境中，我们使用串口作为主要的输出设备
+ *
+ * print! / println! 是给用户看的输出（演示结果、命令输出、统计表格）；
+ * 内核事件用 klog!，serial_print! 只用于 panic 等底层场合（分工见 klog 模块）
+ *
+ * 输出捕获（测试和 capture 特性下启用）：
+ *   let output = console::capture();
+ *   ...;  // println! / klog! / serial_println! 照常输出到串口，同时记录下来
+ *   assert!(output.take().contains("..."));
+ * 捕获可以嵌套：输出记录到当前所有的捕获中；
+ * 中断处理函数中的输出默认不记录（capture_isr 创建的捕获会记录）
  * ============================================
  */
 
@@ -46,13 +56,25 @@ impl Writer {
     }
 
     /// 写入字符串
+    ///
+    /// # 说明
+    /// 串口终端按 UTF-8 显示，非 ASCII 字符（中文、制表符号）原样输出；
+    /// 终端控制字符（\r、\t、ESC）原样输出，readline 靠它们移动光标
     pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                // 可打印 ASCII 字符或换行符
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // 不可打印字符，输出 ■
-                _ => self.write_byte(0xfe),
+        for c in s.chars() {
+            match c {
+                '\n' => self.new_line(),
+                '\r' => {
+                    self.write_char_to_serial(c);
+                    self.column_position = 0;
+                }
+                '\t' | '\x1b' => self.write_char_to_serial(c),
+                // 其他不可打印字符，输出 ■
+                c if c.is_control() => self.write_char_to_serial('■'),
+                c => {
+                    self.write_char_to_serial(c);
+                    self.column_position += 1;
+                }
             }
         }
     }
@@ -65,12 +87,17 @@ impl Writer {
 
     /// 通过串口输出字节
     fn write_to_serial(&mut self, byte: u8) {
+        self.write_char_to_serial(byte as char);
+    }
+
+    /// 通过串口输出字符（UTF-8 编码）
+    fn write_char_to_serial(&mut self, c: char) {
         use crate::serial::SERIAL1;
         use core::fmt::Write;
 
         // 直接写入串口（不需要通过临界区，因为已经持有 WRITER 锁）
         let mut serial = SERIAL1.lock();
-        let _ = serial.write_char(byte as char);
+        let _ = serial.write_char(c);
     }
 }
 
@@ -85,12 +112,12 @@ impl fmt::Write for Writer {
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
-    use crate::interrupts;
+    use crate::interrupts::InterruptGuard;
 
     // 在临界区内执行，禁用中断以防止死锁
-    interrupts::without_interrupts(|| {
-        WRITER.lock().write_fmt(args).unwrap();
-    });
+    let _irq = InterruptGuard::new();
+    record(args);
+    WRITER.lock().write_fmt(args).unwrap();
 }
 
 /// 打印宏（不换行）
@@ -118,3 +145,221 @@ macro_rules! println {
     () => ($crate::print!("\n"));
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
+
+// ============================================
+// 输出捕获
+// ============================================
+
+#[cfg(any(test, feature = "capture"))]
+pub use capture::{active_captures, capture, capture_isr, Capture};
+
+#[cfg(any(test, feature = "capture"))]
+pub(crate) use capture::record;
+
+/// 把一次输出记录到捕获中（捕获未启用：什么也不做）
+#[cfg(not(any(test, feature = "capture")))]
+#[inline(always)]
+pub(crate) fn record(_args: fmt::Arguments) {}
+
+#[cfg(any(test, feature = "capture"))]
+mod capture {
+    use crate::interrupts::{in_interrupt, without_interrupts};
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::fmt::{self, Write};
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use spin::Mutex;
+
+    /// 一层捕获
+    struct Sink {
+        /// 对应守卫的编号
+        id: u64,
+        /// 是否记录中断处理函数中的输出
+        isr: bool,
+        /// 捕获到的输出
+        text: String,
+    }
+
+    /// 捕获栈（最内层在最后）
+    static SINKS: Mutex<Vec<Sink>> = Mutex::new(Vec::new());
+
+    /// 下一个守卫的编号
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    /// capture_isr 为中断处理函数的输出预留的字节数
+    ///
+    /// # 说明
+    /// 中断处理函数不能分配堆内存（见 interrupts 模块）：中断中的输出只写进预留的容量，
+    /// 写不下的部分丢弃；任务上下文中的下一次输出再把预留补足
+    pub const ISR_RESERVE: usize = 1024;
+
+    /// 只写进 String 已有容量的写入器（不分配内存，写不下的部分丢弃）
+    struct Reserved<'a>(&'a mut String);
+
+    impl Write for Reserved<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let mut end = s.len().min(self.0.capacity() - self.0.len());
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            self.0.push_str(&s[..end]);
+            Ok(())
+        }
+    }
+
+    /// 捕获的守卫，丢弃或调用 take 时结束捕获
+    ///
+    /// # 说明
+    /// 结束一层捕获时，还没有结束的内层捕获一起结束，捕获栈恢复为创建这层之前的样子
+    #[must_use = "捕获在守卫被丢弃时立即结束"]
+    pub struct Capture {
+        id: u64,
+    }
+
+    impl Capture {
+        /// 结束捕获，返回捕获到的输出
+        pub fn take(self) -> String {
+            self.end()
+        }
+
+        /// 从捕获栈中移除本层及其内层，返回本层的输出（已经结束时返回空串）
+        fn end(&self) -> String {
+            without_interrupts(|| {
+                let mut sinks = SINKS.lock();
+                match sinks.iter().position(|sink| sink.id == self.id) {
+                    Some(index) => {
+                        let text = core::mem::take(&mut sinks[index].text);
+                        sinks.truncate(index);
+                        text
+                    }
+                    None => String::new(),
+                }
+            })
+        }
+    }
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            self.end();
+        }
+    }
+
+    /// 开始一层捕获
+    fn start(isr: bool) -> Capture {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let text = String::with_capacity(if isr { ISR_RESERVE } else { 0 });
+        without_interrupts(|| SINKS.lock().push(Sink { id, isr, text }));
+        Capture { id }
+    }
+
+    /// 开始捕获输出（不记录中断处理函数中的输出）
+    pub fn capture() -> Capture {
+        start(false)
+    }
+
+    /// 开始捕获输出（包括中断处理函数中的输出，两次任务上下文输出之间最多 `ISR_RESERVE` 字节）
+    pub fn capture_isr() -> Capture {
+        start(true)
+    }
+
+    /// 当前的捕获层数
+    pub fn active_captures() -> usize {
+        without_interrupts(|| SINKS.lock().len())
+    }
+
+    /// 把一次输出记录到当前所有的捕获中（由打印函数在关中断时调用）
+    ///
+    /// # 说明
+    /// - 记录过程中再次输出（例如分配器报错）时捕获栈已被锁住，这次输出不记录
+    /// - 中断处理函数中的输出不分配内存，只写进预留的容量（见 `ISR_RESERVE`）
+    pub(crate) fn record(args: fmt::Arguments) {
+        let Some(mut sinks) = SINKS.try_lock() else {
+            return;
+        };
+        let isr = in_interrupt();
+        for sink in sinks.iter_mut().filter(|sink| sink.isr || !isr) {
+            if isr {
+                let _ = Reserved(&mut sink.text).write_fmt(args);
+                continue;
+            }
+            let _ = sink.text.write_fmt(args);
+            if sink.isr {
+                sink.text.reserve(ISR_RESERVE);
+            }
+        }
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::in_interrupt_context;
+    use crate::serial_print;
+
+    #[test_case]
+    fn test_capture_is_byte_exact_and_nests() {
+        let outer = capture();
+        print!("a\tb\n");
+        let inner = capture();
+        serial_print!("{}", 42);
+        assert_eq!(active_captures(), 2);
+        assert_eq!(inner.take(), "42");
+        println!("c");
+        assert_eq!(outer.take(), "a\tb\n42c\n");
+        assert_eq!(active_captures(), 0);
+    }
+
+    #[test_case]
+    fn test_guard_restores_sink_list() {
+        let outer = capture();
+        let inner = capture();
+        {
+            let _innermost = capture();
+            assert_eq!(active_captures(), 3);
+        }
+        assert_eq!(active_captures(), 2);
+
+        // 外层先结束时内层一起结束；之后丢弃内层的守卫不影响新的捕获
+        drop(outer);
+        assert_eq!(active_captures(), 0);
+        let next = capture();
+        drop(inner);
+        assert_eq!(active_captures(), 1);
+        print!("x");
+        assert_eq!(next.take(), "x");
+    }
+
+    #[test_case]
+    fn test_isr_output_captured_per_flag() {
+        let with_isr = capture_isr();
+        let without_isr = capture();
+        print!("task ");
+        in_interrupt_context(|| print!("isr "));
+        print!("task");
+        assert_eq!(without_isr.take(), "task task");
+        assert_eq!(with_isr.take(), "task isr task");
+    }
+
+    #[test_case]
+    fn test_isr_capture_does_not_allocate() {
+        use crate::interrupts::without_interrupts;
+
+        let with_isr = capture_isr();
+        let _without_isr = capture();
+        print!("task ");
+        // 被打断的代码持有分配器的锁：中断中的记录若分配内存就会死锁
+        let long = "x".repeat(4 * capture::ISR_RESERVE);
+        without_interrupts(|| {
+            let _heap = crate::allocator::hold_lock();
+            in_interrupt_context(|| print!("isr {}", long));
+        });
+        // 预留写满之后中断中的输出被截断
+        let text = with_isr.take();
+        assert!(text.starts_with("task isr xxx"));
+        assert!(text.len() < "task isr ".len() + long.len());
+    }
+}
@@ -0,0 +1,211 @@
+/*
+ * ============================================
+ * 崩溃记录
+ * ============================================
+ * 功能：记录上一次启动的 panic，便于调试反复出现的崩溃
+ *
+ * 内存末尾的一页（memory::crash_record_addr()，DRAM 大小来自设备树）不交给帧分配器，存放：
+ * - 启动次数
+ * - 最后一次 panic 的信息（截断到 MESSAGE_LEN 字节）、sepc 和当时的 uptime
+ *
+ * panic 处理函数在停机前调用 record_panic 写入；下次启动时 init 读出，
+ * 打印 "previous boot panicked at ..."，并把启动次数加一
+ *
+ * 注意：RAM 不是真正的持久存储。记录只在同一个 QEMU 会话内的
+ * 软复位 / 重启之间保留，重新启动 QEMU 后内存内容丢失，计数从 1 开始
+ * ============================================
+ */
+
+use crate::memory::crash_record_addr;
+use crate::klog;
+use core::fmt::{self, Write};
+
+/// 有效记录的魔数（"CRASHLOG"）
+const CRASH_MAGIC: u64 = u64::from_be_bytes(*b"CRASHLOG");
+
+/// 保存的 panic 信息最大字节数
+pub const MESSAGE_LEN: usize = 256;
+
+/// 保留页中的崩溃记录
+#[repr(C)]
+#[derive(Clone)]
+pub struct CrashRecord {
+    /// CRASH_MAGIC 表示内容有效（其余值说明是上电后的随机内容）
+    magic: u64,
+    /// 启动次数（含本次）
+    boot_count: u64,
+    /// 上一次启动是否 panic（读出后清除）
+    panicked: u64,
+    /// panic 时的 sepc（最近一次陷入的位置）
+    sepc: u64,
+    /// panic 时的 uptime（时钟中断数）
+    uptime_ticks: u64,
+    /// message 中有效的字节数
+    message_len: u64,
+    /// panic 信息（UTF-8，可能在字符中间截断）
+    message: [u8; MESSAGE_LEN],
+}
+
+const _: () = assert!(core::mem::size_of::<CrashRecord>() <= crate::memory::PAGE_SIZE);
+
+/// 上一次启动的 panic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreviousPanic<'a> {
+    /// 那次启动的序号
+    pub boot: u64,
+    /// panic 时的 sepc
+    pub sepc: usize,
+    /// panic 时的 uptime（时钟中断数）
+    pub uptime_ticks: u64,
+    /// panic 信息（截断处不完整的字符被丢弃）
+    pub message: &'a str,
+}
+
+impl CrashRecord {
+    /// 空记录
+    pub const fn new() -> Self {
+        CrashRecord {
+            magic: 0,
+            boot_count: 0,
+            panicked: 0,
+            sepc: 0,
+            uptime_ticks: 0,
+            message_len: 0,
+            message: [0; MESSAGE_LEN],
+        }
+    }
+
+    /// 启动时调用：启动次数加一
+    ///
+    /// # 返回
+    /// 上一次启动 panic 时的记录；魔数无效时先清空记录
+    pub fn boot(&mut self) -> Option<PreviousPanic<'_>> {
+        if self.magic != CRASH_MAGIC {
+            *self = CrashRecord { magic: CRASH_MAGIC, ..CrashRecord::new() };
+        }
+        self.boot_count += 1;
+        if core::mem::take(&mut self.panicked) == 0 {
+            return None;
+        }
+
+        let len = (self.message_len as usize).min(MESSAGE_LEN);
+        let message = match core::str::from_utf8(&self.message[..len]) {
+            Ok(message) => message,
+            Err(e) => core::str::from_utf8(&self.message[..e.valid_up_to()]).unwrap(),
+        };
+        Some(PreviousPanic {
+            boot: self.boot_count - 1,
+            sepc: self.sepc as usize,
+            uptime_ticks: self.uptime_ticks,
+            message,
+        })
+    }
+
+    /// 记录一次 panic（不分配内存，信息过长时截断）
+    pub fn write_panic(&mut self, message: fmt::Arguments, sepc: usize, uptime_ticks: u64) {
+        self.magic = CRASH_MAGIC;
+        self.panicked = 1;
+        self.sepc = sepc as u64;
+        self.uptime_ticks = uptime_ticks;
+        self.message_len = 0;
+        let _ = self.write_fmt(message);
+    }
+
+    /// 启动次数（含本次）
+    pub fn boot_count(&self) -> u64 {
+        self.boot_count
+    }
+}
+
+impl Default for CrashRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for CrashRecord {
+    /// 追加到 message，超出部分丢弃
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = self.message_len as usize;
+        let n = s.len().min(MESSAGE_LEN - len);
+        self.message[len..len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.message_len += n as u64;
+        Ok(())
+    }
+}
+
+/// 保留页中的记录
+///
+/// # Safety
+/// 同一时间只能有一个引用（启动早期和 panic 处理函数中只有一个执行流）
+unsafe fn reserved() -> &'static mut CrashRecord {
+    &mut *(crash_record_addr() as *mut CrashRecord)
+}
+
+/// 启动时读出上一次的崩溃记录并打印，启动次数加一
+///
+/// # 返回
+/// 本次的启动序号
+pub fn init() -> u64 {
+    let record = unsafe { reserved() };
+    if let Some(previous) = record.boot() {
+        klog!(
+            "[CRASHLOG] previous boot (#{}) panicked at uptime {} ticks, sepc={:#x}: {}",
+            previous.boot,
+            previous.uptime_ticks,
+            previous.sepc,
+            previous.message
+        );
+    }
+    klog!("[CRASHLOG] boot #{}", record.boot_count());
+    record.boot_count()
+}
+
+/// 在 panic 处理函数中调用：把 panic 信息写入保留页
+pub fn record_panic(info: &core::panic::PanicInfo) {
+    let sepc = riscv::register::sepc::read();
+    let ticks = crate::interrupts::uptime_ticks();
+    unsafe { reserved() }.write_panic(format_args!("{}", info), sepc, ticks);
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_record_survives_reboot() {
+        // 上电后的随机内容：清空后从第 1 次启动开始
+        let mut record = CrashRecord { magic: 0x1234, boot_count: 99, ..CrashRecord::new() };
+        assert_eq!(record.boot(), None);
+        assert_eq!(record.boot_count(), 1);
+
+        let long = "x".repeat(MESSAGE_LEN);
+        record.write_panic(format_args!("panicked at é{}", long), 0x8020_1234, 42);
+        let previous = record.boot().expect("panic not recorded");
+        assert_eq!((previous.boot, previous.sepc, previous.uptime_ticks), (1, 0x8020_1234, 42));
+        assert!(previous.message.starts_with("panicked at é"));
+        assert_eq!(previous.message.len(), MESSAGE_LEN);
+        assert_eq!(record.boot_count(), 2);
+
+        // 读出后清除，再次启动时没有 panic
+        assert_eq!(record.boot(), None);
+        assert_eq!(record.boot_count(), 3);
+    }
+
+    #[test_case]
+    fn test_reserved_page_written_by_panic_path() {
+        let saved = unsafe { reserved() }.clone();
+
+        let boot = init();
+        unsafe { reserved() }.write_panic(format_args!("test panic"), 0x8020_0000, 7);
+        assert_eq!(init(), boot + 1);
+        // init 已经清除了 panic 标志
+        assert_eq!(unsafe { reserved() }.boot(), None);
+
+        *unsafe { reserved() } = saved;
+    }
+}
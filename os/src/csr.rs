@@ -0,0 +1,277 @@
+/*
+ * ============================================
+ * CSR 访问封装
+ * ============================================
+ * 功能：为内核使用的 S 模式 CSR 提供统一的读写接口
+ * 包括：satp、sstatus、sie、stvec、sepc、stval、time、cycle
+ *
+ * 两种实现：
+ * - 默认：直接执行 csrr / csrw 指令，总是返回 Ok（零开销）
+ * - `csr_checked` feature：每条 CSR 指令都登记在异常表中，
+ *   如果当前特权级不允许访问，非法指令异常由陷阱处理函数
+ *   跳转到修复代码，接口返回 Err 而不是让内核崩溃
+ *
+ * 异常表（__ex_table 段）：
+ *   每项两个 u64：可能出错的指令地址、出错后跳转的地址
+ *   链接脚本导出 __ex_table_start / __ex_table_end
+ * ============================================
+ */
+
+use crate::klog;
+use spin::Mutex;
+
+/// CSR 访问结果
+pub type CsrResult<T> = Result<T, &'static str>;
+
+/// CSR 访问被拒绝时的错误
+pub const CSR_DENIED: &str = "CSR access denied";
+
+// ============================================
+// 异常表
+// ============================================
+
+/// 异常表项
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct ExceptionTableEntry {
+    /// 可能触发异常的指令地址
+    insn: usize,
+    /// 异常发生后跳转的地址
+    fixup: usize,
+}
+
+/// 异常表中的所有表项
+fn exception_table() -> &'static [ExceptionTableEntry] {
+    extern "C" {
+        static __ex_table_start: ExceptionTableEntry;
+        static __ex_table_end: ExceptionTableEntry;
+    }
+
+    unsafe {
+        let start = &__ex_table_start as *const ExceptionTableEntry;
+        let end = &__ex_table_end as *const ExceptionTableEntry;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// 查找异常指令对应的修复地址
+///
+/// # 返回
+/// - `Some(fixup)`: `pc` 在异常表中，陷阱处理函数应把 sepc 改为 fixup
+/// - `None`: 不是受保护的指令
+pub fn search_exception_table(pc: usize) -> Option<usize> {
+    exception_table()
+        .iter()
+        .find(|entry| entry.insn == pc)
+        .map(|entry| entry.fixup)
+}
+
+// ============================================
+// CSR 指令
+// ============================================
+
+/// 执行一条 CSR 指令（带异常保护）
+///
+/// 指令前的标签登记到异常表，出错时跳到设置 failed 的修复代码
+#[cfg(feature = "csr_checked")]
+macro_rules! csr_insn {
+    ($insn:expr, $($operands:tt)*) => {{
+        let failed: usize;
+        core::arch::asm!(
+            "li {failed}, 0",
+            concat!("7: ", $insn),
+            "j 9f",
+            "8: li {failed}, 1",
+            "9:",
+            ".pushsection __ex_table, \"a\"",
+            ".balign 8",
+            ".dword 7b, 8b",
+            ".popsection",
+            failed = out(reg) failed,
+            $($operands)*
+        );
+        if failed == 0 {
+            Ok(())
+        } else {
+            Err(CSR_DENIED)
+        }
+    }};
+}
+
+/// 执行一条 CSR 指令（直接访问）
+#[cfg(not(feature = "csr_checked"))]
+macro_rules! csr_insn {
+    ($insn:expr, $($operands:tt)*) => {{
+        core::arch::asm!($insn, $($operands)*);
+        CsrResult::Ok(())
+    }};
+}
+
+/// 生成读取 CSR 的函数
+macro_rules! csr_reader {
+    ($(#[$doc:meta])* $name:ident, $csr:literal) => {
+        $(#[$doc])*
+        pub fn $name() -> CsrResult<usize> {
+            let value: usize;
+            unsafe { csr_insn!(concat!("csrr {value}, ", $csr), value = out(reg) value) }
+                .map(|_| value)
+        }
+    };
+}
+
+/// 生成写入 / 置位 / 清位 CSR 的函数
+macro_rules! csr_writer {
+    ($(#[$doc:meta])* $name:ident, $insn:literal, $csr:literal) => {
+        $(#[$doc])*
+        ///
+        /// # Safety
+        /// 修改 CSR 会影响陷阱、中断或地址转换，调用者需保证修改后的状态合法
+        pub unsafe fn $name(value: usize) -> CsrResult<()> {
+            csr_insn!(concat!($insn, " ", $csr, ", {value}"), value = in(reg) value)
+        }
+    };
+}
+
+csr_reader!(
+    /// 读取 satp
+    read_satp, "satp"
+);
+csr_writer!(
+    /// 写入 satp
+    write_satp, "csrw", "satp"
+);
+
+csr_reader!(
+    /// 读取 sstatus
+    read_sstatus, "sstatus"
+);
+csr_writer!(
+    /// sstatus 置位
+    set_sstatus, "csrs", "sstatus"
+);
+csr_writer!(
+    /// sstatus 清位
+    clear_sstatus, "csrc", "sstatus"
+);
+
+csr_reader!(
+    /// 读取 sie
+    read_sie, "sie"
+);
+csr_writer!(
+    /// sie 置位
+    set_sie, "csrs", "sie"
+);
+csr_writer!(
+    /// sie 清位
+    clear_sie, "csrc", "sie"
+);
+
+csr_reader!(
+    /// 读取 stvec
+    read_stvec, "stvec"
+);
+csr_writer!(
+    /// 写入 stvec
+    write_stvec, "csrw", "stvec"
+);
+
+csr_reader!(
+    /// 读取 sepc
+    read_sepc, "sepc"
+);
+csr_writer!(
+    /// 写入 sepc
+    write_sepc, "csrw", "sepc"
+);
+
+csr_reader!(
+    /// 读取 stval
+    read_stval, "stval"
+);
+
+csr_reader!(
+    /// 读取 time（U/S 模式下由 scounteren 控制是否可访问）
+    read_time, "time"
+);
+
+csr_reader!(
+    /// 读取 cycle（同样受 scounteren 控制）
+    read_cycle, "cycle"
+);
+
+// ============================================
+// 功能降级
+// ============================================
+
+/// 最多记录的被禁用功能数
+const MAX_DISABLED: usize = 8;
+
+/// 因 CSR 不可访问而禁用的功能
+static DISABLED: Mutex<[Option<&'static str>; MAX_DISABLED]> = Mutex::new([None; MAX_DISABLED]);
+
+/// 检查 CSR 访问结果，失败时禁用对应功能并继续启动
+///
+/// # 参数
+/// - `result`: CSR 访问结果
+/// - `feature`: 依赖该 CSR 的功能名称
+///
+/// # 返回
+/// - `Some(value)`: 访问成功
+/// - `None`: 访问失败，已打印降级信息并记录到 `disabled_features`
+pub fn require<T>(result: CsrResult<T>, feature: &'static str) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(_) => {
+            klog!("[CSR] running without S-mode CSR access: {} disabled", feature);
+            let mut disabled = DISABLED.lock();
+            if let Some(slot) = disabled.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(feature);
+            }
+            None
+        }
+    }
+}
+
+/// 因 CSR 不可访问而被禁用的功能
+pub fn disabled_features() -> impl Iterator<Item = &'static str> {
+    let disabled = *DISABLED.lock();
+    disabled.into_iter().flatten()
+}
+
+/// 功能是否因 CSR 不可访问而被禁用
+pub fn is_disabled(feature: &str) -> bool {
+    disabled_features().any(|name| name == feature)
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_csr_reads_in_s_mode() {
+        assert!(read_sstatus().is_ok());
+        assert!(read_satp().is_ok());
+        let before = read_time().unwrap();
+        assert!(read_time().unwrap() >= before);
+    }
+
+    #[cfg(feature = "csr_checked")]
+    #[test_case]
+    fn test_denied_csr_degrades() {
+        // S 模式访问 M 模式 CSR 触发非法指令异常，用来模拟被拒绝的访问
+        let denied = unsafe { csr_insn!("csrr {value}, mscratch", value = out(reg) _) };
+        assert_eq!(denied, Err(CSR_DENIED));
+
+        assert_eq!(require(denied, "test feature"), None);
+        assert!(is_disabled("test feature"));
+
+        // 之后的访问照常进行
+        assert!(require(read_sstatus(), "sstatus").is_some());
+        assert!(!is_disabled("sstatus"));
+    }
+}
@@ -0,0 +1,252 @@
+/*
+ * ============================================
+ * 设备树（FDT）解析
+ * ============================================
+ * 功能：读取扁平设备树（DTB），列出节点及其属性
+ *
+ * DTB 结构（整数均为大端）：
+ *   头部（40 字节）：magic 0xd00dfeed、总大小、结构块和字符串块的偏移……
+ *   结构块：FDT_BEGIN_NODE 名字 / FDT_PROP 长度 名字偏移 值 / FDT_END_NODE …… FDT_END
+ *   字符串块：属性名
+ *
+ * 只实现驱动探测需要的部分：节点路径、属性、compatible 和 reg
+ * ============================================
+ */
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// DTB 头部的魔数
+pub const FDT_MAGIC: u32 = 0xd00d_feed;
+
+/// 头部大小
+const HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// 设备树节点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtNode<'a> {
+    /// 完整路径（根节点为 "/"）
+    pub path: String,
+    /// 节点名（包括 @ 后的单元地址）
+    pub name: &'a str,
+    /// 属性（名字，值）
+    props: Vec<(&'a str, &'a [u8])>,
+    /// 父节点的 #address-cells 和 #size-cells（决定 reg 的格式）
+    cells: (usize, usize),
+}
+
+impl<'a> DtNode<'a> {
+    /// 按名字查找属性
+    pub fn prop(&self, name: &str) -> Option<&'a [u8]> {
+        self.props.iter().find(|(n, _)| *n == name).map(|&(_, value)| value)
+    }
+
+    /// 属性的第一个 32 位整数
+    pub fn prop_u32(&self, name: &str) -> Option<u32> {
+        let value = self.prop(name)?;
+        Some(u32::from_be_bytes(value.get(..4)?.try_into().ok()?))
+    }
+
+    /// 字符串属性（去掉结尾的 NUL）
+    pub fn prop_str(&self, name: &str) -> Option<&'a str> {
+        let value = self.prop(name)?;
+        core::str::from_utf8(value.strip_suffix(&[0]).unwrap_or(value)).ok()
+    }
+
+    /// compatible 列表（从最具体到最通用）
+    pub fn compatibles(&self) -> impl Iterator<Item = &'a str> {
+        self.prop("compatible")
+            .unwrap_or(&[])
+            .split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    /// 是否与某个 compatible 匹配
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatibles().any(|c| c == compatible)
+    }
+
+    /// reg 的第一项（地址，大小）
+    pub fn reg(&self) -> Option<(usize, usize)> {
+        let value = self.prop("reg")?;
+        let (addr_cells, size_cells) = self.cells;
+        let addr = read_cells(value, 0, addr_cells)?;
+        let size = read_cells(value, addr_cells, size_cells)?;
+        Some((addr, size))
+    }
+}
+
+/// 从第 `start` 个 32 位单元开始读取 `count` 个单元组成的整数
+fn read_cells(value: &[u8], start: usize, count: usize) -> Option<usize> {
+    (start..start + count).try_fold(0usize, |acc, i| {
+        let cell = value.get(i * 4..i * 4 + 4)?;
+        Some((acc << 32) | u32::from_be_bytes(cell.try_into().ok()?) as usize)
+    })
+}
+
+/// 解析过的设备树
+pub struct Fdt<'a> {
+    nodes: Vec<DtNode<'a>>,
+}
+
+/// 读取大端 u32
+fn be32(bytes: &[u8], offset: usize) -> Result<u32, &'static str> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+        .ok_or("Device tree truncated")
+}
+
+/// 读取以 NUL 结尾的字符串
+fn cstr(bytes: &[u8], offset: usize) -> Result<&str, &'static str> {
+    let rest = bytes.get(offset..).ok_or("Device tree truncated")?;
+    let len = rest.iter().position(|&b| b == 0).ok_or("Device tree truncated")?;
+    core::str::from_utf8(&rest[..len]).map_err(|_| "Invalid string in device tree")
+}
+
+impl<'a> Fdt<'a> {
+    /// 解析 DTB
+    ///
+    /// # 返回
+    /// - `Err`: 魔数不对、结构块越界或格式错误
+    pub fn parse(dtb: &'a [u8]) -> Result<Self, &'static str> {
+        if dtb.len() < HEADER_SIZE || be32(dtb, 0)? != FDT_MAGIC {
+            return Err("Not a device tree");
+        }
+        let total = be32(dtb, 4)? as usize;
+        let dtb = dtb.get(..total).ok_or("Device tree truncated")?;
+        let structs = be32(dtb, 8)? as usize;
+        let strings = dtb.get(be32(dtb, 12)? as usize..).ok_or("Device tree truncated")?;
+
+        let mut nodes: Vec<DtNode> = Vec::new();
+        // 当前路径上各节点在 nodes 中的下标
+        let mut stack: Vec<usize> = Vec::new();
+        let mut pos = structs;
+        loop {
+            let token = be32(dtb, pos)?;
+            pos += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = cstr(dtb, pos)?;
+                    pos = (pos + name.len() + 1).next_multiple_of(4);
+                    let (path, cells) = match stack.last() {
+                        None => (String::from("/"), (2, 1)),
+                        Some(&parent) => {
+                            let parent = &nodes[parent];
+                            let mut path = parent.path.clone();
+                            if path != "/" {
+                                path.push('/');
+                            }
+                            path.push_str(name);
+                            let addr = parent.prop_u32("#address-cells").unwrap_or(2);
+                            let size = parent.prop_u32("#size-cells").unwrap_or(1);
+                            (path, (addr as usize, size as usize))
+                        }
+                    };
+                    stack.push(nodes.len());
+                    nodes.push(DtNode { path, name, props: Vec::new(), cells });
+                }
+                FDT_END_NODE => {
+                    stack.pop().ok_or("Unbalanced device tree")?;
+                }
+                FDT_PROP => {
+                    let len = be32(dtb, pos)? as usize;
+                    let name = cstr(strings, be32(dtb, pos + 4)? as usize)?;
+                    let value = dtb.get(pos + 8..pos + 8 + len).ok_or("Device tree truncated")?;
+                    pos = (pos + 8 + len).next_multiple_of(4);
+                    let &node = stack.last().ok_or("Property outside a node")?;
+                    nodes[node].props.push((name, value));
+                }
+                FDT_NOP => {}
+                FDT_END => break,
+                _ => return Err("Invalid device tree token"),
+            }
+        }
+        if !stack.is_empty() {
+            return Err("Unbalanced device tree");
+        }
+        Ok(Fdt { nodes })
+    }
+
+    /// 从物理地址解析 DTB（启动时固件通过 a1 传入）
+    ///
+    /// # Safety
+    /// `addr` 处必须是可读的内存，并且在返回的 `Fdt` 使用期间保持不变
+    pub unsafe fn from_addr(addr: usize) -> Result<Fdt<'static>, &'static str> {
+        let total = Fdt::total_size(addr)?;
+        Fdt::parse(core::slice::from_raw_parts(addr as *const u8, total))
+    }
+
+    /// 物理地址处的 DTB 的总字节数（头部的 totalsize）
+    ///
+    /// # 说明
+    /// 解析出的节点引用 DTB 中的字符串，DTB 占用的内存要一直保留（见 `memory::init`）
+    ///
+    /// # Safety
+    /// `addr` 处必须是可读的内存
+    pub unsafe fn total_size(addr: usize) -> Result<usize, &'static str> {
+        let header = core::slice::from_raw_parts(addr as *const u8, HEADER_SIZE);
+        if be32(header, 0)? != FDT_MAGIC {
+            return Err("Not a device tree");
+        }
+        Ok(be32(header, 4)? as usize)
+    }
+
+    /// 全部节点（深度优先顺序）
+    pub fn nodes(&self) -> &[DtNode<'a>] {
+        &self.nodes
+    }
+
+    /// 按路径查找节点
+    pub fn find(&self, path: &str) -> Option<&DtNode<'a>> {
+        self.nodes.iter().find(|node| node.path == path)
+    }
+
+    /// 某个节点的直接子节点（按设备树中的顺序）
+    pub fn children<'b>(&'b self, path: &'b str) -> impl Iterator<Item = &'b DtNode<'a>> {
+        let prefix_len = if path == "/" { 1 } else { path.len() + 1 };
+        self.nodes.iter().filter(move |node| {
+            node.path.len() > prefix_len
+                && node.path.starts_with(path)
+                && (path == "/" || node.path.as_bytes()[path.len()] == b'/')
+                && !node.path[prefix_len..].contains('/')
+        })
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::SAMPLE_DTB;
+
+    #[test_case]
+    fn test_parse_sample_dtb() {
+        let fdt = Fdt::parse(SAMPLE_DTB).expect("parse failed");
+        assert_eq!(fdt.nodes()[0].path, "/");
+
+        let uart = fdt.find("/soc/serial@10000000").expect("uart missing");
+        assert_eq!(uart.reg(), Some((0x1000_0000, 0x100)));
+        assert_eq!(uart.prop_u32("interrupts"), Some(10));
+        let plic = fdt.find("/soc/plic@c000000").expect("plic missing");
+        assert_eq!(plic.compatibles().collect::<Vec<_>>(), ["sifive,plic-1.0.0", "riscv,plic0"]);
+
+        let soc: Vec<_> = fdt.children("/soc").map(|node| node.name).collect();
+        assert_eq!(soc.len(), 5);
+        assert_eq!(soc[1], "serial@10000000");
+        assert!(fdt.children("/").any(|node| node.name == "chosen"));
+
+        assert_eq!(Fdt::parse(&SAMPLE_DTB[4..]).err(), Some("Not a device tree"));
+        assert_eq!(Fdt::parse(&SAMPLE_DTB[..200]).err(), Some("Device tree truncated"));
+    }
+}
@@ -0,0 +1,229 @@
+/*
+ * ============================================
+ * 设备驱动框架
+ * ============================================
+ * 功能：按设备树的 compatible 匹配驱动并探测设备
+ *
+ * 原理：
+ * - 驱动用 driver! 宏声明一个 DriverSpec，放进 .drivers 段
+ *   （链接脚本用 __drivers_start / __drivers_end 标出范围）
+ * - 解析 DTB 之后，probe_all 遍历 /soc 的子节点，
+ *   按驱动的 needs 排序（中断控制器等被依赖的驱动先探测），
+ *   对每个匹配的节点调用 probe，成功的设备登记在注册表中
+ * - 已经登记的节点不会再次探测，probe_all 可以重复调用
+ * - 没有驱动匹配的节点只在第一次调用时列出
+ *
+ * 查询：device::by_compatible("ns16550a")
+ * ============================================
+ */
+
+pub mod fdt;
+pub mod plic;
+
+pub use fdt::{DtNode, Fdt};
+
+use crate::klog;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// 测试和演示用的设备树（QEMU virt 设备树的子集）
+pub static SAMPLE_DTB: &[u8] = include_bytes!("sample.dtb");
+
+/// 驱动描述
+pub struct DriverSpec {
+    /// 驱动名（needs 中引用的名字）
+    pub name: &'static str,
+    /// 能驱动的 compatible
+    pub compatibles: &'static [&'static str],
+    /// 必须先探测的驱动名（只决定顺序，被依赖的驱动探测失败时照常探测）
+    pub needs: &'static [&'static str],
+    /// 探测函数
+    pub probe: fn(&DtNode) -> Result<(), &'static str>,
+}
+
+impl DriverSpec {
+    /// 是否匹配设备树节点
+    pub fn matches(&self, node: &DtNode) -> bool {
+        node.compatibles().any(|c| self.compatibles.contains(&c))
+    }
+}
+
+/// 注册驱动（放进 .drivers 段，由 probe_all 找到）
+///
+/// # 示例
+/// ```ignore
+/// driver!(UART_DRIVER = DriverSpec { name: "uart16550", ... });
+/// ```
+macro_rules! driver {
+    ($ident:ident = $spec:expr) => {
+        #[used]
+        #[link_section = ".drivers"]
+        static $ident: $crate::device::DriverSpec = $spec;
+    };
+}
+pub(crate) use driver;
+
+/// 全部注册的驱动（顺序由链接器决定）
+pub fn drivers() -> &'static [DriverSpec] {
+    extern "C" {
+        static __drivers_start: u8;
+        static __drivers_end: u8;
+    }
+    unsafe {
+        let start = &__drivers_start as *const u8 as *const DriverSpec;
+        let end = &__drivers_end as *const u8 as *const DriverSpec;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// 按 needs 排序驱动：被依赖的在前，其余保持原来的顺序
+///
+/// # 返回
+/// - `Err`: 依赖成环（needs 中不存在的驱动名忽略）
+pub fn sort_by_needs<'a>(drivers: &[&'a DriverSpec]) -> Result<Vec<&'a DriverSpec>, &'static str> {
+    let mut sorted: Vec<&DriverSpec> = Vec::with_capacity(drivers.len());
+    while sorted.len() < drivers.len() {
+        let ready = drivers.iter().find(|driver| {
+            !sorted.iter().any(|done| done.name == driver.name)
+                && driver.needs.iter().all(|need| {
+                    sorted.iter().any(|done| done.name == *need)
+                        || !drivers.iter().any(|d| d.name == *need)
+                })
+        });
+        sorted.push(ready.ok_or("Driver dependency cycle")?);
+    }
+    Ok(sorted)
+}
+
+// ============================================
+// 设备注册表
+// ============================================
+
+/// 探测成功的设备
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// 设备树路径
+    pub path: String,
+    /// 节点的 compatible 列表
+    pub compatibles: Vec<String>,
+    /// 驱动名
+    pub driver: &'static str,
+    /// reg 的起始地址
+    pub base: Option<usize>,
+}
+
+/// 已探测的设备（按探测顺序）
+static DEVICES: Mutex<Vec<Device>> = Mutex::new(Vec::new());
+
+/// 是否已经列出过没有驱动的节点
+static UNMATCHED_LISTED: AtomicBool = AtomicBool::new(false);
+
+/// 按 compatible 查找已探测的设备（第一个匹配的）
+pub fn by_compatible(compatible: &str) -> Option<Device> {
+    let devices = DEVICES.lock();
+    devices.iter().find(|device| device.compatibles.iter().any(|c| c == compatible)).cloned()
+}
+
+/// 全部已探测的设备（按探测顺序）
+pub fn devices() -> Vec<Device> {
+    DEVICES.lock().clone()
+}
+
+/// 探测 /soc 下的全部设备
+///
+/// # 返回
+/// - `Ok(n)`: 本次新探测成功的设备数
+/// - `Err`: 驱动依赖成环
+///
+/// # 说明
+/// 探测失败的设备打印原因后跳过，不登记
+pub fn probe_all(fdt: &Fdt) -> Result<usize, &'static str> {
+    let all: Vec<&DriverSpec> = drivers().iter().collect();
+    probe_with(fdt, &sort_by_needs(&all)?)
+}
+
+/// 用给定顺序的驱动探测
+fn probe_with(fdt: &Fdt, drivers: &[&DriverSpec]) -> Result<usize, &'static str> {
+    let mut probed = 0;
+    for driver in drivers {
+        for node in fdt.children("/soc").filter(|node| driver.matches(node)) {
+            if DEVICES.lock().iter().any(|device| device.path == node.path) {
+                continue;
+            }
+            if let Err(e) = (driver.probe)(node) {
+                klog!("[DEVICE] {} ({}): probe failed: {}", node.path, driver.name, e);
+                continue;
+            }
+            klog!("[DEVICE] {} -> {}", node.path, driver.name);
+            DEVICES.lock().push(Device {
+                path: node.path.clone(),
+                compatibles: node.compatibles().map(|c| c.to_string()).collect(),
+                driver: driver.name,
+                base: node.reg().map(|(base, _)| base),
+            });
+            probed += 1;
+        }
+    }
+
+    if !UNMATCHED_LISTED.swap(true, Ordering::Relaxed) {
+        for node in fdt.children("/soc") {
+            if !drivers.iter().any(|driver| driver.matches(node)) {
+                klog!("[DEVICE] no driver for {}", node.path);
+            }
+        }
+    }
+    Ok(probed)
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_probe(_: &DtNode) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    const fn spec(name: &'static str, needs: &'static [&'static str]) -> DriverSpec {
+        DriverSpec { name, compatibles: &[], needs, probe: no_probe }
+    }
+
+    #[test_case]
+    fn test_sort_by_needs() {
+        let (uart, plic, rtc) = (spec("uart", &["plic"]), spec("plic", &[]), spec("rtc", &["x"]));
+        let sorted = sort_by_needs(&[&uart, &rtc, &plic]).unwrap();
+        let names: Vec<_> = sorted.iter().map(|driver| driver.name).collect();
+        assert_eq!(names, ["rtc", "plic", "uart"]);
+
+        let (a, b) = (spec("a", &["b"]), spec("b", &["a"]));
+        assert_eq!(sort_by_needs(&[&a, &b]).err(), Some("Driver dependency cycle"));
+    }
+
+    #[test_case]
+    fn test_probe_sample_dtb() {
+        let fdt = Fdt::parse(SAMPLE_DTB).expect("parse failed");
+        let names: Vec<_> = drivers().iter().map(|driver| driver.name).collect();
+        assert!(names.contains(&"plic") && names.contains(&"uart16550"));
+
+        // 设备树中串口在 PLIC 之前，但 PLIC 必须先探测
+        assert_eq!(probe_all(&fdt), Ok(2));
+        let order: Vec<_> = devices().iter().map(|device| device.driver).collect();
+        assert_eq!(order, ["plic", "uart16550"]);
+
+        let uart = by_compatible("ns16550a").expect("uart not registered");
+        assert_eq!((uart.path.as_str(), uart.base), ("/soc/serial@10000000", Some(0x1000_0000)));
+        let plic = by_compatible("riscv,plic0").expect("plic not registered");
+        assert_eq!(plic.base, Some(0x0c00_0000));
+        assert_eq!(plic::base(), Some(0x0c00_0000));
+        assert!(by_compatible("virtio,mmio").is_none());
+
+        // 再次探测不会重复登记
+        assert_eq!(probe_all(&fdt), Ok(0));
+        assert_eq!(devices().len(), 2);
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * ============================================
+ * PLIC（Platform-Level Interrupt Controller）驱动
+ * ============================================
+ * 功能：外部中断的优先级、使能、认领（claim）和完成（complete）
+ *
+ * 寄存器布局（相对基地址）：
+ *   0x000000 + 4 * irq              中断源优先级（0 表示屏蔽）
+ *   0x002000 + 0x80 * ctx           使能位图
+ *   0x200000 + 0x1000 * ctx         优先级阈值
+ *   0x200004 + 0x1000 * ctx         认领 / 完成
+ *
+ * 只使用 hart 0 的 S 模式上下文（ctx = 1）；基地址来自设备树
+ *
+ * 每个中断源的使能位和优先级另外记在影子表中，与写入硬件的值保持一致，
+ * 查询时读影子表（irqctl、/proc/interrupts 使用）
+ * ============================================
+ */
+
+use super::{driver, DriverSpec, DtNode};
+use core::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+/// hart 0 的 S 模式上下文
+const CONTEXT: usize = 1;
+
+const PRIORITY: usize = 0x0;
+const ENABLE: usize = 0x2000 + 0x80 * CONTEXT;
+const THRESHOLD: usize = 0x20_0000 + 0x1000 * CONTEXT;
+const CLAIM: usize = THRESHOLD + 4;
+
+/// 支持的中断源数（0 号保留表示"没有中断"，QEMU virt 的设备使用 1 - 95）
+pub const MAX_IRQS: usize = 128;
+
+/// 最高优先级（QEMU virt 的 PLIC 实现 7 级，0 表示永不触发）
+pub const MAX_PRIORITY: u8 = 7;
+
+/// PLIC 基地址（0 表示还没有探测到）
+static BASE: AtomicUsize = AtomicUsize::new(0);
+
+/// 影子表：各中断源的使能位（与 ENABLE 寄存器的位图相同）
+static ENABLED: [AtomicU32; MAX_IRQS / 32] = [const { AtomicU32::new(0) }; MAX_IRQS / 32];
+
+/// 影子表：各中断源的优先级
+static PRIORITIES: [AtomicU8; MAX_IRQS] = [const { AtomicU8::new(0) }; MAX_IRQS];
+
+driver!(PLIC_DRIVER = DriverSpec {
+    name: "plic",
+    compatibles: &["sifive,plic-1.0.0", "riscv,plic0"],
+    needs: &[],
+    probe,
+});
+
+/// 探测：记录基地址，阈值设为 0（接受所有优先级非 0 的中断），登记外部中断
+fn probe(node: &DtNode) -> Result<(), &'static str> {
+    let (base, _) = node.reg().ok_or("PLIC node has no reg")?;
+    BASE.store(base, Ordering::Relaxed);
+    unsafe { write(base + THRESHOLD, 0) };
+    crate::interrupts::register_source(crate::interrupts::InterruptSource::External);
+    Ok(())
+}
+
+fn read(addr: usize) -> u32 {
+    unsafe { (addr as *const u32).read_volatile() }
+}
+
+unsafe fn write(addr: usize, value: u32) {
+    (addr as *mut u32).write_volatile(value);
+}
+
+/// PLIC 基地址（还没有探测到时为 `None`）
+pub fn base() -> Option<usize> {
+    Some(BASE.load(Ordering::Relaxed)).filter(|&base| base != 0)
+}
+
+/// 检查中断源编号，返回基地址
+fn check_irq(irq: u32) -> Result<usize, &'static str> {
+    let base = base().ok_or("PLIC not probed")?;
+    if irq == 0 || irq as usize >= MAX_IRQS {
+        return Err("IRQ out of range");
+    }
+    Ok(base)
+}
+
+/// 使能一个中断源（优先级设为 1）
+///
+/// # 返回
+/// PLIC 还没有探测到时返回 false
+pub fn enable(irq: u32) -> bool {
+    set_priority(irq, 1).and_then(|_| set_enabled(irq, true)).is_ok()
+}
+
+/// 使能或屏蔽一个中断源
+///
+/// # 说明
+/// 屏蔽之后硬件不再向本 hart 发出这个中断源的中断；
+/// 屏蔽之前已经挂起的一次仍可能被认领（见 `interrupts::external_interrupt_handler`）
+///
+/// # 返回
+/// - `Err`: PLIC 还没有探测到，或编号超出范围
+pub fn set_enabled(irq: u32, enabled: bool) -> Result<(), &'static str> {
+    let base = check_irq(irq)?;
+    let (word, bit) = (irq as usize / 32, 1 << (irq % 32));
+    crate::interrupts::without_interrupts(|| {
+        let bits = if enabled {
+            ENABLED[word].fetch_or(bit, Ordering::Relaxed) | bit
+        } else {
+            ENABLED[word].fetch_and(!bit, Ordering::Relaxed) & !bit
+        };
+        unsafe { write(base + ENABLE + 4 * word, bits) };
+    });
+    Ok(())
+}
+
+/// 设置中断源的优先级（0 - MAX_PRIORITY，0 表示永不触发）
+///
+/// # 返回
+/// - `Err`: PLIC 还没有探测到，编号或优先级超出范围
+pub fn set_priority(irq: u32, priority: u8) -> Result<(), &'static str> {
+    let base = check_irq(irq)?;
+    if priority > MAX_PRIORITY {
+        return Err("Priority out of range");
+    }
+    PRIORITIES[irq as usize].store(priority, Ordering::Relaxed);
+    unsafe { write(base + PRIORITY + 4 * irq as usize, priority as u32) };
+    Ok(())
+}
+
+/// 中断源是否使能（读影子表）
+pub fn is_enabled(irq: u32) -> bool {
+    let irq = irq as usize;
+    irq < MAX_IRQS && ENABLED[irq / 32].load(Ordering::Relaxed) & 1 << (irq % 32) != 0
+}
+
+/// 中断源的优先级（读影子表）
+pub fn priority(irq: u32) -> u8 {
+    PRIORITIES.get(irq as usize).map_or(0, |priority| priority.load(Ordering::Relaxed))
+}
+
+/// 认领一个待处理的中断
+pub fn claim() -> Option<u32> {
+    Some(read(base()? + CLAIM)).filter(|&irq| irq != 0)
+}
+
+/// 通知中断处理完成
+pub fn complete(irq: u32) {
+    if let Some(base) = base() {
+        unsafe { write(base + CLAIM, irq) };
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{probe_all, Fdt, SAMPLE_DTB};
+
+    #[test_case]
+    fn test_shadow_matches_registers() {
+        let fdt = Fdt::parse(SAMPLE_DTB).expect("parse failed");
+        probe_all(&fdt).expect("probe failed");
+        let base = base().expect("PLIC not probed");
+
+        // 同一个字中的另一个中断源不受影响
+        const IRQ: u32 = 33;
+        let enable = base + ENABLE + 4;
+        set_enabled(IRQ + 1, true).unwrap();
+        set_enabled(IRQ, true).unwrap();
+        assert!(is_enabled(IRQ));
+        assert_eq!(read(enable), ENABLED[1].load(Ordering::Relaxed));
+        set_enabled(IRQ, false).unwrap();
+        assert!(!is_enabled(IRQ) && is_enabled(IRQ + 1));
+        assert_eq!(read(enable) & 0b110, 0b100);
+        set_enabled(IRQ + 1, false).unwrap();
+
+        set_priority(IRQ, 3).unwrap();
+        assert_eq!((priority(IRQ), read(base + PRIORITY + 4 * IRQ as usize)), (3, 3));
+        set_priority(IRQ, 0).unwrap();
+
+        assert_eq!(set_priority(IRQ, MAX_PRIORITY + 1), Err("Priority out of range"));
+        assert_eq!(set_enabled(0, true), Err("IRQ out of range"));
+        assert_eq!(set_enabled(MAX_IRQS as u32, true), Err("IRQ out of range"));
+    }
+}
@@ -0,0 +1,783 @@
+/*
+ * ============================================
+ * RISC-V 指令反汇编（诊断用）
+ * ============================================
+ * 功能：异常 / 系统调用诊断时显示出错指令
+ * 包括：
+ * - RV64GC 常用子集的解码（不分配堆内存）
+ * - 通过页表安全地读取指令字节
+ *
+ * 支持的指令：
+ * - RV64I：load/store、分支、jal/jalr、算术、ecall/ebreak
+ * - M / A 扩展、CSR 指令、sret/wfi/sfence.vma
+ * - F/D 扩展的 load/store
+ * - C 扩展（压缩指令，按 c.xxx 形式显示）
+ *
+ * 输出格式与 objdump -M no-aliases 接近：
+ *   0x00a50533 → add a0,a0,a0
+ * 分支 / 跳转目标显示为相对偏移（解码时不知道 PC）
+ * ============================================
+ */
+
+use crate::memory::{
+    walk_page_table, memory_end, PhysAddr, VirtAddr, MEMORY_START, PAGE_SIZE,
+};
+use core::fmt::{self, Write};
+
+/// 一次最多读取的指令字节数
+pub const FETCH_MAX: usize = 8;
+
+/// 反汇编文本缓冲区大小
+const INSN_TEXT_LEN: usize = 64;
+
+/// 整数寄存器 ABI 名称
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3",
+    "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+    "t3", "t4", "t5", "t6",
+];
+
+/// 浮点寄存器 ABI 名称
+const FREG_NAMES: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1",
+    "fa2", "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7",
+    "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+// ============================================
+// 文本缓冲区
+// ============================================
+
+/// 定长文本缓冲区（不依赖堆，可在异常处理中使用）
+///
+/// 超出容量的内容会被截断
+#[derive(Clone, Copy)]
+pub struct InsnText {
+    buf: [u8; INSN_TEXT_LEN],
+    len: usize,
+}
+
+impl InsnText {
+    /// 创建空缓冲区
+    pub const fn new() -> Self {
+        InsnText {
+            buf: [0; INSN_TEXT_LEN],
+            len: 0,
+        }
+    }
+
+    /// 获取文本内容
+    pub fn as_str(&self) -> &str {
+        // 只写入过完整的 UTF-8 片段（见 write_str）
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl Default for InsnText {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for InsnText {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = INSN_TEXT_LEN - self.len;
+        let mut n = s.len().min(room);
+        // 不在多字节字符中间截断
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+impl fmt::Display for InsnText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for InsnText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+// ============================================
+// 解码
+// ============================================
+
+/// 根据指令低 16 位判断指令长度（字节）
+///
+/// 低两位为 0b11 的是 32 位指令，其余为 16 位压缩指令
+pub fn insn_len(low: u16) -> usize {
+    if low & 0b11 == 0b11 {
+        4
+    } else {
+        2
+    }
+}
+
+/// 反汇编一条指令
+///
+/// # 参数
+/// - `word`: 指令编码（压缩指令只使用低 16 位）
+///
+/// # 返回
+/// 指令文本，无法识别时为 `unknown`
+pub fn decode(word: u32) -> InsnText {
+    let mut text = InsnText::new();
+    let known = if insn_len(word as u16) == 4 {
+        decode32(word, &mut text)
+    } else {
+        decode16(word as u16, &mut text)
+    };
+
+    if known.is_err() {
+        text = InsnText::new();
+        let _ = text.write_str("unknown");
+    }
+    text
+}
+
+/// 把符号扩展 `bits` 位的立即数
+fn sext(value: u32, bits: u32) -> i32 {
+    ((value << (32 - bits)) as i32) >> (32 - bits)
+}
+
+fn reg(index: u32) -> &'static str {
+    REG_NAMES[(index & 0x1f) as usize]
+}
+
+fn freg(index: u32) -> &'static str {
+    FREG_NAMES[(index & 0x1f) as usize]
+}
+
+/// 压缩指令中的 3 位寄存器编号（x8 ~ x15）
+fn creg(index: u16) -> &'static str {
+    REG_NAMES[8 + (index & 0x7) as usize]
+}
+
+fn cfreg(index: u16) -> &'static str {
+    FREG_NAMES[8 + (index & 0x7) as usize]
+}
+
+/// CSR 名称（只列出内核常用的几个）
+fn write_csr(out: &mut InsnText, csr: u32) -> fmt::Result {
+    let name = match csr {
+        0x100 => "sstatus",
+        0x104 => "sie",
+        0x105 => "stvec",
+        0x140 => "sscratch",
+        0x141 => "sepc",
+        0x142 => "scause",
+        0x143 => "stval",
+        0x144 => "sip",
+        0x180 => "satp",
+        0xc00 => "cycle",
+        0xc01 => "time",
+        0xc02 => "instret",
+        _ => return write!(out, "{:#x}", csr),
+    };
+    out.write_str(name)
+}
+
+/// fence 指令的 pred / succ 集合（iorw）
+fn write_fence_set(out: &mut InsnText, set: u32) -> fmt::Result {
+    for (bit, c) in [(8, "i"), (4, "o"), (2, "r"), (1, "w")] {
+        if set & bit != 0 {
+            out.write_str(c)?;
+        }
+    }
+    Ok(())
+}
+
+/// 解码 32 位指令
+///
+/// 无法识别时返回 `Err`
+fn decode32(w: u32, out: &mut InsnText) -> fmt::Result {
+    let opcode = w & 0x7f;
+    let rd = (w >> 7) & 0x1f;
+    let funct3 = (w >> 12) & 0x7;
+    let rs1 = (w >> 15) & 0x1f;
+    let rs2 = (w >> 20) & 0x1f;
+    let funct7 = w >> 25;
+
+    let imm_i = sext(w >> 20, 12);
+    let imm_s = sext(((w >> 25) << 5) | ((w >> 7) & 0x1f), 12);
+    let imm_b = sext(
+        ((w >> 31) << 12)
+            | (((w >> 7) & 0x1) << 11)
+            | (((w >> 25) & 0x3f) << 5)
+            | (((w >> 8) & 0xf) << 1),
+        13,
+    );
+    let imm_j = sext(
+        ((w >> 31) << 20)
+            | (((w >> 12) & 0xff) << 12)
+            | (((w >> 20) & 0x1) << 11)
+            | (((w >> 21) & 0x3ff) << 1),
+        21,
+    );
+
+    match opcode {
+        // LUI / AUIPC
+        0x37 => write!(out, "lui {},{:#x}", reg(rd), w >> 12),
+        0x17 => write!(out, "auipc {},{:#x}", reg(rd), w >> 12),
+
+        // JAL / JALR
+        0x6f => write!(out, "jal {},{}", reg(rd), imm_j),
+        0x67 if funct3 == 0 => write!(out, "jalr {},{}({})", reg(rd), imm_i, reg(rs1)),
+
+        // 条件分支
+        0x63 => {
+            let name = match funct3 {
+                0 => "beq",
+                1 => "bne",
+                4 => "blt",
+                5 => "bge",
+                6 => "bltu",
+                7 => "bgeu",
+                _ => return Err(fmt::Error),
+            };
+            write!(out, "{} {},{},{}", name, reg(rs1), reg(rs2), imm_b)
+        }
+
+        // 整数 load
+        0x03 => {
+            let name = match funct3 {
+                0 => "lb",
+                1 => "lh",
+                2 => "lw",
+                3 => "ld",
+                4 => "lbu",
+                5 => "lhu",
+                6 => "lwu",
+                _ => return Err(fmt::Error),
+            };
+            write!(out, "{} {},{}({})", name, reg(rd), imm_i, reg(rs1))
+        }
+
+        // 整数 store
+        0x23 => {
+            let name = match funct3 {
+                0 => "sb",
+                1 => "sh",
+                2 => "sw",
+                3 => "sd",
+                _ => return Err(fmt::Error),
+            };
+            write!(out, "{} {},{}({})", name, reg(rs2), imm_s, reg(rs1))
+        }
+
+        // 浮点 load / store
+        0x07 | 0x27 => {
+            let name = match (opcode, funct3) {
+                (0x07, 2) => "flw",
+                (0x07, 3) => "fld",
+                (0x27, 2) => "fsw",
+                (0x27, 3) => "fsd",
+                _ => return Err(fmt::Error),
+            };
+            if opcode == 0x07 {
+                write!(out, "{} {},{}({})", name, freg(rd), imm_i, reg(rs1))
+            } else {
+                write!(out, "{} {},{}({})", name, freg(rs2), imm_s, reg(rs1))
+            }
+        }
+
+        // 立即数运算
+        0x13 => {
+            let shamt = (w >> 20) & 0x3f;
+            match (funct3, w >> 26) {
+                (1, 0x00) => write!(out, "slli {},{},{}", reg(rd), reg(rs1), shamt),
+                (5, 0x00) => write!(out, "srli {},{},{}", reg(rd), reg(rs1), shamt),
+                (5, 0x10) => write!(out, "srai {},{},{}", reg(rd), reg(rs1), shamt),
+                (1, _) | (5, _) => Err(fmt::Error),
+                _ => {
+                    let name = ["addi", "", "slti", "sltiu", "xori", "", "ori", "andi"]
+                        [funct3 as usize];
+                    write!(out, "{} {},{},{}", name, reg(rd), reg(rs1), imm_i)
+                }
+            }
+        }
+
+        // 32 位立即数运算（RV64）
+        0x1b => {
+            let shamt = (w >> 20) & 0x1f;
+            match (funct3, funct7) {
+                (0, _) => write!(out, "addiw {},{},{}", reg(rd), reg(rs1), imm_i),
+                (1, 0x00) => write!(out, "slliw {},{},{}", reg(rd), reg(rs1), shamt),
+                (5, 0x00) => write!(out, "srliw {},{},{}", reg(rd), reg(rs1), shamt),
+                (5, 0x20) => write!(out, "sraiw {},{},{}", reg(rd), reg(rs1), shamt),
+                _ => Err(fmt::Error),
+            }
+        }
+
+        // 寄存器运算（含 M 扩展）
+        0x33 => {
+            let name = match (funct7, funct3) {
+                (0x00, 0) => "add",
+                (0x20, 0) => "sub",
+                (0x00, 1) => "sll",
+                (0x00, 2) => "slt",
+                (0x00, 3) => "sltu",
+                (0x00, 4) => "xor",
+                (0x00, 5) => "srl",
+                (0x20, 5) => "sra",
+                (0x00, 6) => "or",
+                (0x00, 7) => "and",
+                (0x01, f) => {
+                    ["mul", "mulh", "mulhsu", "mulhu", "div", "divu", "rem", "remu"][f as usize]
+                }
+                _ => return Err(fmt::Error),
+            };
+            write!(out, "{} {},{},{}", name, reg(rd), reg(rs1), reg(rs2))
+        }
+
+        // 32 位寄存器运算（RV64，含 M 扩展）
+        0x3b => {
+            let name = match (funct7, funct3) {
+                (0x00, 0) => "addw",
+                (0x20, 0) => "subw",
+                (0x00, 1) => "sllw",
+                (0x00, 5) => "srlw",
+                (0x20, 5) => "sraw",
+                (0x01, 0) => "mulw",
+                (0x01, 4) => "divw",
+                (0x01, 5) => "divuw",
+                (0x01, 6) => "remw",
+                (0x01, 7) => "remuw",
+                _ => return Err(fmt::Error),
+            };
+            write!(out, "{} {},{},{}", name, reg(rd), reg(rs1), reg(rs2))
+        }
+
+        // 原子指令（A 扩展）
+        0x2f => {
+            let width = match funct3 {
+                2 => "w",
+                3 => "d",
+                _ => return Err(fmt::Error),
+            };
+            let name = match w >> 27 {
+                0x00 => "amoadd",
+                0x01 => "amoswap",
+                0x02 => "lr",
+                0x03 => "sc",
+                0x04 => "amoxor",
+                0x08 => "amoor",
+                0x0c => "amoand",
+                0x10 => "amomin",
+                0x14 => "amomax",
+                0x18 => "amominu",
+                0x1c => "amomaxu",
+                _ => return Err(fmt::Error),
+            };
+            let order = match (w >> 25) & 0x3 {
+                0b10 => ".aq",
+                0b01 => ".rl",
+                0b11 => ".aqrl",
+                _ => "",
+            };
+            if name == "lr" {
+                if rs2 != 0 {
+                    return Err(fmt::Error);
+                }
+                write!(out, "lr.{}{} {},({})", width, order, reg(rd), reg(rs1))
+            } else {
+                write!(
+                    out,
+                    "{}.{}{} {},{},({})",
+                    name,
+                    width,
+                    order,
+                    reg(rd),
+                    reg(rs2),
+                    reg(rs1)
+                )
+            }
+        }
+
+        // FENCE / FENCE.I
+        0x0f if rd != 0 || rs1 != 0 => Err(fmt::Error),
+        0x0f => match funct3 {
+            0 if w >> 28 == 0 => {
+                out.write_str("fence ")?;
+                write_fence_set(out, (w >> 24) & 0xf)?;
+                out.write_str(",")?;
+                write_fence_set(out, (w >> 20) & 0xf)
+            }
+            1 if w >> 20 == 0 => out.write_str("fence.i"),
+            _ => Err(fmt::Error),
+        },
+
+        // 系统指令与 CSR
+        0x73 => match funct3 {
+            0 => match w {
+                0x0000_0073 => out.write_str("ecall"),
+                0x0010_0073 => out.write_str("ebreak"),
+                0x1020_0073 => out.write_str("sret"),
+                0x3020_0073 => out.write_str("mret"),
+                0x1050_0073 => out.write_str("wfi"),
+                _ if funct7 == 0x09 && rd == 0 => {
+                    write!(out, "sfence.vma {},{}", reg(rs1), reg(rs2))
+                }
+                _ => Err(fmt::Error),
+            },
+            4 => Err(fmt::Error),
+            _ => {
+                let name = ["", "csrrw", "csrrs", "csrrc", "", "csrrwi", "csrrsi", "csrrci"]
+                    [funct3 as usize];
+                write!(out, "{} {},", name, reg(rd))?;
+                write_csr(out, w >> 20)?;
+                if funct3 < 4 {
+                    write!(out, ",{}", reg(rs1))
+                } else {
+                    // rs1 字段是 5 位无符号立即数
+                    write!(out, ",{}", rs1)
+                }
+            }
+        },
+
+        _ => Err(fmt::Error),
+    }
+}
+
+/// 解码 16 位压缩指令
+///
+/// 无法识别时返回 `Err`
+fn decode16(w: u16, out: &mut InsnText) -> fmt::Result {
+    let funct3 = w >> 13;
+    // 完整寄存器编号（CR/CI 格式）
+    let rd = ((w >> 7) & 0x1f) as u32;
+    let rs2 = ((w >> 2) & 0x1f) as u32;
+    // 3 位寄存器编号（CIW/CL/CS/CB 格式）
+    let rd_c = (w >> 2) & 0x7;
+    let rs1_c = (w >> 7) & 0x7;
+    // CI 格式的 6 位立即数（移位量）：imm[5] = bit12，imm[4:0] = bits[6:2]
+    let imm6 = (((w >> 7) & 0x20) | ((w >> 2) & 0x1f)) as u32;
+    let simm6 = sext(imm6, 6);
+
+    // 各格式的偏移量
+    let uimm_w = ((w >> 7) & 0x38) | ((w >> 4) & 0x4) | ((w << 1) & 0x40);
+    let uimm_d = ((w >> 7) & 0x38) | ((w << 1) & 0xc0);
+
+    if w == 0 {
+        return out.write_str("unimp");
+    }
+
+    match (w & 0b11, funct3) {
+        // ---------- Quadrant 0 ----------
+        (0b00, 0) => {
+            let imm = ((w >> 7) & 0x30) | ((w >> 1) & 0x3c0) | ((w >> 4) & 0x4) | ((w >> 2) & 0x8);
+            if imm == 0 {
+                return Err(fmt::Error);
+            }
+            write!(out, "c.addi4spn {},sp,{}", creg(rd_c), imm)
+        }
+        (0b00, 1) => write!(out, "c.fld {},{}({})", cfreg(rd_c), uimm_d, creg(rs1_c)),
+        (0b00, 2) => write!(out, "c.lw {},{}({})", creg(rd_c), uimm_w, creg(rs1_c)),
+        (0b00, 3) => write!(out, "c.ld {},{}({})", creg(rd_c), uimm_d, creg(rs1_c)),
+        (0b00, 5) => write!(out, "c.fsd {},{}({})", cfreg(rd_c), uimm_d, creg(rs1_c)),
+        (0b00, 6) => write!(out, "c.sw {},{}({})", creg(rd_c), uimm_w, creg(rs1_c)),
+        (0b00, 7) => write!(out, "c.sd {},{}({})", creg(rd_c), uimm_d, creg(rs1_c)),
+
+        // ---------- Quadrant 1 ----------
+        (0b01, 0) if rd == 0 => out.write_str("c.nop"),
+        (0b01, 0) => write!(out, "c.addi {},{}", reg(rd), simm6),
+        (0b01, 1) if rd != 0 => write!(out, "c.addiw {},{}", reg(rd), simm6),
+        (0b01, 2) => write!(out, "c.li {},{}", reg(rd), simm6),
+        (0b01, 3) if rd == 2 => {
+            let imm = ((w >> 3) & 0x200)
+                | ((w >> 2) & 0x10)
+                | ((w << 1) & 0x40)
+                | ((w << 4) & 0x180)
+                | ((w << 3) & 0x20);
+            if imm == 0 {
+                return Err(fmt::Error);
+            }
+            write!(out, "c.addi16sp sp,{}", sext(imm as u32, 10))
+        }
+        (0b01, 3) if rd != 0 && imm6 != 0 => {
+            // 立即数放在 [17:12]，按 20 位 lui 立即数显示
+            let imm = simm6 as u32 & 0xfffff;
+            write!(out, "c.lui {},{:#x}", reg(rd), imm)
+        }
+        (0b01, 4) => match (w >> 10) & 0x3 {
+            0 => write!(out, "c.srli {},{}", creg(rs1_c), imm6),
+            1 => write!(out, "c.srai {},{}", creg(rs1_c), imm6),
+            2 => write!(out, "c.andi {},{}", creg(rs1_c), simm6),
+            _ => {
+                let name = match ((w >> 12) & 0x1, (w >> 5) & 0x3) {
+                    (0, 0) => "c.sub",
+                    (0, 1) => "c.xor",
+                    (0, 2) => "c.or",
+                    (0, 3) => "c.and",
+                    (1, 0) => "c.subw",
+                    (1, 1) => "c.addw",
+                    _ => return Err(fmt::Error),
+                };
+                write!(out, "{} {},{}", name, creg(rs1_c), creg(rd_c))
+            }
+        },
+        (0b01, 5) => {
+            let imm = ((w >> 1) & 0x800)
+                | ((w >> 7) & 0x10)
+                | ((w >> 1) & 0x300)
+                | ((w << 2) & 0x400)
+                | ((w >> 1) & 0x40)
+                | ((w << 1) & 0x80)
+                | ((w >> 2) & 0xe)
+                | ((w << 3) & 0x20);
+            write!(out, "c.j {}", sext(imm as u32, 12))
+        }
+        (0b01, 6) | (0b01, 7) => {
+            let imm = ((w >> 4) & 0x100)
+                | ((w >> 7) & 0x18)
+                | ((w << 1) & 0xc0)
+                | ((w >> 2) & 0x6)
+                | ((w << 3) & 0x20);
+            let name = if funct3 == 6 { "c.beqz" } else { "c.bnez" };
+            write!(out, "{} {},{}", name, creg(rs1_c), sext(imm as u32, 9))
+        }
+
+        // ---------- Quadrant 2 ----------
+        (0b10, 0) if rd != 0 => write!(out, "c.slli {},{}", reg(rd), imm6),
+        (0b10, 1) => {
+            let imm = ((w >> 7) & 0x20) | ((w >> 2) & 0x18) | ((w << 4) & 0x1c0);
+            write!(out, "c.fldsp {},{}(sp)", freg(rd), imm)
+        }
+        (0b10, 2) if rd != 0 => {
+            let imm = ((w >> 7) & 0x20) | ((w >> 2) & 0x1c) | ((w << 4) & 0xc0);
+            write!(out, "c.lwsp {},{}(sp)", reg(rd), imm)
+        }
+        (0b10, 3) if rd != 0 => {
+            let imm = ((w >> 7) & 0x20) | ((w >> 2) & 0x18) | ((w << 4) & 0x1c0);
+            write!(out, "c.ldsp {},{}(sp)", reg(rd), imm)
+        }
+        (0b10, 4) => match ((w >> 12) & 0x1, rd, rs2) {
+            (0, 0, 0) => Err(fmt::Error),
+            (0, _, 0) => write!(out, "c.jr {}", reg(rd)),
+            (0, _, _) => write!(out, "c.mv {},{}", reg(rd), reg(rs2)),
+            (_, 0, 0) => out.write_str("c.ebreak"),
+            (_, _, 0) => write!(out, "c.jalr {}", reg(rd)),
+            (_, _, _) => write!(out, "c.add {},{}", reg(rd), reg(rs2)),
+        },
+        (0b10, 5) => {
+            let imm = ((w >> 7) & 0x38) | ((w >> 1) & 0x1c0);
+            write!(out, "c.fsdsp {},{}(sp)", freg(rs2), imm)
+        }
+        (0b10, 6) => {
+            let imm = ((w >> 7) & 0x3c) | ((w >> 1) & 0xc0);
+            write!(out, "c.swsp {},{}(sp)", reg(rs2), imm)
+        }
+        (0b10, 7) => {
+            let imm = ((w >> 7) & 0x38) | ((w >> 1) & 0x1c0);
+            write!(out, "c.sdsp {},{}(sp)", reg(rs2), imm)
+        }
+
+        _ => Err(fmt::Error),
+    }
+}
+
+// ============================================
+// 取指
+// ============================================
+
+/// 当前 satp 指向的根页表（Bare 模式返回 `None`）
+pub fn current_root() -> Option<PhysAddr> {
+    use riscv::register::satp;
+
+    let satp = satp::read();
+    match satp.mode() {
+        satp::Mode::Bare => None,
+        _ => Some(PhysAddr::new(satp.ppn() << 12)),
+    }
+}
+
+/// 把虚拟地址转换为可直接访问的物理地址
+///
+/// 只接受落在 DRAM 中的结果，避免读到 MMIO 或不存在的内存
+fn translate(root: Option<PhysAddr>, vaddr: usize) -> Option<usize> {
+    let paddr = match root {
+        Some(root) => walk_page_table(root, VirtAddr::new(vaddr))?.as_usize(),
+        None => vaddr,
+    };
+
+    if (MEMORY_START..memory_end()).contains(&paddr) {
+        Some(paddr)
+    } else {
+        None
+    }
+}
+
+/// 通过页表读取 `vaddr` 开始的最多 `FETCH_MAX` 个字节
+///
+/// # 参数
+/// - `root`: 根页表（`None` 表示未开启分页）
+/// - `vaddr`: 起始虚拟地址
+/// - `buf`: 输出缓冲区
+///
+/// # 返回
+/// 实际读取的字节数，遇到未映射的页面时提前停止
+///
+/// # 说明
+/// 每一页都先查页表再访问，因此不会在读取过程中触发页错误
+pub fn fetch_bytes(root: Option<PhysAddr>, vaddr: usize, buf: &mut [u8; FETCH_MAX]) -> usize {
+    let mut count = 0;
+
+    while count < FETCH_MAX {
+        let addr = match vaddr.checked_add(count) {
+            Some(addr) => addr,
+            None => break,
+        };
+        let paddr = match translate(root, addr) {
+            Some(paddr) => paddr,
+            None => break,
+        };
+
+        // 本页剩余的字节
+        let chunk = (PAGE_SIZE - addr % PAGE_SIZE).min(FETCH_MAX - count);
+        for (i, byte) in buf[count..count + chunk].iter_mut().enumerate() {
+            *byte = unsafe { core::ptr::read_volatile((paddr + i) as *const u8) };
+        }
+        count += chunk;
+    }
+
+    count
+}
+
+/// 读取 `vaddr` 处的一条指令
+///
+/// # 返回
+/// - `Some(word)`: 指令编码（压缩指令只有低 16 位）
+/// - `None`: 地址未映射或指令不完整
+pub fn fetch_insn(root: Option<PhysAddr>, vaddr: usize) -> Option<u32> {
+    let mut buf = [0u8; FETCH_MAX];
+    let count = fetch_bytes(root, vaddr, &mut buf);
+
+    if count < 2 {
+        return None;
+    }
+    let low = u16::from_le_bytes([buf[0], buf[1]]);
+    if insn_len(low) == 2 {
+        return Some(low as u32);
+    }
+    if count < 4 {
+        return None;
+    }
+    Some(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+}
+
+/// 格式化一条指令：`0x00a50533 (add a0,a0,a0)`
+pub fn describe(word: u32) -> InsnText {
+    let mut text = InsnText::new();
+    let _ = if insn_len(word as u16) == 2 {
+        write!(text, "{:#06x} ({})", word & 0xffff, decode(word))
+    } else {
+        write!(text, "{:#010x} ({})", word, decode(word))
+    };
+    text
+}
+
+/// 格式化 `pc` 处的指令（使用当前页表）
+///
+/// 读取失败时返回 `<unavailable>`，用于异常诊断输出
+pub fn describe_at(pc: usize) -> InsnText {
+    match fetch_insn(current_root(), pc) {
+        Some(word) => describe(word),
+        None => {
+            let mut text = InsnText::new();
+            let _ = text.write_str("<unavailable>");
+            text
+        }
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_decode_rv64i() {
+        let cases: [(u32, &str); 16] = [
+            (0x00a5_0533, "add a0,a0,a0"),
+            (0x0081_3083, "ld ra,8(sp)"),
+            (0xfe81_3823, "sd s0,-16(sp)"),
+            (0x00b5_0863, "beq a0,a1,16"),
+            (0xfeb5_7ee3, "bgeu a0,a1,-4"),
+            (0x801f_f0ef, "jal ra,-2048"),
+            (0x00c5_82e7, "jalr t0,12(a1)"),
+            (0x1234_57b7, "lui a5,0x12345"),
+            (0xfe01_0113, "addi sp,sp,-32"),
+            (0x03f5_9513, "slli a0,a1,63"),
+            (0x4053_d313, "srai t1,t2,5"),
+            (0x40c5_853b, "subw a0,a1,a2"),
+            (0x02c5_8533, "mul a0,a1,a2"),
+            (0x06a4_a223, "sw a0,100(s1)"),
+            (0x00b6_352f, "amoadd.d a0,a1,(a2)"),
+            (0x1005_a52f, "lr.w a0,(a1)"),
+        ];
+        for (word, text) in cases {
+            assert_eq!(decode(word).as_str(), text);
+        }
+    }
+
+    #[test_case]
+    fn test_decode_system_and_compressed() {
+        let cases: [(u32, &str); 12] = [
+            (0x0000_0073, "ecall"),
+            (0x0010_0073, "ebreak"),
+            (0x1020_0073, "sret"),
+            (0x1005_9573, "csrrw a0,sstatus,a1"),
+            (0x1410_22f3, "csrrs t0,sepc,zero"),
+            (0x1200_0073, "sfence.vma zero,zero"),
+            (0x4501, "c.li a0,0"),
+            (0x9002, "c.ebreak"),
+            (0x8082, "c.jr ra"),
+            (0x1141, "c.addi sp,-16"),
+            (0xe406, "c.sdsp ra,8(sp)"),
+            (0xffff_ffff, "unknown"),
+        ];
+        for (word, text) in cases {
+            assert_eq!(decode(word).as_str(), text);
+        }
+        assert_eq!(describe(0x00a5_0533).as_str(), "0x00a50533 (add a0,a0,a0)");
+        assert_eq!(describe(0x4501).as_str(), "0x4501 (c.li a0,0)");
+    }
+
+    // 已知指令序列：一条 32 位 add 和一条压缩 c.li
+    core::arch::global_asm!(
+        ".pushsection .text",
+        ".globl disasm_test_insns",
+        ".p2align 2",
+        "disasm_test_insns:",
+        ".option push",
+        ".option norvc",
+        "   add a0, a0, a0",
+        ".option pop",
+        "   .2byte 0x4501",
+        ".popsection",
+    );
+
+    #[test_case]
+    fn test_fetch_and_describe_kernel_code() {
+        extern "C" {
+            fn disasm_test_insns();
+        }
+        let pc = disasm_test_insns as usize;
+
+        assert_eq!(fetch_insn(current_root(), pc), Some(0x00a5_0533));
+        assert_eq!(describe_at(pc).as_str(), "0x00a50533 (add a0,a0,a0)");
+        assert_eq!(describe_at(pc + 4).as_str(), "0x4501 (c.li a0,0)");
+        // 不在内存中的地址不会触发异常
+        assert_eq!(describe_at(0x10).as_str(), "<unavailable>");
+    }
+}
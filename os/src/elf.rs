@@ -0,0 +1,368 @@
+/*
+ * ============================================
+ * ELF 加载与校验
+ * ============================================
+ * 功能：把 ELF64（RISC-V，小端）可执行文件的 PT_LOAD 段映射进地址空间，
+ *       并在加载后逐字节校验映射的内容
+ *
+ * 每个 PT_LOAD 段：
+ *   [p_vaddr, p_vaddr + p_filesz)           来自文件 [p_offset, p_offset + p_filesz)
+ *   [p_vaddr + p_filesz, p_vaddr + p_memsz)  bss，必须为 0
+ *
+ * 加载器只处理到页粒度：段按页扩展为一个区域（可执行段为 Code，其余为 Data），
+ * 相邻的两个段不能共用一页
+ *
+ * verify_loaded 通过地址空间的页表重新读取每个段，
+ * 用来发现加载器复制错位、漏清 bss 等问题
+ * ============================================
+ */
+
+use crate::memory::{
+    phys_to_kvirt, AddressSpace, MemoryAreaType, SimpleFrameAllocator, VirtAddr, PAGE_SIZE,
+};
+use alloc::vec::Vec;
+
+/// 程序头类型：可加载段
+pub const PT_LOAD: u32 = 1;
+
+/// 段标志：可执行
+pub const PF_X: u32 = 1;
+
+/// 段标志：可写
+pub const PF_W: u32 = 2;
+
+/// 段标志：可读
+pub const PF_R: u32 = 4;
+
+/// e_machine：RISC-V
+const EM_RISCV: u16 = 243;
+
+/// ELF 文件头大小
+const EHDR_SIZE: usize = 64;
+
+/// 程序头大小
+const PHDR_SIZE: usize = 56;
+
+/// ELF 加载 / 校验错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfError {
+    /// 文件太短，或程序头、段内容超出文件
+    Truncated,
+    /// 不是 ELF64 小端 RISC-V 可执行文件
+    BadHeader,
+    /// 段的 filesz 大于 memsz，或地址溢出
+    BadSegment,
+    /// 映射段失败（map_region 的错误）
+    Map(&'static str),
+    /// 段中的地址没有映射
+    NotMapped { vaddr: usize },
+    /// 映射的内容与文件不同
+    Mismatch { vaddr: usize, expected: u8, found: u8 },
+    /// bss 部分没有清零
+    BssNotZeroed { vaddr: usize, found: u8 },
+}
+
+/// 一个 PT_LOAD 段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    /// 起始虚拟地址
+    pub vaddr: usize,
+    /// 在文件中的偏移
+    pub offset: usize,
+    /// 文件中的字节数
+    pub filesz: usize,
+    /// 内存中的字节数（超出 filesz 的部分为 bss）
+    pub memsz: usize,
+    /// PF_R / PF_W / PF_X
+    pub flags: u32,
+}
+
+impl Segment {
+    /// 段在文件中的内容
+    fn file_bytes<'a>(&self, elf: &'a [u8]) -> &'a [u8] {
+        &elf[self.offset..self.offset + self.filesz]
+    }
+}
+
+// ============================================
+// 解析
+// ============================================
+
+/// 读取小端整数
+fn read_le<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N], ElfError> {
+    bytes
+        .get(offset..offset + N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(ElfError::Truncated)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ElfError> {
+    read_le(bytes, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ElfError> {
+    read_le(bytes, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<usize, ElfError> {
+    read_le(bytes, offset).map(|b| u64::from_le_bytes(b) as usize)
+}
+
+/// 检查文件头
+fn check_header(elf: &[u8]) -> Result<(), ElfError> {
+    let ident = elf.get(..EHDR_SIZE).ok_or(ElfError::Truncated)?;
+    // 魔数、ELFCLASS64、ELFDATA2LSB
+    if ident[..4] != *b"\x7fELF" || ident[4] != 2 || ident[5] != 1 {
+        return Err(ElfError::BadHeader);
+    }
+    let e_type = read_u16(elf, 16)?;
+    // ET_EXEC 或 ET_DYN
+    if !(e_type == 2 || e_type == 3) || read_u16(elf, 18)? != EM_RISCV {
+        return Err(ElfError::BadHeader);
+    }
+    if read_u16(elf, 54)? as usize != PHDR_SIZE {
+        return Err(ElfError::BadHeader);
+    }
+    Ok(())
+}
+
+/// 入口地址（e_entry）
+pub fn entry(elf: &[u8]) -> Result<usize, ElfError> {
+    check_header(elf)?;
+    read_u64(elf, 24)
+}
+
+/// 解析全部 PT_LOAD 段
+///
+/// # 返回
+/// - `Err`: 文件头无效、程序头或段内容超出文件、filesz > memsz
+pub fn load_segments(elf: &[u8]) -> Result<Vec<Segment>, ElfError> {
+    check_header(elf)?;
+    let phoff = read_u64(elf, 32)?;
+    let phnum = read_u16(elf, 56)? as usize;
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let ph = phoff.checked_add(i * PHDR_SIZE).ok_or(ElfError::Truncated)?;
+        if read_u32(elf, ph)? != PT_LOAD {
+            continue;
+        }
+        let segment = Segment {
+            flags: read_u32(elf, ph + 4)?,
+            offset: read_u64(elf, ph + 8)?,
+            vaddr: read_u64(elf, ph + 16)?,
+            filesz: read_u64(elf, ph + 32)?,
+            memsz: read_u64(elf, ph + 40)?,
+        };
+        if segment.filesz > segment.memsz || segment.vaddr.checked_add(segment.memsz).is_none() {
+            return Err(ElfError::BadSegment);
+        }
+        match segment.offset.checked_add(segment.filesz) {
+            Some(end) if end <= elf.len() => segments.push(segment),
+            _ => return Err(ElfError::Truncated),
+        }
+    }
+    Ok(segments)
+}
+
+// ============================================
+// 加载与校验
+// ============================================
+
+/// `vaddr` 开始、不跨页的一段映射内容在内核中的地址和长度
+///
+/// # 返回
+/// 最多 `len` 字节，到页末为止（物理内存通过 `phys_to_kvirt` 访问）
+fn mapped_range(
+    space: &AddressSpace,
+    vaddr: usize,
+    len: usize,
+) -> Result<(usize, usize), ElfError> {
+    let paddr = space.translate(VirtAddr::new(vaddr)).ok_or(ElfError::NotMapped { vaddr })?;
+    Ok((phys_to_kvirt(paddr).as_usize(), len.min(PAGE_SIZE - vaddr % PAGE_SIZE)))
+}
+
+/// 读取 `vaddr` 开始、不跨页的一段映射内容（见 `mapped_range`）
+fn mapped_bytes(space: &AddressSpace, vaddr: usize, len: usize) -> Result<&[u8], ElfError> {
+    let (addr, len) = mapped_range(space, vaddr, len)?;
+    Ok(unsafe { core::slice::from_raw_parts(addr as *const u8, len) })
+}
+
+/// 同 `mapped_bytes`，可写
+fn mapped_bytes_mut(
+    space: &mut AddressSpace,
+    vaddr: usize,
+    len: usize,
+) -> Result<&mut [u8], ElfError> {
+    let (addr, len) = mapped_range(space, vaddr, len)?;
+    Ok(unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) })
+}
+
+/// 把 ELF 的全部 PT_LOAD 段映射进地址空间
+///
+/// # 参数
+/// - `space`: 目标地址空间（Sv39）
+/// - `elf`: 文件内容
+/// - `allocator`: 帧分配器
+///
+/// # 返回
+/// - `Ok(entry)`: 入口地址
+/// - `Err`: 解析失败，或段映射失败（已经映射的段保持映射）
+pub fn load(
+    space: &mut AddressSpace,
+    elf: &[u8],
+    allocator: &mut SimpleFrameAllocator,
+) -> Result<usize, ElfError> {
+    let entry = entry(elf)?;
+    for segment in load_segments(elf)? {
+        if segment.memsz == 0 {
+            continue;
+        }
+        let start = segment.vaddr & !(PAGE_SIZE - 1);
+        let end = (segment.vaddr + segment.memsz).next_multiple_of(PAGE_SIZE);
+        let area_type = if segment.flags & PF_X != 0 {
+            MemoryAreaType::Code
+        } else {
+            MemoryAreaType::Data
+        };
+        space
+            .map_region(VirtAddr::new(start), end - start, area_type, allocator)
+            .map_err(ElfError::Map)?;
+
+        // 新页帧的内容未定义：整页清零（bss 和页内空隙），再复制文件内容
+        for page in (start..end).step_by(PAGE_SIZE) {
+            mapped_bytes_mut(space, page, PAGE_SIZE)?.fill(0);
+        }
+        let mut copied = 0;
+        let data = segment.file_bytes(elf);
+        while copied < data.len() {
+            let dst = mapped_bytes_mut(space, segment.vaddr + copied, data.len() - copied)?;
+            let len = dst.len();
+            dst.copy_from_slice(&data[copied..copied + len]);
+            copied += len;
+        }
+    }
+    Ok(entry)
+}
+
+/// 校验加载结果：每个 PT_LOAD 段映射的内容与文件一致，bss 为 0
+///
+/// # 返回
+/// - `Err(NotMapped)`: 段中有地址没有映射
+/// - `Err(Mismatch)`: 第一个与文件不同的字节
+/// - `Err(BssNotZeroed)`: bss 中第一个非零字节
+pub fn verify_loaded(space: &AddressSpace, elf: &[u8]) -> Result<(), ElfError> {
+    for segment in load_segments(elf)? {
+        let data = segment.file_bytes(elf);
+        let mut done = 0;
+        while done < segment.memsz {
+            let vaddr = segment.vaddr + done;
+            let mapped = mapped_bytes(space, vaddr, segment.memsz - done)?;
+            for (i, &found) in mapped.iter().enumerate() {
+                let vaddr = vaddr + i;
+                match data.get(done + i) {
+                    Some(&expected) if expected != found => {
+                        return Err(ElfError::Mismatch { vaddr, expected, found });
+                    }
+                    None if found != 0 => return Err(ElfError::BssNotZeroed { vaddr, found }),
+                    _ => {}
+                }
+            }
+            done += mapped.len();
+        }
+    }
+    Ok(())
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::test_frame_allocator;
+
+    const TEXT: usize = 0x1_0000;
+    const DATA: usize = 0x1_1ff8;
+    const BSS_LEN: usize = 0x20;
+
+    /// 构造一个两段的小 ELF：代码段和跨页的数据段（后接 bss）
+    fn tiny_elf(code: &[u8], data: &[u8]) -> Vec<u8> {
+        let text_off = EHDR_SIZE + 2 * PHDR_SIZE;
+        let data_off = text_off + code.len();
+        let mut elf = Vec::new();
+
+        elf.extend_from_slice(b"\x7fELF\x02\x01\x01");
+        elf.resize(16, 0);
+        elf.extend_from_slice(&2u16.to_le_bytes());
+        elf.extend_from_slice(&EM_RISCV.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        for value in [TEXT, EHDR_SIZE, 0] {
+            elf.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        for value in [EHDR_SIZE, PHDR_SIZE, 2, 0, 0, 0] {
+            elf.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+
+        let segments = [
+            (PF_R | PF_X, text_off, TEXT, code.len(), code.len()),
+            (PF_R | PF_W, data_off, DATA, data.len(), data.len() + BSS_LEN),
+        ];
+        for (flags, offset, vaddr, filesz, memsz) in segments {
+            elf.extend_from_slice(&PT_LOAD.to_le_bytes());
+            elf.extend_from_slice(&flags.to_le_bytes());
+            for value in [offset, vaddr, vaddr, filesz, memsz, PAGE_SIZE] {
+                elf.extend_from_slice(&(value as u64).to_le_bytes());
+            }
+        }
+        elf.extend_from_slice(code);
+        elf.extend_from_slice(data);
+        elf
+    }
+
+    #[test_case]
+    fn test_verify_loaded_detects_corruption() {
+        let code = [0x13, 0x05, 0x10, 0x00, 0x73, 0x00, 0x00, 0x00];
+        let data: Vec<u8> = (1..=24).collect();
+        let elf = tiny_elf(&code, &data);
+
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        assert_eq!(load(&mut space, &elf, &mut allocator), Ok(TEXT));
+        assert_eq!(verify_loaded(&space, &elf), Ok(()));
+
+        // 数据段跨页：破坏第二页中的一个字节
+        let vaddr = DATA + 10;
+        mapped_bytes_mut(&mut space, vaddr, 1).unwrap()[0] ^= 0xff;
+        let mismatch = ElfError::Mismatch { vaddr, expected: 11, found: 11 ^ 0xff };
+        assert_eq!(verify_loaded(&space, &elf), Err(mismatch));
+        mapped_bytes_mut(&mut space, vaddr, 1).unwrap()[0] ^= 0xff;
+
+        // bss 中的非零字节
+        let vaddr = DATA + data.len() + BSS_LEN - 1;
+        mapped_bytes_mut(&mut space, vaddr, 1).unwrap()[0] = 0x5a;
+        let bss = ElfError::BssNotZeroed { vaddr, found: 0x5a };
+        assert_eq!(verify_loaded(&space, &elf), Err(bss));
+
+        space.destroy(&mut allocator);
+    }
+
+    #[test_case]
+    fn test_load_rejects_bad_files() {
+        let elf = tiny_elf(&[0; 4], &[1, 2, 3]);
+        assert_eq!(load_segments(&elf[..EHDR_SIZE]), Err(ElfError::Truncated));
+        assert_eq!(load_segments(&elf[..elf.len() - 1]), Err(ElfError::Truncated));
+
+        let mut bad = elf.clone();
+        bad[18] = 0x3e;
+        assert_eq!(load_segments(&bad), Err(ElfError::BadHeader));
+
+        // 第一个程序头的 filesz 大于 memsz
+        let mut bad = elf;
+        let memsz = EHDR_SIZE + 40;
+        bad[memsz..memsz + 8].copy_from_slice(&0u64.to_le_bytes());
+        assert_eq!(load_segments(&bad), Err(ElfError::BadSegment));
+    }
+}
@@ -0,0 +1,110 @@
+/*
+ * ============================================
+ * 熵源
+ * ============================================
+ * 功能：为伪随机数生成器提供种子
+ *
+ * 默认实现 TimerJitter 混合三种来源：
+ * - time：启动以来的时间
+ * - cycle：处理器周期数（不可访问时为 0）
+ * - 中断时序抖动：时钟中断延迟统计的累计值（每次中断到达的时刻都略有不同）
+ * 每 8 字节把这些样本和内部状态一起用 splitmix64 搅拌一次
+ *
+ * 注意：这不是密码学安全的随机数！
+ * 在 QEMU 上这些来源都可以预测，只是比固定种子好，
+ * 不能用于密钥、令牌等安全用途
+ * ============================================
+ */
+
+use crate::interrupts::{latency_stats, InterruptSource};
+use alloc::vec::Vec;
+use riscv::register::time;
+use spin::Mutex;
+
+/// 熵源
+pub trait Entropy {
+    /// 用熵填满 `buf`
+    fn gather(&mut self, buf: &mut [u8]);
+}
+
+/// 基于时间和中断抖动的熵源（不是密码学安全的）
+pub struct TimerJitter {
+    /// 搅拌状态（连续两次在同一时刻取样也不会得到相同的输出）
+    state: u64,
+}
+
+impl TimerJitter {
+    /// 创建熵源
+    pub const fn new() -> Self {
+        TimerJitter { state: 0x9e37_79b9_7f4a_7c15 }
+    }
+
+    /// 取一次原始样本
+    fn sample() -> u64 {
+        let cycle = crate::csr::read_cycle().unwrap_or(0) as u64;
+        let jitter = latency_stats(InterruptSource::Timer);
+        time::read64() ^ cycle.rotate_left(21) ^ jitter.total.rotate_left(42) ^ jitter.count
+    }
+}
+
+impl Default for TimerJitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// splitmix64 的输出函数
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Entropy for TimerJitter {
+    fn gather(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let value = mix(self.state ^ Self::sample());
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+/// 全局默认熵源
+static DEFAULT: Mutex<TimerJitter> = Mutex::new(TimerJitter::new());
+
+/// 从默认熵源取 `n` 字节种子（不是密码学安全的）
+pub fn seed_bytes(n: usize) -> Vec<u8> {
+    let mut buf = alloc::vec![0; n];
+    crate::interrupts::without_interrupts(|| DEFAULT.lock().gather(&mut buf));
+    buf
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_consecutive_gathers_differ() {
+        let mut source = TimerJitter::new();
+        let (mut first, mut second) = ([0u8; 32], [0u8; 32]);
+        let start = time::read64();
+        source.gather(&mut first);
+        while time::read64() == start {}
+        source.gather(&mut second);
+        assert_ne!(first, second);
+
+        // 两个新建的熵源在不同时刻取样，结果也不同
+        let mut other = [0u8; 32];
+        TimerJitter::new().gather(&mut other);
+        assert_ne!(first, other);
+
+        let seed = seed_bytes(13);
+        assert_eq!(seed.len(), 13);
+        assert_ne!(seed, seed_bytes(13));
+    }
+}
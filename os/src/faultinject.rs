@@ -0,0 +1,207 @@
+/*
+ * ============================================
+ * 故障注入
+ * ============================================
+ * 功能：让测试可以触发平时很难走到的错误路径
+ *
+ * 用法：
+ *   let _fault = faultinject::arm("frame_alloc", Policy::NthCall(3));
+ *   // 之后第 3 次分配页帧返回 None；_fault 离开作用域时自动解除
+ *
+ * 注入点（被测代码使用 fault_point!，为 true 时走错误路径）：
+ * - "frame_alloc"：SimpleFrameAllocator::allocate 返回 None
+ * - "heap_alloc"：全局堆分配器返回空指针
+ * - "sbi_set_timer"：SBI 设置定时器调用报告失败
+ * - "user_copy"：sys_write 读取用户缓冲区失败（EFAULT）
+ *
+ * 只在测试和 faultinject 特性下启用；
+ * 其他构建中 fault_point! 展开为字面量 false，不调用任何函数，
+ * 注入点名字也不会留在内核镜像中（下面的编译期断言检查这一点）
+ *
+ * 注意：堆分配器也是注入点，因此这里不能分配内存（注入表是固定大小的数组）
+ * ============================================
+ */
+
+#[cfg(any(test, feature = "faultinject"))]
+pub use enabled::*;
+
+/// 注入点：是否应当走错误路径
+///
+/// # 用法
+/// ```rust
+/// if crate::fault_point!("frame_alloc") {
+///     return None;
+/// }
+/// ```
+#[cfg(any(test, feature = "faultinject"))]
+#[macro_export]
+macro_rules! fault_point {
+    ($name:literal) => {
+        $crate::faultinject::check($name)
+    };
+}
+
+/// 注入点（故障注入未启用：展开为字面量 false）
+#[cfg(not(any(test, feature = "faultinject")))]
+#[macro_export]
+macro_rules! fault_point {
+    ($name:literal) => {
+        false
+    };
+}
+
+// 未启用时注入点必须是编译期常量 false，错误路径的判断才会在编译时被删除
+#[cfg(not(any(test, feature = "faultinject")))]
+const _: () = assert!(!fault_point!("frame_alloc"));
+
+#[cfg(any(test, feature = "faultinject"))]
+mod enabled {
+    use crate::interrupts::without_interrupts;
+    use spin::Mutex;
+
+    /// 同时布置的注入点上限
+    const MAX_POINTS: usize = 8;
+
+    /// 失败策略（调用次数从布置时开始计，从 1 开始）
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Policy {
+        /// 只有第 n 次调用失败
+        NthCall(u64),
+        /// 每 n 次调用失败一次（第 n、2n、3n……次）
+        EveryN(u64),
+        /// 每次调用都失败
+        Always,
+    }
+
+    impl Policy {
+        /// 第 `call` 次调用是否失败
+        fn fails(self, call: u64) -> bool {
+            match self {
+                Policy::NthCall(n) => call == n,
+                Policy::EveryN(n) => call.is_multiple_of(n),
+                Policy::Always => true,
+            }
+        }
+    }
+
+    /// 已布置的注入点
+    #[derive(Clone, Copy)]
+    struct Point {
+        name: &'static str,
+        policy: Policy,
+        /// 布置以来的调用次数
+        calls: u64,
+    }
+
+    /// 注入表（可能在中断上下文中被检查，访问时关闭中断）
+    static POINTS: Mutex<[Option<Point>; MAX_POINTS]> = Mutex::new([None; MAX_POINTS]);
+
+    /// 已布置注入点的守卫，离开作用域时解除
+    #[must_use = "注入点在守卫被丢弃时立即解除"]
+    pub struct Armed {
+        name: &'static str,
+    }
+
+    impl Armed {
+        /// 布置以来的调用次数
+        pub fn calls(&self) -> u64 {
+            without_interrupts(|| {
+                let points = POINTS.lock();
+                points.iter().flatten().find(|p| p.name == self.name).map_or(0, |p| p.calls)
+            })
+        }
+    }
+
+    impl Drop for Armed {
+        fn drop(&mut self) {
+            without_interrupts(|| {
+                for slot in POINTS.lock().iter_mut() {
+                    if slot.is_some_and(|p| p.name == self.name) {
+                        *slot = None;
+                    }
+                }
+            });
+        }
+    }
+
+    /// 布置注入点
+    ///
+    /// # 参数
+    /// - `name`: 注入点名字
+    /// - `policy`: 何时失败
+    ///
+    /// # 返回
+    /// 守卫；丢弃时解除注入点
+    ///
+    /// # Panics
+    /// 同名注入点已经布置，或注入表已满
+    pub fn arm(name: &'static str, policy: Policy) -> Armed {
+        without_interrupts(|| {
+            let mut points = POINTS.lock();
+            assert!(
+                !points.iter().flatten().any(|p| p.name == name),
+                "fault point {} already armed",
+                name
+            );
+            let slot = points.iter_mut().find(|slot| slot.is_none());
+            *slot.expect("too many fault points") = Some(Point { name, policy, calls: 0 });
+        });
+        Armed { name }
+    }
+
+    /// 解除全部注入点
+    ///
+    /// # 说明
+    /// 内置测试从 panic 的测试中恢复时使用（panic 跳过了 Armed 的 Drop）
+    pub fn disarm_all() {
+        without_interrupts(|| *POINTS.lock() = [None; MAX_POINTS]);
+    }
+
+    /// 注入点是否应当失败
+    ///
+    /// # 说明
+    /// 没有布置的注入点总是返回 false，也不计数
+    pub fn check(name: &str) -> bool {
+        without_interrupts(|| {
+            let mut points = POINTS.lock();
+            match points.iter_mut().flatten().find(|p| p.name == name) {
+                Some(point) => {
+                    point.calls += 1;
+                    point.policy.fails(point.calls)
+                }
+                None => false,
+            }
+        })
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_policies_and_scope_disarm() {
+        {
+            let fault = arm("test_point", Policy::NthCall(2));
+            let results: [bool; 4] = core::array::from_fn(|_| check("test_point"));
+            assert_eq!(results, [false, true, false, false]);
+            assert_eq!(fault.calls(), 4);
+        }
+        // 守卫丢弃后解除
+        assert!(!check("test_point"));
+
+        {
+            let _fault = arm("test_point", Policy::EveryN(3));
+            let results: [bool; 6] = core::array::from_fn(|_| check("test_point"));
+            assert_eq!(results, [false, false, true, false, false, true]);
+        }
+
+        let _always = arm("test_point", Policy::Always);
+        assert!(check("test_point") && check("test_point"));
+        assert!(!check("other_point"));
+    }
+}
@@ -0,0 +1,209 @@
+/*
+ * ============================================
+ * 规范化输出（canonical output）
+ * ============================================
+ * 功能：让输出在无关的代码改动后保持不变，便于和固定的
+ *       期望输出（golden）逐字比较
+ *
+ * 规范化模式下：
+ * - 地址通过 Canon 打印为稳定的符号编号 P1、P2……
+ *   （同一个地址总是得到同一个编号，编号按首次出现的顺序分配）
+ * - 时钟中断数、cycle 数等易变的计数通过 Elided 打印为 *
+ *
+ * 普通模式下两者按原样以十六进制 / 十进制打印
+ *
+ * 开启方式：命令行参数 canon=1（见 apply_cmdline）、sysctl kernel.canon=1
+ *          或 set_canonical(true)
+ * ============================================
+ */
+
+use core::fmt::{self, Write};
+use crate::sysctl::{tunable, Kind, Tunable};
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// 最多分配的地址编号数
+const MAX_IDS: usize = 64;
+
+/// 是否处于规范化模式
+static CANONICAL: AtomicBool = AtomicBool::new(false);
+
+tunable!(CANONICAL_TUNABLE = Tunable {
+    name: "kernel.canon",
+    kind: Kind::Bool,
+    min: 0,
+    max: 1,
+    get: || is_canonical() as u64,
+    set: |value| set_canonical(value != 0),
+});
+
+/// 已分配编号的地址（下标 + 1 即编号）
+static IDS: Mutex<([usize; MAX_IDS], usize)> = Mutex::new(([0; MAX_IDS], 0));
+
+/// 开启 / 关闭规范化模式
+pub fn set_canonical(enabled: bool) {
+    CANONICAL.store(enabled, Ordering::Relaxed);
+}
+
+/// 是否处于规范化模式
+pub fn is_canonical() -> bool {
+    CANONICAL.load(Ordering::Relaxed)
+}
+
+/// 根据内核命令行设置规范化模式
+///
+/// # 说明
+/// 命令行中出现 `canon=1` 时开启，`canon=0` 时关闭，其余参数忽略
+pub fn apply_cmdline(cmdline: &str) {
+    for arg in cmdline.split_whitespace() {
+        match arg {
+            "canon=1" => set_canonical(true),
+            "canon=0" => set_canonical(false),
+            _ => {}
+        }
+    }
+}
+
+/// 清空地址编号（下一个地址重新从 P1 开始）
+pub fn reset_ids() {
+    crate::interrupts::without_interrupts(|| {
+        IDS.lock().1 = 0;
+    });
+}
+
+/// 查找或分配地址的编号（编号表满时返回 None）
+fn symbolic_id(addr: usize) -> Option<usize> {
+    crate::interrupts::without_interrupts(|| {
+        let mut ids = IDS.lock();
+        let (table, len) = &mut *ids;
+
+        if let Some(index) = table[..*len].iter().position(|&a| a == addr) {
+            return Some(index + 1);
+        }
+        if *len == MAX_IDS {
+            return None;
+        }
+        table[*len] = addr;
+        *len += 1;
+        Some(*len)
+    })
+}
+
+/// 栈上的短字符串缓冲区（用于先格式化再按宽度对齐）
+struct Buffer {
+    bytes: [u8; 24],
+    len: usize,
+}
+
+impl Buffer {
+    fn new() -> Self {
+        Buffer {
+            bytes: [0; 24],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for Buffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.bytes.len() {
+            return Err(fmt::Error);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// 地址的规范化打印
+///
+/// # 示例
+/// ```ignore
+/// println!("heap_value at {}", Canon::ptr(&*heap_value));
+/// println!("{:>12}", Canon(paddr.as_usize()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Canon(pub usize);
+
+impl Canon {
+    /// 由指针创建
+    pub fn ptr<T: ?Sized>(ptr: *const T) -> Self {
+        Canon(ptr as *const u8 as usize)
+    }
+}
+
+impl fmt::Display for Canon {
+    /// 支持宽度和对齐，例如 `{:>12}`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = Buffer::new();
+        if is_canonical() {
+            match symbolic_id(self.0) {
+                Some(id) => write!(buf, "P{}", id)?,
+                None => buf.write_str("P?")?,
+            }
+        } else {
+            write!(buf, "{:#x}", self.0)?;
+        }
+        f.pad(buf.as_str())
+    }
+}
+
+/// 易变计数的规范化打印（规范化模式下打印为 `*`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elided<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Elided<T> {
+    /// 支持宽度和对齐，例如 `{:>8}`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if is_canonical() {
+            return f.pad("*");
+        }
+        let mut buf = Buffer::new();
+        write!(buf, "{}", self.0)?;
+        f.pad(buf.as_str())
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test_case]
+    fn test_canonical_addresses_are_stable_ids() {
+        assert_eq!(format!("{}", Canon(0x8020_1000)), "0x80201000");
+        assert_eq!(format!("{:>6}", Elided(42)), "    42");
+
+        set_canonical(true);
+        reset_ids();
+        let (a, b) = (0x8020_1000, 0x8030_0000);
+        let first = format!("{} {} {}", Canon(a), Canon(b), Canon(a));
+        // 地址整体平移后输出不变
+        reset_ids();
+        let (a, b) = (a + 0x4000, b + 0x4000);
+        let shifted = format!("{} {} {}", Canon(a), Canon(b), Canon(a));
+        let padded = format!("[{:>4}] [{:<3}]", Canon(b), Elided(123_456u64));
+        set_canonical(false);
+
+        assert_eq!(first, "P1 P2 P1");
+        assert_eq!(first, shifted);
+        assert_eq!(padded, "[  P2] [*  ]");
+    }
+
+    #[test_case]
+    fn test_apply_cmdline() {
+        apply_cmdline("console=ttyS0 canon=1 quiet");
+        assert!(is_canonical());
+        apply_cmdline("canon=0");
+        assert!(!is_canonical());
+    }
+}
@@ -19,63 +19,293 @@
  * - 页错误（Page Fault）
  * - 非法指令（Illegal Instruction）
  * - 断点（Breakpoint）
+ *
+ * 两阶段启用：
+ * - init_idt 安装陷阱向量（跳板页中的 trap_entry）并屏蔽所有中断源
+ * - 各子系统在自己的初始化中用 register_source 登记需要的中断源
+ * - 最后 enable_configured 只启用已登记的中断源（时钟中断此时才开始计时），
+ *   再打开全局中断
+ *
+ * 中断处理函数不能分配堆内存：被打断的代码可能正持有分配器的自旋锁，
+ * 在中断中再次加锁会永远等待。中断路径只使用预先分配好的无锁队列
+ * （键盘输入、执行器的唤醒队列）和在关中断状态下访问的自旋锁
  * ============================================
  */
 
-use crate::{serial_println, println};
+use crate::device::plic;
+use crate::memory::VirtAddr;
+use crate::trampoline::{self, TrapFrame};
+use crate::sysctl::{tunable, Kind, Tunable};
+use crate::{disasm, klog, print, println};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use riscv::register::{
     scause::{self, Exception, Interrupt, Trap},
-    sepc, stval, stvec,
+    sie, sip, stval, stvec, time,
 };
+use spin::Mutex;
+
+/// 启动以来的时钟中断次数
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// 异常类型数（scause 异常码 0..16）
+pub const EXCEPTION_KINDS: usize = 16;
+
+/// 各异常码对应的名称
+pub const EXCEPTION_NAMES: [&str; EXCEPTION_KINDS] = [
+    "InstructionMisaligned",
+    "InstructionFault",
+    "IllegalInstruction",
+    "Breakpoint",
+    "LoadMisaligned",
+    "LoadFault",
+    "StoreMisaligned",
+    "StoreFault",
+    "UserEnvCall",
+    "SupervisorEnvCall",
+    "Reserved(10)",
+    "Reserved(11)",
+    "InstructionPageFault",
+    "LoadPageFault",
+    "Reserved(14)",
+    "StorePageFault",
+];
+
+/// 每秒的时钟中断数（时钟中断间隔 100ms）
+const TICKS_PER_SECOND: u64 = 10;
+
+/// 异常频率超过该值（次/秒）时输出警告，通常意味着异常处理陷入死循环（默认值）
+pub const FAULT_STORM_THRESHOLD: u64 = 1000;
+
+/// 当前的异常风暴阈值（sysctl irq.fault_storm）
+static FAULT_STORM: AtomicU64 = AtomicU64::new(FAULT_STORM_THRESHOLD);
+
+tunable!(FAULT_STORM_TUNABLE = Tunable {
+    name: "irq.fault_storm",
+    kind: Kind::U64,
+    min: 1,
+    max: 1_000_000,
+    get: || FAULT_STORM.load(Ordering::Relaxed),
+    set: |value| FAULT_STORM.store(value, Ordering::Relaxed),
+});
+
+/// 各类异常的累计次数
+static EXCEPTION_COUNTS: [AtomicU64; EXCEPTION_KINDS] =
+    [const { AtomicU64::new(0) }; EXCEPTION_KINDS];
+
+/// 上次采样时的累计次数
+static SAMPLED_COUNTS: [AtomicU64; EXCEPTION_KINDS] =
+    [const { AtomicU64::new(0) }; EXCEPTION_KINDS];
+
+/// 各类异常的滑动频率（次/秒 × 1000）
+static FAULT_RATES: [AtomicU64; EXCEPTION_KINDS] =
+    [const { AtomicU64::new(0) }; EXCEPTION_KINDS];
+
+/// 可单独屏蔽的中断源（对应 sie 寄存器中的位）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptSource {
+    /// S 模式时钟中断（STIE）
+    Timer,
+    /// S 模式外部中断（SEIE）
+    External,
+    /// S 模式软件中断（SSIE）
+    Software,
+}
+
+impl InterruptSource {
+    /// 所有中断源
+    pub const ALL: [InterruptSource; 3] = [
+        InterruptSource::Timer,
+        InterruptSource::External,
+        InterruptSource::Software,
+    ];
+
+    /// 在 sie / sip 寄存器中对应的位
+    pub fn sie_bit(&self) -> usize {
+        match self {
+            InterruptSource::Software => 1 << 1,
+            InterruptSource::Timer => 1 << 5,
+            InterruptSource::External => 1 << 9,
+        }
+    }
+
+    /// 陷阱原因对应的中断源
+    fn from_interrupt(interrupt: Interrupt) -> Option<InterruptSource> {
+        match interrupt {
+            Interrupt::SupervisorTimer => Some(InterruptSource::Timer),
+            Interrupt::SupervisorExternal => Some(InterruptSource::External),
+            Interrupt::SupervisorSoft => Some(InterruptSource::Software),
+            _ => None,
+        }
+    }
+
+    /// 中断源名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            InterruptSource::Timer => "Timer",
+            InterruptSource::External => "External",
+            InterruptSource::Software => "Software",
+        }
+    }
+}
 
 /// 初始化中断描述符表（RISC-V 陷阱向量）
 ///
 /// # 功能
-/// - 设置 stvec 寄存器指向中断处理入口
-/// - 启用 S-mode 中断
-/// - 启用并设置定时器中断
+/// - 设置 stvec 寄存器指向跳板页中的陷阱入口（物理地址，见 `trampoline`）
+/// - 屏蔽所有中断源（由 `enable_configured` 启用已登记的中断源）
 pub fn init_idt() {
+    let entry = trampoline::trap_entry_addr();
     unsafe {
         // 设置陷阱向量地址（Direct 模式）
-        // 所有中断和异常都跳转到同一个处理函数
-        stvec::write(trap_handler as usize, stvec::TrapMode::Direct);
+        // 所有中断和异常都跳转到同一个入口，保存上下文后调用 trap_handler
+        stvec::write(entry, stvec::TrapMode::Direct);
     }
 
-    serial_println!("[INTERRUPT] Trap vector initialized");
+    // 读回 stvec，确认写入的基址和模式都生效
+    if let Some(value) = crate::csr::require(crate::csr::read_stvec(), "trap vector check") {
+        if let Err(e) = check_trap_vector(value, entry) {
+            panic!(
+                "Trap vector not installed: {}\n\
+                stvec: {:#x}\n\
+                trap_entry: {:#x}",
+                e,
+                value,
+                entry
+            );
+        }
+    }
 
-    // 启用定时器中断
-    unsafe {
-        // 启用 sie 寄存器中的 timer 中断位
-        riscv::register::sie::set_stimer();
+    klog!("[INTERRUPT] Trap vector initialized");
+    mask_all_sources();
+}
+
+// ============================================
+// 中断源登记（两阶段启用）
+// ============================================
+
+/// 已登记的中断源（sie 位的集合）
+static REGISTERED: AtomicUsize = AtomicUsize::new(0);
+
+/// enable_configured 是否已经执行
+static CONFIGURED: AtomicBool = AtomicBool::new(false);
+
+/// 屏蔽所有中断源
+fn mask_all_sources() {
+    let all = InterruptSource::ALL.iter().fold(0, |bits, source| bits | source.sie_bit());
+    // 受限环境下 sie 可能不可访问，此时中断源本来就无法启用
+    crate::csr::require(unsafe { crate::csr::clear_sie(all) }, "interrupt masking");
+}
+
+/// 登记中断源：它的处理函数依赖的状态已经初始化
+///
+/// # 说明
+/// `enable_configured` 之前登记的中断源在那时启用；之后登记的立即启用
+pub fn register_source(source: InterruptSource) {
+    REGISTERED.fetch_or(source.sie_bit(), Ordering::Relaxed);
+    if CONFIGURED.load(Ordering::Relaxed) {
+        unmask(source);
+    }
+}
+
+/// 中断源是否已登记
+pub fn is_registered(source: InterruptSource) -> bool {
+    REGISTERED.load(Ordering::Relaxed) & source.sie_bit() != 0
+}
+
+/// 第二阶段：启用已登记的中断源，然后打开全局中断
+///
+/// # 说明
+/// 在所有子系统初始化之后调用（见 `crate::init_late`）；
+/// 时钟中断在这时才设置第一次期限
+pub fn enable_configured() {
+    for source in InterruptSource::ALL.into_iter().filter(|&s| is_registered(s)) {
+        unmask(source);
+    }
+    CONFIGURED.store(true, Ordering::Relaxed);
+    enable_interrupts();
+}
+
+/// 启用一个已登记的中断源（时钟中断同时设置第一次期限）
+fn unmask(source: InterruptSource) {
+    // 受限环境下 sie 或 time 可能不可访问，此时不启用该中断源，继续启动
+    if source == InterruptSource::Timer {
+        if crate::csr::require(crate::csr::read_time(), "timer interrupts").is_none() {
+            return;
+        }
+        if let Err(error) = set_next_timer() {
+            klog!("[INTERRUPT] Failed to set timer (SBI error {})", error);
+            return;
+        }
     }
+    let feature = match source {
+        InterruptSource::Timer => "timer interrupts",
+        InterruptSource::External => "external interrupts",
+        InterruptSource::Software => "software interrupts",
+    };
+    if crate::csr::require(unsafe { crate::csr::set_sie(source.sie_bit()) }, feature).is_some() {
+        klog!("[INTERRUPT] {} interrupt enabled", source.name());
+    }
+}
 
-    // 设置第一个定时器中断
-    set_next_timer();
+/// stvec 的 MODE 字段（低 2 位，0 = Direct）
+const STVEC_MODE_MASK: usize = 0b11;
 
-    serial_println!("[INTERRUPT] Timer interrupt enabled");
+/// 检查 stvec 的值是否以 Direct 模式指向 `entry`
+///
+/// # 返回
+/// - `Err`: 入口没有 4 字节对齐（stvec 的基址丢弃低 2 位）、
+///   模式不是 Direct，或基址不是 `entry`
+fn check_trap_vector(stvec: usize, entry: usize) -> Result<(), &'static str> {
+    if entry & STVEC_MODE_MASK != 0 {
+        return Err("trap entry is not 4-byte aligned");
+    }
+    if stvec & STVEC_MODE_MASK != 0 {
+        return Err("stvec mode is not Direct");
+    }
+    if stvec & !STVEC_MODE_MASK != entry {
+        return Err("stvec base does not match trap entry");
+    }
+    Ok(())
 }
 
-/// 统一的陷阱处理入口
+/// 统一的陷阱处理函数（由跳板页中的 `trap_entry` 调用）
 ///
 /// # 功能
 /// - 读取 scause 寄存器判断中断/异常类型
 /// - 分发到对应的处理函数
 ///
 /// # 参数
-/// - 通过 CSR 寄存器传递上下文信息
+/// - `context`: 入口保存的上下文；返回后从 `context.sepc` 继续执行
 #[no_mangle]
-pub extern "C" fn trap_handler() {
+pub extern "C" fn trap_handler(context: &mut TrapFrame) {
+    // 最先读取 time，用于计算中断延迟
+    let entered = time::read64();
     let scause = scause::read();
     let stval = stval::read();
-    let sepc = sepc::read();
+    let sepc = context.sepc;
+
+    #[cfg(feature = "satp_watch_trap")]
+    crate::memory::satp_watch::check();
 
     match scause.cause() {
         // ============================================
         // 中断处理
         // ============================================
-        Trap::Interrupt(interrupt) => {
+        Trap::Interrupt(interrupt) => in_interrupt_context(|| {
+            // 未登记的中断源应当一直被屏蔽
+            debug_assert!(
+                InterruptSource::from_interrupt(interrupt).is_none_or(is_registered),
+                "{:?} interrupt from an unregistered source",
+                interrupt
+            );
             match interrupt {
                 Interrupt::SupervisorTimer => {
+                    let deadline = TIMER_DEADLINE.load(Ordering::Relaxed);
+                    record_latency(InterruptSource::Timer, entered.saturating_sub(deadline), sepc);
                     timer_interrupt_handler();
                 }
                 Interrupt::SupervisorExternal => {
@@ -96,30 +326,57 @@ pub extern "C" fn trap_handler() {
                     );
                 }
             }
-        }
+        }),
 
         // ============================================
         // 异常处理
         // ============================================
         Trap::Exception(exception) => {
+            if let Some(count) = EXCEPTION_COUNTS.get(scause.code()) {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+
             match exception {
                 Exception::Breakpoint => {
-                    breakpoint_handler(sepc);
+                    context.sepc = breakpoint_handler(sepc);
                 }
                 Exception::LoadPageFault |
                 Exception::StorePageFault |
                 Exception::InstructionPageFault => {
-                    page_fault_handler(scause.cause(), stval, sepc);
+                    let mut frame = FaultFrame::current(sepc);
+                    match handle_recoverable_fault(scause.cause(), stval, &mut frame) {
+                        // 返回后从 frame.sepc 继续（出错的指令或异常表中的修复代码）
+                        FaultOutcome::Retry => context.sepc = frame.sepc,
+                        FaultOutcome::Fatal(reason) => {
+                            page_fault_handler(scause.cause(), stval, sepc, reason)
+                        }
+                        FaultOutcome::Terminate(reason) => {
+                            user_fault_handler(scause.cause(), stval, sepc, reason)
+                        }
+                    }
                 }
                 Exception::IllegalInstruction => {
-                    illegal_instruction_handler(sepc, stval);
+                    // 受异常表保护的指令（如 csr_checked 下的 CSR 访问）跳到修复代码
+                    match crate::csr::search_exception_table(sepc) {
+                        Some(fixup) => context.sepc = fixup,
+                        None => illegal_instruction_handler(sepc, stval),
+                    }
+                }
+                Exception::LoadFault | Exception::StoreFault => {
+                    // 受异常表保护的访问（如 peek / poke 访问不存在的物理地址）
+                    match crate::csr::search_exception_table(sepc) {
+                        Some(fixup) => context.sepc = fixup,
+                        None => access_fault_handler(sepc, stval),
+                    }
                 }
                 Exception::UserEnvCall => {
                     // 系统调用处理入口（预留，暂未实现）
                     panic!(
                         "System call not implemented!\n\
-                        sepc: {:#x}",
-                        sepc
+                        sepc: {:#x}\n\
+                        insn: {}",
+                        sepc,
+                        disasm::describe_at(sepc)
                     );
                 }
                 _ => {
@@ -127,10 +384,12 @@ pub extern "C" fn trap_handler() {
                         "Unhandled exception!\n\
                         scause: {:?}\n\
                         sepc: {:#x}\n\
-                        stval: {:#x}",
+                        stval: {:#x}\n\
+                        insn: {}",
                         scause.cause(),
                         sepc,
-                        stval
+                        stval,
+                        disasm::describe_at(sepc)
                     );
                 }
             }
@@ -148,12 +407,44 @@ pub extern "C" fn trap_handler() {
 /// - 处理定时器中断
 /// - 用于任务调度和时间管理
 /// - 轮询键盘输入
+///
+/// # 说明
+/// 与所有中断处理函数一样不分配堆内存（见文件开头）
 fn timer_interrupt_handler() {
+    // 安装虚拟时钟时，时间相关的状态只由 TestClock::advance 推进
+    if !crate::clock::is_virtual() {
+        process_tick();
+    }
+
+    // 检查 satp 是否被绕过 activate 改写
+    crate::memory::satp_watch::check();
+
     // 轮询键盘输入（通过 SBI console）
     crate::task::keyboard::poll_keyboard();
 
     // 设置下一次定时器中断
-    set_next_timer();
+    if let Err(error) = set_next_timer() {
+        klog!("[INTERRUPT] Failed to set timer (SBI error {})", error);
+    }
+}
+
+/// 每个时钟中断的处理逻辑
+///
+/// # 功能
+/// - 时钟中断计数，每秒采样一次异常频率
+/// - 要求正在运行的异步任务尽快让出
+/// - 唤醒到期的睡眠任务
+///
+/// # 说明
+/// 由时钟中断和 `clock::TestClock::advance` 调用，调用时中断已关闭
+pub(crate) fn process_tick() {
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    if ticks.is_multiple_of(TICKS_PER_SECOND) {
+        sample_fault_rates();
+    }
+
+    crate::task::budget::request_yield();
+    crate::task::timer::wake_expired();
 }
 
 /// 外部中断处理
@@ -161,9 +452,32 @@ fn timer_interrupt_handler() {
 /// # 功能
 /// - 处理外部设备中断（如键盘、网卡等）
 fn external_interrupt_handler() {
-    // RISC-V PLIC（Platform-Level Interrupt Controller）处理
-    // 在这里可以添加键盘等外设的中断处理
-    serial_println!("[INTERRUPT] External interrupt received");
+    // 向 PLIC 认领中断源（PLIC 由设备树探测，见 device::plic）
+    match crate::device::plic::claim() {
+        Some(irq) => {
+            handle_irq(irq);
+            crate::device::plic::complete(irq);
+        }
+        None => {
+            klog!("[INTERRUPT] External interrupt received");
+        }
+    }
+}
+
+/// 处理认领到的外部中断源：计数并记录时刻
+///
+/// # 说明
+/// 屏蔽之前已经挂起的一次仍可能被认领；这时不计数，当作没有发生
+fn handle_irq(irq: u32) {
+    if !crate::device::plic::is_enabled(irq) {
+        return;
+    }
+    let index = irq as usize;
+    if let (Some(count), Some(last)) = (IRQ_COUNTS.get(index), IRQ_LAST_TICK.get(index)) {
+        count.fetch_add(1, Ordering::Relaxed);
+        last.store(uptime_ticks(), Ordering::Relaxed);
+    }
+    klog!("[INTERRUPT] External interrupt {}", irq);
 }
 
 /// 软件中断处理
@@ -172,7 +486,129 @@ fn external_interrupt_handler() {
 /// - 处理核间中断（IPI）
 /// - 用于多核同步
 fn software_interrupt_handler() {
-    serial_println!("[INTERRUPT] Software interrupt received");
+    // 清除挂起位，否则返回后立即再次触发
+    unsafe { sip::clear_ssoft() };
+    klog!("[INTERRUPT] Software interrupt received");
+}
+
+// ============================================
+// 外部中断线（IRQ）
+// ============================================
+//
+// 驱动用 register_irq 登记自己的中断源（名字 + PLIC 中的编号），
+// irq_info 列出每个登记的中断源的使能状态、优先级、次数和最近一次的时刻，
+// 供 /proc/interrupts 和 irqctl 命令使用
+//
+// 屏蔽一个中断源不影响设备本身：驱动不再收到它的中断，
+// 依靠轮询继续工作（例如键盘输入在时钟中断中通过 SBI 轮询）
+
+/// 登记的中断源名称（按 PLIC 编号索引）
+static IRQ_NAMES: Mutex<[Option<&'static str>; plic::MAX_IRQS]> =
+    Mutex::new([None; plic::MAX_IRQS]);
+
+/// 各中断源的中断次数
+static IRQ_COUNTS: [AtomicU64; plic::MAX_IRQS] = [const { AtomicU64::new(0) }; plic::MAX_IRQS];
+
+/// 各中断源最近一次中断时的 uptime_ticks
+static IRQ_LAST_TICK: [AtomicU64; plic::MAX_IRQS] =
+    [const { AtomicU64::new(0) }; plic::MAX_IRQS];
+
+/// 一个登记的中断源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrqInfo {
+    /// PLIC 中的编号
+    pub irq: u32,
+    /// 登记时的名字
+    pub name: &'static str,
+    /// 是否使能
+    pub enabled: bool,
+    /// 优先级
+    pub priority: u8,
+    /// 中断次数
+    pub count: u64,
+    /// 最近一次中断时的 uptime_ticks（还没有中断过时为 None）
+    pub last_tick: Option<u64>,
+}
+
+/// 登记并使能一个外部中断源（优先级 1）
+///
+/// # 返回
+/// - `Err`: PLIC 还没有探测到，或编号超出范围
+pub fn register_irq(irq: u32, name: &'static str) -> Result<(), &'static str> {
+    plic::set_priority(irq, 1)?;
+    without_interrupts(|| IRQ_NAMES.lock()[irq as usize] = Some(name));
+    plic::set_enabled(irq, true)
+}
+
+/// 登记的中断源，按编号排列
+pub fn irq_info() -> Vec<IrqInfo> {
+    let names = without_interrupts(|| *IRQ_NAMES.lock());
+    names
+        .iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let irq = index as u32;
+            let count = IRQ_COUNTS[index].load(Ordering::Relaxed);
+            Some(IrqInfo {
+                irq,
+                name: (*name)?,
+                enabled: plic::is_enabled(irq),
+                priority: plic::priority(irq),
+                count,
+                last_tick: (count > 0).then(|| IRQ_LAST_TICK[index].load(Ordering::Relaxed)),
+            })
+        })
+        .collect()
+}
+
+/// 读取 /proc/interrupts 的内容
+///
+/// # 格式
+/// 每个登记的中断源一行：编号、次数、使能状态、优先级、最近一次的时刻、名字
+pub fn read_proc_interrupts() -> String {
+    let mut text = String::from(" IRQ      COUNT  STATE   PRIO   LAST_TICK  NAME\n");
+    for info in irq_info() {
+        let last = info.last_tick.map_or(String::from("-"), |tick| format!("{}", tick));
+        let state = if info.enabled { "enabled" } else { "masked" };
+        text += &format!(
+            "{:>4} {:>10}  {:<7} {:>4}  {:>10}  {}\n",
+            info.irq, info.count, state, info.priority, last, info.name
+        );
+    }
+    text
+}
+
+/// 解析登记过的中断源编号
+fn parse_irq(arg: &str) -> Result<u32, &'static str> {
+    let irq: usize = arg.parse().map_err(|_| "Invalid IRQ number")?;
+    if irq >= plic::MAX_IRQS || without_interrupts(|| IRQ_NAMES.lock()[irq].is_none()) {
+        return Err("IRQ not registered");
+    }
+    Ok(irq as u32)
+}
+
+/// 解析并执行一条 irqctl 命令
+///
+/// # 命令
+/// - `irqctl list`：打印 /proc/interrupts
+/// - `irqctl mask <n>` / `irqctl unmask <n>`：屏蔽 / 使能中断源
+/// - `irqctl prio <n> <p>`：设置优先级（0 - 7）
+pub fn run_command(line: &str) -> Result<(), &'static str> {
+    let args: Vec<&str> = line.split_whitespace().collect();
+    match args.as_slice() {
+        ["irqctl", "list"] => {
+            print!("{}", read_proc_interrupts());
+        }
+        ["irqctl", "mask", irq] => plic::set_enabled(parse_irq(irq)?, false)?,
+        ["irqctl", "unmask", irq] => plic::set_enabled(parse_irq(irq)?, true)?,
+        ["irqctl", "prio", irq, priority] => {
+            let priority = priority.parse().map_err(|_| "Invalid priority")?;
+            plic::set_priority(parse_irq(irq)?, priority)?;
+        }
+        ["irqctl", ..] => return Err("usage: irqctl list|mask <n>|unmask <n>|prio <n> <p>"),
+        _ => return Err("Unknown command"),
+    }
+    Ok(())
 }
 
 // ============================================
@@ -183,36 +619,159 @@ fn software_interrupt_handler() {
 ///
 /// # 参数
 /// - `sepc`: 异常发生时的程序计数器
-fn breakpoint_handler(sepc: usize) {
-    serial_println!("[EXCEPTION] Breakpoint at {:#x}", sepc);
-    println!("EXCEPTION: BREAKPOINT at {:#x}", sepc);
+///
+/// # 返回
+/// - 继续执行的地址（跳过 ebreak 指令）
+fn breakpoint_handler(sepc: usize) -> usize {
+    klog!("[EXCEPTION] Breakpoint at {:#x}", sepc);
+
+    sepc + 2 // ebreak 是 2 字节指令
+}
+
+/// 页错误发生时的陷阱上下文
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultFrame {
+    /// 出错指令的地址；恢复策略可以把它改为修复代码的地址
+    pub sepc: usize,
+    /// 页错误是否发生在用户态（sstatus.SPP）
+    pub from_user: bool,
+}
+
+impl FaultFrame {
+    /// 当前陷阱的上下文
+    fn current(sepc: usize) -> Self {
+        use riscv::register::sstatus::{self, SPP};
 
-    // 断点指令后继续执行（跳过 ebreak 指令）
-    riscv::register::sepc::write(sepc + 2); // ebreak 是 2 字节指令
+        FaultFrame {
+            sepc,
+            from_user: sstatus::read().spp() == SPP::User,
+        }
+    }
+}
+
+/// 页错误的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultOutcome {
+    /// 已恢复，从陷阱返回后从 `frame.sepc` 继续执行
+    Retry,
+    /// 内核中无法恢复的页错误（附带最后一个策略给出的原因）
+    Fatal(&'static str),
+    /// 用户态无法恢复的页错误，应终止进程
+    Terminate(&'static str),
 }
 
-/// 页错误处理
+/// 依次尝试各种恢复策略处理页错误
+///
+/// # 参数
+/// - `cause`: 异常类型（Load/Store/Instruction Page Fault）
+/// - `stval`: 触发异常的虚拟地址
+/// - `frame`: 陷阱上下文（异常表修复会改写其中的 sepc）
+///
+/// # 策略（按顺序）
+/// 1. 登记的地址空间：写时复制、按需映射（见 `handle_active_page_fault`）
+/// 2. 异常表：受保护的访问跳到修复代码
+///
+/// 都失败时，用户态的页错误终止进程，内核态的页错误是致命错误；
+/// 访问栈保护页的原因是 `STACK_OVERFLOW`。
+/// 每次的结果记录到页错误跟踪中（`memory::fault_trace`，trace_faults 特性）
+pub fn handle_recoverable_fault(cause: Trap, stval: usize, frame: &mut FaultFrame) -> FaultOutcome {
+    use crate::memory::{fault_trace, FaultResolution};
+
+    let is_store = cause == Trap::Exception(Exception::StorePageFault);
+    let reason = match crate::memory::handle_active_page_fault(VirtAddr::new(stval), is_store) {
+        Ok(resolution) => {
+            fault_trace::record(cause, stval, resolution);
+            return FaultOutcome::Retry;
+        }
+        Err(e) => e,
+    };
+
+    if let Some(fixup) = crate::csr::search_exception_table(frame.sepc) {
+        fault_trace::record(cause, stval, FaultResolution::Fixup);
+        frame.sepc = fixup;
+        return FaultOutcome::Retry;
+    }
+
+    if frame.from_user {
+        fault_trace::record(cause, stval, FaultResolution::Terminated(reason));
+        FaultOutcome::Terminate(reason)
+    } else {
+        fault_trace::record(cause, stval, FaultResolution::Fatal(reason));
+        FaultOutcome::Fatal(reason)
+    }
+}
+
+/// 读取当前的栈指针
+///
+/// # 说明
+/// 陷阱处理函数与出错的代码使用同一个栈，
+/// 因此这个值比出错时的 sp 略低（低出处理函数自身的栈帧）
+fn read_sp() -> usize {
+    let sp: usize;
+    unsafe { core::arch::asm!("mv {}, sp", out(reg) sp, options(nomem, nostack)) };
+    sp
+}
+
+/// 内核中无法恢复的页错误：打印诊断信息后停机
 ///
 /// # 参数
 /// - `cause`: 异常类型（Load/Store/Instruction Page Fault）
 /// - `stval`: 触发异常的虚拟地址
 /// - `sepc`: 异常发生时的程序计数器
-fn page_fault_handler(cause: Trap, stval: usize, sepc: usize) {
-    serial_println!(
+/// - `reason`: `handle_recoverable_fault` 给出的原因
+fn page_fault_handler(cause: Trap, stval: usize, sepc: usize, reason: &str) -> ! {
+    // 取指页错误时 sepc 本身不可读，describe_at 会返回 <unavailable>
+    let insn = disasm::describe_at(sepc);
+
+    if reason == crate::memory::STACK_OVERFLOW {
+        let stack = crate::memory::active_guarded_stack(VirtAddr::new(stval)).unwrap_or("stack");
+        panic!(
+            "STACK OVERFLOW in {}, sp={:#x}, sepc={:#x}\n\
+            Guard page: {:#x}\n\
+            insn: {}",
+            stack,
+            read_sp(),
+            sepc,
+            stval,
+            insn
+        );
+    }
+
+    klog!(
         "[EXCEPTION] Page Fault\n\
         Type: {:?}\n\
         Address: {:#x}\n\
-        PC: {:#x}",
+        PC: {:#x}\n\
+        Reason: {}\n\
+        insn: {}",
         cause,
         stval,
-        sepc
+        sepc,
+        reason,
+        insn
     );
 
-    println!("EXCEPTION: PAGE FAULT");
-    println!("Accessed Address: {:#x}", stval);
-    println!("Exception PC: {:#x}", sepc);
-    println!("Fault Type: {:?}", cause);
+    crate::hlt_loop();
+}
 
+/// 用户态无法恢复的页错误
+///
+/// # 说明
+/// 目前还没有进程可以终止，打印诊断信息后停机。
+/// 诊断信息写入内核日志，控制台上只有给用户看的 "Segmentation fault"
+fn user_fault_handler(cause: Trap, stval: usize, sepc: usize, reason: &str) -> ! {
+    klog!(
+        "[EXCEPTION] User page fault, terminating\n\
+        Type: {:?}\n\
+        Address: {:#x}\n\
+        PC: {:#x}\n\
+        Reason: {}",
+        cause,
+        stval,
+        sepc,
+        reason
+    );
+    println!("Segmentation fault at {:#x}", stval);
     crate::hlt_loop();
 }
 
@@ -225,9 +784,28 @@ fn illegal_instruction_handler(sepc: usize, stval: usize) {
     panic!(
         "EXCEPTION: ILLEGAL INSTRUCTION\n\
         PC: {:#x}\n\
-        Instruction: {:#x}",
+        Instruction: {:#x}\n\
+        insn: {}",
         sepc,
-        stval
+        stval,
+        disasm::describe_at(sepc)
+    );
+}
+
+/// 访问错误处理（物理地址不存在或被 PMP 拒绝）
+///
+/// # 参数
+/// - `sepc`: 异常发生时的程序计数器
+/// - `stval`: 访问的地址
+fn access_fault_handler(sepc: usize, stval: usize) {
+    panic!(
+        "EXCEPTION: ACCESS FAULT\n\
+        PC: {:#x}\n\
+        Address: {:#x}\n\
+        insn: {}",
+        sepc,
+        stval,
+        disasm::describe_at(sepc)
     );
 }
 
@@ -235,13 +813,92 @@ fn illegal_instruction_handler(sepc: usize, stval: usize) {
 // 中断控制函数
 // ============================================
 
+/// 当前 hart 上存活的 InterruptGuard 数
+///
+/// # 说明
+/// 内核只运行在一个 hart 上，因此只有一个计数器；
+/// panic = "abort" 时 panic 之前创建的守卫不会被丢弃，panic 处理函数可以读出层数
+static GUARD_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// 关闭中断的守卫
+///
+/// # 说明
+/// 创建时记录 sstatus.SIE 并关闭中断，丢弃时恢复为创建时的状态：
+/// - 嵌套使用时只有最外层的守卫会重新打开中断
+/// - 闭包提前返回（`?`）时同样恢复
+///
+/// 守卫存活期间调用 `enable_interrupts` 会破坏外层的临界区（调试构建中断言失败）
+#[must_use = "丢弃守卫会立即恢复中断"]
+pub struct InterruptGuard {
+    /// 创建时中断是否打开
+    was_enabled: bool,
+    /// 守卫只能在创建它的 hart 上丢弃
+    _not_send: PhantomData<*const ()>,
+}
+
+impl InterruptGuard {
+    /// 关闭中断
+    pub fn new() -> Self {
+        use riscv::register::sstatus;
+
+        let was_enabled = sstatus::read().sie();
+        if was_enabled {
+            unsafe { sstatus::clear_sie() };
+        }
+        GUARD_DEPTH.fetch_add(1, Ordering::Relaxed);
+        InterruptGuard { was_enabled, _not_send: PhantomData }
+    }
+
+    /// 创建时中断是否打开
+    pub fn was_enabled(&self) -> bool {
+        self.was_enabled
+    }
+}
+
+impl Default for InterruptGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        GUARD_DEPTH.fetch_sub(1, Ordering::Relaxed);
+        if self.was_enabled {
+            unsafe { riscv::register::sstatus::set_sie() };
+        }
+    }
+}
+
+/// 当前存活的 InterruptGuard 层数
+pub fn guard_depth() -> usize {
+    GUARD_DEPTH.load(Ordering::Relaxed)
+}
+
+/// 放弃 panic 跳过的守卫：层数恢复为 `depth`，中断恢复为 `enabled`
+///
+/// # 说明
+/// 内置测试从 panic 的测试中恢复时使用（那些守卫的 Drop 永远不会运行）
+pub(crate) fn abandon_guards(depth: usize, enabled: bool) {
+    use riscv::register::sstatus;
+
+    GUARD_DEPTH.store(depth, Ordering::Relaxed);
+    unsafe {
+        if enabled {
+            sstatus::set_sie();
+        } else {
+            sstatus::clear_sie();
+        }
+    }
+}
+
 /// 禁用中断并执行闭包
 ///
 /// # 功能
 /// - 保存当前中断状态
 /// - 禁用中断
 /// - 执行闭包
-/// - 恢复原始中断状态
+/// - 恢复原始中断状态（由 `InterruptGuard` 完成）
 ///
 /// # 用途
 /// 用于实现临界区，防止死锁
@@ -249,29 +906,32 @@ pub fn without_interrupts<F, R>(f: F) -> R
 where
     F: FnOnce() -> R,
 {
-    use riscv::register::sstatus;
+    let _guard = InterruptGuard::new();
+    f()
+}
 
-    // 读取当前中断状态
-    let sie = sstatus::read().sie();
+/// 正在处理的中断层数
+static INTERRUPT_DEPTH: AtomicUsize = AtomicUsize::new(0);
 
-    if sie {
-        // 如果中断启用，则禁用
-        unsafe { riscv::register::sstatus::clear_sie(); }
-    }
+/// 当前是否在中断处理函数中（异常处理不算）
+pub fn in_interrupt() -> bool {
+    INTERRUPT_DEPTH.load(Ordering::Relaxed) != 0
+}
 
-    // 执行闭包
+/// 以中断上下文执行闭包（陷阱处理函数和 `clock::TestClock` 的模拟时钟中断使用）
+pub(crate) fn in_interrupt_context<R>(f: impl FnOnce() -> R) -> R {
+    INTERRUPT_DEPTH.fetch_add(1, Ordering::Relaxed);
     let ret = f();
-
-    if sie {
-        // 恢复中断状态
-        unsafe { riscv::register::sstatus::set_sie(); }
-    }
-
+    INTERRUPT_DEPTH.fetch_sub(1, Ordering::Relaxed);
     ret
 }
 
 /// 启用中断
+///
+/// # 说明
+/// 不能在 InterruptGuard 存活期间调用（会在外层临界区中打开中断）
 pub fn enable_interrupts() {
+    debug_assert_eq!(guard_depth(), 0, "enable_interrupts() inside an InterruptGuard");
     unsafe {
         riscv::register::sstatus::set_sie();
     }
@@ -285,54 +945,716 @@ pub fn disable_interrupts() {
     }
 }
 
+/// 启用单个中断源
+///
+/// # 说明
+/// 只修改 sie 中对应的位，全局开关 sstatus.SIE 不受影响
+pub fn enable_source(source: InterruptSource) {
+    unsafe {
+        match source {
+            InterruptSource::Timer => sie::set_stimer(),
+            InterruptSource::External => sie::set_sext(),
+            InterruptSource::Software => sie::set_ssoft(),
+        }
+    }
+}
+
+/// 屏蔽单个中断源
+///
+/// # 说明
+/// 屏蔽期间到来的中断保持挂起（sip），重新启用后立即触发
+pub fn disable_source(source: InterruptSource) {
+    unsafe {
+        match source {
+            InterruptSource::Timer => sie::clear_stimer(),
+            InterruptSource::External => sie::clear_sext(),
+            InterruptSource::Software => sie::clear_ssoft(),
+        }
+    }
+}
+
+/// 查询中断源是否启用
+pub fn is_source_enabled(source: InterruptSource) -> bool {
+    let sie = sie::read();
+    match source {
+        InterruptSource::Timer => sie.stimer(),
+        InterruptSource::External => sie.sext(),
+        InterruptSource::Software => sie.ssoft(),
+    }
+}
+
+/// 启动以来的时钟中断次数（每次约 100ms）
+pub fn uptime_ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// ============================================
+// 异常统计
+// ============================================
+
+/// 各类异常的累计次数（按 scause 异常码索引）
+pub fn exception_counts() -> [u64; EXCEPTION_KINDS] {
+    core::array::from_fn(|i| EXCEPTION_COUNTS[i].load(Ordering::Relaxed))
+}
+
+/// 各类异常的滑动频率（按 scause 异常码索引）
+///
+/// # 返回
+/// 定点数，单位为 次/秒 × 1000（不依赖 FPU）
+///
+/// # 说明
+/// 每秒采样一次，频率为本次采样值与上次结果的平均，
+/// 因此异常停止后频率每秒减半
+pub fn fault_rates() -> [u64; EXCEPTION_KINDS] {
+    core::array::from_fn(|i| FAULT_RATES[i].load(Ordering::Relaxed))
+}
+
+/// 采样异常计数并更新滑动频率（每秒由时钟中断调用一次）
+fn sample_fault_rates() {
+    for (code, name) in EXCEPTION_NAMES.iter().enumerate() {
+        let count = EXCEPTION_COUNTS[code].load(Ordering::Relaxed);
+        let last = SAMPLED_COUNTS[code].swap(count, Ordering::Relaxed);
+
+        // 采样间隔正好一秒，差值即为本秒的次数
+        let sample = (count - last) * 1000;
+        let rate = (FAULT_RATES[code].load(Ordering::Relaxed) + sample) / 2;
+        FAULT_RATES[code].store(rate, Ordering::Relaxed);
+
+        let threshold = FAULT_STORM.load(Ordering::Relaxed);
+        if rate > threshold * 1000 {
+            klog!(
+                "[WARNING] {} rate {}.{:03}/s exceeds {}/s, possible fault loop",
+                name,
+                rate / 1000,
+                rate % 1000,
+                threshold
+            );
+        }
+    }
+}
+
+// ============================================
+// 中断延迟
+// ============================================
+//
+// 延迟 = 处理函数入口读到的 time - 中断开始挂起的时间
+// 时钟中断的挂起时间就是设置的定时器期限；外部中断和软件中断
+// 不知道挂起的时刻，目前不记录（对应的统计保持为空）
+
+/// 延迟直方图的桶数
+pub const LATENCY_BUCKETS: usize = 7;
+
+/// 各桶的上界（time 计数，10MHz 下依次为 1µs、10µs、100µs、1ms、10ms、100ms），
+/// 最后一个桶收集更大的值
+pub const LATENCY_BUCKET_LIMITS: [u64; LATENCY_BUCKETS - 1] =
+    [10, 100, 1_000, 10_000, 100_000, 1_000_000];
+
+/// 新的最坏延迟超过该值（time 计数，1ms）时输出警告（默认值）
+pub const LATENCY_WARN_THRESHOLD: u64 = 10_000;
+
+/// 当前的延迟警告阈值（sysctl irq.latency_warn）
+static LATENCY_WARN: AtomicU64 = AtomicU64::new(LATENCY_WARN_THRESHOLD);
+
+tunable!(LATENCY_WARN_TUNABLE = Tunable {
+    name: "irq.latency_warn",
+    kind: Kind::U64,
+    min: 10,
+    max: 10_000_000,
+    get: || LATENCY_WARN.load(Ordering::Relaxed),
+    set: |value| LATENCY_WARN.store(value, Ordering::Relaxed),
+});
+
+/// 当前定时器的期限（time 计数）
+static TIMER_DEADLINE: AtomicU64 = AtomicU64::new(0);
+
+/// 下一次时钟中断的期限（time 计数，还没有设置过定时器时为 0）
+pub(crate) fn timer_deadline() -> u64 {
+    TIMER_DEADLINE.load(Ordering::Relaxed)
+}
+
+/// 各中断源的延迟统计（按 InterruptSource::ALL 的顺序）
+static LATENCY: Mutex<[LatencyStats; 3]> = Mutex::new([LatencyStats::new(); 3]);
+
+/// 一个中断源的延迟统计（单位：time 计数）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// 记录次数
+    pub count: u64,
+    /// 最小延迟
+    pub min: u64,
+    /// 最大延迟
+    pub max: u64,
+    /// 延迟总和
+    pub total: u64,
+    /// 出现最大延迟时被打断的 sepc
+    pub worst_sepc: usize,
+    /// 延迟直方图（见 LATENCY_BUCKET_LIMITS）
+    pub histogram: [u64; LATENCY_BUCKETS],
+}
+
+impl LatencyStats {
+    /// 空的统计
+    pub const fn new() -> Self {
+        LatencyStats {
+            count: 0,
+            min: u64::MAX,
+            max: 0,
+            total: 0,
+            worst_sepc: 0,
+            histogram: [0; LATENCY_BUCKETS],
+        }
+    }
+
+    /// 平均延迟（没有记录时为 0）
+    pub fn average(&self) -> u64 {
+        self.total.checked_div(self.count).unwrap_or(0)
+    }
+
+    /// 记录一次延迟
+    ///
+    /// # 返回
+    /// 是否为新的最坏情况
+    fn record(&mut self, latency: u64, sepc: usize) -> bool {
+        self.count += 1;
+        self.total = self.total.saturating_add(latency);
+        self.min = self.min.min(latency);
+        let bucket = LATENCY_BUCKET_LIMITS
+            .iter()
+            .position(|&limit| latency < limit)
+            .unwrap_or(LATENCY_BUCKETS - 1);
+        self.histogram[bucket] += 1;
+
+        if latency > self.max {
+            self.max = latency;
+            self.worst_sepc = sepc;
+            return true;
+        }
+        false
+    }
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 记录一次中断延迟（在陷阱处理函数中调用，中断已关闭）
+fn record_latency(source: InterruptSource, latency: u64, sepc: usize) {
+    let index = source as usize;
+    let worst = LATENCY.lock()[index].record(latency, sepc);
+
+    if worst && latency > LATENCY_WARN.load(Ordering::Relaxed) {
+        // 被打断的代码通常是一段很长的关中断临界区
+        klog!(
+            "[WARNING] {} interrupt latency {} ticks (new worst), interrupted at {:#x}: {}",
+            source.name(),
+            latency,
+            sepc,
+            disasm::describe_at(sepc)
+        );
+    }
+}
+
+/// 中断延迟统计
+pub fn latency_stats(source: InterruptSource) -> LatencyStats {
+    without_interrupts(|| LATENCY.lock()[source as usize])
+}
+
+/// 清空中断延迟统计
+pub fn reset_latency_stats() {
+    without_interrupts(|| *LATENCY.lock() = [LatencyStats::new(); 3]);
+}
+
+/// 打印中断延迟统计（单位：µs，10MHz 下 1µs = 10 个 time 计数）
+pub fn print_latency_stats() {
+    let to_us = |ticks: u64| ticks * 1_000_000 / crate::clock::TIME_FREQ;
+
+    println!("┌──────────┬──────────┬──────────┬──────────┬──────────┬──────────────┐");
+    println!("│ 中断源   │ 次数     │ 最小 µs  │ 平均 µs  │ 最大 µs  │ 最坏 sepc    │");
+    println!("├──────────┼──────────┼──────────┼──────────┼──────────┼──────────────┤");
+    for source in InterruptSource::ALL {
+        let stats = latency_stats(source);
+        if stats.count == 0 {
+            println!(
+                "│ {:<8} │ {:>8} │ {:>8} │ {:>8} │ {:>8} │ {:>12} │",
+                source.name(),
+                0,
+                "-",
+                "-",
+                "-",
+                "-"
+            );
+            continue;
+        }
+        println!(
+            "│ {:<8} │ {:>8} │ {:>8} │ {:>8} │ {:>8} │ {:>#12x} │",
+            source.name(),
+            stats.count,
+            to_us(stats.min),
+            to_us(stats.average()),
+            to_us(stats.max),
+            stats.worst_sepc
+        );
+    }
+    println!("└──────────┴──────────┴──────────┴──────────┴──────────┴──────────────┘");
+
+    let timer = latency_stats(InterruptSource::Timer);
+    println!(
+        "时钟中断延迟分布: <1µs {} <10µs {} <100µs {} <1ms {} <10ms {} <100ms {} 更长 {}",
+        timer.histogram[0],
+        timer.histogram[1],
+        timer.histogram[2],
+        timer.histogram[3],
+        timer.histogram[4],
+        timer.histogram[5],
+        timer.histogram[6]
+    );
+}
+
 /// 设置下一次定时器中断
 ///
 /// # 功能
 /// - 通过 SBI 调用设置定时器
-/// - 时间间隔：1,000,000 时钟周期（约 100ms @ 10MHz）
-fn set_next_timer() {
-    // QEMU RISC-V virt 机器的时钟频率为 10MHz
-    const TIMER_INTERVAL: u64 = 1_000_000; // 100ms (降低中断频率)
-
+/// - 时间间隔：clock::TICK_INTERVAL（1,000,000 时钟周期，约 100ms @ 10MHz）
+/// - 总是按真实时间设置，与是否安装虚拟时钟无关
+///
+/// # 返回
+/// - `Err(error)`: SBI 返回的错误码（定时器保持原来的期限）
+fn set_next_timer() -> Result<(), isize> {
     // 读取当前时间
     let time = riscv::register::time::read64();
 
-    // 设置下一次定时器中断
-    sbi_set_timer(time + TIMER_INTERVAL);
+    // 设置下一次定时器中断，记录期限用于计算延迟
+    let deadline = time + crate::clock::TICK_INTERVAL;
+    sbi_set_timer(deadline)?;
+    TIMER_DEADLINE.store(deadline, Ordering::Relaxed);
+    Ok(())
 }
 
+/// SBI 的通用失败错误码
+const SBI_ERR_FAILED: isize = -1;
+
 /// SBI 调用：设置定时器
 ///
 /// # 参数
 /// - `stime_value`: 定时器触发的时间值
-fn sbi_set_timer(stime_value: u64) {
+///
+/// # 返回
+/// - `Err(error)`: SBI 返回的错误码（a0 为负）
+fn sbi_set_timer(stime_value: u64) -> Result<(), isize> {
+    if crate::fault_point!("sbi_set_timer") {
+        return Err(SBI_ERR_FAILED);
+    }
+    let error: isize;
     unsafe {
         core::arch::asm!(
             "mv a0, {0}",         // 参数：时间值
             "li a7, 0",           // SBI extension ID: Timer (legacy)
             "ecall",              // 调用 SBI
             in(reg) stime_value,
-            out("a0") _,          // SBI可能修改a0
+            lateout("a0") error,  // 返回值：0 表示成功
             out("a1") _,          // 保护其他寄存器
             options(nostack)
         );
     }
+    if error < 0 {
+        Err(error)
+    } else {
+        Ok(())
+    }
 }
 
 // ============================================
 // 测试
 // ============================================
 
+#[cfg(test)]
+#[test_case]
+fn test_stvec_points_to_trap_entry() {
+    let value = crate::csr::read_stvec().expect("stvec not readable");
+    let entry = trampoline::trap_entry_addr();
+    assert_eq!(value, entry);
+    assert_eq!(check_trap_vector(value, entry), Ok(()));
+
+    // 向量模式、基址不符、入口未对齐
+    let entry = 0x8020_1000;
+    assert_eq!(check_trap_vector(entry | 1, entry), Err("stvec mode is not Direct"));
+    assert_eq!(
+        check_trap_vector(entry + 4, entry),
+        Err("stvec base does not match trap entry")
+    );
+    assert_eq!(
+        check_trap_vector(entry, entry + 2),
+        Err("trap entry is not 4-byte aligned")
+    );
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_sources_stay_masked_until_enable_configured() {
+    use crate::clock::TICK_INTERVAL;
+
+    // 回到启动早期：撤销登记并屏蔽所有中断源（键盘等子系统“还没有初始化”）
+    let registered = without_interrupts(|| {
+        CONFIGURED.store(false, Ordering::Relaxed);
+        mask_all_sources();
+        REGISTERED.swap(0, Ordering::Relaxed)
+    });
+
+    // 全局中断开着，但没有中断源被启用：时钟中断处理（包括键盘轮询）一次也不执行
+    let ticks = uptime_ticks();
+    let start = time::read64();
+    while time::read64() - start < 3 * TICK_INTERVAL {}
+    assert_eq!(uptime_ticks(), ticks);
+
+    // 登记本身不启用中断源
+    register_source(InterruptSource::Timer);
+    register_source(InterruptSource::Software);
+    assert!(!is_source_enabled(InterruptSource::Timer));
+    assert!(!is_source_enabled(InterruptSource::External));
+
+    enable_configured();
+    assert!(is_source_enabled(InterruptSource::Timer));
+    assert!(!is_source_enabled(InterruptSource::External));
+    let start = time::read64();
+    while uptime_ticks() == ticks && time::read64() - start < 3 * TICK_INTERVAL {}
+    assert!(uptime_ticks() > ticks);
+
+    // 软件中断：处理函数清除挂起位
+    unsafe { sip::set_ssoft() };
+    let start = time::read64();
+    while sip::read().ssoft() && time::read64() - start < TICK_INTERVAL {}
+    assert!(!sip::read().ssoft());
+
+    // 恢复原来的登记
+    without_interrupts(|| {
+        REGISTERED.store(registered, Ordering::Relaxed);
+        mask_all_sources();
+    });
+    enable_configured();
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_interrupt_latency_tracks_worst_case() {
+    // 关中断保持到定时器期限之后 HOLD 个 time 计数（20ms）
+    const HOLD: u64 = 200_000;
+
+    reset_latency_stats();
+    without_interrupts(|| {
+        let deadline = TIMER_DEADLINE.load(Ordering::Relaxed);
+        while time::read64() < deadline + HOLD {}
+    });
+
+    // 重新开中断后，挂起的时钟中断立即触发
+    let start = time::read64();
+    while latency_stats(InterruptSource::Timer).count == 0
+        && time::read64() - start < 2 * crate::clock::TICK_INTERVAL
+    {}
+
+    let stats = latency_stats(InterruptSource::Timer);
+    assert!(stats.count >= 1);
+    assert!(stats.max >= HOLD, "worst latency {} < {}", stats.max, HOLD);
+    assert!(stats.max < HOLD + HOLD / 2, "worst latency {} too large", stats.max);
+    assert!(stats.min <= stats.average() && stats.average() <= stats.max);
+    assert_eq!(stats.histogram.iter().sum::<u64>(), stats.count);
+    // 20ms 落在 <100ms 的桶中
+    assert!(stats.histogram[5] >= 1);
+    assert_eq!(latency_stats(InterruptSource::External), LatencyStats::new());
+}
+
 #[cfg(test)]
 #[test_case]
 fn test_breakpoint_exception() {
-    use crate::serial_println;
-    serial_println!("[TEST] test_breakpoint_exception...");
+    klog!("[TEST] test_breakpoint_exception...");
 
     // 触发断点异常
     unsafe {
         core::arch::asm!("ebreak");
     }
 
-    serial_println!("[TEST] Breakpoint handled successfully");
+    klog!("[TEST] Breakpoint handled successfully");
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_breakpoint_reported_once() {
+    let output = crate::console::capture_isr();
+    unsafe {
+        core::arch::asm!("ebreak");
+    }
+
+    // 只在内核日志中报告一次
+    let output = output.take();
+    assert_eq!(output.matches("Breakpoint at").count(), 1);
+    assert!(output.contains("[EXCEPTION] Breakpoint at"));
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_breakpoint_burst_raises_fault_rate() {
+    use crate::clock::{self, TestClock};
+
+    static CLOCK: TestClock = TestClock::new();
+    const BREAKPOINT: usize = 3;
+    let _clock = clock::install(&CLOCK);
+    let before = exception_counts()[BREAKPOINT];
+
+    for _ in 0..8 {
+        unsafe {
+            core::arch::asm!("ebreak");
+        }
+    }
+    assert_eq!(exception_counts()[BREAKPOINT], before + 8);
+
+    // 推进到下一次每秒一次的采样
+    CLOCK.advance(TICKS_PER_SECOND - uptime_ticks() % TICKS_PER_SECOND);
+    assert!(fault_rates()[BREAKPOINT] > 0);
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_page_fault_maps_lazy_page() {
+    use crate::memory::{
+        test_frame_allocator, walk_page_table, AddressSpace, MemoryAreaType, PAGE_SIZE,
+    };
+
+    let mut allocator = test_frame_allocator();
+    let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+    let stack = VirtAddr::new(0x2000_0000);
+    space
+        .map_region_lazy(stack, 16 * PAGE_SIZE, MemoryAreaType::Stack)
+        .expect("map_region_lazy failed");
+    let root = space.page_table_paddr();
+    let free_before = allocator.stats().free_frames;
+
+    let fault_addr = stack + 5 * PAGE_SIZE + 0x18;
+    assert_eq!(walk_page_table(root, fault_addr), None);
+    {
+        let _active = space.set_active(&mut allocator);
+        // 模拟访问按需映射页面时的读页错误
+        let mut frame = FaultFrame { sepc: 0x8020_0000, from_user: false };
+        let cause = Trap::Exception(Exception::LoadPageFault);
+        let outcome = handle_recoverable_fault(cause, fault_addr.as_usize(), &mut frame);
+        assert_eq!(outcome, FaultOutcome::Retry);
+        assert_eq!(frame.sepc, 0x8020_0000);
+    }
+
+    // 只分配了出错的那一页（外加中间页表）
+    let paddr = walk_page_table(root, fault_addr).expect("lazy page not mapped");
+    assert!(walk_page_table(root, stack + 4 * PAGE_SIZE).is_none());
+    assert_eq!(allocator.stats().free_frames, free_before - 3);
+    unsafe {
+        let word = paddr.as_usize() as *mut u64;
+        assert_eq!(word.read_volatile(), 0);
+        word.write_volatile(0xdead_beef);
+        assert_eq!(word.read_volatile(), 0xdead_beef);
+    }
+
+    // 区域外的地址不会被修复
+    let outside = (stack + 16 * PAGE_SIZE).as_usize();
+    assert!(crate::memory::handle_active_page_fault(VirtAddr::new(outside), false).is_err());
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_fault_trace_records_lazy_map() {
+    use crate::memory::{
+        fault_trace, test_frame_allocator, AddressSpace, FaultResolution, MemoryAreaType,
+        PAGE_SIZE,
+    };
+
+    let mut allocator = test_frame_allocator();
+    let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+    let heap = VirtAddr::new(0x3000_0000);
+    space
+        .map_region_lazy(heap, 4 * PAGE_SIZE, MemoryAreaType::Heap)
+        .expect("map_region_lazy failed");
+    let fault_addr = (heap + 2 * PAGE_SIZE + 0x40).as_usize();
+    let outside = (heap + 4 * PAGE_SIZE).as_usize();
+
+    fault_trace::clear();
+    {
+        let _active = space.set_active(&mut allocator);
+        let mut frame = FaultFrame { sepc: 0x8020_0000, from_user: true };
+        let cause = Trap::Exception(Exception::StorePageFault);
+        assert_eq!(handle_recoverable_fault(cause, fault_addr, &mut frame), FaultOutcome::Retry);
+        let outcome = handle_recoverable_fault(cause, outside, &mut frame);
+        assert!(matches!(outcome, FaultOutcome::Terminate(_)));
+    }
+
+    let records = fault_trace::records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].addr, fault_addr);
+    assert_eq!(records[0].cause, Trap::Exception(Exception::StorePageFault));
+    assert_eq!(records[0].area, Some(MemoryAreaType::Heap));
+    assert_eq!(records[0].resolution, FaultResolution::LazyMapped);
+    assert_eq!(records[1].addr, outside);
+    assert_eq!(records[1].area, None);
+    assert_eq!(records[1].resolution, FaultResolution::Terminated("Address not in a lazy area"));
+
+    let output = crate::console::capture();
+    fault_trace::print_trace();
+    let text = output.take();
+    assert!(text.contains(&alloc::format!("at {:#x} area=Heap → lazy-mapped", fault_addr)));
+    assert!(text.contains("→ terminated (Address not in a lazy area)"));
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_unrecoverable_page_fault_outcomes() {
+    use crate::memory::{test_frame_allocator, AddressSpace, MemoryAreaType, PAGE_SIZE};
+
+    let mut allocator = test_frame_allocator();
+    let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+    let heap = VirtAddr::new(0x2000_0000);
+    space
+        .map_region_lazy(heap, 4 * PAGE_SIZE, MemoryAreaType::Heap)
+        .expect("map_region_lazy failed");
+    let cause = Trap::Exception(Exception::StorePageFault);
+    let unmapped = (heap + 4 * PAGE_SIZE).as_usize();
+
+    // 没有登记地址空间时，内核访问未映射的地址是致命错误
+    let mut frame = FaultFrame { sepc: 0x8020_0000, from_user: false };
+    let outcome = handle_recoverable_fault(cause, unmapped, &mut frame);
+    assert_eq!(outcome, FaultOutcome::Fatal("No active address space"));
+
+    let _active = space.set_active(&mut allocator);
+    let outcome = handle_recoverable_fault(cause, unmapped, &mut frame);
+    assert_eq!(outcome, FaultOutcome::Fatal("Address not in a lazy area"));
+    assert_eq!(frame.sepc, 0x8020_0000);
+
+    // 同样的页错误发生在用户态时终止进程
+    frame.from_user = true;
+    let outcome = handle_recoverable_fault(cause, unmapped, &mut frame);
+    assert_eq!(outcome, FaultOutcome::Terminate("Address not in a lazy area"));
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_sbi_timer_failure_keeps_deadline() {
+    use crate::faultinject::{arm, Policy};
+
+    without_interrupts(|| {
+        let deadline = TIMER_DEADLINE.load(Ordering::Relaxed);
+        {
+            let _fault = arm("sbi_set_timer", Policy::Always);
+            assert_eq!(set_next_timer(), Err(SBI_ERR_FAILED));
+        }
+        assert_eq!(TIMER_DEADLINE.load(Ordering::Relaxed), deadline);
+
+        // 解除后重新设置成功，时钟中断继续
+        assert_eq!(set_next_timer(), Ok(()));
+        assert!(TIMER_DEADLINE.load(Ordering::Relaxed) > deadline);
+    });
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_guard_nesting_restores_state() {
+    use riscv::register::sstatus;
+
+    // 从两种初始状态出发，嵌套三层后都应恢复原状
+    for initially_enabled in [false, true] {
+        let _outer = InterruptGuard::new();
+        let base = guard_depth();
+        if initially_enabled {
+            unsafe { sstatus::set_sie() };
+        }
+        {
+            let first = InterruptGuard::new();
+            assert_eq!(first.was_enabled(), initially_enabled);
+            without_interrupts(|| {
+                without_interrupts(|| {
+                    assert!(!sstatus::read().sie());
+                    assert_eq!(guard_depth(), base + 3);
+                });
+                assert!(!sstatus::read().sie());
+            });
+            assert!(!sstatus::read().sie());
+            assert_eq!(guard_depth(), base + 1);
+        }
+        assert_eq!(sstatus::read().sie(), initially_enabled);
+        assert_eq!(guard_depth(), base);
+    }
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_guard_restores_on_early_return() {
+    use riscv::register::sstatus;
+
+    fn fails() -> Result<(), &'static str> {
+        Err("early")
+    }
+
+    let _outer = InterruptGuard::new();
+    let base = guard_depth();
+    unsafe { sstatus::set_sie() };
+    let result: Result<u32, &'static str> = without_interrupts(|| {
+        fails()?;
+        Ok(1)
+    });
+    assert_eq!(result, Err("early"));
+    assert!(sstatus::read().sie());
+    assert_eq!(guard_depth(), base);
+
+    // 守卫在函数中途 return 时同样释放
+    fn returns_early(flag: bool) -> u32 {
+        let _irq = InterruptGuard::new();
+        if flag {
+            return 1;
+        }
+        2
+    }
+    assert_eq!(returns_early(true), 1);
+    assert!(sstatus::read().sie());
+    unsafe { sstatus::clear_sie() };
+}
+
+#[cfg(test)]
+#[test_case]
+fn test_irqctl_masks_uart() {
+    use crate::device::{probe_all, Fdt, SAMPLE_DTB};
+
+    let fdt = Fdt::parse(SAMPLE_DTB).expect("parse failed");
+    probe_all(&fdt).expect("probe failed");
+    let uart = irq_info().into_iter().find(|info| info.name == "uart16550").expect("no UART IRQ");
+    assert!(uart.enabled);
+    assert_eq!(uart.priority, 1);
+    let irq = uart.irq;
+    let deliver = || in_interrupt_context(|| handle_irq(irq));
+    let count = || IRQ_COUNTS[irq as usize].load(Ordering::Relaxed);
+
+    // 使能时经中断路径收到的每次中断都计数
+    let before = count();
+    deliver();
+    assert_eq!(count(), before + 1);
+    let proc = read_proc_interrupts();
+    assert!(proc.lines().any(|line| line.contains("enabled") && line.ends_with("uart16550")));
+
+    // 屏蔽后次数冻结，/proc/interrupts 显示 masked
+    assert_eq!(run_command(&format!("irqctl mask {}", irq)), Ok(()));
+    assert!(!plic::is_enabled(irq));
+    deliver();
+    deliver();
+    assert_eq!(count(), before + 1);
+    let info = irq_info().into_iter().find(|info| info.irq == irq).unwrap();
+    assert!(!info.enabled && info.last_tick.is_some());
+    assert!(read_proc_interrupts().lines().any(|line| line.contains("masked")));
+
+    // 重新使能后恢复计数
+    assert_eq!(run_command(&format!("irqctl unmask {}", irq)), Ok(()));
+    deliver();
+    assert_eq!(count(), before + 2);
+
+    assert_eq!(run_command(&format!("irqctl prio {} 3", irq)), Ok(()));
+    assert_eq!(plic::priority(irq), 3);
+    assert_eq!(run_command(&format!("irqctl prio {} 8", irq)), Err("Priority out of range"));
+    assert_eq!(run_command(&format!("irqctl prio {} 1", irq)), Ok(()));
+    assert_eq!(run_command("irqctl mask 99"), Err("IRQ not registered"));
+    assert_eq!(run_command("irqctl mask x"), Err("Invalid IRQ number"));
+    assert!(run_command("irqctl mask").unwrap_err().starts_with("usage"));
+    assert_eq!(run_command("irq list"), Err("Unknown command"));
 }
@@ -0,0 +1,144 @@
+/*
+ * ============================================
+ * 内核日志
+ * ============================================
+ * 功能：klog! 宏，记录一行内核事件（"[子系统] ..."）
+ *
+ * 三种输出的分工：
+ * - print! / println!（console）：给用户看的输出——演示结果、命令输出、统计表格。
+ *   经过输出捕获（以后还有颜色、分页）
+ * - klog!（本模块）：内核事件日志——初始化进度、警告、异常报告。
+ *   写入串口调试通道，同时保留在环形缓冲区中（recent），任何上下文都可以调用
+ * - serial_print! / serial_println!：最底层的串口通道，只用于 console 和 klog
+ *   可能不可用的场合——测试运行器、panic 处理函数、分配器内部。
+ *   允许使用的模块见 SERIAL_MODULES，测试 test_serial_macros_stay_low_level 检查
+ *
+ * 一个事件在每个通道上只报告一次：同一件事不要既 klog! 又 println!，
+ * 除非两者说的是不同的内容（例如用户态页错误：klog 记录诊断信息，
+ * console 输出给用户看的 "Segmentation fault"）
+ * ============================================
+ */
+
+use core::fmt::{self, Write};
+use spin::Mutex;
+
+/// 环形缓冲区的字节数
+pub const LOG_SIZE: usize = 4096;
+
+/// 保留最近日志的环形缓冲区
+struct Ring {
+    bytes: [u8; LOG_SIZE],
+    /// 写入的总字节数（下一个字节写在 written % LOG_SIZE）
+    written: usize,
+}
+
+impl Write for Ring {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.bytes[self.written % LOG_SIZE] = byte;
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
+
+static LOG: Mutex<Ring> = Mutex::new(Ring { bytes: [0; LOG_SIZE], written: 0 });
+
+/// 底层日志函数
+///
+/// # 说明
+/// 在中断处理函数中也可以调用：环形缓冲区正被占用时（日志被异常打断）
+/// 这一行只写串口
+#[doc(hidden)]
+pub fn _log(args: fmt::Arguments) {
+    let _irq = crate::interrupts::InterruptGuard::new();
+    if let Some(mut ring) = LOG.try_lock() {
+        let _ = ring.write_fmt(args);
+    }
+    crate::serial::_print(args);
+}
+
+/// 环形缓冲区中最近的日志（只保留完整的行，从旧到新）
+pub fn recent() -> alloc::string::String {
+    use alloc::string::String;
+
+    let (bytes, start) = crate::interrupts::without_interrupts(|| {
+        let ring = LOG.lock();
+        let start = ring.written.saturating_sub(LOG_SIZE);
+        let bytes: alloc::vec::Vec<u8> =
+            (start..ring.written).map(|index| ring.bytes[index % LOG_SIZE]).collect();
+        (bytes, start)
+    });
+    // 缓冲区绕回后第一行可能不完整
+    let skip = match start {
+        0 => 0,
+        _ => bytes.iter().position(|&byte| byte == b'\n').map_or(bytes.len(), |end| end + 1),
+    };
+    String::from_utf8_lossy(&bytes[skip..]).into_owned()
+}
+
+/// 内核日志宏（自动换行）
+///
+/// # 用法
+/// ```rust
+/// klog!("[MEMORY] {} frames free", free);
+/// ```
+#[macro_export]
+macro_rules! klog {
+    ($($arg:tt)*) => ($crate::klog::_log(format_args!("{}\n", format_args!($($arg)*))));
+}
+
+// ============================================
+// 输出通道检查
+// ============================================
+
+/// 可以使用 serial_print! / serial_println! 的模块
+///
+/// # 说明
+/// - serial / console / klog：输出通道本身
+/// - lib / selftest：测试运行器与测试的 panic 处理函数
+/// - allocator：分配失败和堆初始化之前的报告，这时不能再经过会分配内存的代码
+pub const SERIAL_MODULES: &[&str] =
+    &["serial.rs", "console.rs", "klog.rs", "lib.rs", "selftest.rs", "allocator.rs"];
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// src 下的全部源文件（构建脚本 build.rs 生成）
+    const SOURCES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/kernel_sources.rs"));
+
+    #[test_case]
+    fn test_serial_macros_stay_low_level() {
+        assert!(SOURCES.iter().any(|(name, _)| *name == "memory/paging.rs"));
+        for (name, source) in SOURCES.iter().filter(|(name, _)| !SERIAL_MODULES.contains(name)) {
+            for (line, text) in source.lines().enumerate() {
+                // 同时匹配 serial_print! 和 serial_println!，以及 use 它们的语句
+                let uses_serial = text.contains("serial_print");
+                assert!(!uses_serial, "{}:{} uses the serial macros", name, line + 1);
+            }
+        }
+    }
+
+    #[test_case]
+    fn test_klog_keeps_recent_lines() {
+        let output = crate::console::capture();
+        klog!("[TEST] klog line {}", 1);
+        assert_eq!(output.take(), "[TEST] klog line 1\n");
+        assert!(recent().ends_with("[TEST] klog line 1\n"));
+
+        // 绕回之后只保留完整的行
+        let lines = LOG_SIZE / 64 + 1;
+        for index in 0..lines {
+            klog!("[TEST] {:>56}", index);
+        }
+        let recent = recent();
+        assert!(recent.len() <= LOG_SIZE);
+        assert!(recent.starts_with("[TEST] "));
+        assert!(recent.ends_with(&alloc::format!("[TEST] {:>56}\n", lines - 1)));
+    }
+}
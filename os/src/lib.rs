@@ -8,6 +8,7 @@
  * 主要模块：
  * - 串口输出（serial）
  * - 控制台（console）
+ * - 内核日志（klog）
  * - 中断处理（interrupts）
  * - 内存管理（memory）
  * - 堆分配器（allocator）
@@ -21,6 +22,7 @@
 #![test_runner(crate::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 #![feature(abi_riscv_interrupt)]  // RISC-V 中断 ABI（实验性功能）
+#![feature(alloc_error_handler)]  // 自定义内存不足处理
 
 use core::panic::PanicInfo;
 
@@ -29,10 +31,30 @@ use core::panic::PanicInfo;
 // ============================================
 
 pub mod serial;      // 串口驱动
+pub mod device;      // 设备树与驱动探测
 pub mod console;     // 控制台输出
+pub mod klog;        // 内核日志（输出通道的分工见此模块）
+pub mod csr;         // CSR 访问封装
+pub mod clock;       // 时钟源（测试用虚拟时钟）
+pub mod fmt;         // 规范化输出（golden 测试）
 pub mod interrupts;  // 中断和异常处理
+pub mod trampoline;  // 跳板页（陷阱入口/出口）
+pub mod disasm;      // 指令反汇编（异常诊断）
+pub mod peek;        // 内存查看命令（peek / poke）
+pub mod memory;      // 内存管理（物理帧、页表、地址空间）
+pub mod elf;         // ELF 加载与校验
 pub mod allocator;   // 堆分配器
 pub mod task;        // 异步任务系统
+pub mod syscall;     // 系统调用
+pub mod bench;       // 性能测量辅助
+pub mod entropy;     // 熵源（随机数种子，非密码学安全）
+pub mod faultinject; // 故障注入（测试错误路径）
+pub mod sysctl;      // 运行时内核参数
+pub mod crashlog;    // 崩溃记录（保留页，跨软复位）
+pub mod objects;     // 内核对象登记（泄漏检测）
+pub mod selftest;    // 内置测试（普通内核中运行单元测试）
+#[cfg(feature = "selfmod_demo")]
+pub mod selfmod;     // 自修改代码演示（fence.i）
 
 // ============================================
 // 外部 crate
@@ -40,6 +62,12 @@ pub mod task;        // 异步任务系统
 
 extern crate alloc;  // 启用 alloc crate（堆分配）
 
+/// 不可失败的堆分配失败时调用（处理函数见 `allocator::set_oom_handler`）
+#[alloc_error_handler]
+fn alloc_error(layout: core::alloc::Layout) -> ! {
+    allocator::handle_oom(layout)
+}
+
 // ============================================
 // 测试框架
 // ============================================
@@ -73,10 +101,30 @@ pub fn test_runner(tests: &[&dyn Testable]) {
 pub fn test_panic_handler(info: &PanicInfo) -> ! {
     serial_println!("[failed]\n");
     serial_println!("Error: {}\n", info);
+    let depth = interrupts::guard_depth();
+    if depth > 0 {
+        serial_println!("(panicked with {} interrupt guard(s) live, interrupts disabled)\n", depth);
+    }
     exit_qemu(QemuExitCode::Failed);
     hlt_loop();
 }
 
+/// 打印 panic 信息（内核的 panic 处理函数调用）
+///
+/// # 说明
+/// 走串口的 panic 专用路径（`serial::_print_panic`）：panic 可能发生在持有控制台、
+/// 日志或串口锁的时候
+pub fn report_panic(info: &PanicInfo) {
+    serial::_print_panic(format_args!("{}\n", info));
+    let depth = interrupts::guard_depth();
+    if depth > 0 {
+        serial::_print_panic(format_args!(
+            "(panicked with {} interrupt guard(s) live, interrupts disabled)\n",
+            depth
+        ));
+    }
+}
+
 // ============================================
 // QEMU 退出码
 // ============================================
@@ -116,21 +164,30 @@ pub fn exit_qemu(exit_code: QemuExitCode) {
 // 初始化函数
 // ============================================
 
-/// 初始化操作系统
+/// 初始化操作系统（第一阶段）
 ///
 /// # 功能
-/// - 初始化中断描述符表
-/// - 启用中断
+/// - 初始化中断描述符表，屏蔽所有中断源
+/// - 登记时钟中断（处理逻辑只使用静态状态，键盘队列未初始化时输入被丢弃）
+///
+/// # 说明
+/// 中断要等 `init_late` 之后才会到来
 pub fn init() {
-    serial_println!("[INIT] Initializing RISC-V OS");
+    klog!("[INIT] Initializing RISC-V OS");
 
     // 初始化中断系统
     interrupts::init_idt();
+    interrupts::register_source(interrupts::InterruptSource::Timer);
 
-    // 启用中断
-    interrupts::enable_interrupts();
+    klog!("[INIT] Initialization complete");
+}
 
-    serial_println!("[INIT] Initialization complete");
+/// 初始化操作系统（第二阶段，其他子系统初始化之后调用）
+///
+/// # 功能
+/// - 启用已登记的中断源，打开全局中断
+pub fn init_late() {
+    interrupts::enable_configured();
 }
 
 /// 无限循环（使用 wfi 指令节能）
@@ -152,6 +209,8 @@ pub fn hlt_loop() -> ! {
 #[cfg(test)]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    // selftest::run_one 运行的测试 panic 时回到它那里
+    selftest::fail_current(info);
     test_panic_handler(info)
 }
 
@@ -159,10 +218,61 @@ fn panic(info: &PanicInfo) -> ! {
 // 测试入口点
 // ============================================
 
+// 与 main.rs 相同的汇编入口：设置栈指针、清零 BSS 段，再进入 Rust 代码
+// （直接用 Rust 函数作为入口时栈指针是固件留下的值，BSS 中的静态变量也没有清零）
+#[cfg(test)]
+core::arch::global_asm!(
+    ".section .text.entry",
+    ".globl _start",
+    "_start:",
+    "   la sp, stack_end",
+    "   la t0, bss_start",
+    "   la t1, bss_end",
+    "1:",
+    "   bgeu t0, t1, 2f",
+    "   sd zero, (t0)",
+    "   addi t0, t0, 8",
+    "   j 1b",
+    "2:",
+    "   call test_kernel_main",
+    "3:",
+    "   wfi",
+    "   j 3b",
+);
+
+/// 单元测试的内核主函数（由汇编入口调用）
+///
+/// # 功能
+/// - 初始化中断
+/// - 初始化堆，使用 alloc 的测试可以运行
 #[cfg(test)]
 #[no_mangle]
-pub extern "C" fn _start() -> ! {
+pub extern "C" fn test_kernel_main() -> ! {
     init();
+    allocator::init_heap_simple(memory::kernel_end_addr()).expect("heap initialization failed");
+    init_late();
     test_main();
     hlt_loop();
 }
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+
+    #[test_case]
+    fn test_heap_available_in_lib_tests() {
+        let boxed = Box::new(0x5a5a_u32);
+        assert_eq!(*boxed, 0x5a5a);
+
+        let values: Vec<usize> = (0..1000).collect();
+        assert_eq!(values.iter().sum::<usize>(), 999 * 1000 / 2);
+
+        let mut map = BTreeMap::new();
+        map.insert("heap", 1);
+        assert_eq!(map.get("heap"), Some(&1));
+    }
+}
@@ -6,7 +6,7 @@
 
 use core::arch::global_asm;
 use core::panic::PanicInfo;
-use os::println;
+use os::{klog, println};
 
 // RISC-V 汇编入口点
 // 定义在汇编中，负责：
@@ -28,7 +28,7 @@ global_asm!(
     "   addi t0, t0, 8",
     "   j 1b",
     "2:",
-    // 跳转到 kernel_main
+    // 跳转到 kernel_main（a0 = hartid，a1 = 设备树地址，由固件传入，上面没有改动）
     "   call kernel_main",
     // 如果返回，进入死循环
     "3:",
@@ -39,7 +39,10 @@ global_asm!(
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
+    // 内置测试失败：报告后回到测试运行器，不停机
+    os::selftest::fail_current(info);
+    os::crashlog::record_panic(info);
+    os::report_panic(info);
     os::hlt_loop();            // new
 }
 
@@ -57,9 +60,13 @@ use alloc::{boxed::Box, vec, vec::Vec, rc::Rc};
 /// - 初始化内核
 /// - 设置堆分配器
 /// - 启动异步执行器
+///
+/// # 参数
+/// - `_hartid`: 当前 hart 的编号
+/// - `dtb`: 设备树的物理地址
 #[no_mangle]
-pub extern "C" fn kernel_main() -> ! {
-    use os::allocator;
+pub extern "C" fn kernel_main(_hartid: usize, dtb: usize) -> ! {
+    use os::{allocator, clock, device, fmt, fmt::Canon, memory, selftest, sysctl};
 
     println!("Welcome to Error OS{}", "!");
     os::init();
@@ -74,20 +81,90 @@ pub extern "C" fn kernel_main() -> ! {
     allocator::init_heap_simple(kernel_end_addr)
         .expect("heap initialization failed");
 
+    // 按设备树探测驱动（需要堆）
+    match unsafe { device::Fdt::from_addr(dtb) } {
+        Ok(fdt) => {
+            device::probe_all(&fdt).expect("driver dependency cycle");
+            clock::set_timebase_from(&fdt);
+            if memory::set_dram_from(&fdt).is_none() {
+                klog!("[MEMORY] device tree has no usable /memory node, assuming 128MB");
+            }
+            // 命令行中的 sysctl.name=value、canon=1 和自检参数
+            if let Some(bootargs) = fdt.find("/chosen").and_then(|n| n.prop_str("bootargs")) {
+                sysctl::apply_cmdline(bootargs);
+                fmt::apply_cmdline(bootargs);
+                selftest::apply_cmdline(bootargs);
+            }
+        }
+        Err(e) => klog!("[DEVICE] device tree at {:#x} unusable: {}", dtb, e),
+    }
+    klog!(
+        "[MEMORY] DRAM: {:#x} - {:#x} ({} MB)",
+        memory::MEMORY_START,
+        memory::memory_end(),
+        (memory::memory_end() - memory::MEMORY_START) >> 20
+    );
+
+    // 崩溃记录在 DRAM 的最后一页，知道内存大小之后才能读取
+    os::crashlog::init();
+
+    // 堆和驱动都已就绪，启用已登记的中断源
+    os::init_late();
+
+    // 命令行 run_tests=1：运行内置测试（需要 builtin_tests 特性），之后照常启动。
+    // 测试的页帧与帧分配器管理的内存重叠，必须在 memory::init 之前运行
+    if selftest::run_at_boot() {
+        selftest::run_full().expect("built-in tests");
+    }
+
+    // 初始化物理帧分配器：内核映像、堆和设备树已经在使用，不能分配出去
+    let dtb_size = unsafe { device::Fdt::total_size(dtb) }.unwrap_or(0);
+    let mut memory_manager = memory::init(&[
+        memory::phys_range(memory::kernel_start_addr()..kernel_end_addr),
+        memory::phys_range(allocator::simple_heap_range(kernel_end_addr)),
+        memory::phys_range(dtb..dtb + dtb_size),
+    ]);
+
+    // 建立内核地址空间，观察页表占用了多少页帧
+    #[cfg(not(feature = "higher_half"))]
+    let kernel_space = memory::create_kernel_address_space(&mut memory_manager.frame_allocator)
+        .expect("failed to create kernel address space");
+
+    // 切换到高半部分别名运行
+    #[cfg(feature = "higher_half")]
+    let kernel_space = {
+        let frames = &mut memory_manager.frame_allocator;
+        let space = memory::create_kernel_address_space_high(frames)
+            .expect("failed to create kernel address space");
+        unsafe { memory::enter_high_half(&space) };
+        // 栈上的局部变量现在通过高半部分别名访问
+        klog!("[MEMORY] running from the high half, stack at {:#x}", &space as *const _ as usize);
+        space
+    };
+    kernel_space.print_layout(false);
+    memory_manager.print_memory_usage();
+
+    #[cfg(feature = "selfmod_demo")]
+    os::selfmod::run_demo(&mut memory_manager.frame_allocator).expect("selfmod demo failed");
+
     let heap_value = Box::new(41);
-    println!("heap_value at {:p}", heap_value);
+    println!("heap_value at {}", Canon::ptr(&*heap_value));
 
     let mut vec = Vec::new();
     for i in 0..500 {
         vec.push(i);
     }
-    println!("vec at {:p}", vec.as_slice());
+    println!("vec at {}", Canon::ptr(vec.as_slice()));
 
     let reference_counted = Rc::new(vec![1, 2, 3]);
     let cloned_reference = reference_counted.clone();
     println!("current reference count is {}", Rc::strong_count(&cloned_reference));
     core::mem::drop(reference_counted);
     println!("reference count is {} now", Rc::strong_count(&cloned_reference));
+    let weak_reference = Rc::downgrade(&cloned_reference);
+    println!("weak reference upgrades: {}", weak_reference.upgrade().is_some());
+    core::mem::drop(cloned_reference);
+    println!("after the last strong reference: {}", weak_reference.upgrade().is_some());
 
     println!("\n========================================");
     println!("  所有测试完成！");
@@ -0,0 +1,2417 @@
+/*
+ * ============================================
+ * 地址空间抽象
+ * ============================================
+ * 功能：管理一个独立的虚拟地址空间
+ * 包括：
+ * - 根页表（satp 指向的页表）
+ * - 内存区域列表（代码、数据、堆、栈等）
+ *
+ * 映射方式：
+ * - 普通映射：为每个页面分配新的物理页帧
+ * - 恒等映射：虚拟地址 = 物理地址（内核、MMIO）
+ * - 按需映射：只记录区域，第一次访问触发页错误时才分配页帧
+ *   （页错误处理函数通过 set_active 登记的地址空间处理）
+ *
+ * fork：子地址空间与父地址空间只读共享普通映射的页帧（写时复制），
+ * 第一次写入触发页错误，由 handle_cow_fault 复制页帧并恢复写权限
+ * ============================================
+ */
+
+use super::paging::{
+    alloc_table, dump_page_table_mode, flush_tlb, free_page_tables, leaf_entry_mut,
+    leaf_permissions, leaf_size, map_page, map_page_1gb, map_page_2mb, map_range, protect_page,
+    prune_tables, register_asid, unmap_page, unmap_page_1gb, unmap_page_2mb, unregister_asid,
+    walk_page_table_mode, MappingSize, Mappings, LEAF_PERMISSIONS,
+};
+use super::fault_trace::FaultResolution;
+use super::high_half::{kvirt_to_phys, phys_to_kvirt};
+use super::tlb;
+use super::{
+    FrameAllocator, PageIter, PageTable, PagingMode, PhysAddr, PhysFrame, PteFlags, VirtAddr,
+    GIGAPAGE_SIZE, KERNEL_SPACE_START, MEGAPAGE_SIZE, PAGE_SIZE,
+};
+use crate::fmt::Canon;
+use crate::{klog, println};
+use crate::trampoline::{self, TRAMPOLINE};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU16, Ordering};
+use spin::Mutex;
+
+/// 内存区域类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAreaType {
+    /// 代码段（R-X）
+    Code,
+    /// 数据段（RW-）
+    Data,
+    /// 堆（RW-）
+    Heap,
+    /// 栈（RW-）
+    Stack,
+    /// 内核区域（RWX，恒等映射）
+    Kernel,
+    /// 设备寄存器（RW-，恒等映射）
+    Mmio,
+    /// 与内核共享的跳板代码（R-X，用户地址空间中也不带 U 位）
+    Trampoline,
+}
+
+impl MemoryAreaType {
+    /// 该类型区域的默认页表标志位
+    pub fn default_flags(&self) -> PteFlags {
+        match self {
+            MemoryAreaType::Code => PteFlags::READ | PteFlags::EXECUTE,
+            MemoryAreaType::Data
+            | MemoryAreaType::Heap
+            | MemoryAreaType::Stack
+            | MemoryAreaType::Mmio => PteFlags::READ | PteFlags::WRITE,
+            MemoryAreaType::Kernel => PteFlags::READ | PteFlags::WRITE | PteFlags::EXECUTE,
+            MemoryAreaType::Trampoline => PteFlags::READ | PteFlags::EXECUTE,
+        }
+    }
+
+    /// 用户地址空间中是否为用户态可访问的区域（代码、数据、堆、栈）
+    ///
+    /// # 说明
+    /// 内核区域、设备寄存器和跳板即使映射进用户地址空间也不带 U 位
+    pub fn is_user_accessible(&self) -> bool {
+        matches!(
+            self,
+            MemoryAreaType::Code
+                | MemoryAreaType::Data
+                | MemoryAreaType::Heap
+                | MemoryAreaType::Stack
+        )
+    }
+
+    /// 区域类型名称（用于打印布局）
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryAreaType::Code => "Code",
+            MemoryAreaType::Data => "Data",
+            MemoryAreaType::Heap => "Heap",
+            MemoryAreaType::Stack => "Stack",
+            MemoryAreaType::Kernel => "Kernel",
+            MemoryAreaType::Mmio => "MMIO",
+            MemoryAreaType::Trampoline => "Tramp",
+        }
+    }
+}
+
+/// 连续的虚拟页范围 [start_page, start_page + count)
+///
+/// 以页为单位记录，避免字节范围在页对齐时的包含 / 不包含歧义
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRange {
+    /// 起始虚拟页号
+    start_page: usize,
+    /// 页数
+    count: usize,
+}
+
+impl PageRange {
+    /// 由起始页号和页数创建
+    pub const fn new(start_page: usize, count: usize) -> Self {
+        PageRange { start_page, count }
+    }
+
+    /// 覆盖字节范围 [start, start + size) 的最小页范围
+    ///
+    /// # 说明
+    /// 起始地址向下、结束地址向上取整到页边界；size 为 0 时页数为 0
+    pub fn covering(start: VirtAddr, size: usize) -> Self {
+        let start_page = start.page_number();
+        let end_page = (start + size).as_usize().div_ceil(PAGE_SIZE);
+        PageRange::new(start_page, end_page - start_page)
+    }
+
+    /// 起始地址（页对齐）
+    pub fn start(&self) -> VirtAddr {
+        VirtAddr::new(self.start_page * PAGE_SIZE)
+    }
+
+    /// 结束地址（页对齐，不包含）
+    pub fn end(&self) -> VirtAddr {
+        VirtAddr::new((self.start_page + self.count) * PAGE_SIZE)
+    }
+
+    /// 页数
+    pub fn page_count(&self) -> usize {
+        self.count
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// 依次返回每一页的起始地址
+    pub fn iter_pages(&self) -> PageIter<VirtAddr> {
+        PageIter::new(self.start_page, self.start_page + self.count, |page| {
+            VirtAddr::new(page * PAGE_SIZE)
+        })
+    }
+
+    /// 地址是否落在范围内
+    pub fn contains(&self, vaddr: VirtAddr) -> bool {
+        let page = vaddr.page_number();
+        page >= self.start_page && page < self.start_page + self.count
+    }
+
+    /// 两个范围是否有公共页
+    pub fn overlaps(&self, other: &PageRange) -> bool {
+        self.start_page < other.start_page + other.count
+            && other.start_page < self.start_page + self.count
+    }
+
+    /// 在 `vaddr` 处拆分为 [start, vaddr) 和 [vaddr, end)
+    ///
+    /// # 返回
+    /// `vaddr` 未页对齐或不在范围内部（两边都非空）时返回 `None`
+    pub fn split_at(&self, vaddr: VirtAddr) -> Option<(PageRange, PageRange)> {
+        if vaddr.page_offset() != 0 {
+            return None;
+        }
+        let page = vaddr.page_number();
+        if page <= self.start_page || page >= self.start_page + self.count {
+            return None;
+        }
+
+        let left = page - self.start_page;
+        Some((
+            PageRange::new(self.start_page, left),
+            PageRange::new(page, self.count - left),
+        ))
+    }
+}
+
+/// 内存区域
+#[derive(Debug, Clone)]
+pub struct MemoryArea {
+    /// 覆盖的虚拟页
+    pages: PageRange,
+    /// 原始字节大小（从 pages.start() 起算，最后一页可能只用了一部分）
+    size: usize,
+    /// 区域类型
+    pub area_type: MemoryAreaType,
+    /// 页表标志位
+    pub flags: PteFlags,
+    /// 是否为恒等映射（物理页帧不属于此地址空间；跳板区域同样不拥有页帧）
+    pub identity: bool,
+    /// 是否按需映射（页面在第一次访问时才分配页帧）
+    pub lazy: bool,
+    /// 线性映射中虚拟地址与物理地址之差（恒等映射为 0，见 `map_region_linear`）
+    pub offset: usize,
+}
+
+impl MemoryArea {
+    /// 创建内存区域
+    ///
+    /// # 参数
+    /// - `start`: 起始虚拟地址（向下取整到页）
+    /// - `size`: 字节大小（结束地址向上取整到页）
+    pub fn new(
+        start: VirtAddr,
+        size: usize,
+        area_type: MemoryAreaType,
+        flags: PteFlags,
+        identity: bool,
+    ) -> Self {
+        let pages = PageRange::covering(start, size);
+        MemoryArea {
+            pages,
+            size: (start + size) - pages.start(),
+            area_type,
+            flags,
+            identity,
+            lazy: false,
+            offset: 0,
+        }
+    }
+
+    /// 覆盖的虚拟页
+    pub fn pages(&self) -> PageRange {
+        self.pages
+    }
+
+    /// 起始地址（页对齐）
+    pub fn start(&self) -> VirtAddr {
+        self.pages.start()
+    }
+
+    /// 结束地址（页对齐，不包含）
+    pub fn end(&self) -> VirtAddr {
+        self.pages.end()
+    }
+
+    /// 原始字节大小
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// 区域占用的页数
+    pub fn page_count(&self) -> usize {
+        self.pages.page_count()
+    }
+
+    /// 在 `vaddr`（页对齐）处拆分区域
+    ///
+    /// # 返回
+    /// 见 `PageRange::split_at`
+    pub fn split_at(&self, vaddr: VirtAddr) -> Option<(MemoryArea, MemoryArea)> {
+        let (left_pages, right_pages) = self.pages.split_at(vaddr)?;
+        let left_size = vaddr - self.pages.start();
+
+        let left = MemoryArea {
+            pages: left_pages,
+            size: left_size,
+            ..self.clone()
+        };
+        let right = MemoryArea {
+            pages: right_pages,
+            size: self.size - left_size,
+            ..self.clone()
+        };
+        Some((left, right))
+    }
+}
+
+/// 栈下方的保护页
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuardPage {
+    /// 保护页（一页，始终不映射）
+    pub page: PageRange,
+    /// 被保护的栈（栈溢出诊断中显示）
+    pub stack: &'static str,
+}
+
+/// 地址空间
+pub struct AddressSpace {
+    /// 根页表（位于帧分配器分配的物理页帧中）
+    page_table: *mut PageTable,
+    /// 已映射的内存区域
+    areas: Vec<MemoryArea>,
+    /// 栈下方的保护页
+    guards: Vec<GuardPage>,
+    /// 分页模式
+    mode: PagingMode,
+    /// 地址空间标识（写入 satp.ASID，TLB 条目按它区分）
+    asid: u16,
+    /// 是否为用户地址空间（用户区域的页表项带 U 位）
+    user: bool,
+}
+
+/// 下一个分配的 ASID（0 留给启动时的 Bare 模式和测试中的临时页表）
+static NEXT_ASID: AtomicU16 = AtomicU16::new(1);
+
+/// 分配一个 ASID
+///
+/// # 说明
+/// 计数器单调递增；回绕后重新从 1 开始，并刷新整个 TLB 清除旧条目
+/// （仍然存活的地址空间可能与新地址空间共用 ASID，目前不会同时存在这么多地址空间）
+fn alloc_asid() -> u16 {
+    let asid = NEXT_ASID.fetch_add(1, Ordering::Relaxed);
+    if asid != 0 {
+        return asid;
+    }
+    tlb::flush_all();
+    NEXT_ASID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl AddressSpace {
+    /// 创建新的地址空间（Sv39）
+    ///
+    /// # 功能
+    /// - 分配并清空根页表（根页表被固定，不参与页面置换）
+    /// - 分配一个新的 ASID
+    pub fn new(allocator: &mut dyn FrameAllocator) -> Result<Self, &'static str> {
+        Self::with_mode(PagingMode::Sv39, allocator)
+    }
+
+    /// 创建指定分页模式的地址空间
+    ///
+    /// # 说明
+    /// 区域映射（map_region 等）只支持 Sv39；Sv48 地址空间目前通过
+    /// `paging::map_page_mode` 直接映射页面，用于演示四级页表
+    pub fn with_mode(
+        mode: PagingMode,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<Self, &'static str> {
+        let frame = alloc_table(allocator)?;
+        let page_table = phys_to_kvirt(frame.start_address()).as_usize() as *mut PageTable;
+        let asid = alloc_asid();
+        register_asid(frame.start_address(), asid);
+
+        Ok(AddressSpace {
+            page_table,
+            areas: Vec::new(),
+            guards: Vec::new(),
+            mode,
+            asid,
+            user: false,
+        })
+    }
+
+    /// 创建用户地址空间（Sv39）
+    ///
+    /// # 说明
+    /// 代码、数据、堆、栈区域的页表项带 U 位，并且必须位于内核保留的高半部分
+    /// （`KERNEL_SPACE_START` 起）之下；内核区域、设备寄存器和跳板不带 U 位
+    ///
+    /// 创建时映射跳板页（见 `map_trampoline`）
+    pub fn new_user(allocator: &mut dyn FrameAllocator) -> Result<Self, &'static str> {
+        let mut space = Self::new(allocator)?;
+        space.user = true;
+        space.map_trampoline(allocator)?;
+        Ok(space)
+    }
+
+    /// 是否为用户地址空间
+    pub fn is_user(&self) -> bool {
+        self.user
+    }
+
+    /// 分页模式
+    pub fn mode(&self) -> PagingMode {
+        self.mode
+    }
+
+    /// 地址空间标识（ASID）
+    pub fn asid(&self) -> u16 {
+        self.asid
+    }
+
+    /// 获取根页表
+    pub fn page_table(&mut self) -> &mut PageTable {
+        unsafe { &mut *self.page_table }
+    }
+
+    /// 获取根页表的物理地址
+    pub fn page_table_paddr(&self) -> PhysAddr {
+        kvirt_to_phys(VirtAddr::new(self.page_table as usize))
+    }
+
+    /// 获取所有内存区域
+    pub fn areas(&self) -> &[MemoryArea] {
+        &self.areas
+    }
+
+    /// 虚拟地址转换为物理地址（在此地址空间的页表中查找）
+    ///
+    /// # 返回
+    /// - `Some(PhysAddr)`: 转换结果（包含页内偏移）
+    /// - `None`: 未映射（包括按需映射区域中还没有访问过的页面）
+    pub fn translate(&self, vaddr: VirtAddr) -> Option<PhysAddr> {
+        walk_page_table_mode(self.page_table_paddr(), vaddr, self.mode)
+    }
+
+    /// `vaddr` 所在的页面是否已映射
+    pub fn is_mapped(&self, vaddr: VirtAddr) -> bool {
+        self.translate(vaddr).is_some()
+    }
+
+    /// 包含 `vaddr` 的内存区域
+    pub fn area_containing(&self, vaddr: VirtAddr) -> Option<&MemoryArea> {
+        self.areas.iter().find(|area| area.pages().contains(vaddr))
+    }
+
+    /// 包含 `vaddr` 的区域的范围和权限
+    ///
+    /// # 返回
+    /// - `Some((start, end, flags))`: 区域的起始地址、结束地址（页对齐，不包含）和标志位
+    /// - `None`: 地址不在任何区域中（`end` 本身属于下一个区域或不属于任何区域）
+    ///
+    /// # 用途
+    /// 把缓冲区长度截断到区域末尾：`len.min(end - vaddr)`
+    pub fn region_bounds(&self, vaddr: VirtAddr) -> Option<(VirtAddr, VirtAddr, PteFlags)> {
+        self.area_containing(vaddr)
+            .map(|area| (area.start(), area.end(), area.flags))
+    }
+
+    /// 获取所有保护页
+    pub fn guard_pages(&self) -> &[GuardPage] {
+        &self.guards
+    }
+
+    /// `vaddr` 是否位于保护页中
+    pub fn is_guard_page(&self, vaddr: VirtAddr) -> bool {
+        self.guard_at(vaddr).is_some()
+    }
+
+    /// 包含 `vaddr` 的保护页
+    pub fn guard_at(&self, vaddr: VirtAddr) -> Option<&GuardPage> {
+        self.guards.iter().find(|guard| guard.page.contains(vaddr))
+    }
+
+    /// 把 `vaddr` 所在的页登记为 `stack` 的保护页
+    ///
+    /// # 返回
+    /// - `Err`: 该页已经属于某个区域
+    ///
+    /// # 说明
+    /// 用于自行映射栈的情况（如内核地址空间中链接脚本给出的内核栈）；
+    /// `map_stack` 和 `map_region(.., MemoryAreaType::Stack, ..)` 会自动登记
+    pub fn add_guard_page(
+        &mut self,
+        vaddr: VirtAddr,
+        stack: &'static str,
+    ) -> Result<(), &'static str> {
+        let page = PageRange::new(vaddr.page_number(), 1);
+        if self.areas.iter().any(|area| area.pages().overlaps(&page)) {
+            return Err("Guard page already mapped");
+        }
+        if self.guard_at(vaddr).is_none() {
+            self.guards.push(GuardPage { page, stack });
+        }
+        Ok(())
+    }
+
+    /// 区域操作使用的是 Sv39 的页表函数
+    fn require_sv39(&self) -> Result<(), &'static str> {
+        if self.mode != PagingMode::Sv39 {
+            return Err("Region operations require Sv39");
+        }
+        Ok(())
+    }
+
+    /// 检查要映射的范围不与保护页和已有的区域重叠（并且地址空间支持区域映射）
+    ///
+    /// # 说明
+    /// 与已有区域重叠时打印冲突区域的类型和地址
+    fn check_free(&self, pages: &PageRange) -> Result<(), &'static str> {
+        self.require_sv39()?;
+        if self.guards.iter().any(|guard| guard.page.overlaps(pages)) {
+            return Err("Range overlaps a guard page");
+        }
+        if let Some(area) = self.areas.iter().find(|area| area.pages().overlaps(pages)) {
+            klog!(
+                "[MEMORY] {:#x} - {:#x} overlaps {} area at {:#x}",
+                pages.start().as_usize(),
+                pages.end().as_usize(),
+                area.area_type.name(),
+                area.start().as_usize()
+            );
+            return Err("Range overlaps an existing area");
+        }
+        Ok(())
+    }
+
+    /// 检查区域参数：起始地址页对齐，大小不为 0
+    fn check_bounds(start: VirtAddr, size: usize) -> Result<(), &'static str> {
+        if !start.is_aligned(PAGE_SIZE) {
+            return Err("Region start not page-aligned");
+        }
+        if size == 0 {
+            return Err("Region size is zero");
+        }
+        Ok(())
+    }
+
+    /// 区域的页表标志位
+    ///
+    /// # 说明
+    /// 用户地址空间中，用户区域在类型的默认标志位上加 U 位，其余区域保持不带 U 位
+    ///
+    /// # 返回
+    /// - `Err`: 用户区域伸进了内核保留的高半部分
+    fn area_flags(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        area_type: MemoryAreaType,
+    ) -> Result<PteFlags, &'static str> {
+        let flags = area_type.default_flags();
+        if !self.user || !area_type.is_user_accessible() {
+            return Ok(flags);
+        }
+        let pages = PageRange::covering(start, size);
+        let last = pages.start() + (pages.page_count() - 1) * PAGE_SIZE;
+        if last.as_usize() >= KERNEL_SPACE_START {
+            return Err("User area in kernel address range");
+        }
+        Ok(flags | PteFlags::USER)
+    }
+
+    /// 映射内存区域（为每个页面分配新的物理页帧）
+    ///
+    /// # 参数
+    /// - `start`: 起始虚拟地址（页对齐）
+    /// - `size`: 区域大小（字节，向上取整到页）
+    /// - `area_type`: 区域类型，决定页表标志位
+    /// - `allocator`: 帧分配器
+    ///
+    /// # 说明
+    /// 先分配全部页帧，再把物理上连续的每一段用一次 `map_range` 映射；
+    /// 中途失败时撤销已建立的映射、释放页帧和新建的中间页表，
+    /// 页表和帧分配器都恢复到调用之前的状态
+    ///
+    /// `MemoryAreaType::Stack` 区域的最低一页不映射，登记为保护页
+    /// （等同于 `map_stack(start + PAGE_SIZE, size - PAGE_SIZE)`）
+    ///
+    /// # 返回
+    /// - `Err`: `start` 没有页对齐、`size` 为 0、与已有的区域或保护页重叠，
+    ///   或内存不足（此时没有任何改动）
+    pub fn map_region(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        area_type: MemoryAreaType,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        Self::check_bounds(start, size)?;
+        if area_type == MemoryAreaType::Stack {
+            let pages = PageRange::covering(start, size);
+            if pages.page_count() < 2 {
+                return Err("Stack too small for a guard page");
+            }
+            let size = (start + size) - (pages.start() + PAGE_SIZE);
+            return self.map_stack(pages.start() + PAGE_SIZE, size, allocator);
+        }
+        self.map_frames(start, size, area_type, allocator)
+    }
+
+    /// `map_region` 的映射部分（不处理栈的保护页）
+    fn map_frames(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        area_type: MemoryAreaType,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        let flags = self.area_flags(start, size, area_type)?;
+        let area = MemoryArea::new(start, size, area_type, flags, false);
+        self.check_free(&area.pages())?;
+
+        let mut frames = Vec::with_capacity(area.page_count());
+        for _ in 0..area.page_count() {
+            match allocator.allocate() {
+                Some(frame) => frames.push(frame),
+                None => {
+                    frames.into_iter().for_each(|frame| allocator.deallocate(frame));
+                    return Err("Out of memory");
+                }
+            }
+        }
+
+        let mut mapped = 0;
+        while mapped < frames.len() {
+            // 物理上连续的一段
+            let first = frames[mapped];
+            let run = frames[mapped..]
+                .iter()
+                .zip(first.number()..)
+                .take_while(|(frame, number)| frame.number() == *number)
+                .count();
+
+            let vaddr = area.start() + mapped * PAGE_SIZE;
+            let paddr = first.start_address();
+            if let Err(e) = map_range(self.page_table(), vaddr, paddr, run, flags, allocator) {
+                // map_range 已经撤销了这一段，只需撤销之前的各段
+                self.roll_back(area.pages(), mapped, allocator);
+                frames.into_iter().for_each(|frame| allocator.deallocate(frame));
+                return Err(e);
+            }
+            mapped += run;
+        }
+
+        self.areas.push(area);
+        Ok(())
+    }
+
+    /// 恒等映射内存区域（虚拟地址 = 物理地址）
+    ///
+    /// # 用途
+    /// 内核代码/数据、MMIO 设备寄存器
+    ///
+    /// # 说明
+    /// 2MB 对齐且足够长的部分使用 2MB 大页，首尾不足 2MB 的部分用 `map_range`
+    /// 批量映射为 4KB 页；中途失败时撤销已建立的映射和新建的中间页表
+    ///
+    /// 与 `map_region` 一样拒绝未对齐的起始地址、大小为 0 和与已有区域重叠的范围
+    pub fn map_region_identity(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        area_type: MemoryAreaType,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        self.map_region_linear(start, PhysAddr::new(start.as_usize()), size, area_type, allocator)
+    }
+
+    /// 线性映射内存区域（虚拟地址 = 物理地址 + 固定偏移）
+    ///
+    /// # 用途
+    /// 内核在高半部分的别名（见 `high_half`）；恒等映射是偏移为 0 的特例
+    ///
+    /// # 说明
+    /// 与恒等映射一样，物理页帧不属于此地址空间，销毁时只拆除页表；
+    /// 偏移是 2MB 的整数倍时，对齐的部分同样使用 2MB 大页
+    pub fn map_region_linear(
+        &mut self,
+        start: VirtAddr,
+        phys_start: PhysAddr,
+        size: usize,
+        area_type: MemoryAreaType,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        Self::check_bounds(start, size)?;
+        if start.page_offset() != phys_start.as_usize() % PAGE_SIZE {
+            return Err("Physical address not aligned like the virtual address");
+        }
+        let flags = self.area_flags(start, size, area_type)?;
+        let mut area = MemoryArea::new(start, size, area_type, flags, true);
+        area.offset = start.as_usize().wrapping_sub(phys_start.as_usize());
+        self.check_free(&area.pages())?;
+        let offset = area.offset;
+        let first = area.start().as_usize();
+        let end = area.end().as_usize();
+
+        let mut addr = first;
+        while addr < end {
+            let vaddr = VirtAddr::new(addr);
+            let paddr = PhysAddr::new(addr.wrapping_sub(offset));
+            let huge = addr.is_multiple_of(MEGAPAGE_SIZE)
+                && paddr.as_usize().is_multiple_of(MEGAPAGE_SIZE)
+                && end - addr >= MEGAPAGE_SIZE;
+            let result = if huge {
+                map_page_2mb(self.page_table(), vaddr, paddr, flags, allocator)
+                    .map(|_| MEGAPAGE_SIZE)
+            } else {
+                // 4KB 页一直映射到下一个 2MB 边界或区域末尾
+                // （虚拟地址和物理地址的 2MB 对齐方式不同时，下一段仍然是 4KB 页）
+                let run_end = ((addr / MEGAPAGE_SIZE + 1) * MEGAPAGE_SIZE).min(end);
+                let count = (run_end - addr) / PAGE_SIZE;
+                map_range(self.page_table(), vaddr, paddr, count, flags, allocator)
+                    .map(|n| n * PAGE_SIZE)
+            };
+
+            match result {
+                Ok(step) => addr += step,
+                Err(e) => {
+                    let mapped = (addr - first) / PAGE_SIZE;
+                    self.roll_back(area.pages(), mapped, allocator);
+                    return Err(e);
+                }
+            }
+        }
+
+        self.areas.push(area);
+        Ok(())
+    }
+
+    /// 把跳板页映射到 `TRAMPOLINE`（R-X，不带 U 位）
+    ///
+    /// # 说明
+    /// 跳板页属于内核镜像，区域按恒等映射处理：销毁时只拆除页表，不释放页帧
+    ///
+    /// # 返回
+    /// - `Err`: 已经映射过，或页表分配失败
+    pub fn map_trampoline(
+        &mut self,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        let start = VirtAddr::new(TRAMPOLINE);
+        let area_type = MemoryAreaType::Trampoline;
+        let flags = self.area_flags(start, PAGE_SIZE, area_type)?;
+        let area = MemoryArea::new(start, PAGE_SIZE, area_type, flags, true);
+        self.check_free(&area.pages())?;
+        map_page(self.page_table(), start, trampoline::frame().start_address(), flags, allocator)?;
+        self.areas.push(area);
+        Ok(())
+    }
+
+    /// 按需映射内存区域（只记录区域，不分配页帧）
+    ///
+    /// # 说明
+    /// 区域中的页面在第一次访问触发页错误时由 `handle_page_fault` 分配并映射，
+    /// 适合栈、堆这类很大但只用到一小部分的区域
+    ///
+    /// # 返回
+    /// - `Err`: 区域与保护页重叠
+    pub fn map_region_lazy(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        area_type: MemoryAreaType,
+    ) -> Result<(), &'static str> {
+        let flags = self.area_flags(start, size, area_type)?;
+        let mut area = MemoryArea::new(start, size, area_type, flags, false);
+        self.check_free(&area.pages())?;
+        area.lazy = true;
+        self.areas.push(area);
+        Ok(())
+    }
+
+    /// 映射栈，并在栈底下方留出一个保护页
+    ///
+    /// # 参数
+    /// - `start`: 栈的最低地址（页对齐）
+    /// - `size`: 栈大小（字节，向上取整到页）
+    ///
+    /// # 说明
+    /// 保护页 `[start - PAGE_SIZE, start)` 始终不映射，之后的映射也不能覆盖它；
+    /// 栈溢出时访问保护页，页错误处理函数报告 STACK OVERFLOW
+    pub fn map_stack(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        let stack_page = start.page_number();
+        if stack_page == 0 {
+            return Err("No room for a guard page");
+        }
+        let page = PageRange::new(stack_page - 1, 1);
+        if self.areas.iter().any(|area| area.pages().overlaps(&page)) {
+            return Err("Guard page already mapped");
+        }
+
+        self.map_frames(start, size, MemoryAreaType::Stack, allocator)?;
+        self.guards.push(GuardPage { page, stack: "stack" });
+        Ok(())
+    }
+
+    /// 包含 `vaddr` 的按需映射区域
+    fn lazy_area(&self, vaddr: VirtAddr) -> Option<&MemoryArea> {
+        self.areas
+            .iter()
+            .find(|area| area.lazy && area.pages().contains(vaddr))
+    }
+
+    /// 处理按需映射区域中的页错误
+    ///
+    /// # 功能
+    /// 分配一个清零的页帧，按区域的标志位映射到出错的页面
+    ///
+    /// # 返回
+    /// - `Ok(())`: 已映射，返回后重新执行出错的指令即可
+    /// - `Err`: 地址不在按需映射的区域中，或页面已经映射（真正的访问错误）
+    pub fn handle_page_fault(
+        &mut self,
+        fault_addr: VirtAddr,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        let flags = self.lazy_area(fault_addr).ok_or("Address not in a lazy area")?.flags;
+        let vaddr = fault_addr.align_down(PAGE_SIZE)?;
+
+        let frame = allocator.allocate().ok_or("Out of memory")?;
+        unsafe {
+            let page = phys_to_kvirt(frame.start_address()).as_usize() as *mut u8;
+            core::ptr::write_bytes(page, 0, PAGE_SIZE);
+        }
+        map_page(self.page_table(), vaddr, frame.start_address(), flags, allocator).inspect_err(
+            |_| allocator.deallocate(frame),
+        )
+    }
+
+    /// 设为页错误处理使用的地址空间
+    ///
+    /// # 说明
+    /// 不写 satp（见 `activate`）；返回的守卫存在期间，页错误处理函数用此地址空间
+    /// 和 `allocator` 处理按需映射与写时复制
+    pub fn set_active<'a>(
+        &'a mut self,
+        allocator: &'a mut (dyn FrameAllocator + 'static),
+    ) -> ActiveGuard<'a> {
+        let active = Active {
+            space: self,
+            allocator,
+        };
+        crate::interrupts::without_interrupts(|| *ACTIVE.lock() = Some(active));
+        ActiveGuard {
+            _borrow: PhantomData,
+        }
+    }
+
+    /// 用一个 1GB 大页恒等映射 `addr` 所在的 1GB 窗口
+    ///
+    /// # 说明
+    /// 窗口可能超出实际的物理内存，访问不存在的部分会产生访问错误
+    pub fn map_gigapage_identity(
+        &mut self,
+        addr: VirtAddr,
+        area_type: MemoryAreaType,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        let start = addr.align_down(GIGAPAGE_SIZE)?;
+        let flags = self.area_flags(start, GIGAPAGE_SIZE, area_type)?;
+        self.check_free(&PageRange::covering(start, GIGAPAGE_SIZE))?;
+
+        map_page_1gb(self.page_table(), start, PhysAddr::new(start.as_usize()), flags, allocator)?;
+        self.areas.push(MemoryArea::new(start, GIGAPAGE_SIZE, area_type, flags, true));
+        Ok(())
+    }
+
+    /// 取消一段页范围的映射（对齐处优先按 1GB / 2MB 大页取消）
+    fn unmap_pages(
+        &mut self,
+        pages: PageRange,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        let end = pages.end().as_usize();
+        let mut addr = pages.start().as_usize();
+
+        while addr < end {
+            let vaddr = VirtAddr::new(addr);
+            if addr.is_multiple_of(GIGAPAGE_SIZE)
+                && end - addr >= GIGAPAGE_SIZE
+                && unmap_page_1gb(self.page_table(), vaddr).is_ok()
+            {
+                addr += GIGAPAGE_SIZE;
+                continue;
+            }
+            if addr.is_multiple_of(MEGAPAGE_SIZE)
+                && end - addr >= MEGAPAGE_SIZE
+                && unmap_page_2mb(self.page_table(), vaddr, allocator).is_ok()
+            {
+                addr += MEGAPAGE_SIZE;
+                continue;
+            }
+
+            match unmap_page(self.page_table(), vaddr, allocator) {
+                Ok(_) => {}
+                // 按需映射的区域中还没有访问过的页面
+                Err("Page not mapped") if self.lazy_area(vaddr).is_some() => {}
+                Err(e) => return Err(e),
+            }
+            addr += PAGE_SIZE;
+        }
+        Ok(())
+    }
+
+    /// 范围内普通映射页面的页帧，以及页表项是否带 COW 标记
+    ///
+    /// # 说明
+    /// 恒等映射（包括线性映射）的区域不拥有物理内存，不在结果中
+    fn owned_frames(&mut self, pages: PageRange) -> Vec<(PhysFrame, bool)> {
+        let mut frames = Vec::new();
+        for vaddr in pages.iter_pages() {
+            if self.area_containing(vaddr).is_none_or(|area| area.identity) {
+                continue;
+            }
+            if let Some(pte) = leaf_entry_mut(self.page_table(), vaddr) {
+                let cow = pte.flags().contains(PteFlags::COW);
+                frames.push((PhysFrame::from_addr(pte.phys_addr()), cow));
+            }
+        }
+        frames
+    }
+
+    /// 撤销映射失败的区域
+    ///
+    /// # 参数
+    /// - `pages`: 正在映射的区域
+    /// - `mapped`: 已经映射的页数（从区域开头算起）
+    ///
+    /// # 说明
+    /// 取消已建立的映射，并释放为失败的页面新建、但没有留下任何映射的中间页表；
+    /// 之后页表与映射之前相同（页帧由调用者释放）
+    fn roll_back(&mut self, pages: PageRange, mapped: usize, allocator: &mut dyn FrameAllocator) {
+        let done = PageRange::new(pages.start().page_number(), mapped);
+        let _ = self.unmap_pages(done, allocator);
+        prune_tables(self.page_table(), pages.start(), pages.page_count(), allocator);
+    }
+
+    /// 取消映射内存区域
+    ///
+    /// # 参数
+    /// - `start`: 区域起始虚拟地址
+    /// - `size`: 区域大小（字节，向上取整到页）
+    /// - `allocator`: 分配页帧时使用的帧分配器（回收页帧和变空的中间页表）
+    ///
+    /// # 返回
+    /// - `Err`: 范围内有既未映射、也不属于按需映射区域的页面，或范围只覆盖了大页的一部分；
+    ///   此时不做任何修改
+    ///
+    /// # 说明
+    /// - 完全被覆盖的区域被移除，部分覆盖的区域被拆分（或缩短），只保留未取消映射的部分；
+    ///   范围可以跨越多个区域
+    /// - 普通映射页面的页帧归还给 `allocator`，仍与其他地址空间共享的写时复制页帧
+    ///   只减少共享计数；恒等映射的物理内存不释放（同 `destroy`）
+    /// - 栈区域的最低一页被取消映射时，栈下方的保护页随之移除
+    pub fn unmap_region(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        self.require_sv39()?;
+        let pages = PageRange::covering(start, size);
+        // 先检查，保证失败时页表和区域都没有被修改
+        for vaddr in pages.iter_pages() {
+            match leaf_size(self.page_table(), vaddr) {
+                None if self.lazy_area(vaddr).is_none() => return Err("Page not mapped"),
+                Some(leaf) if leaf != MappingSize::Page4K => {
+                    let base = vaddr.as_usize() & !(leaf.bytes() - 1);
+                    if base < pages.start().as_usize()
+                        || base + leaf.bytes() > pages.end().as_usize()
+                    {
+                        return Err("Cannot unmap part of a huge page");
+                    }
+                }
+                _ => {}
+            }
+        }
+        // unmap_pages 只拆除页表项，页帧在之后归还
+        let frames = self.owned_frames(pages);
+        self.unmap_pages(pages, allocator)?;
+        for (frame, cow) in frames {
+            free_frame(frame, cow, allocator);
+        }
+
+        let mut remaining = Vec::with_capacity(self.areas.len());
+        for area in self.areas.drain(..) {
+            if !area.pages().overlaps(&pages) {
+                remaining.push(area);
+                continue;
+            }
+            if area.area_type == MemoryAreaType::Stack && pages.contains(area.start()) {
+                self.guards.retain(|guard| guard.page.end() != area.start());
+            }
+
+            // 保留 [area.start, pages.start) 部分
+            let mut rest = area;
+            if let Some((left, right)) = rest.split_at(pages.start()) {
+                remaining.push(left);
+                rest = right;
+            }
+            // 保留 [pages.end, area.end) 部分
+            if let Some((_, right)) = rest.split_at(pages.end()) {
+                remaining.push(right);
+            }
+        }
+        self.areas = remaining;
+        Ok(())
+    }
+
+    /// 修改内存区域的访问权限
+    ///
+    /// # 参数
+    /// - `start`: 起始虚拟地址
+    /// - `size`: 大小（字节，向上取整到页）
+    /// - `new_flags`: 新的 R/W/X/U 位（见 `paging::protect_page`）
+    ///
+    /// # 返回
+    /// - `Err`: 新权限无效，或范围内有既未映射、也不属于按需映射区域的页面；
+    ///   此时不做任何修改
+    ///
+    /// # 说明
+    /// - 部分覆盖的区域被拆分，只有范围内的部分使用新权限
+    /// - 按需映射区域中还没有访问过的页面在之后的页错误中按新权限映射
+    /// - 范围内的大页整体修改
+    pub fn protect_region(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        new_flags: PteFlags,
+    ) -> Result<(), &'static str> {
+        self.require_sv39()?;
+        let new_flags = leaf_permissions(new_flags)?;
+        let pages = PageRange::covering(start, size);
+        // 先检查，保证失败时页表和区域都没有被修改
+        for vaddr in pages.iter_pages() {
+            if !self.is_mapped(vaddr) && self.lazy_area(vaddr).is_none() {
+                return Err("Page not mapped");
+            }
+        }
+        for vaddr in pages.iter_pages() {
+            match protect_page(self.page_table(), vaddr, new_flags) {
+                Ok(()) | Err("Page not mapped") => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut updated = Vec::with_capacity(self.areas.len() + 2);
+        for area in self.areas.drain(..) {
+            if !area.pages().overlaps(&pages) {
+                updated.push(area);
+                continue;
+            }
+
+            // [area.start, pages.start) 和 [pages.end, area.end) 保持原权限
+            let mut rest = area;
+            if let Some((left, right)) = rest.split_at(pages.start()) {
+                updated.push(left);
+                rest = right;
+            }
+            let right = rest.split_at(pages.end()).map(|(middle, right)| {
+                rest = middle;
+                right
+            });
+            rest.flags = (rest.flags & !LEAF_PERMISSIONS) | new_flags;
+            updated.push(rest);
+            updated.extend(right);
+        }
+        self.areas = updated;
+        Ok(())
+    }
+
+    /// 创建写时复制的子地址空间
+    ///
+    /// # 返回
+    /// - `Ok(child)`: 子地址空间（见 `share_cow`）
+    /// - `Err`: 页表分配失败（已标记 COW 的父页面保持原样，写入时照常处理）
+    pub fn fork(
+        &mut self,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<AddressSpace, &'static str> {
+        let mut child = AddressSpace::with_mode(self.mode, allocator)?;
+        child.user = self.user;
+        match child.share_cow(self, allocator) {
+            Ok(()) => Ok(child),
+            Err(e) => {
+                child.destroy(allocator);
+                Err(e)
+            }
+        }
+    }
+
+    /// 以写时复制方式把 `parent` 的全部区域映射到此地址空间
+    ///
+    /// # 功能
+    /// - 恒等映射的区域（内核、MMIO）重新建立相同的映射，直接共享
+    /// - 普通映射的区域复制页表结构，叶子页表项指向同一个页帧；
+    ///   页面在两边都去掉 W 位并标记 COW，页帧的共享计数加一
+    ///   （只读的页面也标记，以便共享计数知道页帧何时可以释放，写入仍按访问错误处理）
+    /// - 保护页一并复制
+    ///
+    /// # 返回
+    /// - `Err`: 分页模式不同、区域与此地址空间已有的区域重叠，或页表分配失败
+    ///   （已经共享的页面保持共享，销毁时按共享计数释放）
+    ///
+    /// # 说明
+    /// 之后任意一方写入共享页面时，写页错误由 `handle_cow_fault` 复制出独立的页帧
+    pub fn share_cow(
+        &mut self,
+        parent: &mut AddressSpace,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        if self.mode != parent.mode {
+            return Err("Paging modes differ");
+        }
+        let overlaps = parent.areas.iter().any(|area| {
+            self.areas.iter().any(|own| own.pages().overlaps(&area.pages()))
+        });
+        if overlaps {
+            return Err("Range overlaps an existing area");
+        }
+        self.guards.extend_from_slice(&parent.guards);
+
+        for area in parent.areas.clone() {
+            if area.identity {
+                self.share_identity(&area, allocator)?;
+                continue;
+            }
+
+            self.areas.push(area.clone());
+            for vaddr in area.pages().iter_pages() {
+                let Some(pte) = leaf_entry_mut(parent.page_table(), vaddr) else {
+                    continue;
+                };
+                let flags = (pte.flags() & !PteFlags::WRITE) | PteFlags::COW;
+                let paddr = pte.phys_addr();
+                map_page(self.page_table(), vaddr, paddr, flags, allocator)?;
+                pte.set(pte.ppn(), flags);
+                add_cow_share(PhysFrame::from_addr(paddr));
+            }
+        }
+
+        // 父地址空间的页表项失去了 W 位
+        tlb::flush_asid(parent.asid);
+        Ok(())
+    }
+
+    /// 销毁地址空间，回收它占用的全部页帧
+    ///
+    /// # 参数
+    /// - `allocator`: 分配页帧和页表时使用的帧分配器
+    ///
+    /// # 功能
+    /// - 释放普通映射区域中叶子页表项指向的页帧；
+    ///   仍与其他地址空间共享的写时复制页帧只减少共享计数
+    /// - 释放所有中间页表和根页表
+    ///
+    /// # 说明
+    /// - 恒等映射的区域（内核、MMIO）只拆除页表，不释放其物理内存
+    /// - 刷新此地址空间 ASID 的 TLB 条目，ASID 不再对应这棵页表
+    /// - 释放的帧需要归还给分配者，`Drop` 拿不到分配器，因此需要显式调用
+    ///   （直接丢弃的地址空间泄漏它的页帧）；不能销毁当前 satp 指向的地址空间
+    pub fn destroy(mut self, allocator: &mut dyn FrameAllocator) {
+        for area in core::mem::take(&mut self.areas) {
+            if area.identity {
+                continue;
+            }
+            for vaddr in area.pages().iter_pages() {
+                let Some(pte) = leaf_entry_mut(self.page_table(), vaddr) else {
+                    continue;
+                };
+                let cow = pte.flags().contains(PteFlags::COW);
+                free_frame(PhysFrame::from_addr(pte.phys_addr()), cow, allocator);
+            }
+        }
+        unregister_asid(self.page_table_paddr());
+        free_page_tables(self.page_table_paddr(), self.mode, allocator);
+        tlb::flush_asid(self.asid);
+    }
+
+    /// 深拷贝地址空间（不共享任何普通映射的页帧）
+    ///
+    /// # 功能
+    /// - 恒等映射的区域（内核、MMIO）重新建立相同的映射，直接共享
+    /// - 普通映射区域中已经映射的每个页面分配新页帧，复制 4KB 内容，
+    ///   按区域当前的标志位映射（父页面上的 COW 标记不会带到子地址空间）
+    /// - 按需映射区域中还没有访问过的页面在子地址空间中同样按需映射
+    ///
+    /// # 返回
+    /// - `Ok(child)`: 子地址空间
+    /// - `Err`: 内存不足；已经分配的页帧和页表全部释放
+    ///
+    /// # 说明
+    /// 与 `fork` 的写时复制相比，复制的代价在 fork 时一次付清
+    pub fn fork_from(
+        parent: &AddressSpace,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<AddressSpace, &'static str> {
+        let mut child = AddressSpace::with_mode(parent.mode, allocator)?;
+        child.user = parent.user;
+        child.guards = parent.guards.clone();
+
+        match child.copy_areas_from(parent, allocator) {
+            Ok(()) => Ok(child),
+            Err(e) => {
+                child.destroy(allocator);
+                Err(e)
+            }
+        }
+    }
+
+    /// `fork_from` 的复制部分（出错时由调用者销毁 `self`）
+    fn copy_areas_from(
+        &mut self,
+        parent: &AddressSpace,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        for area in parent.areas() {
+            if area.identity {
+                self.share_identity(area, allocator)?;
+                continue;
+            }
+
+            // 先登记区域，出错时 destroy 能找到已经映射的页帧
+            self.areas.push(area.clone());
+            for vaddr in area.pages().iter_pages() {
+                let Some(src) = parent.translate(vaddr) else {
+                    continue;
+                };
+                let frame = allocator.allocate().ok_or("Out of memory")?;
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        phys_to_kvirt(src).as_usize() as *const u8,
+                        phys_to_kvirt(frame.start_address()).as_usize() as *mut u8,
+                        PAGE_SIZE,
+                    );
+                }
+                let paddr = frame.start_address();
+                if let Err(e) = map_page(self.page_table(), vaddr, paddr, area.flags, allocator) {
+                    allocator.deallocate(frame);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 在此地址空间中建立与 `area` 相同的恒等映射（fork 时共享内核、MMIO 和跳板页）
+    fn share_identity(
+        &mut self,
+        area: &MemoryArea,
+        allocator: &mut dyn FrameAllocator,
+    ) -> Result<(), &'static str> {
+        let start = area.start();
+        let phys_start = PhysAddr::new(start.as_usize().wrapping_sub(area.offset));
+        if area.area_type == MemoryAreaType::Trampoline {
+            self.map_trampoline(allocator)
+        } else if area.offset == 0
+            && area.size() == GIGAPAGE_SIZE
+            && start.is_aligned(GIGAPAGE_SIZE)
+        {
+            self.map_gigapage_identity(start, area.area_type, allocator)
+        } else {
+            self.map_region_linear(start, phys_start, area.size(), area.area_type, allocator)
+        }
+    }
+
+    /// 激活此地址空间（写入 satp，见 `memory::activate`）
+    #[track_caller]
+    pub fn activate(&self) {
+        super::activate(self);
+        klog!(
+            "[MEMORY] Activated address space at PPN {:#x}",
+            self.page_table_paddr().page_number()
+        );
+    }
+
+    /// 按虚拟地址顺序列出页表中的全部叶子映射（见 `paging::Mappings`）
+    ///
+    /// # 返回
+    /// - `Err`: 不是 Sv39 地址空间
+    pub fn mappings(&self) -> Result<Mappings<'_>, &'static str> {
+        self.require_sv39()?;
+        Ok(Mappings::new(unsafe { &*self.page_table }))
+    }
+
+    /// 打印页表中的全部映射（见 `paging::dump_page_table`）
+    pub fn dump(&self) {
+        dump_page_table_mode(self.page_table_paddr(), self.mode);
+    }
+
+    /// 打印地址空间布局
+    ///
+    /// # 参数
+    /// - `verbose`: 为 true 时在区域表之后打印页表中的全部映射（`dump`）
+    pub fn print_layout(&self, verbose: bool) {
+        println!("┌──────────────────────────────────────────────────────────────┐");
+        println!("│ 地址空间布局  根页表: {}", Canon(self.page_table_paddr().as_usize()));
+        println!("├────────┬───────────────────────────────┬──────────┬──────────┤");
+        println!("│ 类型   │ 虚拟地址范围                  │ 页数     │ 映射方式 │");
+        println!("├────────┼───────────────────────────────┼──────────┼──────────┤");
+        for area in &self.areas {
+            println!(
+                "│ {:<6} │ {:>12} - {:>12} │ {:>8} │ {:<8} │",
+                area.area_type.name(),
+                Canon(area.start().as_usize()),
+                Canon(area.end().as_usize()),
+                area.page_count(),
+                if area.area_type == MemoryAreaType::Trampoline {
+                    "shared"
+                } else if area.offset != 0 {
+                    "offset"
+                } else if area.identity {
+                    "identity"
+                } else if area.lazy {
+                    "lazy"
+                } else {
+                    "framed"
+                }
+            );
+        }
+        println!("└────────┴───────────────────────────────┴──────────┴──────────┘");
+        for area in self.areas.iter().filter(|area| area.offset != 0) {
+            println!(
+                "  {} - {} → 物理地址 {}（偏移 {:#x}）",
+                Canon(area.start().as_usize()),
+                Canon(area.end().as_usize()),
+                Canon(area.start().as_usize().wrapping_sub(area.offset)),
+                area.offset
+            );
+        }
+        if verbose {
+            self.dump();
+        }
+    }
+}
+
+// ============================================
+// 页错误处理
+// ============================================
+
+/// 页错误落在保护页中时 `handle_active_page_fault` 返回的错误
+pub const STACK_OVERFLOW: &str = "Stack overflow";
+
+/// 页错误处理使用的地址空间与帧分配器（见 `AddressSpace::set_active`）
+#[derive(Clone, Copy)]
+struct Active {
+    space: *mut AddressSpace,
+    allocator: *mut dyn FrameAllocator,
+}
+
+// 安全性：指针只在 ActiveGuard 存在期间被解引用（单核，访问时关中断）
+unsafe impl Send for Active {}
+
+static ACTIVE: Mutex<Option<Active>> = Mutex::new(None);
+
+/// `AddressSpace::set_active` 返回的守卫，离开作用域时取消登记
+pub struct ActiveGuard<'a> {
+    _borrow: PhantomData<(&'a mut AddressSpace, &'a mut dyn FrameAllocator)>,
+}
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        crate::interrupts::without_interrupts(|| *ACTIVE.lock() = None);
+    }
+}
+
+/// 登记的地址空间中包含 `fault_addr` 的保护页所保护的栈（栈溢出诊断用）
+pub fn active_guarded_stack(fault_addr: VirtAddr) -> Option<&'static str> {
+    crate::interrupts::without_interrupts(|| {
+        let active = (*ACTIVE.lock())?;
+        // 安全性：同 handle_active_page_fault，只读取保护页列表
+        let space = unsafe { &*active.space };
+        space.guard_at(fault_addr).map(|guard| guard.stack)
+    })
+}
+
+/// 登记的地址空间中包含 `fault_addr` 的区域的类型（页错误跟踪用）
+pub fn active_area_type(fault_addr: VirtAddr) -> Option<MemoryAreaType> {
+    crate::interrupts::without_interrupts(|| {
+        let active = (*ACTIVE.lock())?;
+        // 安全性：同 handle_active_page_fault，只读取区域列表
+        let space = unsafe { &*active.space };
+        space.area_containing(fault_addr).map(|area| area.area_type)
+    })
+}
+
+/// 在登记的地址空间中处理页错误（由页错误处理函数调用）
+///
+/// # 参数
+/// - `fault_addr`: 出错的虚拟地址（stval）
+/// - `is_store`: 是否为写页错误（先尝试写时复制）
+///
+/// # 返回
+/// - `Ok(resolution)`: 已处理（写时复制或按需映射），返回后重新执行出错的指令
+/// - `Err(STACK_OVERFLOW)`: 访问了栈下方的保护页
+/// - `Err`: 没有登记的地址空间，或不是可以修复的页错误
+pub fn handle_active_page_fault(
+    fault_addr: VirtAddr,
+    is_store: bool,
+) -> Result<FaultResolution, &'static str> {
+    let active = crate::interrupts::without_interrupts(|| *ACTIVE.lock())
+        .ok_or("No active address space")?;
+    // 安全性：守卫借用了两者，在守卫存在期间指针有效且没有其他引用
+    let space = unsafe { &mut *active.space };
+    let allocator = unsafe { &mut *active.allocator };
+
+    if space.is_guard_page(fault_addr) {
+        return Err(STACK_OVERFLOW);
+    }
+    if is_store {
+        if let Ok(resolution) = handle_cow_fault(space, fault_addr, allocator) {
+            return Ok(resolution);
+        }
+    }
+    space.handle_page_fault(fault_addr, allocator)?;
+    Ok(FaultResolution::LazyMapped)
+}
+
+// ============================================
+// 写时复制
+// ============================================
+
+/// 写时复制页帧的共享计数（页帧 → 仍标记 COW 引用它的地址空间数）
+///
+/// # 说明
+/// 计数降到 1 时移除，表中没有的 COW 页帧由唯一的引用者独占
+static COW_SHARES: Mutex<BTreeMap<PhysFrame, usize>> = Mutex::new(BTreeMap::new());
+
+/// fork 时增加页帧的共享计数（第一次共享时计为 2）
+fn add_cow_share(frame: PhysFrame) {
+    crate::interrupts::without_interrupts(|| {
+        *COW_SHARES.lock().entry(frame).or_insert(1) += 1;
+    });
+}
+
+/// 页帧是否仍被多个地址空间共享
+fn is_cow_shared(frame: PhysFrame) -> bool {
+    crate::interrupts::without_interrupts(|| COW_SHARES.lock().contains_key(&frame))
+}
+
+/// 一个地址空间不再共享页帧时减少计数
+fn release_cow_share(frame: PhysFrame) {
+    crate::interrupts::without_interrupts(|| {
+        let mut shares = COW_SHARES.lock();
+        if let Some(count) = shares.get_mut(&frame) {
+            *count -= 1;
+            if *count <= 1 {
+                shares.remove(&frame);
+            }
+        }
+    });
+}
+
+/// 归还普通映射页面的页帧（`cow`：页表项带 COW 标记）
+///
+/// # 说明
+/// 仍与其他地址空间共享的写时复制页帧只减少共享计数，由最后一个使用者释放
+fn free_frame(frame: PhysFrame, cow: bool, allocator: &mut dyn FrameAllocator) {
+    if cow && is_cow_shared(frame) {
+        release_cow_share(frame);
+    } else {
+        allocator.deallocate(frame);
+    }
+}
+
+/// 处理写时复制页面上的写页错误
+///
+/// # 参数
+/// - `space`: 发生页错误的地址空间
+/// - `fault_addr`: 出错的虚拟地址（stval）
+/// - `allocator`: 帧分配器（分配副本页帧）
+///
+/// # 功能
+/// - 页帧仍被共享：分配新页帧，复制内容，映射到新页帧并恢复写权限
+/// - 页帧已经独占：直接恢复写权限
+///
+/// # 返回
+/// - `Ok(CowCopied)` / `Ok(CowReclaimed)`: 复制了页帧 / 只恢复了写权限
+/// - `Err`: 地址未映射、不是写时复制页面，或所在区域不可写（真正的访问错误）
+pub fn handle_cow_fault(
+    space: &mut AddressSpace,
+    fault_addr: VirtAddr,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<FaultResolution, &'static str> {
+    let vaddr = fault_addr.align_down(PAGE_SIZE)?;
+    // protect_region 可能已经去掉了区域的写权限
+    let writable = space
+        .area_containing(vaddr)
+        .is_some_and(|area| area.flags.is_writable());
+    let pte = leaf_entry_mut(space.page_table(), vaddr).ok_or("Page not mapped")?;
+    let flags = pte.flags();
+    if !flags.contains(PteFlags::COW) {
+        return Err("Not a copy-on-write page");
+    }
+    if !writable {
+        return Err("Page is read-only");
+    }
+
+    let frame = PhysFrame::from_addr(pte.phys_addr());
+    let flags = (flags & !PteFlags::COW) | PteFlags::WRITE;
+
+    let resolution = if is_cow_shared(frame) {
+        let copy = allocator.allocate().ok_or("Out of memory")?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_kvirt(frame.start_address()).as_usize() as *const u8,
+                phys_to_kvirt(copy.start_address()).as_usize() as *mut u8,
+                PAGE_SIZE,
+            );
+        }
+        release_cow_share(frame);
+        pte.set(copy.number(), flags);
+        FaultResolution::CowCopied
+    } else {
+        pte.set(frame.number(), flags);
+        FaultResolution::CowReclaimed
+    };
+
+    flush_tlb(space.page_table_paddr(), vaddr);
+    Ok(resolution)
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{test_frame_allocator, walk_page_table, SimpleFrameAllocator};
+    use crate::faultinject::{self, Policy};
+
+    #[test_case]
+    fn test_map_region() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+
+        let start = VirtAddr::new(0x1000_0000);
+        space
+            .map_region(start, 3 * PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+
+        let root = space.page_table_paddr();
+        assert!(walk_page_table(root, VirtAddr::new(0x1000_2fff)).is_some());
+        assert!(walk_page_table(root, VirtAddr::new(0x1000_3000)).is_none());
+        assert_eq!(space.areas().len(), 1);
+        assert_eq!(space.areas()[0].page_count(), 3);
+
+        let output = crate::console::capture();
+        space.print_layout(false);
+        let layout = output.take();
+        assert_eq!(layout.lines().count(), 7);
+        assert!(layout.contains("│ Data   │"));
+        assert!(layout.contains("│        3 │ framed   │\n"));
+    }
+
+    #[test_case]
+    fn test_map_region_rejects_overlap() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let start = VirtAddr::new(0x1000_0000);
+        space
+            .map_region(start, 4 * PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+        let free_before = allocator.stats().free_frames;
+
+        // 完全重叠、与开头重叠、与末尾重叠：什么都不改动
+        let overlapping = [(start, 4), (start - 2 * PAGE_SIZE, 3), (start + 3 * PAGE_SIZE, 2)];
+        for (vaddr, pages) in overlapping {
+            let size = pages * PAGE_SIZE;
+            assert_eq!(
+                space.map_region(vaddr, size, MemoryAreaType::Data, &mut allocator),
+                Err("Range overlaps an existing area")
+            );
+            assert_eq!(
+                space.map_region_identity(vaddr, size, MemoryAreaType::Mmio, &mut allocator),
+                Err("Range overlaps an existing area")
+            );
+        }
+        assert_eq!(allocator.stats().free_frames, free_before);
+        assert_eq!(space.areas().len(), 1);
+
+        // 紧挨着的区域可以映射
+        space
+            .map_region(start - PAGE_SIZE, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("adjacent below failed");
+        space
+            .map_region(start + 4 * PAGE_SIZE, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("adjacent above failed");
+        assert_eq!(space.areas().len(), 3);
+
+        // 参数检查
+        let far = VirtAddr::new(0x2000_0000);
+        assert_eq!(
+            space.map_region(far + 8, PAGE_SIZE, MemoryAreaType::Data, &mut allocator),
+            Err("Region start not page-aligned")
+        );
+        assert_eq!(
+            space.map_region_identity(far, 0, MemoryAreaType::Mmio, &mut allocator),
+            Err("Region size is zero")
+        );
+    }
+
+    #[test_case]
+    fn test_address_spaces_get_distinct_asids() {
+        let mut allocator = test_frame_allocator();
+        let first = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let second = AddressSpace::new(&mut allocator).expect("Out of memory");
+
+        assert_ne!(first.asid(), 0);
+        assert_ne!(second.asid(), 0);
+        assert_ne!(first.asid(), second.asid());
+
+        first.destroy(&mut allocator);
+        second.destroy(&mut allocator);
+    }
+
+    #[test_case]
+    fn test_default_flags() {
+        let code = MemoryAreaType::Code.default_flags();
+        assert!(code.is_readable() && code.is_executable());
+        assert!(!code.is_writable());
+
+        for area_type in [MemoryAreaType::Data, MemoryAreaType::Stack, MemoryAreaType::Mmio] {
+            assert_eq!(area_type.default_flags(), PteFlags::READ | PteFlags::WRITE);
+        }
+        assert!(MemoryAreaType::Kernel.default_flags().contains(code | PteFlags::WRITE));
+        // 区域标志位都不包含 V 和 U，V 在映射时设置
+        for area_type in [MemoryAreaType::Code, MemoryAreaType::Heap, MemoryAreaType::Kernel] {
+            let flags = area_type.default_flags();
+            assert!(!flags.intersects(PteFlags::VALID | PteFlags::USER));
+        }
+    }
+
+    #[test_case]
+    fn test_user_space_sets_user_bit_only_on_user_areas() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new_user(&mut allocator).expect("Out of memory");
+        assert!(space.is_user());
+
+        // 用户栈：带 U 位（栈底下方一页是保护页）
+        let stack = VirtAddr::new(0x7fff_0000);
+        space.map_region(stack, 4 * PAGE_SIZE, MemoryAreaType::Stack, &mut allocator).unwrap();
+        let top = stack + 3 * PAGE_SIZE;
+        let pte = *leaf_entry_mut(space.page_table(), top).expect("stack not mapped");
+        assert!(pte.flags().contains(PteFlags::USER | PteFlags::WRITE));
+        assert!(space.area_containing(top).unwrap().flags.contains(PteFlags::USER));
+
+        // 内核跳板：创建时映射在高半部分，不带 U 位
+        let trampoline = VirtAddr::new(TRAMPOLINE);
+        let pte = *leaf_entry_mut(space.page_table(), trampoline).expect("trampoline not mapped");
+        assert!(!pte.flags().contains(PteFlags::USER));
+        assert!(pte.flags().contains(PteFlags::READ | PteFlags::EXECUTE));
+
+        // 用户区域不能伸进内核保留的高半部分
+        let kernel = VirtAddr::new(KERNEL_SPACE_START);
+        for area_type in [MemoryAreaType::Code, MemoryAreaType::Heap] {
+            assert_eq!(
+                space.map_region(kernel, PAGE_SIZE, area_type, &mut allocator),
+                Err("User area in kernel address range")
+            );
+        }
+        assert_eq!(
+            space.map_region_lazy(kernel - PAGE_SIZE, 2 * PAGE_SIZE, MemoryAreaType::Heap),
+            Err("User area in kernel address range")
+        );
+
+        // 内核地址空间中同样的栈不带 U 位
+        let mut kernel_space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        kernel_space
+            .map_region(stack, 4 * PAGE_SIZE, MemoryAreaType::Stack, &mut allocator)
+            .unwrap();
+        let pte = *leaf_entry_mut(kernel_space.page_table(), top).unwrap();
+        assert!(!pte.flags().contains(PteFlags::USER));
+
+        // fork 出的子地址空间仍是用户地址空间
+        let child = space.fork(&mut allocator).expect("fork failed");
+        assert!(child.is_user());
+
+        child.destroy(&mut allocator);
+        kernel_space.destroy(&mut allocator);
+        space.destroy(&mut allocator);
+    }
+
+    #[test_case]
+    fn test_translate_and_area_containing() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let code = VirtAddr::new(0x1000_0000);
+        let heap = VirtAddr::new(0x1800_0000);
+        space
+            .map_region(code, 2 * PAGE_SIZE, MemoryAreaType::Code, &mut allocator)
+            .expect("map_region failed");
+        space.map_region_lazy(heap, PAGE_SIZE, MemoryAreaType::Heap).unwrap();
+
+        let last = code + 2 * PAGE_SIZE - 1;
+        let paddr = space.translate(last).expect("translate failed");
+        assert_eq!(Some(paddr), walk_page_table(space.page_table_paddr(), last));
+        assert_eq!(paddr.as_usize() & (PAGE_SIZE - 1), PAGE_SIZE - 1);
+        assert!(space.is_mapped(code));
+        assert_eq!(space.translate(code + 2 * PAGE_SIZE), None);
+        assert!(!space.is_mapped(code + 2 * PAGE_SIZE));
+
+        let area_type = |vaddr| space.area_containing(vaddr).map(|area| area.area_type);
+        assert_eq!(area_type(last), Some(MemoryAreaType::Code));
+        assert_eq!(area_type(code + 2 * PAGE_SIZE), None);
+        // 按需映射的页面属于区域，但还没有映射
+        assert_eq!(area_type(heap + 8), Some(MemoryAreaType::Heap));
+        assert!(!space.is_mapped(heap));
+    }
+
+    #[test_case]
+    fn test_translate_uses_own_table() {
+        use riscv::register::satp;
+
+        let mut allocator = test_frame_allocator();
+        let mut first = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let mut second = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let vaddr = VirtAddr::new(0x1000_0000);
+        for space in [&mut first, &mut second] {
+            space
+                .map_region(vaddr, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+                .expect("map_region failed");
+        }
+
+        // 两个地址空间都没有激活：各自在自己的页表中查找，satp 不变
+        let satp_before = satp::read().bits();
+        let (in_first, in_second) = (first.translate(vaddr + 8), second.translate(vaddr + 8));
+        assert!(in_first.is_some() && in_second.is_some());
+        assert_ne!(in_first, in_second);
+        assert_eq!(first.translate(vaddr + PAGE_SIZE), None);
+        assert_eq!(second.translate(vaddr - 1), None);
+        assert_eq!(satp::read().bits(), satp_before);
+    }
+
+    #[test_case]
+    fn test_region_bounds() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let heap = VirtAddr::new(0x1800_0000);
+        let end = heap + 4 * PAGE_SIZE;
+        space
+            .map_region(heap, 4 * PAGE_SIZE, MemoryAreaType::Heap, &mut allocator)
+            .expect("map_region failed");
+
+        let expected = Some((heap, end, PteFlags::READ | PteFlags::WRITE));
+        assert_eq!(space.region_bounds(heap + 2 * PAGE_SIZE + 0x123), expected);
+        // 边界：起始地址和最后一个字节属于区域，结束地址不属于
+        assert_eq!(space.region_bounds(heap), expected);
+        assert_eq!(space.region_bounds(end - 1), expected);
+        assert_eq!(space.region_bounds(end), None);
+        assert_eq!(space.region_bounds(heap - 1), None);
+
+        let vaddr = heap + 3 * PAGE_SIZE + 0x800;
+        let (_, end, _) = space.region_bounds(vaddr).unwrap();
+        assert_eq!(0x2000usize.min(end - vaddr), 0x800);
+    }
+
+    #[test_case]
+    fn test_map_region_rolls_back_on_error() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let free_before = allocator.stats().free_frames;
+        let map = |space: &mut AddressSpace,
+                   start,
+                   pages,
+                   allocator: &mut SimpleFrameAllocator,
+                   nth| {
+            let _fault = faultinject::arm("frame_alloc", Policy::NthCall(nth));
+            let size = pages * PAGE_SIZE;
+            space.map_region(VirtAddr::new(start), size, MemoryAreaType::Data, allocator)
+        };
+
+        // 分配数据页帧时失败
+        assert_eq!(map(&mut space, 0x4000_0000, 4, &mut allocator, 3), Err("Out of memory"));
+        assert_eq!(allocator.stats().free_frames, free_before);
+
+        // 4 个数据页帧 + 一级页表 + 第一个零级页表，第二个零级页表分配失败：
+        // 跨过 2MB 边界，前两页映射成功后失败
+        assert_eq!(map(&mut space, 0x401f_e000, 4, &mut allocator, 7), Err("Out of memory"));
+        assert_eq!(allocator.stats().free_frames, free_before);
+        assert!(space.page_table().is_empty());
+        assert!(space.areas().is_empty());
+
+        // 一级页表建好后零级页表分配失败：没有映射任何页面，一级页表也要释放
+        assert_eq!(map(&mut space, 0x8000_0000, 5, &mut allocator, 7), Err("Out of memory"));
+        assert_eq!(allocator.stats().free_frames, free_before);
+        assert!(space.page_table().is_empty());
+
+        // 恒等映射同样回滚
+        let start = VirtAddr::new(0x8000_0000);
+        let fault = faultinject::arm("frame_alloc", Policy::NthCall(2));
+        assert_eq!(
+            space.map_region_identity(start, PAGE_SIZE, MemoryAreaType::Mmio, &mut allocator),
+            Err("Out of memory")
+        );
+        drop(fault);
+        assert_eq!(allocator.stats().free_frames, free_before);
+        assert!(space.page_table().is_empty());
+        assert!(space.areas().is_empty());
+    }
+
+    #[test_case]
+    fn test_page_range_off_by_one() {
+        let base = VirtAddr::new(0x1000_0000);
+        // (size, 页数)
+        let cases = [
+            (0, 0),
+            (1, 1),
+            (PAGE_SIZE - 1, 1),
+            (PAGE_SIZE, 1),
+            (PAGE_SIZE + 1, 2),
+            (2 * PAGE_SIZE, 2),
+        ];
+
+        for (size, pages) in cases {
+            let area = MemoryArea::new(base, size, MemoryAreaType::Data, PteFlags::empty(), false);
+            assert_eq!(area.page_count(), pages);
+            assert_eq!(area.size(), size);
+            assert_eq!(area.end(), base + pages * PAGE_SIZE);
+            assert_eq!(area.pages().iter_pages().count(), pages);
+            if size > 0 {
+                // 最后一个字节在范围内，结束地址不在
+                assert!(area.pages().contains(base + (size - 1)));
+            }
+            assert!(!area.pages().contains(area.end()));
+        }
+
+        // 起始地址不对齐：[0x1000_0ff0, 0x1000_1010) 跨两页
+        let start = VirtAddr::new(0x1000_0ff0);
+        let area = MemoryArea::new(start, 0x20, MemoryAreaType::Data, PteFlags::empty(), false);
+        assert_eq!(area.start(), base);
+        assert_eq!(area.page_count(), 2);
+        assert_eq!(area.size(), 0x1010);
+    }
+
+    #[test_case]
+    fn test_page_range_sv39_boundary() {
+        // Sv39 低半部分的上界 2^38
+        const TOP: usize = 1 << 38;
+        let range = PageRange::covering(VirtAddr::new(TOP - 2 * PAGE_SIZE), 2 * PAGE_SIZE);
+
+        assert_eq!(range.page_count(), 2);
+        assert_eq!(range.end(), VirtAddr::new(TOP));
+        assert!(range.contains(VirtAddr::new(TOP - 1)));
+        assert!(!range.contains(VirtAddr::new(TOP)));
+        assert_eq!(range.iter_pages().last(), Some(VirtAddr::new(TOP - PAGE_SIZE)));
+
+        // 紧挨着的范围不重叠，共享一页则重叠
+        let above = PageRange::covering(VirtAddr::new(TOP), PAGE_SIZE);
+        assert!(!range.overlaps(&above));
+        assert!(!above.overlaps(&range));
+        let straddle = PageRange::covering(VirtAddr::new(TOP - 1), 2);
+        assert!(range.overlaps(&straddle) && above.overlaps(&straddle));
+    }
+
+    #[test_case]
+    fn test_split_at() {
+        let area = MemoryArea::new(
+            VirtAddr::new(0x2000_0000),
+            3 * PAGE_SIZE + 1,
+            MemoryAreaType::Heap,
+            PteFlags::empty(),
+            false,
+        );
+
+        // 不对齐或在边界上不能拆分
+        assert!(area.split_at(VirtAddr::new(0x2000_0800)).is_none());
+        assert!(area.split_at(area.start()).is_none());
+        assert!(area.split_at(area.end()).is_none());
+
+        let (left, right) = area.split_at(VirtAddr::new(0x2000_1000)).unwrap();
+        assert_eq!((left.page_count(), left.size()), (1, PAGE_SIZE));
+        assert_eq!((right.page_count(), right.size()), (3, 2 * PAGE_SIZE + 1));
+        assert_eq!(left.end(), right.start());
+        assert_eq!(right.end(), area.end());
+    }
+
+    #[test_case]
+    fn test_unmap_region_splits_area() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+
+        let start = VirtAddr::new(0x3000_0000);
+        space
+            .map_region(start, 4 * PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+
+        // 取消映射中间两页（大小不对齐，向上取整）
+        space
+            .unmap_region(VirtAddr::new(0x3000_1000), PAGE_SIZE + 1, &mut allocator)
+            .expect("unmap_region failed");
+
+        let areas = space.areas();
+        assert_eq!(areas.len(), 2);
+        assert_eq!((areas[0].start(), areas[0].page_count()), (start, 1));
+        assert_eq!(areas[1].start(), VirtAddr::new(0x3000_3000));
+        assert_eq!(areas[1].page_count(), 1);
+
+        let root = space.page_table_paddr();
+        assert!(walk_page_table(root, VirtAddr::new(0x3000_0000)).is_some());
+        assert!(walk_page_table(root, VirtAddr::new(0x3000_1000)).is_none());
+        assert!(walk_page_table(root, VirtAddr::new(0x3000_2fff)).is_none());
+        assert!(walk_page_table(root, VirtAddr::new(0x3000_3000)).is_some());
+    }
+
+    #[test_case]
+    fn test_unmap_region_geometries() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let baseline = allocator.stats().free_frames;
+        let page = |n: usize| VirtAddr::new(0x3000_0000 + n * PAGE_SIZE);
+        let layout = |space: &AddressSpace| -> Vec<(VirtAddr, usize)> {
+            space.areas().iter().map(|area| (area.start(), area.page_count())).collect()
+        };
+
+        // 两个相邻的区域：[0, 4) 和 [4, 8)，以及不相邻的 [10, 14)
+        for (first, count) in [(0, 4), (4, 4), (10, 4)] {
+            space
+                .map_region(page(first), count * PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+                .expect("map_region failed");
+        }
+
+        // 范围内有未映射的页面（第 8、9 页）：什么都不改
+        let before = layout(&space);
+        assert_eq!(
+            space.unmap_region(page(6), 6 * PAGE_SIZE, &mut allocator),
+            Err("Page not mapped")
+        );
+        assert_eq!(layout(&space), before);
+        assert!(space.is_mapped(page(6)) && space.is_mapped(page(10)));
+
+        // 头部：[0, 4) 缩短为 [1, 4)
+        space.unmap_region(page(0), PAGE_SIZE, &mut allocator).unwrap();
+        assert_eq!(layout(&space), [(page(1), 3), (page(4), 4), (page(10), 4)]);
+
+        // 尾部：[10, 14) 缩短为 [10, 12)
+        space.unmap_region(page(12), 2 * PAGE_SIZE, &mut allocator).unwrap();
+        assert_eq!(layout(&space), [(page(1), 3), (page(4), 4), (page(10), 2)]);
+
+        // 跨越两个区域：[1, 4) 的尾部和 [4, 8) 的头部
+        space.unmap_region(page(3), 2 * PAGE_SIZE, &mut allocator).unwrap();
+        assert_eq!(layout(&space), [(page(1), 2), (page(5), 3), (page(10), 2)]);
+
+        // 整个区域
+        space.unmap_region(page(10), 2 * PAGE_SIZE, &mut allocator).unwrap();
+        assert_eq!(layout(&space), [(page(1), 2), (page(5), 3)]);
+
+        let root = space.page_table_paddr();
+        for n in [0, 3, 4, 10, 11, 12] {
+            assert!(walk_page_table(root, page(n)).is_none());
+        }
+        for n in [1, 2, 5, 6, 7] {
+            assert!(walk_page_table(root, page(n)).is_some());
+        }
+
+        // 取消映射的页帧和中间页表都已归还：再取消剩下的页面后回到基线
+        assert_eq!(allocator.stats().free_frames, baseline - 5 - 2);
+        space.unmap_region(page(1), 2 * PAGE_SIZE, &mut allocator).unwrap();
+        space.unmap_region(page(5), 3 * PAGE_SIZE, &mut allocator).unwrap();
+        assert!(space.areas().is_empty());
+        assert_eq!(allocator.stats().free_frames, baseline);
+    }
+
+    #[test_case]
+    fn test_unmap_region_releases_cow_share() {
+        let mut allocator = test_frame_allocator();
+        let mut parent = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let vaddr = VirtAddr::new(0x1000_0000);
+        parent
+            .map_region(vaddr, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+        let paddr = walk_page_table(parent.page_table_paddr(), vaddr).unwrap();
+        let frame = PhysFrame::from_addr(paddr);
+
+        // 子地址空间取消映射共享的页面：只归还它的两级中间页表，
+        // 页帧仍属于父地址空间，不再计为共享
+        let mut child = parent.fork(&mut allocator).expect("fork failed");
+        assert!(is_cow_shared(frame));
+        let before = allocator.stats().free_frames;
+        child.unmap_region(vaddr, PAGE_SIZE, &mut allocator).expect("unmap_region failed");
+        assert_eq!(allocator.stats().free_frames, before + 2);
+        assert!(!is_cow_shared(frame));
+
+        // 父地址空间随后取消映射时释放页帧
+        parent.unmap_region(vaddr, PAGE_SIZE, &mut allocator).expect("unmap_region failed");
+        assert_eq!(allocator.stats().free_frames, before + 2 + 3);
+    }
+
+    #[test_case]
+    fn test_unmap_region_rejects_part_of_huge_page() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let baseline = allocator.stats().free_frames;
+
+        // 一个 4KB 页，紧接着一个 2MB 大页和一个 4KB 页
+        let mmio = VirtAddr::new(0x1_0000_0000);
+        let below = mmio - PAGE_SIZE;
+        space
+            .map_region(below, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+        let size = MEGAPAGE_SIZE + PAGE_SIZE;
+        space
+            .map_region_identity(mmio, size, MemoryAreaType::Mmio, &mut allocator)
+            .expect("map_region_identity failed");
+        let free = allocator.stats().free_frames;
+
+        // 范围的开头、中间或末尾只覆盖大页的一部分：什么都不改
+        let partial = [(below, 2 * PAGE_SIZE), (mmio + PAGE_SIZE, PAGE_SIZE), (mmio, PAGE_SIZE)];
+        for (vaddr, size) in partial {
+            assert_eq!(
+                space.unmap_region(vaddr, size, &mut allocator),
+                Err("Cannot unmap part of a huge page")
+            );
+        }
+        assert!(space.is_mapped(below) && space.is_mapped(mmio));
+        assert_eq!(space.areas().len(), 2);
+        assert_eq!(allocator.stats().free_frames, free);
+
+        // 覆盖整个大页
+        space
+            .unmap_region(below, MEGAPAGE_SIZE + 2 * PAGE_SIZE, &mut allocator)
+            .expect("unmap_region failed");
+        assert!(!space.is_mapped(below) && !space.is_mapped(mmio + MEGAPAGE_SIZE));
+        assert!(space.areas().is_empty());
+        assert_eq!(allocator.stats().free_frames, baseline);
+    }
+
+    #[test_case]
+    fn test_unmap_region_removes_stack_guard() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let region = VirtAddr::new(0x3800_0000);
+        space
+            .map_region(region, 3 * PAGE_SIZE, MemoryAreaType::Stack, &mut allocator)
+            .expect("map_region failed");
+
+        // 栈顶的页面：保护页保留
+        space
+            .unmap_region(region + 2 * PAGE_SIZE, PAGE_SIZE, &mut allocator)
+            .expect("unmap_region failed");
+        assert!(space.is_guard_page(region));
+
+        // 栈的最低一页：保护页随之移除，之后可以重新映射
+        space
+            .unmap_region(region + PAGE_SIZE, PAGE_SIZE, &mut allocator)
+            .expect("unmap_region failed");
+        assert!(space.guard_pages().is_empty());
+        space
+            .map_region(region, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+    }
+
+    #[test_case]
+    fn test_fork_copy_on_write() {
+        let mut allocator = test_frame_allocator();
+        let mut parent = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let vaddr = VirtAddr::new(0x1000_0000);
+        parent
+            .map_region(vaddr, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+
+        let frame = walk_page_table(parent.page_table_paddr(), vaddr).unwrap();
+        let bytes = frame.as_usize() as *mut u8;
+        unsafe { core::ptr::write_bytes(bytes, 0x5a, PAGE_SIZE) };
+
+        let mut child = parent.fork(&mut allocator).expect("fork failed");
+        assert_eq!(walk_page_table(child.page_table_paddr(), vaddr), Some(frame));
+        for space in [&mut parent, &mut child] {
+            let flags = leaf_entry_mut(space.page_table(), vaddr).unwrap().flags();
+            assert!(!flags.is_writable());
+            assert!(flags.contains(PteFlags::COW));
+        }
+
+        // 子地址空间写入：复制出新页帧后写入副本
+        handle_cow_fault(&mut child, vaddr + 0x10, &mut allocator).expect("COW fault failed");
+        let copy = walk_page_table(child.page_table_paddr(), vaddr).unwrap();
+        assert_ne!(copy, frame);
+        unsafe {
+            assert_eq!(*(copy.as_usize() as *const u8).add(PAGE_SIZE - 1), 0x5a);
+            *(copy.as_usize() as *mut u8).add(0x10) = 0xa5;
+        }
+        assert_eq!(unsafe { *bytes.add(0x10) }, 0x5a);
+        assert_eq!(walk_page_table(parent.page_table_paddr(), vaddr), Some(frame));
+
+        // 父地址空间此时独占原页帧，只恢复写权限
+        handle_cow_fault(&mut parent, vaddr, &mut allocator).expect("COW fault failed");
+        assert_eq!(walk_page_table(parent.page_table_paddr(), vaddr), Some(frame));
+        let flags = leaf_entry_mut(parent.page_table(), vaddr).unwrap().flags();
+        assert!(flags.is_writable());
+        assert_eq!(
+            handle_cow_fault(&mut parent, vaddr, &mut allocator),
+            Err("Not a copy-on-write page")
+        );
+    }
+
+    #[test_case]
+    fn test_destroy_in_any_order_returns_all_frames() {
+        let mut allocator = test_frame_allocator();
+        let baseline = allocator.stats().free_frames;
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        for (vaddr, pages) in [(0x1000_0000, 3), (0x4020_0000, 600)] {
+            let (vaddr, size) = (VirtAddr::new(vaddr), pages * PAGE_SIZE);
+            space
+                .map_region(vaddr, size, MemoryAreaType::Data, &mut allocator)
+                .expect("map_region failed");
+        }
+        assert!(allocator.stats().free_frames < baseline - 603);
+        space.destroy(&mut allocator);
+        assert_eq!(allocator.stats().free_frames, baseline);
+
+        // 写时复制共享的页帧：先销毁父地址空间，子地址空间仍然可以读取，之后一并释放
+        let vaddr = VirtAddr::new(0x1000_0000);
+        let mut parent = AddressSpace::new(&mut allocator).expect("Out of memory");
+        parent
+            .map_region(vaddr, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+        let frame = walk_page_table(parent.page_table_paddr(), vaddr).unwrap();
+        unsafe { (frame.as_usize() as *mut u64).write_volatile(0x5a5a) };
+        let child = parent.fork(&mut allocator).expect("fork failed");
+        parent.destroy(&mut allocator);
+        assert_eq!(child.translate(vaddr), Some(frame));
+        assert_eq!(unsafe { (frame.as_usize() as *const u64).read_volatile() }, 0x5a5a);
+        child.destroy(&mut allocator);
+        assert_eq!(allocator.stats().free_frames, baseline);
+    }
+
+    #[test_case]
+    fn test_fork_out_of_memory_leaves_parent_intact() {
+        let mut allocator = test_frame_allocator();
+        let mut parent = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let (low, high) = (VirtAddr::new(0x1000_0000), VirtAddr::new(0x4000_0000));
+        for vaddr in [low, high] {
+            parent
+                .map_region(vaddr, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+                .expect("map_region failed");
+        }
+        let frames = [low, high].map(|vaddr| walk_page_table(parent.page_table_paddr(), vaddr));
+        let free_before = allocator.stats().free_frames;
+
+        // 根页表 + 第一个页面的两级页表成功，第二个页面的一级页表分配失败
+        let fault = faultinject::arm("frame_alloc", Policy::NthCall(4));
+        assert_eq!(parent.fork(&mut allocator).err(), Some("Out of memory"));
+        assert_eq!(fault.calls(), 4);
+        drop(fault);
+
+        // 子地址空间的页表全部释放，父地址空间的映射不变，也不再被共享
+        assert_eq!(allocator.stats().free_frames, free_before);
+        for (vaddr, frame) in [low, high].into_iter().zip(frames) {
+            assert_eq!(walk_page_table(parent.page_table_paddr(), vaddr), frame);
+            assert!(!is_cow_shared(PhysFrame::from_addr(frame.unwrap())));
+        }
+        handle_cow_fault(&mut parent, low, &mut allocator).expect("COW fault failed");
+        assert!(leaf_entry_mut(parent.page_table(), low).unwrap().flags().is_writable());
+    }
+
+    #[test_case]
+    fn test_share_cow_write_fault_separates_spaces() {
+        use crate::memory::create_kernel_address_space;
+        use riscv::register::satp;
+
+        let mut allocator = test_frame_allocator();
+        let mut parent = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+        let vaddr = VirtAddr::new(0x4000_0000);
+        parent
+            .map_region(vaddr, PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+        let frame = walk_page_table(parent.page_table_paddr(), vaddr).unwrap();
+        unsafe { (frame.as_usize() as *mut u64).write_volatile(0x1111) };
+
+        let mut child = AddressSpace::new(&mut allocator).expect("Out of memory");
+        child.share_cow(&mut parent, &mut allocator).expect("share_cow failed");
+        assert_eq!(
+            child.share_cow(&mut parent, &mut allocator),
+            Err("Range overlaps an existing area")
+        );
+
+        // 在子地址空间中真正写入：写页错误由写时复制处理后重新执行
+        let old_satp = satp::read();
+        let (asid, root_ppn) = (child.asid() as usize, child.page_table_paddr().page_number());
+        {
+            let _active = child.set_active(&mut allocator);
+            unsafe {
+                satp::set(satp::Mode::Sv39, asid, root_ppn);
+                tlb::flush_all();
+                (vaddr.as_usize() as *mut u64).write_volatile(0x2222);
+                satp::set(old_satp.mode(), old_satp.asid(), old_satp.ppn());
+                tlb::flush_all();
+            }
+        }
+
+        let copy = walk_page_table(child.page_table_paddr(), vaddr).unwrap();
+        assert_ne!(copy, frame);
+        assert_eq!(walk_page_table(parent.page_table_paddr(), vaddr), Some(frame));
+        unsafe {
+            assert_eq!((copy.as_usize() as *const u64).read_volatile(), 0x2222);
+            assert_eq!((frame.as_usize() as *const u64).read_volatile(), 0x1111);
+        }
+        // 父地址空间成为原页帧的唯一所有者
+        assert!(!is_cow_shared(PhysFrame::from_addr(frame)));
+
+        child.destroy(&mut allocator);
+        parent.destroy(&mut allocator);
+    }
+
+    #[test_case]
+    fn test_lazy_region_populated_by_real_access() {
+        use crate::memory::create_kernel_address_space;
+        use riscv::register::satp;
+
+        let mut allocator = test_frame_allocator();
+        let mut space = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+        let heap = VirtAddr::new(0x4000_0000);
+        space
+            .map_region_lazy(heap, 256 * PAGE_SIZE, MemoryAreaType::Heap)
+            .expect("map_region_lazy failed");
+        let free_before = allocator.stats().free_frames;
+        let ptr = (heap + 100 * PAGE_SIZE + 8).as_usize() as *mut u64;
+
+        // 在这个地址空间中真正访问：读页错误映射清零的页帧，之后的写入不再出错
+        let old_satp = satp::read();
+        let (asid, root_ppn) = (space.asid() as usize, space.page_table_paddr().page_number());
+        let (first, second) = {
+            let _active = space.set_active(&mut allocator);
+            unsafe {
+                satp::set(satp::Mode::Sv39, asid, root_ppn);
+                tlb::flush_all();
+                let first = ptr.read_volatile();
+                ptr.write_volatile(0xfeed_f00d);
+                let second = ptr.read_volatile();
+                satp::set(old_satp.mode(), old_satp.asid(), old_satp.ppn());
+                tlb::flush_all();
+                (first, second)
+            }
+        };
+
+        assert_eq!((first, second), (0, 0xfeed_f00d));
+        let paddr = space.translate(VirtAddr::new(ptr as usize)).expect("page not mapped");
+        assert_eq!(unsafe { (paddr.as_usize() as *const u64).read_volatile() }, 0xfeed_f00d);
+        // 256 页的区域只用掉了一个页帧（外加中间页表）
+        assert!(free_before - allocator.stats().free_frames <= 3);
+        assert!(!space.is_mapped(heap));
+    }
+
+    #[test_case]
+    fn test_destroy_reclaims_all_frames() {
+        let mut allocator = test_frame_allocator();
+        let free_before = allocator.stats().free_frames;
+
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let code = VirtAddr::new(0x1000_0000);
+        space
+            .map_region(code, 3 * PAGE_SIZE, MemoryAreaType::Code, &mut allocator)
+            .expect("map_region failed");
+        // 跨越 1GB 边界，需要两套中间页表
+        let heap = VirtAddr::new(0x3fff_f000);
+        space
+            .map_region(heap, 2 * PAGE_SIZE, MemoryAreaType::Heap, &mut allocator)
+            .expect("map_region failed");
+        let mmio = VirtAddr::new(0x1_0000_0000);
+        space
+            .map_region_identity(mmio, MEGAPAGE_SIZE, MemoryAreaType::Mmio, &mut allocator)
+            .expect("map_region_identity failed");
+
+        // 写时复制的页帧在两个地址空间都销毁之后才释放
+        let child = space.fork(&mut allocator).expect("fork failed");
+        let shared = walk_page_table(child.page_table_paddr(), code).unwrap();
+        space.destroy(&mut allocator);
+        assert!(allocator.stats().free_frames < free_before);
+        assert_eq!(walk_page_table(child.page_table_paddr(), code), Some(shared));
+
+        child.destroy(&mut allocator);
+        assert_eq!(allocator.stats().free_frames, free_before);
+    }
+
+    #[test_case]
+    fn test_fork_from_copies_data() {
+        let mut allocator = test_frame_allocator();
+        let free_before = allocator.stats().free_frames;
+        let mut parent = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let data = VirtAddr::new(0x2000_0000);
+        parent
+            .map_region(data, 2 * PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+        let uart = VirtAddr::new(crate::memory::UART_BASE);
+        parent
+            .map_region_identity(uart, PAGE_SIZE, MemoryAreaType::Mmio, &mut allocator)
+            .expect("map_region_identity failed");
+
+        let original = parent.translate(data + PAGE_SIZE).unwrap().as_usize() as *mut u8;
+        unsafe { core::ptr::write_bytes(original, 0x5a, PAGE_SIZE) };
+
+        let child = AddressSpace::fork_from(&parent, &mut allocator).expect("fork_from failed");
+        assert_eq!(child.areas().len(), parent.areas().len());
+        // 恒等映射共享，普通映射各自独立
+        assert_eq!(child.translate(uart), parent.translate(uart));
+        let copy = child.translate(data + PAGE_SIZE).unwrap().as_usize() as *mut u8;
+        assert_ne!(copy, original);
+        let flags = child.area_containing(data).unwrap().flags;
+        assert_eq!(flags, MemoryAreaType::Data.default_flags());
+
+        unsafe {
+            assert_eq!(*copy.add(PAGE_SIZE - 1), 0x5a);
+            core::ptr::write_bytes(copy, 0xa5, PAGE_SIZE);
+            assert_eq!(*original, 0x5a);
+            assert_eq!(*original.add(PAGE_SIZE - 1), 0x5a);
+        }
+
+        child.destroy(&mut allocator);
+        parent.destroy(&mut allocator);
+        assert_eq!(allocator.stats().free_frames, free_before);
+    }
+
+    #[test_case]
+    fn test_map_stack_leaves_guard_page() {
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+        let stack = VirtAddr::new(0x3000_1000);
+        space.map_stack(stack, 4 * PAGE_SIZE, &mut allocator).expect("map_stack failed");
+
+        let root = space.page_table_paddr();
+        let guard = stack - PAGE_SIZE;
+        assert!(walk_page_table(root, stack).is_some());
+        assert!(walk_page_table(root, guard).is_none());
+        assert!(space.is_guard_page(guard + 0x80));
+        assert!(!space.is_guard_page(stack));
+
+        // 保护页不能再被映射
+        assert_eq!(
+            space.map_region(guard, PAGE_SIZE, MemoryAreaType::Data, &mut allocator),
+            Err("Range overlaps a guard page")
+        );
+        assert_eq!(
+            space.map_region_lazy(guard - PAGE_SIZE, 2 * PAGE_SIZE, MemoryAreaType::Heap),
+            Err("Range overlaps a guard page")
+        );
+        assert!(walk_page_table(root, guard).is_none());
+
+        // map_region 映射栈时自动把最低一页留作保护页
+        let region = VirtAddr::new(0x3800_0000);
+        space
+            .map_region(region, 3 * PAGE_SIZE, MemoryAreaType::Stack, &mut allocator)
+            .expect("map_region failed");
+        assert!(walk_page_table(root, region).is_none());
+        assert!(walk_page_table(root, region + PAGE_SIZE).is_some());
+        assert_eq!(space.guard_at(region).map(|guard| guard.stack), Some("stack"));
+        assert_eq!(
+            space.map_region(region - PAGE_SIZE, PAGE_SIZE, MemoryAreaType::Stack, &mut allocator),
+            Err("Stack too small for a guard page")
+        );
+
+        // 页错误处理识别栈溢出，并能找到被保护的栈
+        let _active = space.set_active(&mut allocator);
+        assert_eq!(handle_active_page_fault(guard + 8, true), Err(STACK_OVERFLOW));
+        assert_eq!(active_guarded_stack(region + 8), Some("stack"));
+        assert_eq!(active_guarded_stack(region + PAGE_SIZE), None);
+    }
+
+    /// 向 `addr` 写入一个字，页错误由异常表修复
+    ///
+    /// # 返回
+    /// 写入是否触发了异常
+    unsafe fn try_store(addr: usize, value: u64) -> bool {
+        let faulted: usize;
+        core::arch::asm!(
+            "li {faulted}, 0",
+            "7: sd {value}, 0({addr})",
+            "j 9f",
+            "8: li {faulted}, 1",
+            "9:",
+            ".pushsection __ex_table, \"a\"",
+            ".balign 8",
+            ".dword 7b, 8b",
+            ".popsection",
+            faulted = out(reg) faulted,
+            value = in(reg) value,
+            addr = in(reg) addr,
+        );
+        faulted != 0
+    }
+
+    #[test_case]
+    fn test_protect_region_read_only() {
+        use crate::interrupts::exception_counts;
+        use crate::memory::create_kernel_address_space;
+        use riscv::register::satp;
+
+        const STORE_PAGE_FAULT: usize = 15;
+
+        let mut allocator = test_frame_allocator();
+        let mut space = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+        let vaddr = VirtAddr::new(0x4000_0000);
+        space
+            .map_region(vaddr, 2 * PAGE_SIZE, MemoryAreaType::Data, &mut allocator)
+            .expect("map_region failed");
+        let paddr = walk_page_table(space.page_table_paddr(), vaddr).unwrap();
+        let ptr = vaddr.as_usize() as *mut u64;
+
+        let old_satp = satp::read();
+        let (first, second, faults) = unsafe {
+            let root_ppn = space.page_table_paddr().page_number();
+            satp::set(satp::Mode::Sv39, space.asid() as usize, root_ppn);
+            tlb::flush_all();
+
+            let first = try_store(ptr as usize, 0x1234);
+            space.protect_region(vaddr, PAGE_SIZE, PteFlags::READ).unwrap();
+            let before = exception_counts()[STORE_PAGE_FAULT];
+            let second = try_store(ptr as usize, 0x5678);
+            let faults = exception_counts()[STORE_PAGE_FAULT] - before;
+
+            satp::set(old_satp.mode(), old_satp.asid(), old_satp.ppn());
+            tlb::flush_all();
+            (first, second, faults)
+        };
+
+        assert!(!first);
+        assert!(second);
+        assert_eq!(faults, 1);
+        assert_eq!(unsafe { (paddr.as_usize() as *const u64).read_volatile() }, 0x1234);
+
+        // 物理页号保留，区域被拆分，只有第一页变为只读
+        assert_eq!(walk_page_table(space.page_table_paddr(), vaddr), Some(paddr));
+        let data: Vec<_> = space.areas().iter().filter(|a| !a.identity).collect();
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].flags, PteFlags::READ);
+        assert_eq!(data[1].flags, MemoryAreaType::Data.default_flags());
+
+        assert_eq!(
+            space.protect_region(vaddr, PAGE_SIZE, PteFlags::WRITE),
+            Err("Invalid leaf permissions")
+        );
+        assert_eq!(
+            space.protect_region(vaddr + 2 * PAGE_SIZE, PAGE_SIZE, PteFlags::READ),
+            Err("Page not mapped")
+        );
+    }
+}
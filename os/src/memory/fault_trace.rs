@@ -0,0 +1,130 @@
+/*
+ * ============================================
+ * 页错误跟踪
+ * ============================================
+ * 功能：记录每次页错误的原因、地址、命中的区域和处理结果，
+ *       用于观察按需映射和写时复制的过程
+ *
+ * 记录由 handle_recoverable_fault 在得出结果后写入，保存在固定大小的环形缓冲区中
+ * （页错误可能发生在任何地方，记录时不分配内存），满了之后覆盖最旧的记录
+ *
+ * 只在测试和 trace_faults 特性下启用；
+ * 其他构建中 record 是空的内联函数
+ * ============================================
+ */
+
+use super::MemoryAreaType;
+use core::fmt;
+use riscv::register::scause::Trap;
+
+/// 页错误的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultResolution {
+    /// 按需映射区域：分配了清零的页帧
+    LazyMapped,
+    /// 写时复制：页帧仍被共享，复制了一份
+    CowCopied,
+    /// 写时复制：页帧已经独占，只恢复了写权限
+    CowReclaimed,
+    /// 异常表：跳到了修复代码
+    Fixup,
+    /// 内核中无法恢复（附带原因）
+    Fatal(&'static str),
+    /// 用户态无法恢复，终止进程（附带原因）
+    Terminated(&'static str),
+}
+
+impl fmt::Display for FaultResolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaultResolution::LazyMapped => f.write_str("lazy-mapped"),
+            FaultResolution::CowCopied => f.write_str("COW-copied"),
+            FaultResolution::CowReclaimed => f.write_str("COW-reclaimed"),
+            FaultResolution::Fixup => f.write_str("fixup"),
+            FaultResolution::Fatal(reason) => write!(f, "fatal ({})", reason),
+            FaultResolution::Terminated(reason) => write!(f, "terminated ({})", reason),
+        }
+    }
+}
+
+/// 一次页错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultRecord {
+    /// 异常类型（Load/Store/Instruction Page Fault）
+    pub cause: Trap,
+    /// 出错的虚拟地址（stval）
+    pub addr: usize,
+    /// 地址所在区域的类型（没有登记的地址空间或不在任何区域中时为 None）
+    pub area: Option<MemoryAreaType>,
+    /// 处理结果
+    pub resolution: FaultResolution,
+}
+
+/// 记录一次页错误（跟踪未启用：什么都不做）
+#[cfg(not(any(test, feature = "trace_faults")))]
+#[inline(always)]
+pub fn record(_cause: Trap, _addr: usize, _resolution: FaultResolution) {}
+
+#[cfg(any(test, feature = "trace_faults"))]
+pub use trace::{clear, print_trace, record, records};
+
+#[cfg(any(test, feature = "trace_faults"))]
+mod trace {
+    use super::{FaultRecord, FaultResolution, Trap};
+    use crate::interrupts::without_interrupts;
+    use crate::memory::VirtAddr;
+    use crate::println;
+    use alloc::vec::Vec;
+    use spin::Mutex;
+
+    /// 保留的记录数
+    const CAPACITY: usize = 32;
+
+    /// 环形缓冲区
+    struct Ring {
+        records: [Option<FaultRecord>; CAPACITY],
+        /// 下一条记录写入的位置
+        next: usize,
+    }
+
+    static RING: Mutex<Ring> = Mutex::new(Ring { records: [None; CAPACITY], next: 0 });
+
+    /// 记录一次页错误（区域从登记的地址空间中查找）
+    pub fn record(cause: Trap, addr: usize, resolution: FaultResolution) {
+        let area = crate::memory::active_area_type(VirtAddr::new(addr));
+        let entry = FaultRecord { cause, addr, area, resolution };
+        without_interrupts(|| {
+            let mut ring = RING.lock();
+            let next = ring.next;
+            ring.records[next] = Some(entry);
+            ring.next = (next + 1) % CAPACITY;
+        });
+    }
+
+    /// 保留的记录，从旧到新
+    pub fn records() -> Vec<FaultRecord> {
+        without_interrupts(|| {
+            let ring = RING.lock();
+            let (newer, older) = ring.records.split_at(ring.next);
+            older.iter().chain(newer).flatten().copied().collect()
+        })
+    }
+
+    /// 清空记录
+    pub fn clear() {
+        without_interrupts(|| *RING.lock() = Ring { records: [None; CAPACITY], next: 0 });
+    }
+
+    /// 打印保留的记录
+    pub fn print_trace() {
+        for entry in records() {
+            println!(
+                "[FAULT] {:?} at {:#x} area={} → {}",
+                entry.cause,
+                entry.addr,
+                entry.area.map_or("-", |area| area.name()),
+                entry.resolution
+            );
+        }
+    }
+}
@@ -0,0 +1,204 @@
+/*
+ * ============================================
+ * 内核高半部分
+ * ============================================
+ * 功能：把 DRAM 线性映射到 Sv39 的高半部分（别名），并让内核切换到别名上执行
+ *
+ * 物理地址 MEMORY_START 映射到 KERNEL_VIRT_BASE（0xFFFF_FFC0_0000_0000）：
+ *   内核虚拟地址 = 物理地址 + KERNEL_VIRT_OFFSET
+ *
+ * 过渡：
+ * - create_kernel_address_space_high 在内核地址空间之外再建立 DRAM 的高半部分别名，
+ *   内核栈的保护页在别名中同样保留
+ * - enter_high_half 写入 satp 后调用 high_half_jump，把 sp 和返回地址加上偏移，
+ *   调用者从这里开始在高地址执行
+ *
+ * 内核以 medany 代码模型编译，代码和静态变量都按 PC 相对寻址，不重新链接也能在别名上运行；
+ * 链接时写入的绝对地址（函数指针表、trap_handler 指针等）仍指向低地址，
+ * 因此低半部分的恒等映射保留（切换 satp 的那一刻取指也依赖它）
+ *
+ * phys_to_kvirt / kvirt_to_phys 是内核访问物理内存时的地址转换：页表、帧分配器位图、
+ * 页错误处理中清零和复制的页帧、ELF 加载、hexdump 都经过它。进入高半部分之前偏移为 0
+ *
+ * 这只是别名，不是内核重定位：内核仍链接在物理地址，低半部分的恒等映射也不能撤掉。
+ * 把内核链接到高半部分、只保留高地址映射的重定位没有实现
+ * ============================================
+ */
+
+use super::{
+    create_kernel_address_space, kernel_stack_bottom, memory_end, AddressSpace, MemoryAreaType,
+    PhysAddr, SimpleFrameAllocator, VirtAddr, KERNEL_SPACE_START, MEMORY_START, PAGE_SIZE,
+};
+use crate::klog;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// DRAM 在高半部分的起始虚拟地址
+pub const KERNEL_VIRT_BASE: usize = KERNEL_SPACE_START;
+
+/// 高半部分别名与物理地址之差
+pub const KERNEL_VIRT_OFFSET: usize = KERNEL_VIRT_BASE - MEMORY_START;
+
+/// 内核访问 DRAM 时加在物理地址上的偏移（进入高半部分之前为 0）
+static PHYS_OFFSET: AtomicUsize = AtomicUsize::new(0);
+
+/// 物理地址在内核中的虚拟地址
+///
+/// # 说明
+/// 只转换 DRAM 中的地址；MMIO 等其他物理地址保持恒等映射
+pub fn phys_to_kvirt(paddr: PhysAddr) -> VirtAddr {
+    let addr = paddr.as_usize();
+    if (MEMORY_START..memory_end()).contains(&addr) {
+        VirtAddr::new(addr + PHYS_OFFSET.load(Ordering::Relaxed))
+    } else {
+        VirtAddr::new(addr)
+    }
+}
+
+/// 内核虚拟地址对应的物理地址
+///
+/// # 说明
+/// 高半部分别名中的地址减去偏移，其余地址按恒等映射处理
+pub fn kvirt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+    let addr = vaddr.as_usize();
+    if (KERNEL_VIRT_BASE..KERNEL_VIRT_BASE + (memory_end() - MEMORY_START)).contains(&addr) {
+        PhysAddr::new(addr - KERNEL_VIRT_OFFSET)
+    } else {
+        PhysAddr::new(addr)
+    }
+}
+
+/// 内核是否已经进入高半部分
+pub fn in_high_half() -> bool {
+    PHYS_OFFSET.load(Ordering::Relaxed) != 0
+}
+
+/// 创建带高半部分别名的内核地址空间
+///
+/// # 功能
+/// - `create_kernel_address_space` 的全部映射（DRAM 恒等映射、UART、跳板页）
+/// - DRAM 线性映射到 `KERNEL_VIRT_BASE`，内核栈最低一页的别名同样作为保护页
+pub fn create_kernel_address_space_high(
+    allocator: &mut SimpleFrameAllocator,
+) -> Result<AddressSpace, &'static str> {
+    let mut space = create_kernel_address_space(allocator)?;
+
+    klog!(
+        "[MEMORY] Mapping kernel alias: {:#x} - {:#x} → {:#x}",
+        KERNEL_VIRT_BASE,
+        memory_end() + KERNEL_VIRT_OFFSET,
+        MEMORY_START
+    );
+    let guard = kernel_stack_bottom();
+    for (start, end) in [(MEMORY_START, guard), (guard + PAGE_SIZE, memory_end())] {
+        space.map_region_linear(
+            VirtAddr::new(start + KERNEL_VIRT_OFFSET),
+            PhysAddr::new(start),
+            end - start,
+            MemoryAreaType::Kernel,
+            allocator,
+        )?;
+    }
+    space.add_guard_page(VirtAddr::new(guard + KERNEL_VIRT_OFFSET), "kernel stack")?;
+    Ok(space)
+}
+
+core::arch::global_asm!(
+    ".pushsection .text",
+    ".globl high_half_jump",
+    ".p2align 2",
+    // a0 = 偏移：栈指针和返回地址都换成高半部分的别名
+    "high_half_jump:",
+    "   add sp, sp, a0",
+    "   add ra, ra, a0",
+    "   ret",
+    ".popsection",
+);
+
+/// 激活 `space` 并切换到高半部分执行
+///
+/// # 说明
+/// 内联到调用者中：从这里返回后，调用者的 pc 和 sp 都在高半部分；
+/// 调用者自己返回时回到它的调用者保存的低地址（低半部分仍然映射）
+///
+/// # Safety
+/// - `space` 必须由 `create_kernel_address_space_high` 创建，并且一直不被销毁
+/// - 只能调用一次；之后不能再激活没有高半部分别名的地址空间
+#[inline(always)]
+pub unsafe fn enter_high_half(space: &AddressSpace) {
+    extern "C" {
+        fn high_half_jump(offset: usize);
+    }
+    space.activate();
+    PHYS_OFFSET.store(KERNEL_VIRT_OFFSET, Ordering::Relaxed);
+    high_half_jump(KERNEL_VIRT_OFFSET);
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{test_frame_allocator, tlb};
+    use riscv::register::satp;
+
+    /// 通过高半部分别名读取
+    static MARKER: u64 = 0x5eed_cafe;
+
+    #[inline(never)]
+    extern "C" fn answer() -> usize {
+        42
+    }
+
+    #[test_case]
+    fn test_kvirt_conversions() {
+        assert!(!in_high_half());
+        let paddr = PhysAddr::new(MEMORY_START + 0x1234);
+        assert_eq!(phys_to_kvirt(paddr), VirtAddr::new(MEMORY_START + 0x1234));
+        assert_eq!(kvirt_to_phys(VirtAddr::new(KERNEL_VIRT_BASE + 0x1234)), paddr);
+        assert_eq!(kvirt_to_phys(VirtAddr::new(MEMORY_START + 0x1234)), paddr);
+        // DRAM 之外的地址不转换
+        assert_eq!(phys_to_kvirt(PhysAddr::new(0x1000_0000)), VirtAddr::new(0x1000_0000));
+        let past_end = KERNEL_VIRT_BASE + (memory_end() - MEMORY_START);
+        assert_eq!(kvirt_to_phys(VirtAddr::new(past_end)), PhysAddr::new(past_end));
+    }
+
+    #[test_case]
+    fn test_high_half_alias_reads_and_executes() {
+        let mut allocator = test_frame_allocator();
+        let space = create_kernel_address_space_high(&mut allocator).expect("high space failed");
+
+        // 别名和恒等映射指向同一物理地址；内核栈的保护页在别名中同样不映射
+        let marker = &MARKER as *const u64 as usize;
+        let alias = VirtAddr::new(marker + KERNEL_VIRT_OFFSET);
+        assert_eq!(space.translate(alias), Some(PhysAddr::new(marker)));
+        let guard = VirtAddr::new(kernel_stack_bottom() + KERNEL_VIRT_OFFSET);
+        assert!(!space.is_mapped(guard));
+        assert_eq!(space.guard_at(guard).map(|guard| guard.stack), Some("kernel stack"));
+
+        // 布局中显示偏移映射
+        let output = crate::console::capture();
+        space.print_layout(false);
+        let layout = output.take();
+        assert!(layout.contains("│ offset   │"));
+        assert!(layout.contains("（偏移 0xffffffbf80000000）"));
+
+        // 真正通过别名读取静态变量、执行函数
+        let answer_alias = answer as *const () as usize + KERNEL_VIRT_OFFSET;
+        let old_satp = satp::read();
+        let (asid, root_ppn) = (space.asid() as usize, space.page_table_paddr().page_number());
+        let (value, result) = unsafe {
+            satp::set(satp::Mode::Sv39, asid, root_ppn);
+            tlb::flush_all();
+            let value = (alias.as_usize() as *const u64).read_volatile();
+            let call: extern "C" fn() -> usize = core::mem::transmute(answer_alias);
+            let result = call();
+            satp::set(old_satp.mode(), old_satp.asid(), old_satp.ppn());
+            tlb::flush_all();
+            (value, result)
+        };
+        assert_eq!((value, result), (0x5eed_cafe, 42));
+        space.destroy(&mut allocator);
+    }
+}
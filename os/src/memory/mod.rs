@@ -0,0 +1,1778 @@
+/*
+ * ============================================
+ * RISC-V 内存管理模块
+ * ============================================
+ * 功能：物理内存管理与 Sv39 虚拟内存
+ * 包括：
+ * - 物理地址 / 虚拟地址抽象
+ * - 物理页帧与帧分配器
+ * - 页表项与页表
+ * - 页表遍历与映射（paging）
+ * - 地址空间抽象（address_space）
+ * - 固定页帧与页面置换（replace）
+ * - 用户栈初始布局（user_stack）
+ * - 页错误跟踪（fault_trace）
+ *
+ * Sv39 虚拟地址格式：
+ * | VPN[2] (9) | VPN[1] (9) | VPN[0] (9) | offset (12) |
+ * ============================================
+ */
+
+use core::{fmt, ops};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::fmt::Elided;
+use crate::{klog, println};
+
+pub mod paging;
+pub mod address_space;
+pub mod replace;
+pub mod user_stack;
+pub mod satp_watch;
+pub mod tlb;
+pub mod high_half;
+pub mod fault_trace;
+#[cfg(any(test, feature = "builtin_tests"))]
+pub(crate) mod synthetic;
+
+// 重新导出常用类型
+pub use paging::{
+    dump_page_table, map_huge_page, map_page, map_page_1gb, map_page_2mb, map_page_mode, map_range,
+    unmap_page, unmap_page_1gb, unmap_page_2mb, verify_page_table, walk_mappings, walk_page_table,
+    walk_page_table_mode, walk_page_table_verbose, translate_addr as translate_addr_current,
+    MappingInfo, MappingSize, Mappings,
+};
+pub use address_space::{
+    active_area_type, active_guarded_stack, handle_active_page_fault, handle_cow_fault,
+    ActiveGuard, AddressSpace, GuardPage, MemoryArea, MemoryAreaType, PageRange, STACK_OVERFLOW,
+};
+pub use fault_trace::{FaultRecord, FaultResolution};
+pub use replace::{is_pinned, pin_frame, unpin_frame, PageReplacer};
+pub use satp_watch::activate;
+pub use high_half::{
+    create_kernel_address_space_high, enter_high_half, kvirt_to_phys, phys_to_kvirt,
+    KERNEL_VIRT_BASE, KERNEL_VIRT_OFFSET,
+};
+pub use user_stack::{UserStack, UserStackBuilder, UserStackError};
+
+// ============================================
+// 内存布局常量
+// ============================================
+
+/// 页大小：4KB（RISC-V 标准页大小）
+pub const PAGE_SIZE: usize = 4096;
+
+/// DRAM 物理内存起始地址
+pub const MEMORY_START: usize = 0x8000_0000;
+
+/// 设备树没有给出内存大小时使用的 DRAM 结束地址（128MB，QEMU virt 的默认值）
+pub const MEMORY_END: usize = 0x8800_0000;
+
+/// 支持的最大 DRAM 结束地址（1GB：`huge_kernel_map` 的大页窗口，固定页帧位图按它分配）
+pub const MAX_MEMORY_END: usize = MEMORY_START + 0x4000_0000;
+
+/// Sv39 高半部分的起始地址（内核保留，用户区域不能映射到这里）
+pub const KERNEL_SPACE_START: usize = 0xFFFF_FFC0_0000_0000;
+
+/// UART 设备基地址（MMIO）
+pub const UART_BASE: usize = 0x1000_0000;
+
+/// 每个页表包含的页表项数量（4KB / 8 字节）
+pub const ENTRIES_PER_TABLE: usize = 512;
+
+/// 2MB 大页（Level 1 叶子）大小
+pub const MEGAPAGE_SIZE: usize = PAGE_SIZE * ENTRIES_PER_TABLE;
+
+/// 1GB 大页（Level 2 叶子）大小
+pub const GIGAPAGE_SIZE: usize = MEGAPAGE_SIZE * ENTRIES_PER_TABLE;
+
+// ============================================
+// 物理地址 / 虚拟地址
+// ============================================
+
+/// 物理地址类型
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct PhysAddr(usize);
+
+impl PhysAddr {
+    /// 创建新的物理地址
+    pub const fn new(addr: usize) -> Self {
+        Self(addr)
+    }
+
+    /// 获取地址值
+    pub const fn as_usize(&self) -> usize {
+        self.0
+    }
+
+    /// 物理页号（PPN）
+    pub const fn page_number(&self) -> usize {
+        self.0 / PAGE_SIZE
+    }
+
+    /// 页内偏移
+    pub const fn page_offset(&self) -> usize {
+        self.0 % PAGE_SIZE
+    }
+}
+
+impl fmt::Debug for PhysAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PhysAddr({:#x})", self.0)
+    }
+}
+
+impl fmt::Display for PhysAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// 虚拟地址类型
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct VirtAddr(usize);
+
+impl VirtAddr {
+    /// 创建新的虚拟地址
+    pub const fn new(addr: usize) -> Self {
+        Self(addr)
+    }
+
+    /// 获取地址值
+    pub const fn as_usize(&self) -> usize {
+        self.0
+    }
+
+    /// 虚拟页号
+    pub const fn page_number(&self) -> usize {
+        self.0 / PAGE_SIZE
+    }
+
+    /// 页内偏移（低 12 位）
+    pub const fn page_offset(&self) -> usize {
+        self.0 % PAGE_SIZE
+    }
+
+    /// VPN[0]：位 12..21
+    pub const fn vpn0(&self) -> usize {
+        (self.0 >> 12) & 0x1FF
+    }
+
+    /// VPN[1]：位 21..30
+    pub const fn vpn1(&self) -> usize {
+        (self.0 >> 21) & 0x1FF
+    }
+
+    /// VPN[2]：位 30..39
+    pub const fn vpn2(&self) -> usize {
+        (self.0 >> 30) & 0x1FF
+    }
+
+    /// VPN[3]：位 39..48（只在 Sv48 中使用）
+    pub const fn vpn3(&self) -> usize {
+        (self.0 >> 39) & 0x1FF
+    }
+
+    /// 第 `index` 级页表的索引 VPN[index]
+    ///
+    /// # 说明
+    /// 各级索引的位置与模式无关，`mode` 只用于检查 `index` 不超过页表级数
+    pub fn vpn(&self, index: usize, mode: PagingMode) -> usize {
+        debug_assert!(index < mode.levels(), "VPN index out of range for {:?}", mode);
+        (self.0 >> (12 + 9 * index)) & 0x1FF
+    }
+}
+
+/// 分页模式
+///
+/// | 模式 | 级数 | 虚拟地址位数 | 根页表索引 |
+/// |------|------|--------------|------------|
+/// | Sv39 | 3    | 39           | VPN[2]     |
+/// | Sv48 | 4    | 48           | VPN[3]     |
+///
+/// 两种模式的页表项格式相同，Sv48 只是在 Sv39 的根页表之上多了一级
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagingMode {
+    Sv39,
+    Sv48,
+}
+
+impl PagingMode {
+    /// 页表级数
+    pub const fn levels(self) -> usize {
+        match self {
+            PagingMode::Sv39 => 3,
+            PagingMode::Sv48 => 4,
+        }
+    }
+
+    /// 虚拟地址的有效位数
+    pub const fn va_bits(self) -> usize {
+        12 + 9 * self.levels()
+    }
+
+    /// 对应的 satp.MODE
+    pub fn satp_mode(self) -> riscv::register::satp::Mode {
+        match self {
+            PagingMode::Sv39 => riscv::register::satp::Mode::Sv39,
+            PagingMode::Sv48 => riscv::register::satp::Mode::Sv48,
+        }
+    }
+
+    /// 当前 satp 使用的模式（Bare 和其他模式返回 `None`）
+    pub fn current() -> Option<PagingMode> {
+        use riscv::register::satp;
+
+        match satp::read().mode() {
+            satp::Mode::Sv39 => Some(PagingMode::Sv39),
+            satp::Mode::Sv48 => Some(PagingMode::Sv48),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for VirtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "VirtAddr({:#x})", self.0)
+    }
+}
+
+impl fmt::Display for VirtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// 为地址类型实现算术运算和对齐方法
+macro_rules! impl_addr_ops {
+    ($addr:ident) => {
+        impl $addr {
+            /// 向下对齐
+            ///
+            /// # 返回
+            /// - `Err`: `align` 不是 2 的幂
+            pub fn align_down(&self, align: usize) -> Result<Self, &'static str> {
+                if !align.is_power_of_two() {
+                    return Err("Alignment must be a power of two");
+                }
+                Ok(Self(self.0 & !(align - 1)))
+            }
+
+            /// 向上对齐
+            ///
+            /// # 返回
+            /// - `Err`: `align` 不是 2 的幂，或对齐后超出地址范围
+            pub fn align_up(&self, align: usize) -> Result<Self, &'static str> {
+                if !align.is_power_of_two() {
+                    return Err("Alignment must be a power of two");
+                }
+                self.0
+                    .checked_add(align - 1)
+                    .map(|addr| Self(addr & !(align - 1)))
+                    .ok_or("Address overflow")
+            }
+
+            /// 是否按 `align` 对齐（`align` 不是 2 的幂时返回 false）
+            pub fn is_aligned(&self, align: usize) -> bool {
+                align.is_power_of_two() && self.0 & (align - 1) == 0
+            }
+        }
+
+        impl core::ops::Add<usize> for $addr {
+            type Output = Self;
+
+            fn add(self, rhs: usize) -> Self {
+                Self(self.0 + rhs)
+            }
+        }
+
+        impl core::ops::Sub<usize> for $addr {
+            type Output = Self;
+
+            fn sub(self, rhs: usize) -> Self {
+                Self(self.0 - rhs)
+            }
+        }
+
+        /// 两个地址之间的字节数
+        impl core::ops::Sub<$addr> for $addr {
+            type Output = usize;
+
+            fn sub(self, rhs: $addr) -> usize {
+                self.0 - rhs.0
+            }
+        }
+    };
+}
+
+impl_addr_ops!(PhysAddr);
+impl_addr_ops!(VirtAddr);
+
+// ============================================
+// 物理页帧
+// ============================================
+
+/// 物理页帧（4KB）
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct PhysFrame {
+    /// 页帧号（PPN）
+    number: usize,
+}
+
+impl PhysFrame {
+    /// 从页帧号创建
+    pub const fn from_number(number: usize) -> Self {
+        Self { number }
+    }
+
+    /// 从物理地址创建（向下对齐）
+    pub const fn from_addr(addr: PhysAddr) -> Self {
+        Self {
+            number: addr.page_number(),
+        }
+    }
+
+    /// 获取页帧号
+    pub const fn number(&self) -> usize {
+        self.number
+    }
+
+    /// 获取页帧的起始物理地址
+    pub const fn start_address(&self) -> PhysAddr {
+        PhysAddr::new(self.number * PAGE_SIZE)
+    }
+}
+
+impl PhysFrame {
+    /// `[start, end)` 范围内的所有页帧
+    ///
+    /// # 说明
+    /// `start` 向下对齐，`end` 向上对齐；`start > end` 时为空
+    pub fn range(start: PhysAddr, end: PhysAddr) -> PageIter<PhysFrame> {
+        let first = start.page_number();
+        let last = end.as_usize().div_ceil(PAGE_SIZE);
+        PageIter::new(first, last.max(first), PhysFrame::from_number)
+    }
+}
+
+impl fmt::Debug for PhysFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PhysFrame(#{}, {:#x})", self.number, self.number * PAGE_SIZE)
+    }
+}
+
+// ============================================
+// 虚拟页
+// ============================================
+
+/// 虚拟页（4KB）
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Page {
+    /// 虚拟页号（VPN）
+    number: usize,
+}
+
+impl Page {
+    /// 从虚拟页号创建
+    pub const fn from_number(number: usize) -> Self {
+        Self { number }
+    }
+
+    /// 包含该虚拟地址的页
+    pub const fn containing_address(addr: VirtAddr) -> Self {
+        Self {
+            number: addr.page_number(),
+        }
+    }
+
+    /// 获取虚拟页号
+    pub const fn number(&self) -> usize {
+        self.number
+    }
+
+    /// 获取页的起始虚拟地址
+    pub const fn start_address(&self) -> VirtAddr {
+        VirtAddr::new(self.number * PAGE_SIZE)
+    }
+
+    /// 包含 `start` 到 `end`（含）之间地址的所有页
+    ///
+    /// # 说明
+    /// `start > end` 时为空
+    pub fn range_inclusive(start: VirtAddr, end: VirtAddr) -> PageIter<Page> {
+        let first = start.page_number();
+        if start > end {
+            return PageIter::new(first, first, Page::from_number);
+        }
+        PageIter::new(first, end.page_number() + 1, Page::from_number)
+    }
+}
+
+impl fmt::Debug for Page {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Page(#{}, {:#x})", self.number, self.number * PAGE_SIZE)
+    }
+}
+
+/// 按页号依次产生页 / 页帧的迭代器
+#[derive(Clone)]
+pub struct PageIter<T> {
+    /// 下一个页号
+    next: usize,
+    /// 结束页号（不包含）
+    end: usize,
+    /// 由页号构造元素
+    make: fn(usize) -> T,
+}
+
+impl<T> PageIter<T> {
+    fn new(next: usize, end: usize, make: fn(usize) -> T) -> Self {
+        PageIter { next, end, make }
+    }
+}
+
+impl<T> Iterator for PageIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next >= self.end {
+            return None;
+        }
+        let number = self.next;
+        self.next += 1;
+        Some((self.make)(number))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end.saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for PageIter<T> {}
+
+// ============================================
+// 物理帧分配器
+// ============================================
+
+/// 物理帧分配器接口
+///
+/// # 说明
+/// 页表和地址空间的代码只通过这个接口分配、释放页帧，
+/// 测试可以换成只交出固定几个页帧的分配器
+pub trait FrameAllocator {
+    /// 分配一个物理页帧（内存不足时返回 `None`）
+    fn allocate(&mut self) -> Option<PhysFrame>;
+
+    /// 释放一个物理页帧
+    fn deallocate(&mut self, frame: PhysFrame);
+}
+
+/// 帧分配器最多记录的保留区域数
+const MAX_RESERVED: usize = 8;
+
+/// 简单物理帧分配器
+///
+/// # 说明
+/// 用位图管理 [start, end) 范围内的页帧，每一位对应一个页帧（1 = 已分配）
+/// 位图本身存放在管理范围开头的几个页帧中，因此不依赖堆。
+/// `reserve` 登记的页帧在位图中置位，永远不会分配出去，也不计入统计
+///
+/// # 布局
+/// | 位图页帧 | 可分配页帧 ... |
+pub struct SimpleFrameAllocator {
+    /// 位图起始物理地址（通过 `bitmap()` 访问）
+    bitmap: *mut u64,
+    /// 第一个可分配的页帧号
+    start: usize,
+    /// 可管理范围的结束页帧号（不包含）
+    end: usize,
+    /// 下一次单帧分配开始搜索的页帧号
+    next: usize,
+    /// 当前已分配的页帧数
+    allocated: usize,
+    /// 已分配页帧数的历史最大值
+    peak_allocated: usize,
+    /// 保留的页帧号范围
+    reserved: [(usize, usize); MAX_RESERVED],
+    /// reserved 中已使用的个数
+    reserved_count: usize,
+    /// 保留的页帧数（不含管理范围之外的部分）
+    reserved_frames: usize,
+}
+
+/// 帧分配器使用统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameAllocatorStats {
+    /// 可分配的页帧总数（不含位图占用的页帧和保留的页帧）
+    pub total_frames: usize,
+    /// 已分配的页帧数
+    pub used_frames: usize,
+    /// 空闲页帧数
+    pub free_frames: usize,
+    /// 已分配页帧占用的字节数
+    pub bytes_used: usize,
+    /// 已分配页帧数的历史最大值
+    pub peak_allocated: usize,
+}
+
+// 位图只通过 &mut self 访问
+unsafe impl Send for SimpleFrameAllocator {}
+
+impl SimpleFrameAllocator {
+    /// 创建新的分配器
+    ///
+    /// # 参数
+    /// - `start`: 可用物理内存起始地址（向上对齐到页）
+    /// - `end`: 可用物理内存结束地址（不包含）
+    ///
+    /// # 说明
+    /// 范围开头的若干页帧用于存放位图，不参与分配
+    pub fn new(start: PhysAddr, end: PhysAddr) -> Self {
+        let first = start.as_usize().div_ceil(PAGE_SIZE);
+        let end = end.page_number().max(first);
+
+        // 每个位图页帧可以记录 PAGE_SIZE * 8 个页帧
+        let bitmap_frames = (end - first).div_ceil(PAGE_SIZE * 8);
+        let bitmap = (first * PAGE_SIZE) as *mut u64;
+        let start = (first + bitmap_frames).min(end);
+
+        let allocator = SimpleFrameAllocator {
+            bitmap,
+            start,
+            end,
+            next: start,
+            allocated: 0,
+            peak_allocated: 0,
+            reserved: [(0, 0); MAX_RESERVED],
+            reserved_count: 0,
+            reserved_frames: 0,
+        };
+
+        unsafe {
+            core::ptr::write_bytes(allocator.bitmap(), 0, allocator.bitmap_words());
+        }
+
+        allocator
+    }
+
+    /// 位图在内核中的地址（见 `phys_to_kvirt`）
+    fn bitmap(&self) -> *mut u64 {
+        phys_to_kvirt(PhysAddr::new(self.bitmap as usize)).as_usize() as *mut u64
+    }
+
+    /// 位图占用的 u64 个数
+    fn bitmap_words(&self) -> usize {
+        (self.end - self.start).div_ceil(64)
+    }
+
+    /// 页帧是否已分配
+    fn is_used(&self, number: usize) -> bool {
+        let index = number - self.start;
+        unsafe { *self.bitmap().add(index / 64) & (1 << (index % 64)) != 0 }
+    }
+
+    /// 设置页帧在位图中的位
+    ///
+    /// # 返回
+    /// 位是否改变了
+    fn set_bit(&mut self, number: usize, used: bool) -> bool {
+        if self.is_used(number) == used {
+            return false;
+        }
+
+        let index = number - self.start;
+        unsafe {
+            let word = self.bitmap().add(index / 64);
+            if used {
+                *word |= 1 << (index % 64);
+            } else {
+                *word &= !(1 << (index % 64));
+            }
+        }
+        true
+    }
+
+    /// 设置页帧的分配状态，同时维护统计
+    fn set_used(&mut self, number: usize, used: bool) {
+        if !self.set_bit(number, used) {
+            return;
+        }
+
+        if used {
+            self.allocated += 1;
+            self.peak_allocated = self.peak_allocated.max(self.allocated);
+        } else {
+            self.allocated -= 1;
+        }
+    }
+
+    /// 页帧是否在可分配范围内
+    fn contains(&self, number: usize) -> bool {
+        (self.start..self.end).contains(&number)
+    }
+
+    /// 页帧是否被保留
+    fn is_reserved(&self, number: usize) -> bool {
+        self.reserved[..self.reserved_count]
+            .iter()
+            .any(|&(start, end)| (start..end).contains(&number))
+    }
+
+    /// 保留一段物理内存，其中的页帧不再分配
+    ///
+    /// # 参数
+    /// - `range`: 物理地址范围，向外扩展到整页；管理范围之外的部分忽略
+    ///
+    /// # 返回
+    /// - `Err`: 还有分配出去的页帧（必须在分配之前保留），或保留区域已达上限
+    ///
+    /// # 用途
+    /// 内核映像、堆、设备树等启动时已经在使用的内存（见 `init`）
+    pub fn reserve(&mut self, range: ops::Range<PhysAddr>) -> Result<(), &'static str> {
+        if self.allocated > 0 {
+            return Err("Frames already allocated");
+        }
+        let first = range.start.page_number().max(self.start);
+        let end = range.end.as_usize().div_ceil(PAGE_SIZE).min(self.end);
+        if first >= end {
+            return Ok(());
+        }
+        if self.reserved_count == MAX_RESERVED {
+            return Err("Too many reserved ranges");
+        }
+        self.reserved[self.reserved_count] = (first, end);
+        self.reserved_count += 1;
+        for number in first..end {
+            if self.set_bit(number, true) {
+                self.reserved_frames += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// 分配一个物理页帧
+    ///
+    /// # 返回
+    /// - `Some(PhysFrame)`: 分配成功
+    /// - `None`: 内存不足
+    pub fn allocate(&mut self) -> Option<PhysFrame> {
+        if crate::fault_point!("frame_alloc") {
+            return None;
+        }
+        // 从 next 开始搜索，到末尾后回到开头
+        let total = self.end - self.start;
+        for i in 0..total {
+            let number = self.start + (self.next - self.start + i) % total;
+            if !self.is_used(number) {
+                self.set_used(number, true);
+                self.next = number + 1;
+                if self.next == self.end {
+                    self.next = self.start;
+                }
+                return Some(PhysFrame::from_number(number));
+            }
+        }
+        None
+    }
+
+    /// 释放一个物理页帧
+    ///
+    /// # 说明
+    /// 不在管理范围内的页帧和保留的页帧会被忽略
+    pub fn deallocate(&mut self, frame: PhysFrame) {
+        if self.contains(frame.number()) && !self.is_reserved(frame.number()) {
+            self.set_used(frame.number(), false);
+        }
+    }
+
+    /// 分配物理上连续的多个页帧
+    ///
+    /// # 参数
+    /// - `count`: 页帧数量
+    /// - `align`: 起始物理地址的对齐（字节，2 的幂，小于 4KB 按 4KB 处理）
+    ///
+    /// # 返回
+    /// - `Some(PhysFrame)`: 连续区域的第一个页帧
+    /// - `None`: 找不到满足条件的连续区域
+    ///
+    /// # 用途
+    /// DMA 缓冲区、virtio 队列等需要物理连续内存的场景
+    pub fn allocate_contiguous(&mut self, count: usize, align: usize) -> Option<PhysFrame> {
+        if count == 0 || !align.is_power_of_two() {
+            return None;
+        }
+        let align_frames = (align / PAGE_SIZE).max(1);
+
+        // 候选起点按物理页帧号对齐
+        let mut candidate = self.start.next_multiple_of(align_frames);
+        while candidate + count <= self.end {
+            match (candidate..candidate + count).find(|&n| self.is_used(n)) {
+                // 跳过已占用的页帧，从它之后的下一个对齐位置继续
+                Some(used) => candidate = (used + 1).next_multiple_of(align_frames),
+                None => {
+                    for number in candidate..candidate + count {
+                        self.set_used(number, true);
+                    }
+                    return Some(PhysFrame::from_number(candidate));
+                }
+            }
+        }
+        None
+    }
+
+    /// 获取使用统计
+    pub fn stats(&self) -> FrameAllocatorStats {
+        let total_frames = self.end - self.start - self.reserved_frames;
+        FrameAllocatorStats {
+            total_frames,
+            used_frames: self.allocated,
+            free_frames: total_frames - self.allocated,
+            bytes_used: self.allocated * PAGE_SIZE,
+            peak_allocated: self.peak_allocated,
+        }
+    }
+
+    /// 打印物理内存使用情况
+    ///
+    /// # 说明
+    /// 已释放回分配器的页帧计入空闲
+    pub fn print_stats(&self) {
+        let stats = self.stats();
+        let kib = |frames: usize| frames * PAGE_SIZE / 1024;
+
+        println!("┌──────────────────────────────────────────────┐");
+        println!("│ 物理内存使用情况                             │");
+        println!("├────────────┬──────────────┬──────────────────┤");
+        println!("│ 项目       │ 页帧数       │ 大小             │");
+        println!("├────────────┼──────────────┼──────────────────┤");
+        // 总数和空闲数随内核大小变化，规范化输出时省略
+        for (name, frames, volatile) in [
+            ("Total", stats.total_frames, true),
+            ("Used", stats.used_frames, false),
+            ("Free", stats.free_frames, true),
+            ("Peak", stats.peak_allocated, false),
+        ] {
+            if volatile {
+                println!(
+                    "│ {:<10} │ {:>12} │ {:>13} KB │",
+                    name,
+                    Elided(frames),
+                    Elided(kib(frames))
+                );
+            } else {
+                println!("│ {:<10} │ {:>12} │ {:>13} KB │", name, frames, kib(frames));
+            }
+        }
+        println!("└────────────┴──────────────┴──────────────────┘");
+    }
+
+    /// 释放 `allocate_contiguous` 分配的连续页帧
+    ///
+    /// # 参数
+    /// - `frame`: 连续区域的第一个页帧
+    /// - `count`: 页帧数量（与分配时相同）
+    pub fn deallocate_contiguous(&mut self, frame: PhysFrame, count: usize) {
+        for number in frame.number()..frame.number() + count {
+            self.deallocate(PhysFrame::from_number(number));
+        }
+    }
+}
+
+impl FrameAllocator for SimpleFrameAllocator {
+    fn allocate(&mut self) -> Option<PhysFrame> {
+        SimpleFrameAllocator::allocate(self)
+    }
+
+    fn deallocate(&mut self, frame: PhysFrame) {
+        SimpleFrameAllocator::deallocate(self, frame)
+    }
+}
+
+// ============================================
+// 页表项与页表
+// ============================================
+
+/// 页表项标志位
+///
+/// | 位 | 名称 | 说明 |
+/// |----|------|------|
+/// | 0  | V    | 有效 |
+/// | 1  | R    | 可读 |
+/// | 2  | W    | 可写 |
+/// | 3  | X    | 可执行 |
+/// | 4  | U    | 用户态可访问 |
+/// | 5  | G    | 全局映射 |
+/// | 6  | A    | 已访问 |
+/// | 7  | D    | 已修改 |
+/// | 8  | COW  | 写时复制（RSW 软件保留位，硬件忽略） |
+///
+/// # 示例
+/// ```ignore
+/// let flags = PteFlags::READ | PteFlags::WRITE;
+/// assert!(flags.contains(PteFlags::WRITE));
+/// let read_only = flags & !PteFlags::WRITE;
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct PteFlags(usize);
+
+impl PteFlags {
+    pub const VALID: PteFlags = PteFlags(1 << 0);
+    pub const READ: PteFlags = PteFlags(1 << 1);
+    pub const WRITE: PteFlags = PteFlags(1 << 2);
+    pub const EXECUTE: PteFlags = PteFlags(1 << 3);
+    pub const USER: PteFlags = PteFlags(1 << 4);
+    pub const GLOBAL: PteFlags = PteFlags(1 << 5);
+    pub const ACCESSED: PteFlags = PteFlags(1 << 6);
+    pub const DIRTY: PteFlags = PteFlags(1 << 7);
+    pub const COW: PteFlags = PteFlags(1 << 8);
+
+    /// 页表项中标志位占用的低 10 位（包括 RSW）
+    const MASK: usize = 0x3FF;
+
+    /// 空标志位
+    pub const fn empty() -> Self {
+        PteFlags(0)
+    }
+
+    /// 由原始值创建，忽略低 10 位以外的位
+    pub const fn from_bits_truncate(bits: usize) -> Self {
+        PteFlags(bits & Self::MASK)
+    }
+
+    /// 原始值
+    pub const fn bits(self) -> usize {
+        self.0
+    }
+
+    /// 并集（可在常量中使用）
+    pub const fn union(self, other: PteFlags) -> Self {
+        PteFlags(self.0 | other.0)
+    }
+
+    /// 是否为空
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// 是否包含 `other` 中的全部位
+    pub const fn contains(self, other: PteFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// 是否包含 `other` 中的任意一位
+    pub const fn intersects(self, other: PteFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// 置位
+    pub fn insert(&mut self, other: PteFlags) {
+        self.0 |= other.0;
+    }
+
+    /// 清位
+    pub fn remove(&mut self, other: PteFlags) {
+        self.0 &= !other.0;
+    }
+
+    /// 是否有效（V）
+    pub const fn is_valid(self) -> bool {
+        self.contains(Self::VALID)
+    }
+
+    /// 是否可读（R）
+    pub const fn is_readable(self) -> bool {
+        self.contains(Self::READ)
+    }
+
+    /// 是否可写（W）
+    pub const fn is_writable(self) -> bool {
+        self.contains(Self::WRITE)
+    }
+
+    /// 是否可执行（X）
+    pub const fn is_executable(self) -> bool {
+        self.contains(Self::EXECUTE)
+    }
+
+    /// 用户态是否可访问（U）
+    pub const fn is_user(self) -> bool {
+        self.contains(Self::USER)
+    }
+
+    /// 是否构成叶子（R/W/X 任一置位）
+    pub const fn is_leaf(self) -> bool {
+        self.intersects(Self::READ.union(Self::WRITE).union(Self::EXECUTE))
+    }
+}
+
+impl ops::BitOr for PteFlags {
+    type Output = PteFlags;
+
+    fn bitor(self, rhs: PteFlags) -> PteFlags {
+        self.union(rhs)
+    }
+}
+
+impl ops::BitOrAssign for PteFlags {
+    fn bitor_assign(&mut self, rhs: PteFlags) {
+        self.insert(rhs);
+    }
+}
+
+impl ops::BitAnd for PteFlags {
+    type Output = PteFlags;
+
+    fn bitand(self, rhs: PteFlags) -> PteFlags {
+        PteFlags(self.0 & rhs.0)
+    }
+}
+
+impl ops::BitAndAssign for PteFlags {
+    fn bitand_assign(&mut self, rhs: PteFlags) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl ops::Not for PteFlags {
+    type Output = PteFlags;
+
+    /// 补集（只在低 10 位内取反）
+    fn not(self) -> PteFlags {
+        PteFlags::from_bits_truncate(!self.0)
+    }
+}
+
+impl fmt::Debug for PteFlags {
+    /// 按 V R W X U G A D 的顺序打印，未置位的写作 `-`，写时复制页面追加 COW
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [(PteFlags, char); 8] = [
+            (PteFlags::VALID, 'V'),
+            (PteFlags::READ, 'R'),
+            (PteFlags::WRITE, 'W'),
+            (PteFlags::EXECUTE, 'X'),
+            (PteFlags::USER, 'U'),
+            (PteFlags::GLOBAL, 'G'),
+            (PteFlags::ACCESSED, 'A'),
+            (PteFlags::DIRTY, 'D'),
+        ];
+
+        for (i, (flag, name)) in NAMES.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            let c = if self.contains(*flag) { *name } else { '-' };
+            write!(f, "{}", c)?;
+        }
+        if self.contains(PteFlags::COW) {
+            f.write_str(" COW")?;
+        }
+        Ok(())
+    }
+}
+
+/// 页表项（64 位）
+///
+/// | 63..54 保留 | 53..10 PPN | 9..8 RSW | 7..0 标志位 |
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct PageTableEntry(usize);
+
+impl PageTableEntry {
+    /// PPN 字段掩码（44 位）
+    const PPN_MASK: usize = (1 << 44) - 1;
+
+    /// 创建空页表项
+    pub const fn new() -> Self {
+        PageTableEntry(0)
+    }
+
+    /// 设置页表项
+    ///
+    /// # 参数
+    /// - `ppn`: 物理页号
+    /// - `flags`: 标志位
+    pub fn set(&mut self, ppn: usize, flags: PteFlags) {
+        self.0 = ((ppn & Self::PPN_MASK) << 10) | flags.bits();
+    }
+
+    /// 获取原始值
+    pub const fn bits(&self) -> usize {
+        self.0
+    }
+
+    /// 获取标志位（低 10 位）
+    pub const fn flags(&self) -> PteFlags {
+        PteFlags::from_bits_truncate(self.0)
+    }
+
+    /// 获取物理页号
+    pub const fn ppn(&self) -> usize {
+        (self.0 >> 10) & Self::PPN_MASK
+    }
+
+    /// 获取指向的物理地址
+    pub const fn phys_addr(&self) -> PhysAddr {
+        PhysAddr::new(self.ppn() << 12)
+    }
+
+    /// 是否有效（V=1）
+    pub const fn is_valid(&self) -> bool {
+        self.flags().is_valid()
+    }
+
+    /// 是否为叶子节点（R/W/X 任一置位）
+    ///
+    /// # 说明
+    /// 非叶子节点的 R/W/X 全为 0，指向下一级页表
+    pub const fn is_leaf(&self) -> bool {
+        self.flags().is_leaf()
+    }
+}
+
+impl fmt::Debug for PageTableEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PTE {{ ppn: {:#x}, flags: {:?} }}",
+            self.ppn(),
+            self.flags()
+        )
+    }
+}
+
+/// 页表（一个 4KB 页帧，包含 512 个页表项）
+#[repr(C, align(4096))]
+pub struct PageTable {
+    entries: [PageTableEntry; ENTRIES_PER_TABLE],
+}
+
+impl PageTable {
+    /// 清空所有页表项
+    pub fn zero(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = PageTableEntry::new();
+        }
+    }
+
+    /// 获取页表项
+    pub fn get_entry(&self, index: usize) -> &PageTableEntry {
+        &self.entries[index]
+    }
+
+    /// 获取页表项（可变）
+    pub fn get_entry_mut(&mut self, index: usize) -> &mut PageTableEntry {
+        &mut self.entries[index]
+    }
+
+    /// 是否没有任何有效的页表项
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(|entry| !entry.is_valid())
+    }
+}
+
+// ============================================
+// DRAM 大小
+// ============================================
+
+/// DRAM 结束地址（`set_dram_from` 按设备树更新）
+static DRAM_END: AtomicUsize = AtomicUsize::new(MEMORY_END);
+
+/// DRAM 结束地址（不包含）
+pub fn memory_end() -> usize {
+    DRAM_END.load(Ordering::Relaxed)
+}
+
+/// 崩溃记录所在的物理页（DRAM 最后一页，不交给帧分配器，见 crashlog）
+pub fn crash_record_addr() -> usize {
+    memory_end() - PAGE_SIZE
+}
+
+/// 设备树中 `device_type = "memory"` 的节点给出的 DRAM 范围
+///
+/// # 返回
+/// - `Some((start, end))`: 从 `MEMORY_START` 开始的范围，结束地址按页对齐，
+///   超过 `MAX_MEMORY_END` 的部分截掉
+/// - `None`: 没有内存节点，或不是从 `MEMORY_START` 开始（内核链接在那里）
+pub fn dram_range(fdt: &crate::device::Fdt) -> Option<(usize, usize)> {
+    let (start, size) = fdt
+        .children("/")
+        .find(|node| node.prop_str("device_type") == Some("memory"))?
+        .reg()?;
+    let end = start.checked_add(size)?.min(MAX_MEMORY_END) / PAGE_SIZE * PAGE_SIZE;
+    (start == MEMORY_START && end > kernel_end_addr()).then_some((start, end))
+}
+
+/// 按设备树设置 DRAM 大小
+///
+/// # 返回
+/// - `Some(end)`: 新的结束地址
+/// - `None`: 设备树没有给出可用的范围（见 `dram_range`），或帧分配器已经建立，
+///   保持原来的值
+///
+/// # 说明
+/// 在 `init` 之前调用；崩溃记录随之移到新的最后一页
+pub fn set_dram_from(fdt: &crate::device::Fdt) -> Option<usize> {
+    if frames_claimed() {
+        return None;
+    }
+    let (_, end) = dram_range(fdt)?;
+    DRAM_END.store(end, Ordering::Relaxed);
+    Some(end)
+}
+
+// ============================================
+// 内存管理器
+// ============================================
+
+/// 内存管理器
+pub struct MemoryManager {
+    /// 物理帧分配器
+    pub frame_allocator: SimpleFrameAllocator,
+}
+
+impl MemoryManager {
+    /// 打印物理内存使用情况
+    pub fn print_memory_usage(&self) {
+        self.frame_allocator.print_stats();
+    }
+}
+
+/// 获取内核起始地址（由链接器脚本导出）
+pub fn kernel_start_addr() -> usize {
+    extern "C" {
+        static kernel_start: u8;
+    }
+    unsafe { &kernel_start as *const u8 as usize }
+}
+
+/// 获取内核结束地址（由链接器脚本导出）
+pub fn kernel_end_addr() -> usize {
+    extern "C" {
+        static kernel_end: u8;
+    }
+    unsafe { &kernel_end as *const u8 as usize }
+}
+
+/// 获取内核栈的最低地址（由链接器脚本导出，栈从 stack_end 向下增长）
+pub fn kernel_stack_bottom() -> usize {
+    extern "C" {
+        static stack_start: u8;
+    }
+    unsafe { &stack_start as *const u8 as usize }
+}
+
+/// `init` 是否已经把内核之后的物理内存交给了帧分配器
+static FRAMES_CLAIMED: AtomicBool = AtomicBool::new(false);
+
+/// 内核之后的物理内存是否已经交给帧分配器
+///
+/// # 说明
+/// 内置测试（`selftest`）的帧分配器使用同一段内存，之后不能再运行
+pub fn frames_claimed() -> bool {
+    FRAMES_CLAIMED.load(Ordering::Relaxed)
+}
+
+/// 初始化内存管理
+///
+/// # 参数
+/// - `reserved`: 启动时已经在使用的物理内存（内核映像、堆、设备树等）
+///
+/// # 说明
+/// 内核之后直到崩溃记录页的物理内存交给帧分配器，`reserved` 中的页帧除外
+/// （见 `boot_frame_allocator`）
+pub fn init(reserved: &[ops::Range<PhysAddr>]) -> MemoryManager {
+    FRAMES_CLAIMED.store(true, Ordering::Relaxed);
+    let frame_allocator = boot_frame_allocator(
+        kernel_end_addr(),
+        PhysAddr::new(crash_record_addr()),
+        reserved,
+    )
+    .expect("failed to reserve boot memory");
+
+    klog!(
+        "[MEMORY] Frame allocator: {:#x} - {:#x} ({} frames)",
+        frame_allocator.start * PAGE_SIZE,
+        crash_record_addr(),
+        frame_allocator.end - frame_allocator.start
+    );
+
+    MemoryManager { frame_allocator }
+}
+
+/// 把地址范围转换为物理地址范围（用于 `init` 的保留区域）
+pub fn phys_range(range: ops::Range<usize>) -> ops::Range<PhysAddr> {
+    PhysAddr::new(range.start)..PhysAddr::new(range.end)
+}
+
+/// 创建管理 [start, end) 的帧分配器，并保留 `reserved` 中的内存
+///
+/// # 说明
+/// 位图放在管理范围的开头，因此起点先越过与位图重叠的保留区域
+/// （例如紧跟在内核之后的堆），位图不会覆盖正在使用的内存
+pub fn boot_frame_allocator(
+    start: usize,
+    end: PhysAddr,
+    reserved: &[ops::Range<PhysAddr>],
+) -> Result<SimpleFrameAllocator, &'static str> {
+    let mut start = start.next_multiple_of(PAGE_SIZE);
+    let overlaps_bitmap = |start: usize, range: &ops::Range<PhysAddr>| {
+        let frames = end.page_number().saturating_sub(start / PAGE_SIZE).div_ceil(PAGE_SIZE * 8);
+        range.start.as_usize() < start + frames * PAGE_SIZE && start < range.end.as_usize()
+    };
+    while let Some(range) = reserved.iter().find(|range| overlaps_bitmap(start, range)) {
+        start = range.end.as_usize().next_multiple_of(PAGE_SIZE);
+    }
+
+    let mut allocator = SimpleFrameAllocator::new(PhysAddr::new(start), end);
+    for range in reserved {
+        allocator.reserve(range.clone())?;
+    }
+    Ok(allocator)
+}
+
+/// 地址转换（使用当前 satp 指向的页表）
+///
+/// # 参数
+/// - `vaddr`: 要转换的虚拟地址
+///
+/// # 返回
+/// - `Some(PhysAddr)`: 转换结果（包含页内偏移）；分页未开启时为恒等转换
+/// - `None`: 地址未映射
+///
+/// # 说明
+/// 页表通过物理地址直接访问，因此内核必须恒等映射页表所在的物理内存
+/// （`create_kernel_address_space` 恒等映射了全部 DRAM）
+pub fn translate_addr(vaddr: VirtAddr) -> Option<PhysAddr> {
+    paging::translate_addr(vaddr)
+}
+
+/// 创建内核地址空间
+///
+/// # 功能
+/// - 恒等映射全部物理内存（内核代码、数据、栈、堆）；
+///   启用 `huge_kernel_map` 时使用一个 1GB 大页映射 RAM 所在的 1GB 窗口
+/// - 内核栈的最低一页不映射，作为保护页（栈溢出时报告 STACK OVERFLOW）；
+///   1GB 大页无法留出空洞，`huge_kernel_map` 下没有保护页
+/// - 恒等映射 UART 设备
+/// - 把跳板页映射到 `TRAMPOLINE`（与用户地址空间相同的位置）
+pub fn create_kernel_address_space(
+    allocator: &mut SimpleFrameAllocator,
+) -> Result<AddressSpace, &'static str> {
+    let mut addr_space = AddressSpace::new(allocator)?;
+
+    klog!(
+        "[MEMORY] Mapping kernel region: {:#x} - {:#x}",
+        MEMORY_START,
+        memory_end()
+    );
+    if cfg!(feature = "huge_kernel_map") {
+        // 一个 1GB 大页覆盖整个 RAM 窗口，不需要额外的页表
+        addr_space.map_gigapage_identity(
+            VirtAddr::new(MEMORY_START),
+            MemoryAreaType::Kernel,
+            allocator,
+        )?;
+    } else {
+        // 保护页两侧分别映射
+        let guard = VirtAddr::new(kernel_stack_bottom());
+        let above = guard + PAGE_SIZE;
+        addr_space.map_region_identity(
+            VirtAddr::new(MEMORY_START),
+            guard.as_usize() - MEMORY_START,
+            MemoryAreaType::Kernel,
+            allocator,
+        )?;
+        addr_space.map_region_identity(
+            above,
+            memory_end() - above.as_usize(),
+            MemoryAreaType::Kernel,
+            allocator,
+        )?;
+        addr_space.add_guard_page(guard, "kernel stack")?;
+    }
+
+    addr_space.map_region_identity(
+        VirtAddr::new(UART_BASE),
+        PAGE_SIZE,
+        MemoryAreaType::Mmio,
+        allocator,
+    )?;
+    addr_space.map_trampoline(allocator)?;
+
+    Ok(addr_space)
+}
+
+// ============================================
+// 物理地址探测
+// ============================================
+
+/// 已知可以读取的物理地址范围（起始，结束，名称）
+///
+/// DRAM 的大小来自设备树（见 `set_dram_from`），设备是固定的 QEMU virt 布局
+pub fn readable_regions() -> [(usize, usize, &'static str); 2] {
+    [
+        (MEMORY_START, memory_end(), "DRAM"),
+        (UART_BASE, UART_BASE + 0x100, "UART"),
+    ]
+}
+
+/// 检查物理地址是否可以安全读取
+///
+/// # 说明
+/// 只按已知的内存 / 设备范围判断，不实际访问该地址。
+/// 注意读取设备寄存器可能有副作用（例如读 UART 数据寄存器会取走一个输入字符）
+pub fn probe_readable(paddr: PhysAddr) -> bool {
+    let addr = paddr.as_usize();
+    readable_regions()
+        .iter()
+        .any(|&(start, end, _)| (start..end).contains(&addr))
+}
+
+/// 以十六进制 + ASCII 形式打印一段物理内存
+///
+/// # 说明
+/// 每行 16 字节；只读取 DRAM，其他字节显示为 `??`：不会触发访问异常，
+/// 也不会读设备寄存器（MMIO 的读取有副作用，例如取走 UART 的输入字符、清除状态位）
+pub fn hexdump(paddr: PhysAddr, len: usize) {
+    let start = paddr.as_usize();
+    let end = start.saturating_add(len);
+
+    for line in (start..end).step_by(16) {
+        let mut hex = [[b' '; 3]; 16];
+        let mut ascii = [b' '; 16];
+        let count = (end - line).min(16);
+
+        for i in 0..count {
+            let addr = PhysAddr::new(line + i);
+            if !(MEMORY_START..memory_end()).contains(&addr.as_usize()) {
+                hex[i] = *b"?? ";
+                ascii[i] = b'?';
+                continue;
+            }
+            let byte = unsafe {
+                core::ptr::read_volatile(phys_to_kvirt(addr).as_usize() as *const u8)
+            };
+            let digits = b"0123456789abcdef";
+            hex[i] = [digits[(byte >> 4) as usize], digits[(byte & 0xf) as usize], b' '];
+            ascii[i] = if byte.is_ascii_graphic() || byte == b' ' { byte } else { b'.' };
+        }
+
+        let hex = hex.as_flattened();
+        println!(
+            "{:#012x}: {} |{}|",
+            line,
+            core::str::from_utf8(hex).unwrap_or(""),
+            core::str::from_utf8(&ascii[..count]).unwrap_or("")
+        );
+    }
+}
+
+/// 测试用帧分配器
+///
+/// 从内核堆（`init_heap_simple` 放在内核结束地址之后）的末尾开始分配，
+/// 避免测试中分配的页帧覆盖堆内存
+#[cfg(any(test, feature = "builtin_tests"))]
+pub(crate) fn test_frame_allocator() -> SimpleFrameAllocator {
+    let heap_end = crate::allocator::simple_heap_range(kernel_end_addr()).end;
+    SimpleFrameAllocator::new(PhysAddr::new(heap_end), PhysAddr::new(crash_record_addr()))
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use riscv::register::satp;
+
+    /// 建立一个恒等映射全部内存和 UART 的根页表，便于在测试中开启分页
+    fn identity_mapped_root(allocator: &mut SimpleFrameAllocator) -> &'static mut PageTable {
+        let frame = allocator.allocate().expect("Out of memory");
+        let root = unsafe { &mut *(frame.start_address().as_usize() as *mut PageTable) };
+        root.zero();
+
+        let flags = PteFlags::READ | PteFlags::WRITE | PteFlags::EXECUTE;
+        for addr in (MEMORY_START..memory_end()).step_by(PAGE_SIZE) {
+            map_page(root, VirtAddr::new(addr), PhysAddr::new(addr), flags, allocator)
+                .expect("identity mapping failed");
+        }
+        map_page(
+            root,
+            VirtAddr::new(UART_BASE),
+            PhysAddr::new(UART_BASE),
+            PteFlags::READ | PteFlags::WRITE,
+            allocator,
+        )
+        .expect("UART mapping failed");
+
+        root
+    }
+
+    #[test_case]
+    fn test_translate_addr_walks_satp_table() {
+        let mut allocator = test_frame_allocator();
+        let root = identity_mapped_root(&mut allocator);
+
+        let frame = allocator.allocate().expect("Out of memory");
+        let vaddr = VirtAddr::new(0x4000_0000);
+        map_page(
+            root,
+            vaddr,
+            frame.start_address(),
+            PteFlags::READ | PteFlags::WRITE,
+            &mut allocator,
+        )
+        .expect("map_page failed");
+
+        let root_ppn = root as *const PageTable as usize >> 12;
+        let old_satp = satp::read();
+        let (mapped, mapped_paging, unmapped) = unsafe {
+            satp::set(satp::Mode::Sv39, 0, root_ppn);
+            tlb::flush_all();
+
+            let mapped = translate_addr(VirtAddr::new(vaddr.as_usize() + 0x123));
+            let mapped_paging = paging::translate_addr(VirtAddr::new(vaddr.as_usize() + 0x123));
+            let unmapped = translate_addr(VirtAddr::new(0x5000_0000));
+
+            satp::set(old_satp.mode(), old_satp.asid(), old_satp.ppn());
+            tlb::flush_all();
+            (mapped, mapped_paging, unmapped)
+        };
+
+        assert_eq!(
+            mapped,
+            Some(PhysAddr::new(frame.start_address().as_usize() + 0x123))
+        );
+        assert_eq!(mapped, mapped_paging);
+        let root_paddr = PhysAddr::new(root_ppn << 12);
+        assert_eq!(
+            mapped,
+            walk_page_table(root_paddr, VirtAddr::new(vaddr.as_usize() + 0x123))
+        );
+        assert_eq!(unmapped, None);
+    }
+
+    #[test_case]
+    fn test_translate_addr_bare_is_identity() {
+        let old_satp = satp::read();
+        let (result, result_paging) = unsafe {
+            satp::set(satp::Mode::Bare, 0, 0);
+            tlb::flush_all();
+
+            let vaddr = VirtAddr::new(0x5000_0123);
+            let result = (translate_addr(vaddr), paging::translate_addr(vaddr));
+
+            satp::set(old_satp.mode(), old_satp.asid(), old_satp.ppn());
+            tlb::flush_all();
+            result
+        };
+
+        assert_eq!(result, Some(PhysAddr::new(0x5000_0123)));
+        assert_eq!(result, result_paging);
+    }
+
+    #[cfg(not(feature = "huge_kernel_map"))]
+    #[test_case]
+    fn test_kernel_space_uses_2mb_pages() {
+        let mut allocator = test_frame_allocator();
+        let free_before = allocator.stats().free_frames;
+        let mut space = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+
+        // 根页表 + DRAM 的一级页表 + 内核栈保护页所在 2MB 的零级页表
+        // + UART 和跳板页各自的一级、零级页表
+        assert_eq!(free_before - allocator.stats().free_frames, 7);
+
+        // 内核栈的最低一页是保护页
+        let guard = VirtAddr::new(kernel_stack_bottom());
+        assert_eq!(walk_page_table(space.page_table_paddr(), guard), None);
+        assert_eq!(space.guard_at(guard + 8).map(|guard| guard.stack), Some("kernel stack"));
+        assert!(space.is_mapped(guard + PAGE_SIZE) && space.is_mapped(guard - 8));
+
+        let vaddr = VirtAddr::new(MEMORY_START + 0x12_3456);
+        let root_paddr = space.page_table_paddr();
+        let output = crate::console::capture();
+        assert_eq!(
+            walk_page_table_verbose(root_paddr, vaddr),
+            Some(PhysAddr::new(vaddr.as_usize()))
+        );
+        let walk = output.take();
+        assert!(walk.contains("│ 页表遍历: 0x80123456\n"));
+        assert!(walk.contains("│ → 叶子节点（2MB 大页）\n"));
+        assert!(walk.contains("│ 结果: 0x80123456 → 0x80123456\n"));
+        assert_eq!(walk.matches("│ Level ").count(), 2);
+
+        // 内核区域通过 Level 1 叶子（2MB 大页）映射
+        let root = space.page_table();
+        let level1 = root.get_entry(vaddr.vpn2()).phys_addr().as_usize() as *const PageTable;
+        assert!(unsafe { &*level1 }.get_entry(vaddr.vpn1()).is_leaf());
+    }
+
+    #[cfg(feature = "huge_kernel_map")]
+    #[test_case]
+    fn test_kernel_space_uses_gigapage() {
+        let mut allocator = test_frame_allocator();
+        let free_before = allocator.stats().free_frames;
+        let space = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+
+        // 只有根页表，以及 UART 和跳板页各自的一级、零级页表
+        assert_eq!(free_before - allocator.stats().free_frames, 5);
+        let vaddr = VirtAddr::new(memory_end() - 8);
+        assert_eq!(
+            walk_page_table(space.page_table_paddr(), vaddr),
+            Some(PhysAddr::new(vaddr.as_usize()))
+        );
+        assert_eq!(space.areas()[0].page_count(), GIGAPAGE_SIZE / PAGE_SIZE);
+    }
+
+    #[test_case]
+    fn test_dram_range_from_device_tree() {
+        use crate::device::{Fdt, SAMPLE_DTB};
+        use alloc::vec::Vec;
+
+        // 示例设备树：/memory@80000000 的 reg = <0x0 0x80000000 0x0 0x8000000>
+        let fdt = Fdt::parse(SAMPLE_DTB).expect("parse failed");
+        assert_eq!(dram_range(&fdt), Some((MEMORY_START, MEMORY_END)));
+
+        // 改写 reg 中的起始地址和大小，相当于 QEMU 的 -m
+        let reg = [0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x08, 0, 0, 0];
+        let offset = SAMPLE_DTB.windows(16).position(|w| w == reg).expect("reg missing");
+        let with_reg = |start: u64, size: u64| {
+            let mut dtb: Vec<u8> = SAMPLE_DTB.to_vec();
+            dtb[offset..offset + 8].copy_from_slice(&start.to_be_bytes());
+            dtb[offset + 8..offset + 16].copy_from_slice(&size.to_be_bytes());
+            let fdt = Fdt::parse(&dtb).expect("parse failed");
+            dram_range(&fdt)
+        };
+        assert_eq!(with_reg(0x8000_0000, 256 << 20), Some((MEMORY_START, 0x9000_0000)));
+        assert_eq!(with_reg(0x8000_0000, 64 << 20), Some((MEMORY_START, 0x8400_0000)));
+        // 超过 1GB 的部分截掉，不足一页的尾巴舍去
+        assert_eq!(with_reg(0x8000_0000, 4 << 30), Some((MEMORY_START, MAX_MEMORY_END)));
+        assert_eq!(with_reg(0x8000_0000, (64 << 20) + 100), Some((MEMORY_START, 0x8400_0000)));
+        // 不从内核所在的地址开始，或装不下内核
+        assert_eq!(with_reg(0x4000_0000, 256 << 20), None);
+        assert_eq!(with_reg(0x8000_0000, PAGE_SIZE as u64), None);
+    }
+
+    #[test_case]
+    fn test_probe_readable() {
+        assert!(probe_readable(PhysAddr::new(kernel_end_addr())));
+        assert!(probe_readable(PhysAddr::new(memory_end() - 1)));
+        // DRAM 之后和设备之间的空洞
+        assert!(!probe_readable(PhysAddr::new(memory_end())));
+        assert!(!probe_readable(PhysAddr::new(0x5000_0000)));
+
+        // 跨过 DRAM 末尾的 hexdump 不会触发异常
+        hexdump(PhysAddr::new(memory_end() - 8), 16);
+    }
+
+    #[test_case]
+    fn test_hexdump_skips_mmio() {
+        // UART 寄存器可以探测到，但 hexdump 不读取
+        assert!(probe_readable(PhysAddr::new(UART_BASE)));
+        let output = crate::console::capture();
+        hexdump(PhysAddr::new(UART_BASE), 8);
+        let line = output.take();
+        assert!(line.contains("?? ?? ?? ?? ?? ?? ?? ??"), "{}", line);
+
+        // 地址空间末尾：长度截断到 usize::MAX，不会溢出
+        let output = crate::console::capture();
+        hexdump(PhysAddr::new(usize::MAX - 7), 16);
+        assert_eq!(output.take().lines().count(), 1);
+    }
+
+    #[test_case]
+    fn test_address_arithmetic_and_alignment() {
+        let vaddr = VirtAddr::new(0x1000_1234);
+        assert_eq!(vaddr + 0x10, VirtAddr::new(0x1000_1244));
+        assert_eq!(vaddr - 0x234, VirtAddr::new(0x1000_1000));
+        assert_eq!(vaddr - VirtAddr::new(0x1000_0000), 0x1234);
+        assert_eq!(vaddr.align_down(PAGE_SIZE), Ok(VirtAddr::new(0x1000_1000)));
+        assert_eq!(vaddr.align_up(PAGE_SIZE), Ok(VirtAddr::new(0x1000_2000)));
+        assert!(!vaddr.is_aligned(PAGE_SIZE));
+        assert!(VirtAddr::new(0x1000_2000).is_aligned(PAGE_SIZE));
+
+        // 已对齐的地址不变，包括 0
+        let paddr = PhysAddr::new(0x8020_0000);
+        assert_eq!(paddr.align_up(MEGAPAGE_SIZE), Ok(paddr));
+        assert_eq!(PhysAddr::new(0).align_up(PAGE_SIZE), Ok(PhysAddr::new(0)));
+
+        // usize::MAX 附近：向下对齐可以，向上对齐溢出
+        let top = PhysAddr::new(usize::MAX - 5);
+        assert_eq!(top.align_down(PAGE_SIZE), Ok(PhysAddr::new(usize::MAX & !(PAGE_SIZE - 1))));
+        assert_eq!(top.align_up(PAGE_SIZE), Err("Address overflow"));
+        assert_eq!(PhysAddr::new(usize::MAX).align_up(1), Ok(PhysAddr::new(usize::MAX)));
+
+        // 非 2 的幂的对齐被拒绝
+        assert_eq!(vaddr.align_up(3000), Err("Alignment must be a power of two"));
+        assert_eq!(paddr.align_down(0), Err("Alignment must be a power of two"));
+        assert!(!PhysAddr::new(0x3000).is_aligned(0x3000));
+    }
+
+    #[test_case]
+    fn test_page_and_frame_ranges() {
+        // 跨 3 页：0x1000_0ff0 ..= 0x1000_2000
+        let pages = Page::range_inclusive(VirtAddr::new(0x1000_0ff0), VirtAddr::new(0x1000_2000));
+        assert_eq!(pages.len(), 3);
+        let starts: [usize; 3] = {
+            let mut iter = pages.map(|page| page.start_address().as_usize());
+            core::array::from_fn(|_| iter.next().unwrap())
+        };
+        assert_eq!(starts, [0x1000_0000, 0x1000_1000, 0x1000_2000]);
+
+        let mut frames = PhysFrame::range(PhysAddr::new(0x8100_0000), PhysAddr::new(0x8100_3000));
+        assert_eq!(frames.size_hint(), (3, Some(3)));
+        assert_eq!(frames.next(), Some(PhysFrame::from_number(0x81000)));
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames.last(), Some(PhysFrame::from_number(0x81002)));
+
+        // start > end 时为空，不会 panic
+        let empty = Page::range_inclusive(VirtAddr::new(0x2000), VirtAddr::new(0x1000));
+        assert_eq!(empty.len(), 0);
+        let mut empty = PhysFrame::range(PhysAddr::new(0x8100_3000), PhysAddr::new(0x8100_0000));
+        assert_eq!(empty.size_hint(), (0, Some(0)));
+        assert_eq!(empty.next(), None);
+    }
+
+    #[test_case]
+    fn test_allocate_contiguous() {
+        let mut allocator = test_frame_allocator();
+        const COUNT: usize = 16;
+        const ALIGN: usize = 64 * 1024;
+
+        // 先打乱对齐：占用一个单独的页帧
+        let single = allocator.allocate().expect("Out of memory");
+
+        let first = allocator
+            .allocate_contiguous(COUNT, ALIGN)
+            .expect("allocate_contiguous failed");
+        let base = first.start_address().as_usize();
+        assert_eq!(base % ALIGN, 0);
+        assert_ne!(first, single);
+
+        // 在整个区域写入并校验数据
+        let words = COUNT * PAGE_SIZE / 8;
+        let region = unsafe { core::slice::from_raw_parts_mut(base as *mut u64, words) };
+        for (i, word) in region.iter_mut().enumerate() {
+            *word = (base + i * 8) as u64 ^ 0xdead_beef;
+        }
+        for (i, word) in region.iter().enumerate() {
+            assert_eq!(*word, (base + i * 8) as u64 ^ 0xdead_beef);
+        }
+
+        // 单帧分配不会落在连续区域内
+        let next = allocator.allocate().expect("Out of memory");
+        assert!(!(first.number()..first.number() + COUNT).contains(&next.number()));
+
+        // 释放后可以重新分配到同一段
+        allocator.deallocate_contiguous(first, COUNT);
+        assert_eq!(allocator.allocate_contiguous(COUNT, ALIGN), Some(first));
+        assert_eq!(allocator.allocate_contiguous(0, ALIGN), None);
+        assert_eq!(allocator.allocate_contiguous(1, 3 * PAGE_SIZE), None);
+    }
+
+    #[test_case]
+    fn test_frame_allocator_stats() {
+        let mut allocator = test_frame_allocator();
+        let initial = allocator.stats();
+        assert_eq!(initial.used_frames, 0);
+        assert_eq!(initial.free_frames, initial.total_frames);
+
+        let a = allocator.allocate().expect("Out of memory");
+        let b = allocator.allocate().expect("Out of memory");
+        let run = allocator.allocate_contiguous(4, PAGE_SIZE).expect("Out of memory");
+        assert_eq!(allocator.stats().used_frames, 6);
+
+        allocator.deallocate(a);
+        allocator.deallocate_contiguous(run, 4);
+        // 重复释放不影响统计
+        allocator.deallocate(a);
+
+        let stats = allocator.stats();
+        assert_eq!(stats.used_frames, 1);
+        assert_eq!(stats.free_frames, stats.total_frames - 1);
+        assert_eq!(stats.peak_allocated, 6);
+
+        allocator.deallocate(b);
+        assert_eq!(allocator.stats().used_frames, 0);
+
+        let frames: alloc::vec::Vec<PhysFrame> = (0..10).filter_map(|_| allocator.allocate()).collect();
+        let stats = allocator.stats();
+        assert_eq!(stats.used_frames, 10);
+        assert_eq!(stats.bytes_used, 10 * PAGE_SIZE);
+        frames.into_iter().for_each(|frame| allocator.deallocate(frame));
+        assert_eq!(allocator.stats().bytes_used, 0);
+    }
+
+    #[test_case]
+    fn test_frame_allocator_skips_reserved_memory() {
+        let kernel_end = kernel_end_addr();
+        let heap = crate::allocator::simple_heap_range(kernel_end);
+        // 模拟的设备树：堆之后 4MB 处，结尾不按页对齐
+        let dtb = heap.end + 4 * 1024 * 1024;
+        let reserved = [
+            phys_range(kernel_start_addr()..kernel_end),
+            phys_range(heap.clone()),
+            phys_range(dtb..dtb + 3 * PAGE_SIZE + 100),
+        ];
+        let end = PhysAddr::new(crash_record_addr());
+        let mut allocator = boot_frame_allocator(kernel_end, end, &reserved).unwrap();
+
+        // 位图和可分配的页帧都在堆之后
+        assert!(allocator.bitmap as usize >= heap.end);
+        let stats = allocator.stats();
+        assert_eq!(stats.total_frames, allocator.end - allocator.start - 4);
+        assert_eq!(stats.free_frames, stats.total_frames);
+
+        // 分配到耗尽，没有一个页帧落在保留区域中
+        let heap_frames = heap.start / PAGE_SIZE..heap.end.div_ceil(PAGE_SIZE);
+        let dtb_frames = dtb / PAGE_SIZE..dtb / PAGE_SIZE + 4;
+        let mut count = 0;
+        while let Some(frame) = allocator.allocate() {
+            assert!(!heap_frames.contains(&frame.number()));
+            assert!(!dtb_frames.contains(&frame.number()));
+            count += 1;
+        }
+        assert_eq!(count, stats.total_frames);
+
+        // 释放保留的页帧被忽略；分配之后不能再保留
+        allocator.deallocate(PhysFrame::from_number(dtb_frames.start));
+        assert_eq!(allocator.allocate(), None);
+        let late = phys_range(dtb..dtb + PAGE_SIZE);
+        assert_eq!(allocator.reserve(late), Err("Frames already allocated"));
+    }
+
+    #[test_case]
+    fn test_pte_flags_algebra() {
+        use alloc::format;
+
+        let rw = PteFlags::READ | PteFlags::WRITE;
+        assert!(rw.contains(PteFlags::READ) && rw.is_writable());
+        assert!(!rw.contains(PteFlags::READ | PteFlags::EXECUTE));
+        assert!(rw.intersects(PteFlags::READ | PteFlags::EXECUTE));
+        assert!(rw.is_leaf() && !PteFlags::VALID.is_leaf());
+
+        // 去掉 W，只在低 10 位内取反
+        let read_only = rw & !PteFlags::WRITE;
+        assert_eq!(read_only, PteFlags::READ);
+        assert_eq!((!PteFlags::empty()).bits(), 0x3FF);
+
+        let mut flags = PteFlags::VALID | rw;
+        flags.insert(PteFlags::COW);
+        flags.remove(PteFlags::WRITE);
+        assert_eq!(flags, PteFlags::VALID | PteFlags::READ | PteFlags::COW);
+        assert_eq!(PteFlags::from_bits_truncate(1 << 10 | 0b11), PteFlags::VALID | PteFlags::READ);
+
+        // 页表项保存并返回同样的标志位
+        let mut pte = PageTableEntry::new();
+        let all = PteFlags::VALID | rw | PteFlags::USER | PteFlags::ACCESSED | PteFlags::DIRTY;
+        pte.set(0x8_0123, all);
+        assert_eq!(pte.flags(), all);
+        assert_eq!(pte.ppn(), 0x8_0123);
+
+        assert_eq!(format!("{:?}", all), "V R W - U - A D");
+        assert_eq!(format!("{:?}", flags), "V R - - - - - - COW");
+        assert_eq!(format!("{:?}", PteFlags::empty()), "- - - - - - - -");
+    }
+}
@@ -0,0 +1,1727 @@
+/*
+ * ============================================
+ * Sv39 页表管理
+ * ============================================
+ * 功能：页表遍历、页面映射与取消映射
+ *
+ * 三级页表结构：
+ * satp.PPN → 根页表（Level 2，VPN[2] 索引）
+ *          → 二级页表（Level 1，VPN[1] 索引）
+ *          → 一级页表（Level 0，VPN[0] 索引）
+ *          → 物理页帧 + 页内偏移
+ *
+ * 叶子节点可以出现在任意一级：
+ * - Level 2 叶子：1GB 大页
+ * - Level 1 叶子：2MB 大页
+ * - Level 0 叶子：4KB 页
+ *
+ * Sv48 在上面再加一级根页表（Level 3，VPN[3] 索引，叶子为 512GB 页）。
+ * walk_page_table_mode / map_page_mode 按 PagingMode 的级数遍历，
+ * 其余函数只处理 Sv39
+ *
+ * 页表通过 phys_to_kvirt 得到的内核虚拟地址访问：进入高半部分之前就是物理地址本身，
+ * 之后是高半部分的别名（见 high_half）
+ * ============================================
+ */
+
+use super::high_half::{kvirt_to_phys, phys_to_kvirt};
+use super::replace::{pin_frame, unpin_frame};
+use super::tlb;
+use super::{
+    FrameAllocator, PageTable, PageTableEntry, PagingMode, PhysAddr, PhysFrame, PteFlags,
+    VirtAddr, ENTRIES_PER_TABLE, GIGAPAGE_SIZE, MEGAPAGE_SIZE, PAGE_SIZE,
+};
+use crate::println;
+use alloc::collections::BTreeMap;
+use spin::Mutex;
+
+/// 页表页在内核中的虚拟地址
+///
+/// # 说明
+/// 测试中先查已安装的合成页表（见 `synthetic`），页表代码因此可以直接在普通数组上运行
+fn table_vaddr(paddr: PhysAddr) -> usize {
+    #[cfg(any(test, feature = "builtin_tests"))]
+    if let Some(vaddr) = super::synthetic::to_host(paddr) {
+        return vaddr;
+    }
+    phys_to_kvirt(paddr).as_usize()
+}
+
+/// 把页表的物理地址转换为引用
+unsafe fn table_ref(paddr: PhysAddr) -> &'static PageTable {
+    &*(table_vaddr(paddr) as *const PageTable)
+}
+
+/// 把页表的物理地址转换为可变引用
+unsafe fn table_mut(paddr: PhysAddr) -> &'static mut PageTable {
+    &mut *(table_vaddr(paddr) as *mut PageTable)
+}
+
+/// 分配一个清零的页表页
+///
+/// # 说明
+/// 页表页在分配时被固定，不会被页面置换换出
+pub(crate) fn alloc_table(
+    allocator: &mut dyn FrameAllocator,
+) -> Result<PhysFrame, &'static str> {
+    let frame = allocator.allocate().ok_or("Out of memory")?;
+    unsafe { table_mut(frame.start_address()) }.zero();
+    pin_frame(frame);
+    Ok(frame)
+}
+
+/// 释放页表页（取消固定）
+fn free_table(allocator: &mut dyn FrameAllocator, paddr: PhysAddr) {
+    let frame = PhysFrame::from_addr(paddr);
+    unpin_frame(frame);
+    allocator.deallocate(frame);
+}
+
+/// 取出虚拟地址在各级页表中的索引 [VPN[0], VPN[1], VPN[2]]
+fn vpns(vaddr: VirtAddr) -> [usize; 3] {
+    [vaddr.vpn0(), vaddr.vpn1(), vaddr.vpn2()]
+}
+
+/// 页表的物理地址
+fn table_addr(table: &PageTable) -> PhysAddr {
+    let vaddr = table as *const PageTable as usize;
+    #[cfg(any(test, feature = "builtin_tests"))]
+    if let Some(paddr) = super::synthetic::to_synthetic(vaddr) {
+        return paddr;
+    }
+    kvirt_to_phys(VirtAddr::new(vaddr))
+}
+
+/// 有效页表项在第 `level` 级是否合法（硬件遍历页表时遇到不合法的项产生页错误）
+///
+/// # 返回
+/// - `Err`: 保留位（63..54）不为 0、零级页表中的非叶子、只写不读的叶子，
+///   或物理页号没有按大页对齐
+fn check_entry(pte: PageTableEntry, level: usize) -> Result<(), &'static str> {
+    if pte.bits() >> 54 != 0 {
+        return Err("Reserved PTE bits set");
+    }
+    if !pte.is_leaf() {
+        return match level {
+            0 => Err("Non-leaf entry at level 0"),
+            _ => Ok(()),
+        };
+    }
+    let flags = pte.flags();
+    if flags.contains(PteFlags::WRITE) && !flags.contains(PteFlags::READ) {
+        return Err("Writable leaf without read permission");
+    }
+    if pte.ppn() & ((1 << (9 * level)) - 1) != 0 {
+        return Err("Misaligned superpage");
+    }
+    Ok(())
+}
+
+// ============================================
+// ASID 与 TLB 刷新
+// ============================================
+
+/// 根页表的物理地址 → 所属地址空间的 ASID（AddressSpace 创建时登记，销毁时取消）
+static ROOT_ASIDS: Mutex<BTreeMap<PhysAddr, u16>> = Mutex::new(BTreeMap::new());
+
+/// 登记根页表所属的 ASID
+pub(crate) fn register_asid(root: PhysAddr, asid: u16) {
+    crate::interrupts::without_interrupts(|| ROOT_ASIDS.lock().insert(root, asid));
+}
+
+/// 取消登记根页表
+pub(crate) fn unregister_asid(root: PhysAddr) {
+    crate::interrupts::without_interrupts(|| ROOT_ASIDS.lock().remove(&root));
+}
+
+/// 根页表所属的 ASID（没有登记的页表返回 `None`）
+fn asid_of(root: PhysAddr) -> Option<u16> {
+    crate::interrupts::without_interrupts(|| ROOT_ASIDS.lock().get(&root).copied())
+}
+
+/// 刷新单个虚拟地址的 TLB
+///
+/// # 说明
+/// 只刷新 `root` 所属 ASID 的条目；没有登记 ASID 的页表刷新所有 ASID 中的这个地址
+pub(crate) fn flush_tlb(root: PhysAddr, vaddr: VirtAddr) {
+    match asid_of(root) {
+        Some(asid) => tlb::flush_addr_asid(vaddr, asid),
+        None => tlb::flush_addr(vaddr),
+    }
+}
+
+/// 刷新一棵页表的全部 TLB 条目（包括缓存的中间页表项）
+///
+/// # 说明
+/// 只刷新 `root` 所属的 ASID；没有登记 ASID 的页表刷新整个 TLB
+pub(crate) fn flush_tlb_root(root: PhysAddr) {
+    match asid_of(root) {
+        Some(asid) => tlb::flush_asid(asid),
+        None => tlb::flush_all(),
+    }
+}
+
+/// 遍历 Sv39 页表，将虚拟地址转换为物理地址
+///
+/// # 参数
+/// - `root_paddr`: 根页表的物理地址
+/// - `vaddr`: 要转换的虚拟地址
+///
+/// # 返回
+/// - `Some(PhysAddr)`: 转换成功（包含页内偏移）
+/// - `None`: 页面未映射
+pub fn walk_page_table(root_paddr: PhysAddr, vaddr: VirtAddr) -> Option<PhysAddr> {
+    walk_page_table_mode(root_paddr, vaddr, PagingMode::Sv39)
+}
+
+/// 按分页模式遍历页表（Sv39 三级，Sv48 四级）
+///
+/// # 返回
+/// - `None`: 页面未映射，或 `vaddr` 超出该模式的地址范围（高位不是符号扩展）
+pub fn walk_page_table_mode(
+    root_paddr: PhysAddr,
+    vaddr: VirtAddr,
+    mode: PagingMode,
+) -> Option<PhysAddr> {
+    if !is_canonical(vaddr, mode) {
+        return None;
+    }
+    let mut table = unsafe { table_ref(root_paddr) };
+
+    for level in (0..mode.levels()).rev() {
+        let pte = *table.get_entry(vaddr.vpn(level, mode));
+
+        // 硬件会拒绝的页表项（包括零级页表中的非叶子）同样视为未映射
+        if !pte.is_valid() || check_entry(pte, level).is_err() {
+            return None;
+        }
+
+        if pte.is_leaf() {
+            // Level 3: 512GB，Level 2: 1GB，Level 1: 2MB，Level 0: 4KB
+            let page_size = PAGE_SIZE << (9 * level);
+            let offset = vaddr.as_usize() & (page_size - 1);
+            return Some(PhysAddr::new(pte.phys_addr().as_usize() + offset));
+        }
+
+        table = unsafe { table_ref(pte.phys_addr()) };
+    }
+
+    None
+}
+
+/// 虚拟地址在 `mode` 下是否合法：有效位以上的高位都等于最高有效位
+fn is_canonical(vaddr: VirtAddr, mode: PagingMode) -> bool {
+    let shift = usize::BITS as usize - mode.va_bits();
+    (((vaddr.as_usize() << shift) as isize) >> shift) as usize == vaddr.as_usize()
+}
+
+/// 查找 4KB 页面的叶子页表项
+///
+/// # 返回
+/// - `Some(pte)`: 有效的零级页表项
+/// - `None`: 页面未映射，或被大页覆盖
+pub(crate) fn leaf_entry_mut(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+) -> Option<&mut PageTableEntry> {
+    let vpns = vpns(vaddr);
+    let mut table = root_table;
+
+    for level in (1..3).rev() {
+        let pte = table.get_entry(vpns[level]);
+        if !pte.is_valid() || pte.is_leaf() {
+            return None;
+        }
+        table = unsafe { table_mut(pte.phys_addr()) };
+    }
+
+    let pte = table.get_entry_mut(vpns[0]);
+    pte.is_valid().then_some(pte)
+}
+
+/// 映射 `vaddr` 的叶子页表项的页大小（Sv39）
+///
+/// # 返回
+/// - `None`: 页面未映射
+pub(crate) fn leaf_size(root_table: &PageTable, vaddr: VirtAddr) -> Option<MappingSize> {
+    let vpns = vpns(vaddr);
+    let mut table = root_table;
+
+    for level in (0..3).rev() {
+        let pte = table.get_entry(vpns[level]);
+        if !pte.is_valid() {
+            return None;
+        }
+        if pte.is_leaf() {
+            return Some(MappingSize::of_level(level));
+        }
+        table = unsafe { table_ref(pte.phys_addr()) };
+    }
+
+    None
+}
+
+/// 遍历页表（可视化版本）
+///
+/// # 功能
+/// - 与 `walk_page_table` 相同
+/// - 逐级打印 VPN 查找过程（教学用）
+pub fn walk_page_table_verbose(root_paddr: PhysAddr, vaddr: VirtAddr) -> Option<PhysAddr> {
+    let vpns = vpns(vaddr);
+
+    println!("┌─────────────────────────────────────────────");
+    println!("│ 页表遍历: {:#x}", vaddr.as_usize());
+    println!(
+        "│ VPN[2]={} VPN[1]={} VPN[0]={} offset={:#x}",
+        vpns[2],
+        vpns[1],
+        vpns[0],
+        vaddr.page_offset()
+    );
+    println!("├─────────────────────────────────────────────");
+
+    let mut table_paddr = root_paddr;
+    for level in (0..3).rev() {
+        let table = unsafe { table_ref(table_paddr) };
+        let pte = table.get_entry(vpns[level]);
+
+        println!(
+            "│ Level {}: 页表 {:#x}[{}] = {:?}",
+            level,
+            table_paddr.as_usize(),
+            vpns[level],
+            pte
+        );
+
+        if !pte.is_valid() {
+            println!("│ → 无效页表项，地址未映射");
+            println!("└─────────────────────────────────────────────");
+            return None;
+        }
+
+        if pte.is_leaf() {
+            let page_size = PAGE_SIZE << (9 * level);
+            let offset = vaddr.as_usize() & (page_size - 1);
+            let paddr = PhysAddr::new(pte.phys_addr().as_usize() + offset);
+            let kind = match level {
+                2 => "1GB 大页",
+                1 => "2MB 大页",
+                _ => "4KB 页",
+            };
+            println!("│ → 叶子节点（{}）", kind);
+            println!("│ 结果: {:#x} → {:#x}", vaddr.as_usize(), paddr.as_usize());
+            println!("└─────────────────────────────────────────────");
+            return Some(paddr);
+        }
+
+        if level == 0 {
+            println!("│ → Level 0 页表项不是叶子，页表损坏");
+            println!("└─────────────────────────────────────────────");
+            return None;
+        }
+
+        println!("│ → 指向下一级页表 {:#x}", pte.phys_addr().as_usize());
+        table_paddr = pte.phys_addr();
+    }
+
+    None
+}
+
+/// 地址转换（使用当前 satp 指向的页表）
+///
+/// # 返回
+/// - 分页未开启（satp 模式为 Bare）时返回恒等转换结果
+/// - 否则遍历 satp 指向的页表，未映射时返回 `None`
+///
+/// # 说明
+/// 与 `walk_page_table` 相同，要求页表本身位于恒等映射的物理内存中
+pub fn translate_addr(vaddr: VirtAddr) -> Option<PhysAddr> {
+    use riscv::register::satp;
+
+    let satp = satp::read();
+    match PagingMode::current() {
+        Some(mode) => walk_page_table_mode(PhysAddr::new(satp.ppn() << 12), vaddr, mode),
+        None => Some(PhysAddr::new(vaddr.as_usize())),
+    }
+}
+
+/// 找到或创建虚拟地址所在的零级页表
+///
+/// # 返回
+/// - `Err`: 中间页表分配失败，或该地址已被大页覆盖
+fn leaf_table<'a>(
+    root_table: &'a mut PageTable,
+    vaddr: VirtAddr,
+    mode: PagingMode,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<&'a mut PageTable, &'static str> {
+    let mut table = root_table;
+
+    // Level 1 以上：找到或创建下一级页表
+    for level in (1..mode.levels()).rev() {
+        let pte = table.get_entry_mut(vaddr.vpn(level, mode));
+
+        if !pte.is_valid() {
+            let frame = alloc_table(allocator)?;
+            pte.set(frame.number(), PteFlags::VALID);
+        } else if pte.is_leaf() {
+            return Err("Address already covered by a huge page");
+        }
+
+        table = unsafe { table_mut(pte.phys_addr()) };
+    }
+
+    Ok(table)
+}
+
+/// 映射单个 4KB 页面（Sv39）
+///
+/// # 参数
+/// - `root_table`: 根页表
+/// - `vaddr`: 虚拟地址
+/// - `paddr`: 物理地址
+/// - `flags`: 页表标志位（V 位会自动设置）
+/// - `allocator`: 帧分配器（用于按需分配中间页表）
+pub fn map_page(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    paddr: PhysAddr,
+    flags: PteFlags,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<(), &'static str> {
+    map_page_mode(root_table, vaddr, paddr, flags, PagingMode::Sv39, allocator)
+}
+
+/// 按分页模式映射单个 4KB 页面（Sv48 多分配一级中间页表）
+///
+/// # 返回
+/// - `Err`: `vaddr` 超出该模式的地址范围，或同 `map_page`
+pub fn map_page_mode(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    paddr: PhysAddr,
+    flags: PteFlags,
+    mode: PagingMode,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<(), &'static str> {
+    let root = table_addr(root_table);
+    if !is_canonical(vaddr, mode) {
+        return Err("Address not canonical");
+    }
+    let table = leaf_table(root_table, vaddr, mode, allocator)?;
+
+    // Level 0：设置最终映射
+    let pte = table.get_entry_mut(vaddr.vpn0());
+    if pte.is_valid() {
+        return Err("Page already mapped");
+    }
+    pte.set(paddr.page_number(), flags | PteFlags::VALID);
+
+    flush_tlb(root, vaddr);
+    Ok(())
+}
+
+/// 批量映射连续的 4KB 页面
+///
+/// # 参数
+/// - `root_table`: 根页表
+/// - `vstart`: 起始虚拟地址（页对齐）
+/// - `pstart`: 起始物理地址（页对齐）
+/// - `page_count`: 页数
+/// - `flags`: 页表标志位（V 位会自动设置）
+/// - `allocator`: 帧分配器（用于中间页表）
+///
+/// # 返回
+/// - `Ok(n)`: 映射的页数（等于 `page_count`）
+/// - `Err`: 中途失败时，本次已经建立的映射和新建的中间页表会全部撤销，
+///   页表恢复到调用之前的状态
+///
+/// # 说明
+/// 每个零级页表只从根页表向下遍历一次，然后连续填写页表项；
+/// 结束时（包括失败时）只刷新一次整个地址空间的 TLB
+pub fn map_range(
+    root_table: &mut PageTable,
+    vstart: VirtAddr,
+    pstart: PhysAddr,
+    page_count: usize,
+    flags: PteFlags,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<usize, &'static str> {
+    let result = fill_range(root_table, vstart, pstart, page_count, flags, allocator);
+    if let Err((index, _)) = result {
+        // 回滚：撤销本次已经建立的映射（失败的那一页不是本次映射的）
+        for i in 0..index {
+            let _ = unmap_page(root_table, vstart + i * PAGE_SIZE, allocator);
+        }
+        prune_tables(root_table, vstart, page_count, allocator);
+    }
+    flush_tlb_root(table_addr(root_table));
+    result.map_err(|(_, e)| e)
+}
+
+/// map_range 的实现（不刷新 TLB）
+fn fill_range(
+    root_table: &mut PageTable,
+    vstart: VirtAddr,
+    pstart: PhysAddr,
+    page_count: usize,
+    flags: PteFlags,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<usize, (usize, &'static str)> {
+    let flags = flags | PteFlags::VALID;
+    let mut mapped = 0;
+
+    while mapped < page_count {
+        let vaddr = vstart + mapped * PAGE_SIZE;
+        let table = leaf_table(root_table, vaddr, PagingMode::Sv39, allocator)
+            .map_err(|e| (mapped, e))?;
+
+        // 填到本页表末尾或范围末尾
+        let first = vaddr.vpn0();
+        let batch = (ENTRIES_PER_TABLE - first).min(page_count - mapped);
+        for index in first..first + batch {
+            let pte = table.get_entry_mut(index);
+            if pte.is_valid() {
+                return Err((mapped, "Page already mapped"));
+            }
+            pte.set((pstart + mapped * PAGE_SIZE).page_number(), flags);
+            mapped += 1;
+        }
+    }
+
+    Ok(mapped)
+}
+
+/// 映射大页（Level 1：2MB，Level 2：1GB）
+///
+/// # 参数
+/// - `root_table`: 根页表
+/// - `vaddr`: 虚拟地址（必须按大页大小对齐）
+/// - `paddr`: 物理地址（必须按大页大小对齐）
+/// - `flags`: 页表标志位，至少包含 R/W/X 之一（V 位会自动设置）
+/// - `level`: 叶子所在的页表级别，1 或 2
+/// - `allocator`: 帧分配器（Level 1 大页可能需要创建二级页表）
+///
+/// # 返回
+/// - `Err`: 级别无效、地址未对齐、标志位不是叶子，或该范围已有映射
+pub fn map_huge_page(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    paddr: PhysAddr,
+    flags: PteFlags,
+    level: usize,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<(), &'static str> {
+    if level != 1 && level != 2 {
+        return Err("Invalid huge page level");
+    }
+    let page_size = PAGE_SIZE << (9 * level);
+    if !vaddr.as_usize().is_multiple_of(page_size) || !paddr.as_usize().is_multiple_of(page_size) {
+        return Err("Huge page address not aligned");
+    }
+    if !flags.is_leaf() {
+        return Err("Huge page flags must include R, W or X");
+    }
+
+    let root = table_addr(root_table);
+    let vpns = vpns(vaddr);
+    let mut table = root_table;
+
+    // 从 Level 2 向下走到目标级别
+    for current in (level + 1..3).rev() {
+        let pte = table.get_entry_mut(vpns[current]);
+
+        if !pte.is_valid() {
+            let frame = alloc_table(allocator)?;
+            pte.set(frame.number(), PteFlags::VALID);
+        } else if pte.is_leaf() {
+            return Err("Address already covered by a huge page");
+        }
+
+        table = unsafe { table_mut(pte.phys_addr()) };
+    }
+
+    let pte = table.get_entry_mut(vpns[level]);
+    if pte.is_valid() {
+        return Err(if pte.is_leaf() {
+            "Page already mapped"
+        } else {
+            "Range already contains smaller mappings"
+        });
+    }
+    pte.set(paddr.page_number(), flags | PteFlags::VALID);
+
+    flush_tlb(root, vaddr);
+    Ok(())
+}
+
+/// 映射单个 2MB 大页（Level 1 叶子）
+///
+/// # 参数
+/// - `vaddr` / `paddr`: 必须 2MB 对齐
+/// - 其余同 `map_huge_page`
+///
+/// # 返回
+/// - `Err`: 地址未对齐，或该 2MB 范围内已有映射（包括已存在的零级页表）
+pub fn map_page_2mb(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    paddr: PhysAddr,
+    flags: PteFlags,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<(), &'static str> {
+    map_huge_page(root_table, vaddr, paddr, flags, 1, allocator)
+}
+
+/// 取消 2MB 大页映射
+///
+/// # 功能
+/// - 清除 Level 1 叶子页表项
+/// - 一级页表因此变空时释放它（同 `unmap_page`）
+///
+/// # 返回
+/// 原来映射到的物理地址；该地址不是 2MB 大页时返回错误
+pub fn unmap_page_2mb(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<PhysAddr, &'static str> {
+    let vpns = vpns(vaddr);
+
+    let root_pte = root_table.get_entry(vpns[2]);
+    if !root_pte.is_valid() {
+        return Err("Page not mapped");
+    }
+    if root_pte.is_leaf() {
+        return Err("Cannot unmap part of a huge page");
+    }
+    let table_paddr = root_pte.phys_addr();
+    let table = unsafe { table_mut(table_paddr) };
+
+    let pte = table.get_entry_mut(vpns[1]);
+    if !pte.is_valid() {
+        return Err("Page not mapped");
+    }
+    if !pte.is_leaf() {
+        return Err("Not a 2MB page");
+    }
+
+    let paddr = pte.phys_addr();
+    *pte = PageTableEntry::new();
+
+    if table.is_empty() {
+        *root_table.get_entry_mut(vpns[2]) = PageTableEntry::new();
+        free_table(allocator, table_paddr);
+    }
+    // 带地址的 sfence.vma 对大页同样有效，但一级页表可能已被释放，刷新整个地址空间
+    flush_tlb_root(table_addr(root_table));
+
+    Ok(paddr)
+}
+
+/// 映射单个 1GB 大页（根页表中的 Level 2 叶子）
+///
+/// # 参数
+/// - `vaddr` / `paddr`: 必须 1GB 对齐
+/// - 其余同 `map_huge_page`
+///
+/// # 返回
+/// - `Err`: 地址未对齐，或根页表中该项已经指向下一级页表或已映射
+pub fn map_page_1gb(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    paddr: PhysAddr,
+    flags: PteFlags,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<(), &'static str> {
+    map_huge_page(root_table, vaddr, paddr, flags, 2, allocator)
+}
+
+/// 取消 1GB 大页映射
+///
+/// # 返回
+/// 原来映射到的物理地址；该地址不是 1GB 大页时返回错误
+pub fn unmap_page_1gb(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+) -> Result<PhysAddr, &'static str> {
+    let root = table_addr(root_table);
+    let pte = root_table.get_entry_mut(vaddr.vpn2());
+    if !pte.is_valid() {
+        return Err("Page not mapped");
+    }
+    if !pte.is_leaf() {
+        return Err("Not a 1GB page");
+    }
+
+    let paddr = pte.phys_addr();
+    *pte = PageTableEntry::new();
+
+    flush_tlb(root, vaddr);
+    Ok(paddr)
+}
+
+/// 取消 4KB 页面映射
+///
+/// # 功能
+/// - 清除叶子页表项
+/// - 零级页表因此变空时释放它，并清除一级页表中指向它的页表项；
+///   一级页表随之变空时同样释放（根页表不释放）
+///
+/// # 返回
+/// 原来映射到的物理地址（页对齐）；该物理页帧本身不会被释放
+pub fn unmap_page(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    allocator: &mut dyn FrameAllocator,
+) -> Result<PhysAddr, &'static str> {
+    let root = table_addr(root_table);
+    let vpns = vpns(vaddr);
+    // tables[level]：沿途第 level 级页表的物理地址（只记录 Level 0 和 Level 1）
+    let mut tables = [PhysAddr::new(0); 2];
+    let mut table = &mut *root_table;
+
+    for level in (1..3).rev() {
+        let pte = table.get_entry(vpns[level]);
+
+        if !pte.is_valid() {
+            return Err("Page not mapped");
+        }
+        if pte.is_leaf() {
+            return Err("Cannot unmap part of a huge page");
+        }
+
+        tables[level - 1] = pte.phys_addr();
+        table = unsafe { table_mut(pte.phys_addr()) };
+    }
+
+    let pte = table.get_entry_mut(vpns[0]);
+    if !pte.is_valid() {
+        return Err("Page not mapped");
+    }
+
+    let paddr = pte.phys_addr();
+    *pte = PageTableEntry::new();
+    flush_tlb(root, vaddr);
+
+    // 自底向上回收变空的中间页表
+    if unsafe { table_ref(tables[0]) }.is_empty() {
+        *unsafe { table_mut(tables[1]) }.get_entry_mut(vpns[1]) = PageTableEntry::new();
+        free_table(allocator, tables[0]);
+
+        if unsafe { table_ref(tables[1]) }.is_empty() {
+            *root_table.get_entry_mut(vpns[2]) = PageTableEntry::new();
+            free_table(allocator, tables[1]);
+        }
+
+        // 带地址的 sfence.vma 只保证刷新叶子页表项，
+        // 释放的页表页可能被重用，因此刷新整个地址空间
+        flush_tlb_root(root);
+    }
+
+    Ok(paddr)
+}
+
+/// 释放范围内不再包含任何映射的中间页表
+///
+/// # 参数
+/// - `root_table`: 根页表
+/// - `vstart`: 起始虚拟地址
+/// - `page_count`: 页数
+/// - `allocator`: 帧分配器（回收页表页）
+///
+/// # 说明
+/// 用于映射失败后的回滚：`map_range` 等函数可能已经为失败的页面
+/// 建立了中间页表，而 `unmap_page` 只回收它经过的页表。
+/// 检查覆盖范围的每个零级页表和一级页表，空的就释放（根页表不释放）
+pub(crate) fn prune_tables(
+    root_table: &mut PageTable,
+    vstart: VirtAddr,
+    page_count: usize,
+    allocator: &mut dyn FrameAllocator,
+) {
+    if page_count == 0 {
+        return;
+    }
+    let start = vstart.as_usize();
+    let last = start + (page_count - 1) * PAGE_SIZE;
+    let mut freed = false;
+
+    // 每个一级页表覆盖 1GB，每个零级页表覆盖 2MB
+    for gb in (start / GIGAPAGE_SIZE)..=(last / GIGAPAGE_SIZE) {
+        let pte2 = *root_table.get_entry(VirtAddr::new(gb * GIGAPAGE_SIZE).vpn2());
+        if !pte2.is_valid() || pte2.is_leaf() {
+            continue;
+        }
+        let table1 = unsafe { table_mut(pte2.phys_addr()) };
+
+        let first_mb = (start / MEGAPAGE_SIZE).max(gb * ENTRIES_PER_TABLE);
+        let last_mb = (last / MEGAPAGE_SIZE).min(gb * ENTRIES_PER_TABLE + ENTRIES_PER_TABLE - 1);
+        for mb in first_mb..=last_mb {
+            let pte1 = table1.get_entry_mut(mb % ENTRIES_PER_TABLE);
+            if !pte1.is_valid() || pte1.is_leaf() {
+                continue;
+            }
+            let paddr = pte1.phys_addr();
+            if unsafe { table_ref(paddr) }.is_empty() {
+                *pte1 = PageTableEntry::new();
+                free_table(allocator, paddr);
+                freed = true;
+            }
+        }
+
+        if table1.is_empty() {
+            *root_table.get_entry_mut(VirtAddr::new(gb * GIGAPAGE_SIZE).vpn2()) =
+                PageTableEntry::new();
+            free_table(allocator, pte2.phys_addr());
+            freed = true;
+        }
+    }
+
+    if freed {
+        flush_tlb_root(table_addr(root_table));
+    }
+}
+
+/// 释放整棵页表：所有中间页表和根页表
+///
+/// # 参数
+/// - `root_paddr`: 根页表的物理地址（调用后不能再使用）
+/// - `mode`: 页表的分页模式（决定级数）
+/// - `allocator`: 帧分配器（回收页表页）
+///
+/// # 说明
+/// 只释放页表页本身，叶子页表项指向的页帧（包括大页）不受影响，
+/// 由调用者在此之前释放
+pub(crate) fn free_page_tables(
+    root_paddr: PhysAddr,
+    mode: PagingMode,
+    allocator: &mut dyn FrameAllocator,
+) {
+    free_table_tree(root_paddr, mode.levels() - 1, allocator);
+}
+
+/// 释放第 `level` 级页表及其下所有页表
+fn free_table_tree(paddr: PhysAddr, level: usize, allocator: &mut dyn FrameAllocator) {
+    if level > 0 {
+        let table = unsafe { table_ref(paddr) };
+        for index in 0..ENTRIES_PER_TABLE {
+            let pte = *table.get_entry(index);
+            // 跳过无效项和叶子（大页），只进入下一级页表
+            if pte.is_valid() && !pte.is_leaf() {
+                free_table_tree(pte.phys_addr(), level - 1, allocator);
+            }
+        }
+    }
+    free_table(allocator, paddr);
+}
+
+/// 叶子页表项中可以修改的权限位（R/W/X/U）
+pub(crate) const LEAF_PERMISSIONS: PteFlags = PteFlags::READ
+    .union(PteFlags::WRITE)
+    .union(PteFlags::EXECUTE)
+    .union(PteFlags::USER);
+
+/// 取出 `flags` 中的 R/W/X/U 位，并检查它们能否构成叶子
+///
+/// # 返回
+/// - `Err`: 没有 R/X，或只写不读（保留的组合）
+pub(crate) fn leaf_permissions(flags: PteFlags) -> Result<PteFlags, &'static str> {
+    let flags = flags & LEAF_PERMISSIONS;
+    if !flags.intersects(PteFlags::READ | PteFlags::EXECUTE)
+        || (flags.is_writable() && !flags.is_readable())
+    {
+        return Err("Invalid leaf permissions");
+    }
+    Ok(flags)
+}
+
+/// 修改已有映射的访问权限
+///
+/// # 参数
+/// - `root_table`: 根页表
+/// - `vaddr`: 虚拟地址（所在的 4KB / 2MB / 1GB 页面都会被修改）
+/// - `new_flags`: 新的 R/W/X/U 位，其余位被忽略
+///
+/// # 返回
+/// - `Err`: 页面未映射，或新权限不能构成叶子（没有 R/X，或只写不读）
+///
+/// # 说明
+/// - 保留物理页号以及 V/G/A/D 位
+/// - 写时复制页面保持 W 位清除，写权限仍由写时复制处理
+pub fn protect_page(
+    root_table: &mut PageTable,
+    vaddr: VirtAddr,
+    new_flags: PteFlags,
+) -> Result<(), &'static str> {
+    let mut new_flags = leaf_permissions(new_flags)?;
+    let root = table_addr(root_table);
+    let vpns = vpns(vaddr);
+    let mut table = root_table;
+
+    for level in (0..3).rev() {
+        let pte = table.get_entry_mut(vpns[level]);
+
+        if !pte.is_valid() {
+            return Err("Page not mapped");
+        }
+
+        if pte.is_leaf() {
+            let flags = pte.flags();
+            if flags.contains(PteFlags::COW) {
+                new_flags.remove(PteFlags::WRITE);
+            }
+            pte.set(pte.ppn(), (flags & !LEAF_PERMISSIONS) | new_flags);
+            flush_tlb(root, vaddr);
+            return Ok(());
+        }
+
+        if level == 0 {
+            return Err("Page not mapped");
+        }
+
+        table = unsafe { table_mut(pte.phys_addr()) };
+    }
+
+    Err("Page not mapped")
+}
+
+// ============================================
+// 遍历全部映射
+// ============================================
+
+/// 按虚拟地址顺序访问页表中的每个叶子（Sv39）
+///
+/// # 参数
+/// - `root_paddr`: 根页表的物理地址
+/// - `visit`: 对每个叶子调用 `visit(虚拟地址, 物理地址, 页大小, 标志位)`，
+///   大页只调用一次
+pub fn walk_mappings<F>(root_paddr: PhysAddr, mut visit: F)
+where
+    F: FnMut(VirtAddr, PhysAddr, usize, PteFlags),
+{
+    walk_mappings_mode(root_paddr, PagingMode::Sv39, &mut visit);
+}
+
+/// 按 `mode` 的级数访问页表中的每个叶子（见 `walk_mappings`）
+pub fn walk_mappings_mode(
+    root_paddr: PhysAddr,
+    mode: PagingMode,
+    visit: &mut dyn FnMut(VirtAddr, PhysAddr, usize, PteFlags),
+) {
+    walk_level(root_paddr, mode.levels() - 1, 0, mode, visit);
+}
+
+/// 访问第 `level` 级页表中的叶子（`base` 是这张页表覆盖的起始虚拟地址）
+///
+/// # 说明
+/// 递归深度不超过页表级数
+fn walk_level(
+    paddr: PhysAddr,
+    level: usize,
+    base: usize,
+    mode: PagingMode,
+    visit: &mut dyn FnMut(VirtAddr, PhysAddr, usize, PteFlags),
+) {
+    let table = unsafe { table_ref(paddr) };
+    let page_size = PAGE_SIZE << (9 * level);
+    for index in 0..ENTRIES_PER_TABLE {
+        let pte = *table.get_entry(index);
+        if !pte.is_valid() {
+            continue;
+        }
+        let vaddr = sign_extend(base + index * page_size, mode);
+        if pte.is_leaf() {
+            visit(VirtAddr::new(vaddr), pte.phys_addr(), page_size, pte.flags());
+        } else if level > 0 {
+            walk_level(pte.phys_addr(), level - 1, vaddr, mode, visit);
+        }
+    }
+}
+
+/// 映射的页大小
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingSize {
+    /// Level 0 叶子
+    Page4K,
+    /// Level 1 叶子
+    Mega2M,
+    /// Level 2 叶子
+    Giga1G,
+}
+
+impl MappingSize {
+    /// 第 `level` 级叶子的页大小（Sv39）
+    fn of_level(level: usize) -> Self {
+        match level {
+            0 => MappingSize::Page4K,
+            1 => MappingSize::Mega2M,
+            _ => MappingSize::Giga1G,
+        }
+    }
+
+    /// 字节数
+    pub const fn bytes(self) -> usize {
+        match self {
+            MappingSize::Page4K => PAGE_SIZE,
+            MappingSize::Mega2M => MEGAPAGE_SIZE,
+            MappingSize::Giga1G => GIGAPAGE_SIZE,
+        }
+    }
+}
+
+/// 一个叶子映射
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappingInfo {
+    /// 起始虚拟地址（高半部分的地址已符号扩展）
+    pub vaddr: VirtAddr,
+    /// 起始物理地址
+    pub paddr: PhysAddr,
+    /// 页大小
+    pub size: MappingSize,
+    /// 页表项的标志位
+    pub flags: PteFlags,
+}
+
+/// 按虚拟地址顺序产生页表中每个叶子的迭代器（Sv39，见 `AddressSpace::mappings`）
+///
+/// # 说明
+/// 不分配内存：记住从根页表到当前页表的三张页表和各级的下一个索引；
+/// 根页表的最后一项访问完之后一直返回 None
+pub struct Mappings<'a> {
+    /// 各级页表（`tables[2]` 是根页表），低于 `level` 的项在下降时填入
+    tables: [&'a PageTable; 3],
+    /// 各级页表中下一个要访问的索引
+    indices: [usize; 3],
+    /// 当前所在的级
+    level: usize,
+}
+
+impl<'a> Mappings<'a> {
+    /// 从根页表开始遍历
+    pub(crate) fn new(root: &'a PageTable) -> Self {
+        Mappings { tables: [root; 3], indices: [0; 3], level: 2 }
+    }
+
+    /// 当前页表中刚访问的那一项覆盖的起始虚拟地址
+    fn current_vaddr(&self) -> VirtAddr {
+        let vaddr = (self.level..3)
+            .map(|level| (self.indices[level] - 1) << (12 + 9 * level))
+            .sum();
+        VirtAddr::new(sign_extend(vaddr, PagingMode::Sv39))
+    }
+}
+
+impl Iterator for Mappings<'_> {
+    type Item = MappingInfo;
+
+    fn next(&mut self) -> Option<MappingInfo> {
+        loop {
+            let level = self.level;
+            let index = self.indices[level];
+            if index == ENTRIES_PER_TABLE {
+                if level == 2 {
+                    return None;
+                }
+                // 这张页表访问完了，回到上一级
+                self.level += 1;
+                continue;
+            }
+            self.indices[level] += 1;
+
+            let pte = *self.tables[level].get_entry(index);
+            if !pte.is_valid() {
+                continue;
+            }
+            if pte.is_leaf() {
+                return Some(MappingInfo {
+                    vaddr: self.current_vaddr(),
+                    paddr: pte.phys_addr(),
+                    size: MappingSize::of_level(level),
+                    flags: pte.flags(),
+                });
+            }
+            if level > 0 {
+                self.level -= 1;
+                self.tables[level - 1] = unsafe { table_ref(pte.phys_addr()) };
+                self.indices[level - 1] = 0;
+            }
+        }
+    }
+}
+
+impl core::iter::FusedIterator for Mappings<'_> {}
+
+/// 检查页表中每个有效的页表项（Sv39，规则见 `check_entry`）
+///
+/// # 返回
+/// - `Ok(n)`: 全部合法，共 `n` 个叶子
+/// - `Err`: 按虚拟地址顺序第一个不合法的页表项的原因
+pub fn verify_page_table(root_paddr: PhysAddr) -> Result<usize, &'static str> {
+    verify_level(root_paddr, 2)
+}
+
+/// 检查第 `level` 级页表及其下的页表
+fn verify_level(paddr: PhysAddr, level: usize) -> Result<usize, &'static str> {
+    let table = unsafe { table_ref(paddr) };
+    let mut leaves = 0;
+    for index in 0..ENTRIES_PER_TABLE {
+        let pte = *table.get_entry(index);
+        if !pte.is_valid() {
+            continue;
+        }
+        check_entry(pte, level)?;
+        leaves += match pte.is_leaf() {
+            true => 1,
+            false => verify_level(pte.phys_addr(), level - 1)?,
+        };
+    }
+    Ok(leaves)
+}
+
+/// 按 `mode` 的有效位数把虚拟地址符号扩展为规范地址
+fn sign_extend(vaddr: usize, mode: PagingMode) -> usize {
+    let shift = usize::BITS as usize - mode.va_bits();
+    (((vaddr << shift) as isize) >> shift) as usize
+}
+
+/// 一段连续、标志位相同的映射
+struct MappingRun {
+    vstart: usize,
+    pstart: usize,
+    page_size: usize,
+    count: usize,
+    flags: PteFlags,
+}
+
+impl MappingRun {
+    /// 下一个叶子是否紧接在这段之后
+    fn extends(&self, vaddr: usize, paddr: usize, page_size: usize, flags: PteFlags) -> bool {
+        let len = self.count * self.page_size;
+        self.page_size == page_size
+            && self.flags == flags
+            && self.vstart.wrapping_add(len) == vaddr
+            && self.pstart + len == paddr
+    }
+
+    fn print(&self) {
+        let len = self.count * self.page_size;
+        let size = match self.page_size {
+            GIGAPAGE_SIZE => "1GB",
+            MEGAPAGE_SIZE => "2MB",
+            _ => "4KB",
+        };
+        let flag = |bit: PteFlags, c: char| if self.flags.contains(bit) { c } else { '-' };
+        println!(
+            "{:#x}..{:#x} -> {:#x}..{:#x} {}{}{}{}{}{} ({} pages, {} entries)",
+            self.vstart,
+            self.vstart.wrapping_add(len),
+            self.pstart,
+            self.pstart + len,
+            flag(PteFlags::READ, 'R'),
+            flag(PteFlags::WRITE, 'W'),
+            flag(PteFlags::EXECUTE, 'X'),
+            if self.flags.is_user() { " U" } else { "" },
+            if self.flags.contains(PteFlags::GLOBAL) { " G" } else { "" },
+            if self.flags.contains(PteFlags::COW) { " COW" } else { "" },
+            size,
+            self.count
+        );
+    }
+}
+
+/// 打印页表中的全部映射（Sv39）
+///
+/// # 说明
+/// 虚拟地址和物理地址都连续、页大小和标志位相同的叶子合并为一行；
+/// A/D 位由硬件维护，不参与比较
+pub fn dump_page_table(root_paddr: PhysAddr) {
+    dump_page_table_mode(root_paddr, PagingMode::Sv39);
+}
+
+/// 按 `mode` 的级数打印页表中的全部映射（见 `dump_page_table`）
+pub fn dump_page_table_mode(root_paddr: PhysAddr, mode: PagingMode) {
+    println!("[PAGING] Mappings of page table at {:#x}:", root_paddr.as_usize());
+    let mut run: Option<MappingRun> = None;
+    let mut leaves = 0;
+    walk_mappings_mode(root_paddr, mode, &mut |vaddr, paddr, page_size, flags| {
+        let flags = flags & !(PteFlags::ACCESSED | PteFlags::DIRTY);
+        let (vaddr, paddr) = (vaddr.as_usize(), paddr.as_usize());
+        leaves += 1;
+        match &mut run {
+            Some(current) if current.extends(vaddr, paddr, page_size, flags) => current.count += 1,
+            _ => {
+                if let Some(done) = run.replace(MappingRun {
+                    vstart: vaddr,
+                    pstart: paddr,
+                    page_size,
+                    count: 1,
+                    flags,
+                }) {
+                    done.print();
+                }
+            }
+        }
+    });
+    if let Some(done) = run {
+        done.print();
+    }
+    println!("[PAGING] {} leaf entries", leaves);
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::selftests::new_root;
+    use crate::memory::{test_frame_allocator, SimpleFrameAllocator, GIGAPAGE_SIZE, MEGAPAGE_SIZE};
+
+    #[test_case]
+    fn test_map_and_walk() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+
+        let vaddr = VirtAddr::new(0x1234_5000);
+        let paddr = PhysAddr::new(0x8100_0000);
+        let flags = PteFlags::READ | PteFlags::WRITE;
+        map_page(root, vaddr, paddr, flags, &mut allocator).expect("map_page failed");
+
+        assert_eq!(
+            walk_page_table(root_paddr, VirtAddr::new(0x1234_5678)),
+            Some(PhysAddr::new(0x8100_0678))
+        );
+        assert_eq!(walk_page_table(root_paddr, VirtAddr::new(0x1234_6000)), None);
+        assert_eq!(map_page(root, vaddr, paddr, flags, &mut allocator), Err("Page already mapped"));
+    }
+
+    #[test_case]
+    fn test_sv48_four_level_walk() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+
+        // 位 39..48 非零，只有 Sv48 能表示
+        let vaddr = VirtAddr::new(0x7f12_3456_7abc);
+        assert_eq!(
+            [0, 1, 2, 3].map(|level| vaddr.vpn(level, PagingMode::Sv48)),
+            [0x167, 0x1a2, 0x48, 0xfe]
+        );
+        assert_eq!(vaddr.vpn3(), 0xfe);
+
+        let paddr = PhysAddr::new(0x8100_0000);
+        let flags = PteFlags::READ | PteFlags::WRITE;
+        let free_before = allocator.stats().free_frames;
+        map_page_mode(root, vaddr, paddr, flags, PagingMode::Sv48, &mut allocator)
+            .expect("map_page_mode failed");
+        // Level 2、Level 1、Level 0 三个中间页表
+        assert_eq!(free_before - allocator.stats().free_frames, 3);
+
+        assert_eq!(
+            walk_page_table_mode(root_paddr, vaddr, PagingMode::Sv48),
+            Some(PhysAddr::new(0x8100_0abc))
+        );
+        // 同一棵页表按 Sv39 解释时，该地址不合法
+        assert_eq!(walk_page_table_mode(root_paddr, vaddr, PagingMode::Sv39), None);
+        assert_eq!(
+            map_page_mode(root, vaddr, paddr, flags, PagingMode::Sv39, &mut allocator),
+            Err("Address not canonical")
+        );
+
+        free_page_tables(root_paddr, PagingMode::Sv48, &mut allocator);
+        assert_eq!(allocator.stats().free_frames, free_before + 1);
+    }
+
+    #[test_case]
+    fn test_unmap_page() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+
+        let vaddr = VirtAddr::new(0x2000_0000);
+        let paddr = PhysAddr::new(0x8200_0000);
+        map_page(root, vaddr, paddr, PteFlags::READ, &mut allocator)
+            .expect("map_page failed");
+
+        assert_eq!(unmap_page(root, vaddr, &mut allocator), Ok(paddr));
+        assert_eq!(walk_page_table(root_paddr, vaddr), None);
+        assert_eq!(unmap_page(root, vaddr, &mut allocator), Err("Page not mapped"));
+    }
+
+    #[test_case]
+    fn test_unmap_single_page_frees_tables() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let free_before = allocator.stats().free_frames;
+
+        let vaddr = VirtAddr::new(0x7000_0000);
+        let paddr = PhysAddr::new(0x8100_0000);
+        map_page(root, vaddr, paddr, PteFlags::READ, &mut allocator)
+            .expect("map_page failed");
+        // 新建了一级页表和零级页表
+        assert_eq!(allocator.stats().free_frames, free_before - 2);
+
+        unmap_page(root, vaddr, &mut allocator).expect("unmap_page failed");
+        assert_eq!(allocator.stats().free_frames, free_before);
+        assert!(root.is_empty());
+    }
+
+    #[test_case]
+    fn test_map_huge_page_2mb() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+
+        let vaddr = VirtAddr::new(0x4020_0000);
+        let paddr = PhysAddr::new(0x8060_0000);
+        let flags = PteFlags::READ | PteFlags::WRITE;
+        map_huge_page(root, vaddr, paddr, flags, 1, &mut allocator).expect("map_huge_page failed");
+
+        // 大页内任意地址都能解析，偏移保留
+        assert_eq!(
+            walk_page_table(root_paddr, VirtAddr::new(0x4020_0000)),
+            Some(PhysAddr::new(0x8060_0000))
+        );
+        assert_eq!(
+            walk_page_table(root_paddr, VirtAddr::new(0x403f_fabc)),
+            Some(PhysAddr::new(0x807f_fabc))
+        );
+        assert_eq!(walk_page_table(root_paddr, VirtAddr::new(0x4040_0000)), None);
+
+        // 大页内不能再映射 4KB 页
+        assert_eq!(
+            map_page(root, VirtAddr::new(0x4030_0000), paddr, flags, &mut allocator),
+            Err("Address already covered by a huge page")
+        );
+    }
+
+    #[test_case]
+    fn test_map_page_2mb_roundtrip() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+        let flags = PteFlags::READ;
+
+        let vaddr = VirtAddr::new(0x4040_0000);
+        let paddr = PhysAddr::new(0x8040_0000);
+        assert_eq!(
+            map_page_2mb(root, VirtAddr::new(0x4040_1000), paddr, flags, &mut allocator),
+            Err("Huge page address not aligned")
+        );
+        map_page_2mb(root, vaddr, paddr, flags, &mut allocator).expect("map_page_2mb failed");
+        assert_eq!(
+            walk_page_table(root_paddr, VirtAddr::new(0x405f_f123)),
+            Some(PhysAddr::new(0x805f_f123))
+        );
+
+        // 大页不能按 4KB 取消，按 2MB 取消后一级页表被回收
+        assert_eq!(
+            unmap_page(root, vaddr, &mut allocator),
+            Err("Cannot unmap part of a huge page")
+        );
+        assert_eq!(unmap_page_2mb(root, vaddr, &mut allocator), Ok(paddr));
+        assert!(root.is_empty());
+    }
+
+    #[test_case]
+    fn test_map_page_1gb() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+        let free_before = allocator.stats().free_frames;
+        let flags = PteFlags::READ | PteFlags::WRITE;
+
+        let base = VirtAddr::new(0x8000_0000);
+        let paddr = PhysAddr::new(0x8000_0000);
+        assert_eq!(
+            map_page_1gb(root, base + MEGAPAGE_SIZE, paddr, flags, &mut allocator),
+            Err("Huge page address not aligned")
+        );
+        map_page_1gb(root, base, paddr, flags, &mut allocator)
+            .expect("map_page_1gb failed");
+        // 不需要任何额外的页表
+        assert_eq!(allocator.stats().free_frames, free_before);
+
+        // 整个 1GB 窗口都恒等转换，窗口之外未映射
+        for offset in [0, 0x123, MEGAPAGE_SIZE + 0x456, 0x0800_0000, GIGAPAGE_SIZE - 1] {
+            assert_eq!(
+                walk_page_table(root_paddr, base + offset),
+                Some(PhysAddr::new(0x8000_0000 + offset))
+            );
+        }
+        assert_eq!(walk_page_table(root_paddr, base + GIGAPAGE_SIZE), None);
+        assert_eq!(walk_page_table(root_paddr, base - 1), None);
+
+        // 已被 1GB 大页覆盖的地址不能再映射更小的页
+        assert_eq!(
+            map_page(root, base + PAGE_SIZE, PhysAddr::new(0x8000_1000), flags, &mut allocator),
+            Err("Address already covered by a huge page")
+        );
+
+        assert_eq!(unmap_page_1gb(root, base), Ok(PhysAddr::new(0x8000_0000)));
+        assert!(root.is_empty());
+    }
+
+    #[test_case]
+    fn test_map_huge_page_rejects_bad_arguments() {
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let flags = PteFlags::READ;
+
+        let unaligned = VirtAddr::new(0x4020_1000);
+        assert_eq!(
+            map_huge_page(root, unaligned, PhysAddr::new(0x8060_0000), flags, 1, &mut allocator),
+            Err("Huge page address not aligned")
+        );
+        assert_eq!(
+            map_huge_page(
+                root,
+                VirtAddr::new(0x4000_0000),
+                PhysAddr::new(0x8060_0000),
+                flags,
+                2,
+                &mut allocator
+            ),
+            Err("Huge page address not aligned")
+        );
+        assert_eq!(
+            map_huge_page(
+                root,
+                VirtAddr::new(0x4020_0000),
+                PhysAddr::new(0x8060_0000),
+                flags,
+                0,
+                &mut allocator
+            ),
+            Err("Invalid huge page level")
+        );
+        assert_eq!(
+            map_huge_page(
+                root,
+                VirtAddr::new(0x4020_0000),
+                PhysAddr::new(0x8060_0000),
+                PteFlags::empty(),
+                1,
+                &mut allocator
+            ),
+            Err("Huge page flags must include R, W or X")
+        );
+
+        // 已经有 4KB 映射的 2MB 范围不能再映射为大页
+        map_page(root, VirtAddr::new(0x4060_0000), PhysAddr::new(0x8100_0000), flags, &mut allocator)
+            .expect("map_page failed");
+        assert_eq!(
+            map_huge_page(
+                root,
+                VirtAddr::new(0x4060_0000),
+                PhysAddr::new(0x8060_0000),
+                flags,
+                1,
+                &mut allocator
+            ),
+            Err("Range already contains smaller mappings")
+        );
+    }
+
+    #[test_case]
+    fn test_map_range_rolls_back_on_oom() {
+        // 一个只有 4 个页帧的分配器：1 个位图页帧 + 3 个可用页帧
+        let base = crate::allocator::simple_heap_range(crate::memory::kernel_end_addr()).end;
+        let mut allocator =
+            SimpleFrameAllocator::new(PhysAddr::new(base), PhysAddr::new(base + 4 * PAGE_SIZE));
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+
+        // 前两页共用一个零级页表，第三页跨过 2MB 边界，需要新的零级页表时内存耗尽
+        let vaddr = VirtAddr::new(0x401f_e000);
+        let paddr = PhysAddr::new(0x8100_0000);
+        let flags = PteFlags::READ;
+        let free_before = allocator.stats().free_frames;
+        assert_eq!(
+            map_range(root, vaddr, paddr, 4, flags, &mut allocator),
+            Err("Out of memory")
+        );
+
+        // 前两页的映射和为它们新建的页表都已撤销
+        for i in 0..4 {
+            assert_eq!(walk_page_table(root_paddr, vaddr + i * PAGE_SIZE), None);
+        }
+        assert!(root.is_empty());
+        assert_eq!(allocator.stats().free_frames, free_before);
+    }
+
+    #[test_case]
+    fn test_map_range_matches_per_page_mapping() {
+        let mut allocator = test_frame_allocator();
+        let batched = new_root(&mut allocator);
+        let single = new_root(&mut allocator);
+
+        // 从零级页表中间开始，跨过两个零级页表边界
+        let vstart = VirtAddr::new(0x7010_0000);
+        let pstart = PhysAddr::new(0x8100_0000);
+        let count = 2 * ENTRIES_PER_TABLE;
+        let flags = PteFlags::READ | PteFlags::WRITE;
+
+        let before = allocator.stats().free_frames;
+        assert_eq!(map_range(batched, vstart, pstart, count, flags, &mut allocator), Ok(count));
+        let batched_tables = before - allocator.stats().free_frames;
+
+        let before = allocator.stats().free_frames;
+        for i in 0..count {
+            let offset = i * PAGE_SIZE;
+            map_page(single, vstart + offset, pstart + offset, flags, &mut allocator)
+                .expect("map_page failed");
+        }
+        assert_eq!(before - allocator.stats().free_frames, batched_tables);
+
+        let batched_paddr = PhysAddr::new(batched as *const PageTable as usize);
+        let single_paddr = PhysAddr::new(single as *const PageTable as usize);
+        for i in 0..count + 1 {
+            let vaddr = vstart + i * PAGE_SIZE + 0x10;
+            assert_eq!(
+                walk_page_table(batched_paddr, vaddr),
+                walk_page_table(single_paddr, vaddr)
+            );
+        }
+    }
+
+    #[test_case]
+    fn test_walk_mappings_and_dump() {
+        use alloc::vec::Vec;
+
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+        let (r, rw) = (PteFlags::READ, PteFlags::READ | PteFlags::WRITE);
+
+        // 三个连续的 4KB 页和一个权限不同的相邻页、2MB 大页、1GB 大页、高半部分的一页
+        let (vaddr, paddr) = (VirtAddr::new(0x5000_0000), PhysAddr::new(0x8100_0000));
+        map_range(root, vaddr, paddr, 3, r, &mut allocator).expect("map_range failed");
+        map_page(root, vaddr + 3 * PAGE_SIZE, paddr + 3 * PAGE_SIZE, rw, &mut allocator)
+            .expect("map_page failed");
+        let (huge, huge_paddr) = (VirtAddr::new(0x4020_0000), PhysAddr::new(0x8060_0000));
+        map_page_2mb(root, huge, huge_paddr, r, &mut allocator).expect("map_page_2mb failed");
+        let (giga, giga_paddr) = (VirtAddr::new(0xc000_0000), PhysAddr::new(0x8000_0000));
+        map_page_1gb(root, giga, giga_paddr, rw, &mut allocator).expect("map_page_1gb failed");
+        let high = VirtAddr::new(0xffff_ffc0_0000_0000);
+        map_page(root, high, paddr, r, &mut allocator).expect("map_page failed");
+
+        // 每个叶子访问一次，按虚拟地址顺序，高半部分的地址符号扩展
+        let mut leaves = Vec::new();
+        walk_mappings(root_paddr, |vaddr, paddr, size, flags| {
+            leaves.push((vaddr.as_usize(), paddr.as_usize(), size, flags.is_writable()));
+        });
+        assert_eq!(leaves.len(), 7);
+        assert_eq!(leaves[0], (0x4020_0000, 0x8060_0000, MEGAPAGE_SIZE, false));
+        assert_eq!(leaves[3], (0x5000_2000, 0x8100_2000, PAGE_SIZE, false));
+        assert_eq!(leaves[4], (0x5000_3000, 0x8100_3000, PAGE_SIZE, true));
+        assert_eq!(leaves[5], (0xc000_0000, 0x8000_0000, GIGAPAGE_SIZE, true));
+        assert_eq!(leaves[6], (high.as_usize(), 0x8100_0000, PAGE_SIZE, false));
+
+        // 相同权限的连续页合并为一行：标题 + 5 段 + 合计
+        let output = crate::console::capture();
+        dump_page_table(root_paddr);
+        let text = output.take();
+        assert_eq!(text.lines().count(), 7);
+        for line in [
+            "0x50000000..0x50003000 -> 0x81000000..0x81003000 R-- (4KB pages, 3 entries)",
+            "0x50003000..0x50004000 -> 0x81003000..0x81004000 RW- (4KB pages, 1 entries)",
+            "0x40200000..0x40400000 -> 0x80600000..0x80800000 R-- (2MB pages, 1 entries)",
+            "0xc0000000..0x100000000 -> 0x80000000..0xc0000000 RW- (1GB pages, 1 entries)",
+            "[PAGING] 7 leaf entries",
+        ] {
+            assert!(text.contains(line), "missing: {}", line);
+        }
+    }
+
+    #[test_case]
+    fn test_map_page_with_mock_allocator() {
+        /// 只交出预先给定的几个页帧，并记录请求和归还的次数
+        struct FixedFrames {
+            frames: [Option<PhysFrame>; 3],
+            requests: usize,
+            returned: usize,
+        }
+
+        impl FrameAllocator for FixedFrames {
+            fn allocate(&mut self) -> Option<PhysFrame> {
+                self.requests += 1;
+                self.frames.iter_mut().find_map(Option::take)
+            }
+
+            fn deallocate(&mut self, _frame: PhysFrame) {
+                self.returned += 1;
+            }
+        }
+
+        let mut allocator = test_frame_allocator();
+        let root = new_root(&mut allocator);
+        let frames = [(); 3].map(|_| allocator.allocate());
+        let mut mock = FixedFrames { frames, requests: 0, returned: 0 };
+        let (r, paddr) = (PteFlags::READ, PhysAddr::new(0x8100_0000));
+
+        // 新的 1GB 区域：一级和零级页表各一个
+        let vaddr = VirtAddr::new(0x4000_0000);
+        map_page(root, vaddr, paddr, r, &mut mock).expect("map_page failed");
+        assert_eq!(mock.requests, 2);
+        // 同一个 2MB 范围内：不再需要页表
+        map_page(root, vaddr + PAGE_SIZE, paddr, r, &mut mock).expect("map_page failed");
+        assert_eq!(mock.requests, 2);
+
+        // 只剩一个页帧：零级页表分配失败
+        let far = VirtAddr::new(0x8000_0000);
+        assert_eq!(map_page(root, far, paddr, r, &mut mock), Err("Out of memory"));
+        assert_eq!(mock.requests, 4);
+
+        // 两个页面都取消映射后，零级和一级页表归还给同一个分配器
+        unmap_page(root, vaddr, &mut mock).expect("unmap_page failed");
+        assert_eq!(mock.returned, 0);
+        unmap_page(root, vaddr + PAGE_SIZE, &mut mock).expect("unmap_page failed");
+        assert_eq!(mock.returned, 2);
+    }
+
+    #[test_case]
+    fn test_mappings_iterator() {
+        use crate::memory::{AddressSpace, MemoryAreaType};
+        use alloc::vec::Vec;
+
+        let mut allocator = test_frame_allocator();
+        let mut space = AddressSpace::new(&mut allocator).expect("Out of memory");
+
+        // 线性区域：两个 4KB 页、一个 2MB 大页、一个 4KB 页
+        let start = VirtAddr::new(0x401f_e000);
+        let size = 2 * PAGE_SIZE + MEGAPAGE_SIZE + PAGE_SIZE;
+        space
+            .map_region_linear(
+                start,
+                PhysAddr::new(0x803f_e000),
+                size,
+                MemoryAreaType::Kernel,
+                &mut allocator,
+            )
+            .expect("map_region_linear failed");
+        // 更低处的 2MB 大页，以及根页表最后一项中的一页
+        let huge = VirtAddr::new(0x2000_0000);
+        let r = PteFlags::READ;
+        map_page_2mb(space.page_table(), huge, PhysAddr::new(0x8060_0000), r, &mut allocator)
+            .expect("map_page_2mb failed");
+        let top = VirtAddr::new(usize::MAX - 2 * PAGE_SIZE + 1);
+        map_page(space.page_table(), top, PhysAddr::new(0x8100_0000), r, &mut allocator)
+            .expect("map_page failed");
+
+        let found: Vec<(usize, MappingSize)> = space
+            .mappings()
+            .expect("mappings failed")
+            .map(|info| (info.vaddr.as_usize(), info.size))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (0x2000_0000, MappingSize::Mega2M),
+                (0x401f_e000, MappingSize::Page4K),
+                (0x401f_f000, MappingSize::Page4K),
+                (0x4020_0000, MappingSize::Mega2M),
+                (0x4040_0000, MappingSize::Page4K),
+                (top.as_usize(), MappingSize::Page4K),
+            ]
+        );
+
+        // 物理地址和标志位与页表一致；与 walk_mappings 看到的叶子相同
+        let mut mappings = space.mappings().expect("mappings failed");
+        let first = mappings.next().expect("no mappings");
+        assert_eq!(first.paddr, PhysAddr::new(0x8060_0000));
+        assert_eq!(first.flags, r | PteFlags::VALID);
+        assert_eq!(mappings.nth(2).map(|info| info.paddr), Some(PhysAddr::new(0x8040_0000)));
+        let mut leaves = 0;
+        walk_mappings(space.page_table_paddr(), |_, _, _, _| leaves += 1);
+        assert_eq!(leaves, found.len());
+
+        // 结束之后一直返回 None
+        assert_eq!(mappings.by_ref().count(), 2);
+        assert!(mappings.next().is_none());
+        space.destroy(&mut allocator);
+    }
+}
+
+/// 在普通内核镜像中也运行的测试（builtin_tests 特性，见 `selftest`）；
+/// 其余测试只在 cargo test 中运行
+#[cfg(any(test, feature = "builtin_tests"))]
+mod selftests {
+    use super::*;
+    use crate::selftest::builtin_tests;
+    use crate::memory::{test_frame_allocator, SimpleFrameAllocator};
+
+    /// 分配并清空一个根页表
+    pub(super) fn new_root(allocator: &mut SimpleFrameAllocator) -> &'static mut PageTable {
+        let frame = allocator.allocate().expect("Out of memory");
+        let root = unsafe { table_mut(frame.start_address()) };
+        root.zero();
+        root
+    }
+
+    builtin_tests! {
+        #[test_case]
+        fn test_unmap_page_frees_empty_tables() {
+            let mut allocator = test_frame_allocator();
+            let root = new_root(&mut allocator);
+            let free_before = allocator.stats().free_frames;
+
+            // 512 页正好填满一个零级页表
+            let vaddr = VirtAddr::new(0x6000_0000);
+            let paddr = PhysAddr::new(0x8100_0000);
+            let flags = PteFlags::READ;
+            map_range(root, vaddr, paddr, 512, flags, &mut allocator).expect("map_range failed");
+            // 一个一级页表 + 一个零级页表
+            assert_eq!(allocator.stats().free_frames, free_before - 2);
+
+            for i in 0..512 {
+                let page = VirtAddr::new(vaddr.as_usize() + i * PAGE_SIZE);
+                assert!(unmap_page(root, page, &mut allocator).is_ok());
+                if i < 511 {
+                    // 零级页表还有其他映射，不能释放
+                    assert_eq!(allocator.stats().free_frames, free_before - 2);
+                }
+            }
+
+            assert_eq!(allocator.stats().free_frames, free_before);
+            assert!(!root.get_entry(vaddr.vpn2()).is_valid());
+        }
+
+        #[test_case]
+        fn test_map_range() {
+            let mut allocator = test_frame_allocator();
+            let root = new_root(&mut allocator);
+            let root_paddr = PhysAddr::new(root as *const PageTable as usize);
+
+            let flags = PteFlags::READ;
+            let mapped = map_range(
+                root,
+                VirtAddr::new(0x5000_0000),
+                PhysAddr::new(0x8100_0000),
+                3,
+                flags,
+                &mut allocator,
+            );
+            assert_eq!(mapped, Ok(3));
+            assert_eq!(
+                walk_page_table(root_paddr, VirtAddr::new(0x5000_2010)),
+                Some(PhysAddr::new(0x8100_2010))
+            );
+            // 与已有映射重叠时失败，冲突之前新建的映射被撤销，已有的映射保留
+            let vaddr = VirtAddr::new(0x4fff_f000);
+            let paddr = PhysAddr::new(0x8200_0000);
+            assert_eq!(
+                map_range(root, vaddr, paddr, 4, flags, &mut allocator),
+                Err("Page already mapped")
+            );
+            assert_eq!(walk_page_table(root_paddr, vaddr), None);
+            assert_eq!(
+                walk_page_table(root_paddr, VirtAddr::new(0x5000_0000)),
+                Some(PhysAddr::new(0x8100_0000))
+            );
+        }
+    }
+}
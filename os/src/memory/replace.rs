@@ -0,0 +1,205 @@
+/*
+ * ============================================
+ * 页面置换
+ * ============================================
+ * 功能：为换出演示选择被换出的页帧
+ * 包括：
+ * - 固定页帧位图：被固定（pinned）的页帧永远不会被换出
+ *   （页表、内核栈、DMA 缓冲区等）
+ * - 时钟（Clock / 二次机会）置换算法
+ *
+ * 页表页在分配时自动固定，释放时取消固定
+ * ============================================
+ */
+
+use super::{PhysFrame, MAX_MEMORY_END, MEMORY_START, PAGE_SIZE};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// ============================================
+// 固定页帧位图
+// ============================================
+
+/// 支持的最大 DRAM 中的页帧数
+const DRAM_FRAMES: usize = (MAX_MEMORY_END - MEMORY_START) / PAGE_SIZE;
+
+/// 固定页帧位图（每位对应一个 DRAM 页帧）
+static PINNED: [AtomicU64; DRAM_FRAMES / 64] = [const { AtomicU64::new(0) }; DRAM_FRAMES / 64];
+
+/// 页帧在位图中的位置（不在 DRAM 中时返回 None）
+fn bit_of(frame: PhysFrame) -> Option<(usize, u64)> {
+    let index = frame
+        .number()
+        .checked_sub(MEMORY_START / PAGE_SIZE)
+        .filter(|&index| index < DRAM_FRAMES)?;
+    Some((index / 64, 1 << (index % 64)))
+}
+
+/// 固定页帧，使其不会被换出
+///
+/// # 说明
+/// DRAM 以外的页帧（MMIO）本来就不参与置换，忽略
+pub fn pin_frame(frame: PhysFrame) {
+    if let Some((word, mask)) = bit_of(frame) {
+        PINNED[word].fetch_or(mask, Ordering::Relaxed);
+    }
+}
+
+/// 取消固定页帧
+pub fn unpin_frame(frame: PhysFrame) {
+    if let Some((word, mask)) = bit_of(frame) {
+        PINNED[word].fetch_and(!mask, Ordering::Relaxed);
+    }
+}
+
+/// 页帧是否被固定
+pub fn is_pinned(frame: PhysFrame) -> bool {
+    bit_of(frame).is_some_and(|(word, mask)| PINNED[word].load(Ordering::Relaxed) & mask != 0)
+}
+
+// ============================================
+// 时钟置换算法
+// ============================================
+
+/// 时钟置换器
+///
+/// # 说明
+/// 页帧按加入顺序排成一个环，指针依次扫描：
+/// - 访问位为 1 的页帧获得第二次机会（清零后跳过）
+/// - 访问位为 0 的页帧被选为换出对象
+/// - 被固定的页帧总是跳过，也不清除访问位
+pub struct PageReplacer {
+    /// 参与置换的页帧
+    frames: Vec<PhysFrame>,
+    /// 与 frames 一一对应的访问位
+    referenced: Vec<bool>,
+    /// 时钟指针
+    hand: usize,
+}
+
+impl PageReplacer {
+    /// 创建空的置换器
+    pub const fn new() -> Self {
+        PageReplacer {
+            frames: Vec::new(),
+            referenced: Vec::new(),
+            hand: 0,
+        }
+    }
+
+    /// 参与置换的页帧数
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// 是否没有页帧
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// 加入页帧（加入时访问位为 1）
+    pub fn insert(&mut self, frame: PhysFrame) {
+        if !self.frames.contains(&frame) {
+            self.frames.push(frame);
+            self.referenced.push(true);
+        }
+    }
+
+    /// 记录一次访问
+    pub fn touch(&mut self, frame: PhysFrame) {
+        if let Some(index) = self.frames.iter().position(|&f| f == frame) {
+            self.referenced[index] = true;
+        }
+    }
+
+    /// 移除页帧（页帧被释放时调用）
+    pub fn remove(&mut self, frame: PhysFrame) {
+        if let Some(index) = self.frames.iter().position(|&f| f == frame) {
+            self.frames.remove(index);
+            self.referenced.remove(index);
+            if index < self.hand {
+                self.hand -= 1;
+            }
+            if self.hand >= self.frames.len() {
+                self.hand = 0;
+            }
+        }
+    }
+
+    /// 选择换出对象
+    ///
+    /// # 返回
+    /// - `Some(frame)`: 被选中的页帧，已从置换器中移除
+    /// - `None`: 所有页帧都被固定（或没有页帧）
+    pub fn select_victim(&mut self) -> Option<PhysFrame> {
+        // 最多扫描两圈：第一圈清除访问位，第二圈必然找到未固定的页帧
+        for _ in 0..2 * self.frames.len() {
+            let index = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            let frame = self.frames[index];
+            if is_pinned(frame) {
+                continue;
+            }
+            if self.referenced[index] {
+                self.referenced[index] = false;
+                continue;
+            }
+            self.remove(frame);
+            return Some(frame);
+        }
+        None
+    }
+}
+
+impl Default for PageReplacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{memory_end, PhysAddr};
+
+    #[test_case]
+    fn test_pinned_frame_is_never_victim() {
+        let frames: Vec<PhysFrame> = PhysFrame::range(
+            PhysAddr::new(memory_end() - 3 * PAGE_SIZE),
+            PhysAddr::new(memory_end()),
+        )
+        .collect();
+        let (a, b) = (frames[0], frames[1]);
+
+        let mut replacer = PageReplacer::new();
+        for &frame in &frames {
+            replacer.insert(frame);
+        }
+        // 时钟指针停在 a，且所有访问位为 0：不固定时 a 就是换出对象
+        replacer.referenced.fill(false);
+        assert_eq!(replacer.hand, 0);
+
+        pin_frame(a);
+        assert!(is_pinned(a));
+        let victim = replacer.select_victim();
+        unpin_frame(a);
+
+        assert_eq!(victim, Some(b));
+        assert!(!is_pinned(a));
+        replacer.insert(b);
+
+        // 全部固定时没有可换出的页帧
+        for &frame in &frames {
+            pin_frame(frame);
+        }
+        assert_eq!(replacer.select_victim(), None);
+        for &frame in &frames {
+            unpin_frame(frame);
+        }
+    }
+}
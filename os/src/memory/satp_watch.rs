@@ -0,0 +1,165 @@
+/*
+ * ============================================
+ * satp 监视
+ * ============================================
+ * 功能：发现绕过 activate 直接改写 satp 的代码
+ *
+ * 原理：
+ * - 所有 satp 写入都应通过 memory::activate（AddressSpace::activate 委托给它），
+ *   它在写入的同时记录期望的 satp 值、所属进程和调用者
+ * - 时钟中断（开启 satp_watch_trap 特性时还有每次陷入）比较实际的 satp 和记录值，
+ *   不一致时打印两个值和最后一次合法写入的调用者
+ * - 同一次不一致只报告一次，直到 satp 恢复或重新 activate
+ *
+ * 早期启动时内核运行在 Bare 模式，还没有调用过 activate：此时监视处于关闭状态。
+ * 之后需要有意回到 Bare 模式的代码先调用 disarm()
+ *
+ * 目前只有一个 hart，因此"每个 hart 一份"的记录用全局变量表示；
+ * 内核还没有进程，记录的 pid 总是 0
+ * ============================================
+ */
+
+use super::AddressSpace;
+use crate::klog;
+use core::panic::Location;
+use spin::Mutex;
+
+/// 一次合法的 satp 写入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SatpRecord {
+    /// 写入的 satp 值
+    pub satp: usize,
+    /// 所属进程（内核为 0）
+    pub pid: usize,
+    /// 调用 activate 的位置
+    pub caller: &'static Location<'static>,
+}
+
+/// 检测到的不一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SatpMismatch {
+    /// 记录的合法写入
+    pub expected: SatpRecord,
+    /// 实际的 satp 值
+    pub actual: usize,
+}
+
+/// 期望的 satp（`None` 表示监视关闭）
+static EXPECTED: Mutex<Option<SatpRecord>> = Mutex::new(None);
+
+/// 最近一次检测到的不一致
+static LAST_MISMATCH: Mutex<Option<SatpMismatch>> = Mutex::new(None);
+
+/// 激活地址空间：写入 satp、刷新 TLB 并记录期望值
+///
+/// # 说明
+/// 这是唯一应该写 satp 的地方；调用者位置通过 `#[track_caller]` 记录，
+/// 经 `AddressSpace::activate` 调用时记录的是它的调用者
+#[track_caller]
+pub fn activate(space: &AddressSpace) {
+    use riscv::register::satp;
+
+    let ppn = space.page_table_paddr().page_number();
+    let caller = Location::caller();
+
+    // 关中断：写 satp 和更新记录之间不能被时钟中断检查
+    crate::interrupts::without_interrupts(|| {
+        unsafe {
+            satp::set(space.mode().satp_mode(), space.asid() as usize, ppn);
+        }
+        // 只刷新此 ASID 的条目，其他地址空间的 TLB 条目保留
+        super::tlb::flush_asid(space.asid());
+        let record = SatpRecord { satp: satp::read().bits(), pid: 0, caller };
+        *EXPECTED.lock() = Some(record);
+    });
+}
+
+/// 关闭监视（有意回到 Bare 模式或切换到未登记的页表之前调用）
+pub fn disarm() {
+    crate::interrupts::without_interrupts(|| *EXPECTED.lock() = None);
+}
+
+/// 当前记录的合法写入（监视关闭时为 `None`）
+pub fn expected() -> Option<SatpRecord> {
+    *EXPECTED.lock()
+}
+
+/// 最近一次检测到的不一致
+pub fn last_mismatch() -> Option<SatpMismatch> {
+    *LAST_MISMATCH.lock()
+}
+
+/// 比较实际的 satp 和记录值
+///
+/// # 返回
+/// - `Some`: 不一致（只在第一次发现时打印）
+/// - `None`: 一致或监视关闭
+///
+/// # 说明
+/// 由时钟中断调用，调用时中断已关闭
+pub(crate) fn check() -> Option<SatpMismatch> {
+    let expected = (*EXPECTED.lock())?;
+    let actual = riscv::register::satp::read().bits();
+    if actual == expected.satp {
+        return None;
+    }
+
+    let mismatch = SatpMismatch { expected, actual };
+    let mut last = LAST_MISMATCH.lock();
+    if *last != Some(mismatch) {
+        klog!("[SATP] !!! satp changed behind activate() !!!");
+        klog!(
+            "[SATP]     expected {:#018x} (pid {}, activated at {})",
+            expected.satp,
+            expected.pid,
+            expected.caller
+        );
+        klog!("[SATP]     actual   {:#018x}", actual);
+        *last = Some(mismatch);
+    }
+    Some(mismatch)
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::{latency_stats, InterruptSource};
+    use crate::memory::{create_kernel_address_space, test_frame_allocator};
+    use riscv::register::{satp, time};
+
+    #[test_case]
+    fn test_raw_satp_write_detected_within_one_tick() {
+        let mut allocator = test_frame_allocator();
+        let space = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+
+        let line = line!() + 1;
+        super::super::activate(&space);
+        let record = expected().expect("watch not armed");
+        assert_eq!(record.satp, satp::read().bits());
+        assert_eq!((record.caller.file(), record.caller.line()), (file!(), line));
+        assert_eq!(check(), None);
+
+        // 测试钩子：绕过 activate 直接回到 Bare 模式
+        unsafe {
+            satp::set(satp::Mode::Bare, 0, 0);
+            crate::memory::tlb::flush_all();
+        }
+
+        // 等待下一次时钟中断
+        let ticks = latency_stats(InterruptSource::Timer).count;
+        let start = time::read64();
+        while latency_stats(InterruptSource::Timer).count == ticks
+            && time::read64() - start < 2 * crate::clock::TICK_INTERVAL
+        {}
+
+        let mismatch = last_mismatch().expect("mismatch not detected");
+        assert_eq!(mismatch, SatpMismatch { expected: record, actual: 0 });
+
+        disarm();
+        assert_eq!(check(), None);
+    }
+}
@@ -0,0 +1,553 @@
+/*
+ * ============================================
+ * 合成页表（测试用）
+ * ============================================
+ * 功能：在普通数组中构造页表树，把页表代码当作纯数据结构测试
+ *
+ * 页表页放在堆上的数组里，对页表代码呈现为从 SYNTHETIC_BASE 开始的"物理地址"：
+ * paging 的 table_ref / table_mut 先通过 to_host 查找已安装的合成页表，
+ * 不依赖真实的物理内存、恒等映射和高半部分别名。
+ * 页表页的物理页号因此是确定的，测试可以直接比较页表项的原始值（快照），
+ * 物理页号移位、标志位掩码之类的编码错误表现为断言失败，而不是莫名其妙的页错误
+ *
+ * 构造函数（map、set_entry）直接写页表项，不经过 map_page，
+ * 可以写入硬件会拒绝的页表项；SyntheticTables 同时是帧分配器，
+ * map_page / unmap_page 从同一块数组中分配和归还页表页
+ *
+ * 同一时间只安装一份合成页表：创建新的一份会替换之前的
+ * ============================================
+ */
+
+use super::paging::MappingSize;
+use super::{
+    FrameAllocator, PageTable, PageTableEntry, PhysAddr, PhysFrame, PteFlags, VirtAddr,
+    ENTRIES_PER_TABLE, PAGE_SIZE,
+};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// 第一个合成页表页的"物理地址"（在 DRAM 和设备寄存器之外）
+pub const SYNTHETIC_BASE: usize = 0x40_0000_0000;
+
+/// 已安装的合成页表的起始地址（0 表示没有安装）
+static ARENA_START: AtomicUsize = AtomicUsize::new(0);
+
+/// 已安装的合成页表的页数
+static ARENA_FRAMES: AtomicUsize = AtomicUsize::new(0);
+
+/// 合成物理地址所在页表页的实际地址（不是合成页表中的地址时为 None）
+pub(super) fn to_host(paddr: PhysAddr) -> Option<usize> {
+    let start = ARENA_START.load(Ordering::Relaxed);
+    let offset = paddr.as_usize().checked_sub(SYNTHETIC_BASE)?;
+    (start != 0 && offset < ARENA_FRAMES.load(Ordering::Relaxed) * PAGE_SIZE)
+        .then_some(start + offset)
+}
+
+/// 页表页的实际地址对应的合成物理地址（不在合成页表中时为 None）
+pub(super) fn to_synthetic(vaddr: usize) -> Option<PhysAddr> {
+    let start = ARENA_START.load(Ordering::Relaxed);
+    let offset = vaddr.checked_sub(start)?;
+    (start != 0 && offset < ARENA_FRAMES.load(Ordering::Relaxed) * PAGE_SIZE)
+        .then(|| PhysAddr::new(SYNTHETIC_BASE + offset))
+}
+
+/// 原始值：指向第 `index` 页合成页表的非叶子页表项
+pub const fn table_entry(index: usize) -> usize {
+    ((SYNTHETIC_BASE / PAGE_SIZE + index) << 10) | PteFlags::VALID.bits()
+}
+
+/// 原始值：映射到 `paddr` 的叶子页表项
+pub const fn leaf_entry(paddr: usize, flags: PteFlags) -> usize {
+    ((paddr / PAGE_SIZE) << 10) | flags.bits() | PteFlags::VALID.bits()
+}
+
+/// 一棵合成页表（第 0 页是根页表）
+pub struct SyntheticTables {
+    /// 页表页
+    tables: Vec<PageTable>,
+    /// 下一个没有用过的页（归还的页不再分配）
+    next: usize,
+    /// 作为帧分配器归还的页数
+    freed: usize,
+}
+
+impl SyntheticTables {
+    /// 分配 `frames` 页并安装（第 0 页作为根页表）
+    pub fn new(frames: usize) -> Self {
+        assert!(frames > 0, "synthetic tables need a root");
+        let mut tables = Vec::with_capacity(frames);
+        tables.resize_with(frames, || PageTable {
+            entries: [PageTableEntry::new(); ENTRIES_PER_TABLE],
+        });
+        // 失败的内置测试不会运行 Drop，直接替换留下的那一份
+        ARENA_START.store(tables.as_ptr() as usize, Ordering::Relaxed);
+        ARENA_FRAMES.store(frames, Ordering::Relaxed);
+        SyntheticTables { tables, next: 1, freed: 0 }
+    }
+
+    /// 根页表的合成物理地址
+    pub fn root(&self) -> PhysAddr {
+        PhysAddr::new(SYNTHETIC_BASE)
+    }
+
+    /// 根页表（供 map_page 等函数修改）
+    ///
+    /// # 说明
+    /// 与 `self` 分开借用：同一次调用中 `self` 还要作为帧分配器
+    pub fn root_table(&self) -> &'static mut PageTable {
+        unsafe { &mut *(self.tables.as_ptr() as *mut PageTable) }
+    }
+
+    /// 已经分配出去的页数（包括根页表）
+    pub fn used(&self) -> usize {
+        self.next
+    }
+
+    /// 作为帧分配器归还的页数
+    pub fn freed(&self) -> usize {
+        self.freed
+    }
+
+    /// 页表项的原始值指向的合成页表页
+    fn table_index(bits: usize) -> usize {
+        let paddr = PageTableEntry(bits).phys_addr().as_usize();
+        let index = paddr.wrapping_sub(SYNTHETIC_BASE) / PAGE_SIZE;
+        assert!(index < ARENA_FRAMES.load(Ordering::Relaxed), "entry points outside the arena");
+        index
+    }
+
+    /// 在 `vaddr` 路径上第 `level` 级写入原始页表项，缺少的中间页表依次分配
+    ///
+    /// # 说明
+    /// 不做任何检查，可以写入保留位、只写的叶子等硬件会拒绝的页表项
+    pub fn set_entry(&mut self, vaddr: VirtAddr, level: usize, bits: usize) {
+        let mut index = 0;
+        for current in (level + 1..3).rev() {
+            let vpn = vaddr.vpn(current, super::PagingMode::Sv39);
+            let entry = self.tables[index].entries[vpn].bits();
+            index = if entry & PteFlags::VALID.bits() != 0 {
+                Self::table_index(entry)
+            } else {
+                let next = self.allocate().expect("synthetic arena exhausted");
+                let next = (next.start_address().as_usize() - SYNTHETIC_BASE) / PAGE_SIZE;
+                self.tables[index].entries[vpn] = PageTableEntry(table_entry(next));
+                next
+            };
+        }
+        let vpn = vaddr.vpn(level, super::PagingMode::Sv39);
+        self.tables[index].entries[vpn] = PageTableEntry(bits);
+    }
+
+    /// 建立一个 4KB / 2MB / 1GB 的叶子映射
+    pub fn map(&mut self, vaddr: VirtAddr, paddr: usize, size: MappingSize, flags: PteFlags) {
+        let level = match size {
+            MappingSize::Page4K => 0,
+            MappingSize::Mega2M => 1,
+            MappingSize::Giga1G => 2,
+        };
+        self.set_entry(vaddr, level, leaf_entry(paddr, flags));
+    }
+
+    /// `vaddr` 路径上第 `level` 级页表项的原始值（路径中断时为 None）
+    pub fn entry(&self, vaddr: VirtAddr, level: usize) -> Option<usize> {
+        let mut index = 0;
+        for current in (level + 1..3).rev() {
+            let vpn = vaddr.vpn(current, super::PagingMode::Sv39);
+            let entry = self.tables[index].entries[vpn].bits();
+            if entry & PteFlags::VALID.bits() == 0 {
+                return None;
+            }
+            index = Self::table_index(entry);
+        }
+        Some(self.tables[index].entries[vaddr.vpn(level, super::PagingMode::Sv39)].bits())
+    }
+}
+
+impl FrameAllocator for SyntheticTables {
+    fn allocate(&mut self) -> Option<PhysFrame> {
+        if self.next == self.tables.len() {
+            return None;
+        }
+        self.next += 1;
+        Some(PhysFrame::from_addr(PhysAddr::new(SYNTHETIC_BASE + (self.next - 1) * PAGE_SIZE)))
+    }
+
+    fn deallocate(&mut self, _frame: PhysFrame) {
+        self.freed += 1;
+    }
+}
+
+impl Drop for SyntheticTables {
+    fn drop(&mut self) {
+        let start = self.tables.as_ptr() as usize;
+        if ARENA_START.compare_exchange(start, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            ARENA_FRAMES.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(any(test, feature = "builtin_tests"))]
+mod tests {
+    use super::*;
+    use crate::memory::paging::{
+        map_page, map_page_1gb, map_page_2mb, unmap_page, verify_page_table, walk_page_table,
+    };
+    use crate::selftest::builtin_tests;
+
+    const R: PteFlags = PteFlags::READ;
+    const RW: PteFlags = PteFlags::READ.union(PteFlags::WRITE);
+    const RESERVED: usize = 1 << 54;
+
+    /// 一个遍历用例：在空的合成页表上执行 `setup`，然后遍历 `vaddr`
+    struct WalkCase {
+        name: &'static str,
+        setup: fn(&mut SyntheticTables),
+        vaddr: usize,
+        expect: Option<usize>,
+    }
+
+    fn va(addr: usize) -> VirtAddr {
+        VirtAddr::new(addr)
+    }
+
+    /// 合法的映射
+    const WALKS: &[WalkCase] = &[
+        WalkCase {
+            name: "4K page, offset 0",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, RW),
+            vaddr: 0x1000,
+            expect: Some(0x8020_0000),
+        },
+        WalkCase {
+            name: "4K page, last byte",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, RW),
+            vaddr: 0x1fff,
+            expect: Some(0x8020_0fff),
+        },
+        WalkCase {
+            name: "4K page, all VPNs 0",
+            setup: |t| t.map(va(0), 0x8020_0000, MappingSize::Page4K, R),
+            vaddr: 0x123,
+            expect: Some(0x8020_0123),
+        },
+        WalkCase {
+            name: "4K page, all VPNs 511 (lower half)",
+            setup: |t| t.map(va(0x3f_ffff_f000), 0x8030_0000, MappingSize::Page4K, R),
+            vaddr: 0x3f_ffff_ffff,
+            expect: Some(0x8030_0fff),
+        },
+        WalkCase {
+            name: "4K page, all VPNs 511 (upper half)",
+            setup: |t| t.map(va(usize::MAX - 0xfff), 0x8030_0000, MappingSize::Page4K, R),
+            vaddr: usize::MAX,
+            expect: Some(0x8030_0fff),
+        },
+        WalkCase {
+            name: "2M page, offset 0",
+            setup: |t| t.map(va(0x20_0000), 0x8040_0000, MappingSize::Mega2M, RW),
+            vaddr: 0x20_0000,
+            expect: Some(0x8040_0000),
+        },
+        WalkCase {
+            name: "2M page, last byte",
+            setup: |t| t.map(va(0x20_0000), 0x8040_0000, MappingSize::Mega2M, RW),
+            vaddr: 0x3f_ffff,
+            expect: Some(0x805f_ffff),
+        },
+        WalkCase {
+            name: "2M page, VPN2 255",
+            setup: |t| t.map(va(0x3f_c000_0000), 0x8020_0000, MappingSize::Mega2M, R),
+            vaddr: 0x3f_c000_4321,
+            expect: Some(0x8020_4321),
+        },
+        WalkCase {
+            name: "1G page, offset 0",
+            setup: |t| t.map(va(0x4000_0000), 0x8000_0000, MappingSize::Giga1G, RW),
+            vaddr: 0x4000_0000,
+            expect: Some(0x8000_0000),
+        },
+        WalkCase {
+            name: "1G page, last byte",
+            setup: |t| t.map(va(0x4000_0000), 0x8000_0000, MappingSize::Giga1G, RW),
+            vaddr: 0x7fff_ffff,
+            expect: Some(0xbfff_ffff),
+        },
+        WalkCase {
+            name: "1G page, kernel high half",
+            setup: |t| t.map(va(0xffff_ffc0_0000_0000), 0x8000_0000, MappingSize::Giga1G, RW),
+            vaddr: 0xffff_ffc0_0012_3456,
+            expect: Some(0x8012_3456),
+        },
+        WalkCase {
+            name: "execute-only leaf",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, PteFlags::EXECUTE),
+            vaddr: 0x1004,
+            expect: Some(0x8020_0004),
+        },
+        WalkCase {
+            name: "U, G, A and D bits",
+            setup: |t| {
+                let flags = R | PteFlags::USER | PteFlags::GLOBAL;
+                let flags = flags | PteFlags::ACCESSED | PteFlags::DIRTY;
+                t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, flags)
+            },
+            vaddr: 0x1010,
+            expect: Some(0x8020_0010),
+        },
+        WalkCase {
+            name: "COW bit in RSW",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, R | PteFlags::COW),
+            vaddr: 0x1000,
+            expect: Some(0x8020_0000),
+        },
+        WalkCase {
+            name: "largest 44-bit PPN",
+            setup: |t| t.map(va(0x1000), ((1 << 44) - 1) << 12, MappingSize::Page4K, R),
+            vaddr: 0x1abc,
+            expect: Some((((1 << 44) - 1) << 12) + 0xabc),
+        },
+    ];
+
+    /// 没有映射或硬件会拒绝的页表项
+    const FAULTS: &[WalkCase] = &[
+        WalkCase {
+            name: "empty root",
+            setup: |_| {},
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "neighbouring 4K page",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, RW),
+            vaddr: 0x2000,
+            expect: None,
+        },
+        WalkCase {
+            name: "invalid level-1 entry",
+            setup: |t| t.set_entry(va(0x1000), 1, 0),
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "invalid leaf",
+            setup: |t| t.set_entry(va(0x1000), 0, 0),
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "V clear with other bits set",
+            setup: |t| t.set_entry(va(0x1000), 0, leaf_entry(0x8020_0000, RW) & !1),
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "non-canonical address",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, RW),
+            vaddr: (1 << 39) | 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "non-leaf at level 0",
+            setup: |t| t.set_entry(va(0x1000), 0, table_entry(0)),
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "A and D without R/W/X at level 0",
+            setup: |t| {
+                let flags = PteFlags::ACCESSED | PteFlags::DIRTY;
+                t.set_entry(va(0x1000), 0, leaf_entry(0x8020_0000, flags))
+            },
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "write-only leaf",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, PteFlags::WRITE),
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "write+execute leaf without read",
+            setup: |t| {
+                let flags = PteFlags::WRITE | PteFlags::EXECUTE;
+                t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, flags)
+            },
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "misaligned 2M page",
+            setup: |t| t.map(va(0x20_0000), 0x8020_1000, MappingSize::Mega2M, RW),
+            vaddr: 0x20_0000,
+            expect: None,
+        },
+        WalkCase {
+            name: "misaligned 1G page",
+            setup: |t| t.map(va(0x4000_0000), 0x8020_0000, MappingSize::Giga1G, RW),
+            vaddr: 0x4000_0000,
+            expect: None,
+        },
+        WalkCase {
+            name: "reserved bits in leaf",
+            setup: |t| t.set_entry(va(0x1000), 0, leaf_entry(0x8020_0000, RW) | RESERVED),
+            vaddr: 0x1000,
+            expect: None,
+        },
+        WalkCase {
+            name: "reserved bits in non-leaf",
+            setup: |t| {
+                t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, RW);
+                t.set_entry(va(0x1000), 2, table_entry(1) | (1 << 63));
+            },
+            vaddr: 0x1000,
+            expect: None,
+        },
+    ];
+
+    fn run_walks(cases: &[WalkCase]) {
+        for case in cases {
+            let mut tables = SyntheticTables::new(4);
+            (case.setup)(&mut tables);
+            let got = walk_page_table(tables.root(), va(case.vaddr));
+            assert_eq!(got, case.expect.map(PhysAddr::new), "{}", case.name);
+        }
+    }
+
+    /// 一个 verify_page_table 用例：期望的叶子数或第一个错误
+    struct VerifyCase {
+        name: &'static str,
+        setup: fn(&mut SyntheticTables),
+        expect: Result<usize, &'static str>,
+    }
+
+    const VERIFIES: &[VerifyCase] = &[
+        VerifyCase {
+            name: "empty root",
+            setup: |_| {},
+            expect: Ok(0),
+        },
+        VerifyCase {
+            name: "one mapping of each size",
+            setup: |t| {
+                t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, RW);
+                t.map(va(0x20_0000), 0x8040_0000, MappingSize::Mega2M, RW);
+                t.map(va(0x4000_0000), 0x8000_0000, MappingSize::Giga1G, RW);
+            },
+            expect: Ok(3),
+        },
+        VerifyCase {
+            name: "reserved bits",
+            setup: |t| t.set_entry(va(0x1000), 0, leaf_entry(0x8020_0000, RW) | RESERVED),
+            expect: Err("Reserved PTE bits set"),
+        },
+        VerifyCase {
+            name: "non-leaf at level 0",
+            setup: |t| t.set_entry(va(0x1000), 0, table_entry(0)),
+            expect: Err("Non-leaf entry at level 0"),
+        },
+        VerifyCase {
+            name: "write-only leaf",
+            setup: |t| t.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, PteFlags::WRITE),
+            expect: Err("Writable leaf without read permission"),
+        },
+        VerifyCase {
+            name: "misaligned 2M page",
+            setup: |t| t.map(va(0x20_0000), 0x8020_1000, MappingSize::Mega2M, RW),
+            expect: Err("Misaligned superpage"),
+        },
+    ];
+
+    builtin_tests! {
+        #[test_case]
+        fn test_synthetic_walks() {
+            run_walks(WALKS);
+        }
+
+        #[test_case]
+        fn test_synthetic_walk_faults() {
+            run_walks(FAULTS);
+        }
+
+        #[test_case]
+        fn test_map_page_snapshot() {
+            let mut tables = SyntheticTables::new(4);
+            let root = tables.root_table();
+            let vaddr = va(0x1000);
+            map_page(root, vaddr, PhysAddr::new(0x8020_0000), R, &mut tables)
+                .expect("map_page failed");
+
+            // 中间页表依次取第 1、2 页；叶子 = PPN 0x80200 << 10 | R | V
+            assert_eq!(tables.used(), 3);
+            assert_eq!(tables.entry(vaddr, 2), Some(0x1_0000_0401));
+            assert_eq!(tables.entry(vaddr, 1), Some(0x1_0000_0801));
+            assert_eq!(tables.entry(vaddr, 0), Some(0x2008_0003));
+            assert_eq!(verify_page_table(tables.root()), Ok(1));
+        }
+
+        #[test_case]
+        fn test_huge_page_snapshots() {
+            let mut tables = SyntheticTables::new(4);
+            let root = tables.root_table();
+            let mega = va(0x20_0000);
+            map_page_2mb(root, mega, PhysAddr::new(0x8040_0000), RW, &mut tables)
+                .expect("map_page_2mb failed");
+            let giga = va(0x4000_0000);
+            map_page_1gb(root, giga, PhysAddr::new(0x8000_0000), RW, &mut tables)
+                .expect("map_page_1gb failed");
+
+            assert_eq!(tables.entry(mega, 2), Some(table_entry(1)));
+            assert_eq!(tables.entry(mega, 1), Some(0x2010_0007));
+            assert_eq!(tables.entry(giga, 2), Some(0x2000_0007));
+            assert_eq!(tables.used(), 2);
+        }
+
+        #[test_case]
+        fn test_map_page_errors() {
+            let mut tables = SyntheticTables::new(4);
+            tables.map(va(0x4000_0000), 0x8000_0000, MappingSize::Giga1G, RW);
+            tables.map(va(0x1000), 0x8020_0000, MappingSize::Page4K, RW);
+            let root = tables.root_table();
+            let paddr = PhysAddr::new(0x8030_0000);
+
+            assert_eq!(
+                map_page(root, va(0x4000_1000), paddr, RW, &mut tables),
+                Err("Address already covered by a huge page")
+            );
+            assert_eq!(
+                map_page(root, va(0x1000), paddr, RW, &mut tables),
+                Err("Page already mapped")
+            );
+            // 原来的页表项没有被改写
+            assert_eq!(tables.entry(va(0x1000), 0), Some(leaf_entry(0x8020_0000, RW)));
+        }
+
+        #[test_case]
+        fn test_unmap_page_frees_tables() {
+            let mut tables = SyntheticTables::new(4);
+            let root = tables.root_table();
+            let vaddr = va(0x1000);
+            map_page(root, vaddr, PhysAddr::new(0x8020_0000), R, &mut tables)
+                .expect("map_page failed");
+
+            assert_eq!(unmap_page(root, vaddr, &mut tables), Ok(PhysAddr::new(0x8020_0000)));
+            assert_eq!(tables.freed(), 2);
+            assert_eq!(tables.entry(vaddr, 2), Some(0));
+            assert_eq!(unmap_page(root, vaddr, &mut tables), Err("Page not mapped"));
+            assert_eq!(walk_page_table(tables.root(), vaddr), None);
+        }
+
+        #[test_case]
+        fn test_verify_page_table() {
+            for case in VERIFIES {
+                let mut tables = SyntheticTables::new(4);
+                (case.setup)(&mut tables);
+                assert_eq!(verify_page_table(tables.root()), case.expect, "{}", case.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * ============================================
+ * TLB 管理
+ * ============================================
+ * 功能：封装 sfence.vma 的四种形式，避免各处手写操作数
+ *
+ * | 函数              | 指令                    | 刷新范围                         |
+ * |-------------------|-------------------------|----------------------------------|
+ * | flush_all         | sfence.vma zero, zero   | 全部地址、全部 ASID              |
+ * | flush_addr        | sfence.vma va, zero     | 一个地址、全部 ASID              |
+ * | flush_asid        | sfence.vma zero, asid   | 一个 ASID 的全部地址（不含全局） |
+ * | flush_addr_asid   | sfence.vma va, asid     | 一个 ASID 中的一个地址           |
+ *
+ * 测试和 tlb_stats 特性下统计每种刷新的次数（stats），用于性能调试
+ * ============================================
+ */
+
+use super::VirtAddr;
+
+/// 刷新整个 TLB
+#[inline]
+pub fn flush_all() {
+    count(Kind::All);
+    unsafe { core::arch::asm!("sfence.vma zero, zero") };
+}
+
+/// 刷新所有 ASID 中 `vaddr` 所在页的条目
+#[inline]
+pub fn flush_addr(vaddr: VirtAddr) {
+    count(Kind::Addr);
+    unsafe { core::arch::asm!("sfence.vma {0}, zero", in(reg) vaddr.as_usize()) };
+}
+
+/// 刷新一个 ASID 的全部条目（全局映射不受影响）
+#[inline]
+pub fn flush_asid(asid: u16) {
+    count(Kind::Asid);
+    unsafe { core::arch::asm!("sfence.vma zero, {0}", in(reg) asid as usize) };
+}
+
+/// 刷新一个 ASID 中 `vaddr` 所在页的条目
+#[inline]
+pub fn flush_addr_asid(vaddr: VirtAddr, asid: u16) {
+    count(Kind::AddrAsid);
+    unsafe {
+        core::arch::asm!("sfence.vma {0}, {1}", in(reg) vaddr.as_usize(), in(reg) asid as usize)
+    };
+}
+
+/// 刷新的种类
+#[derive(Clone, Copy)]
+enum Kind {
+    All,
+    Addr,
+    Asid,
+    AddrAsid,
+}
+
+/// 记录一次刷新（统计未启用：什么都不做）
+#[cfg(not(any(test, feature = "tlb_stats")))]
+#[inline(always)]
+fn count(_kind: Kind) {}
+
+#[cfg(any(test, feature = "tlb_stats"))]
+pub use stats::{stats, TlbStats};
+
+#[cfg(any(test, feature = "tlb_stats"))]
+use stats::count;
+
+#[cfg(any(test, feature = "tlb_stats"))]
+mod stats {
+    use super::Kind;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    /// 各种刷新的次数（按 Kind 的顺序）
+    static COUNTS: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+
+    /// 启动以来各种刷新的次数
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct TlbStats {
+        pub all: u64,
+        pub addr: u64,
+        pub asid: u64,
+        pub addr_asid: u64,
+    }
+
+    pub(super) fn count(kind: Kind) {
+        COUNTS[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 读取刷新次数
+    pub fn stats() -> TlbStats {
+        let [all, addr, asid, addr_asid] =
+            core::array::from_fn(|i| COUNTS[i].load(Ordering::Relaxed));
+        TlbStats { all, addr, asid, addr_asid }
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::paging::leaf_entry_mut;
+    use crate::memory::{create_kernel_address_space, map_page, test_frame_allocator, unmap_page};
+    use crate::memory::PteFlags;
+    use riscv::register::satp;
+
+    #[test_case]
+    fn test_remap_observed_after_flush_addr() {
+        let mut allocator = test_frame_allocator();
+        let mut space = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+        let (a, b) = (allocator.allocate().unwrap(), allocator.allocate().unwrap());
+        unsafe {
+            (a.start_address().as_usize() as *mut u64).write_volatile(0xaaaa);
+            (b.start_address().as_usize() as *mut u64).write_volatile(0xbbbb);
+        }
+        let vaddr = VirtAddr::new(0x4000_0000);
+        let rw = PteFlags::READ | PteFlags::WRITE;
+        map_page(space.page_table(), vaddr, a.start_address(), rw, &mut allocator).unwrap();
+        let ptr = vaddr.as_usize() as *const u64;
+
+        let before = stats();
+        let old_satp = satp::read();
+        let (asid, root_ppn) = (space.asid(), space.page_table_paddr().page_number());
+        let (first, second) = unsafe {
+            satp::set(satp::Mode::Sv39, asid as usize, root_ppn);
+            flush_asid(asid);
+            // 读一次，TLB 中缓存了指向 a 的转换
+            let first = ptr.read_volatile();
+
+            // 直接改写页表项指向 b，只刷新这一个地址
+            let pte = leaf_entry_mut(space.page_table(), vaddr).unwrap();
+            pte.set(b.number(), rw | PteFlags::VALID);
+            flush_addr(vaddr);
+            let second = ptr.read_volatile();
+
+            satp::set(old_satp.mode(), old_satp.asid(), old_satp.ppn());
+            flush_all();
+            (first, second)
+        };
+        assert_eq!((first, second), (0xaaaa, 0xbbbb));
+
+        let after = stats();
+        assert!(after.addr > before.addr);
+        assert!(after.asid > before.asid && after.all > before.all);
+
+        unmap_page(space.page_table(), vaddr, &mut allocator).unwrap();
+        space.destroy(&mut allocator);
+        allocator.deallocate(a);
+        allocator.deallocate(b);
+    }
+}
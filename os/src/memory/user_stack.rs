@@ -0,0 +1,274 @@
+/*
+ * ============================================
+ * 用户栈初始布局
+ * ============================================
+ * 功能：按 RISC-V psABI 在用户栈顶放置 argc / argv / envp / auxv
+ *
+ * 布局（地址从高到低）：
+ *   栈顶
+ *   envp 字符串、argv 字符串（argv[0] 地址最低）
+ *   填充（使 sp 16 字节对齐）
+ *   auxv：(类型, 值) 对，以 AT_NULL (0, 0) 结尾
+ *   envp 指针数组，以 NULL 结尾
+ *   argv 指针数组，以 NULL 结尾
+ *   argc                                  ← sp（16 字节对齐）
+ *
+ * 入口处寄存器不传递参数，程序从 sp 读取 argc 和各数组
+ * ============================================
+ */
+
+use super::{VirtAddr, PAGE_SIZE};
+use alloc::vec::Vec;
+
+/// 单个参数 / 环境变量字符串的最大长度（包括结尾的 NUL）
+pub const MAX_ARG_STRLEN: usize = PAGE_SIZE;
+
+/// 栈指针对齐（RISC-V psABI）
+pub const STACK_ALIGN: usize = 16;
+
+/// 用户栈布局错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserStackError {
+    /// 栈顶没有 16 字节对齐
+    MisalignedTop,
+    /// 字符串超过 MAX_ARG_STRLEN 或包含 NUL
+    BadString,
+    /// 栈区域放不下
+    Overflow,
+}
+
+/// 构建完成的初始用户栈
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserStack {
+    /// 入口处的 sp（指向 argc）
+    pub sp: VirtAddr,
+    /// 参数个数
+    pub argc: usize,
+    /// argv 数组的地址
+    pub argv: VirtAddr,
+    /// envp 数组的地址
+    pub envp: VirtAddr,
+}
+
+/// 用户栈布局构建器
+///
+/// # 示例
+/// ```ignore
+/// let stack = UserStackBuilder::new(&["sh", "-c", "ls"], &["PATH=/bin"])
+///     .build(stack_bytes, stack_top)?;
+/// ```
+pub struct UserStackBuilder<'a> {
+    argv: &'a [&'a str],
+    envp: &'a [&'a str],
+    auxv: &'a [(usize, usize)],
+}
+
+impl<'a> UserStackBuilder<'a> {
+    /// 创建构建器
+    pub fn new(argv: &'a [&'a str], envp: &'a [&'a str]) -> Self {
+        UserStackBuilder {
+            argv,
+            envp,
+            auxv: &[],
+        }
+    }
+
+    /// 设置辅助向量（不包括结尾的 AT_NULL）
+    pub fn auxv(mut self, auxv: &'a [(usize, usize)]) -> Self {
+        self.auxv = auxv;
+        self
+    }
+
+    /// 在栈区域中写入初始布局
+    ///
+    /// # 参数
+    /// - `stack`: 栈区域的内容（内核可写的视图），对应虚拟地址 `[top - stack.len(), top)`
+    /// - `top`: 栈顶虚拟地址（16 字节对齐）
+    ///
+    /// # 返回
+    /// - `Ok(UserStack)`: 入口处的 sp 及各数组地址
+    /// - `Err`: 栈顶未对齐、字符串无效或空间不足；此时 `stack` 的内容未定义
+    pub fn build(&self, stack: &mut [u8], top: VirtAddr) -> Result<UserStack, UserStackError> {
+        if !top.is_aligned(STACK_ALIGN) {
+            return Err(UserStackError::MisalignedTop);
+        }
+        let top = top.as_usize();
+        let bottom = top.checked_sub(stack.len()).ok_or(UserStackError::Overflow)?;
+        let mut writer = StackWriter { stack, bottom, pos: top };
+
+        // 字符串：先放 envp，再放 argv，各自倒序，使 argv[0] 地址最低
+        let mut envp_addrs = Vec::with_capacity(self.envp.len());
+        for s in self.envp.iter().rev() {
+            envp_addrs.push(writer.push_str(s)?);
+        }
+        let mut argv_addrs = Vec::with_capacity(self.argv.len());
+        for s in self.argv.iter().rev() {
+            argv_addrs.push(writer.push_str(s)?);
+        }
+
+        // 指针区：argc + argv + NULL + envp + NULL + auxv + AT_NULL
+        let words = 1
+            + self.argv.len() + 1
+            + self.envp.len() + 1
+            + 2 * (self.auxv.len() + 1);
+        let sp = writer
+            .pos
+            .checked_sub(words * 8)
+            .map(|addr| addr & !(STACK_ALIGN - 1))
+            .filter(|&addr| addr >= bottom)
+            .ok_or(UserStackError::Overflow)?;
+
+        // 填充清零
+        writer.fill_zero(sp, writer.pos);
+        writer.pos = sp;
+
+        let argv = sp + 8;
+        let envp = argv + (self.argv.len() + 1) * 8;
+        let mut cursor = sp;
+        let mut put = |value: usize| {
+            writer.write_word(cursor, value);
+            cursor += 8;
+        };
+        put(self.argv.len());
+        argv_addrs.iter().rev().for_each(|&addr| put(addr));
+        put(0);
+        envp_addrs.iter().rev().for_each(|&addr| put(addr));
+        put(0);
+        for &(kind, value) in self.auxv {
+            put(kind);
+            put(value);
+        }
+        put(0);
+        put(0);
+
+        debug_assert!(sp.is_multiple_of(STACK_ALIGN), "user sp misaligned");
+        Ok(UserStack {
+            sp: VirtAddr::new(sp),
+            argc: self.argv.len(),
+            argv: VirtAddr::new(argv),
+            envp: VirtAddr::new(envp),
+        })
+    }
+}
+
+/// 自顶向下写栈区域
+struct StackWriter<'s> {
+    stack: &'s mut [u8],
+    /// 栈区域最低的虚拟地址
+    bottom: usize,
+    /// 当前已使用部分的最低虚拟地址
+    pos: usize,
+}
+
+impl StackWriter<'_> {
+    /// 压入以 NUL 结尾的字符串，返回其虚拟地址
+    fn push_str(&mut self, s: &str) -> Result<usize, UserStackError> {
+        if s.len() + 1 > MAX_ARG_STRLEN || s.as_bytes().contains(&0) {
+            return Err(UserStackError::BadString);
+        }
+        let addr = self
+            .pos
+            .checked_sub(s.len() + 1)
+            .filter(|&addr| addr >= self.bottom)
+            .ok_or(UserStackError::Overflow)?;
+
+        let offset = addr - self.bottom;
+        self.stack[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+        self.stack[offset + s.len()] = 0;
+        self.pos = addr;
+        Ok(addr)
+    }
+
+    /// 把 `[start, end)` 清零
+    fn fill_zero(&mut self, start: usize, end: usize) {
+        self.stack[start - self.bottom..end - self.bottom].fill(0);
+    }
+
+    /// 写入一个 64 位小端字
+    fn write_word(&mut self, addr: usize, value: usize) {
+        let offset = addr - self.bottom;
+        self.stack[offset..offset + 8].copy_from_slice(&(value as u64).to_le_bytes());
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const TOP: usize = 0x4000_0000;
+
+    /// 按小端字和原始字节拼出期望的栈内容
+    fn fixture(words: &[usize], tail: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = words.iter().flat_map(|w| (*w as u64).to_le_bytes()).collect();
+        bytes.extend_from_slice(tail);
+        bytes
+    }
+
+    /// 构建并返回 sp 以上的全部字节
+    fn layout(argv: &[&str], envp: &[&str]) -> (UserStack, Vec<u8>) {
+        let mut stack = [0xccu8; 256];
+        let built = UserStackBuilder::new(argv, envp)
+            .build(&mut stack, VirtAddr::new(TOP))
+            .expect("build failed");
+        let offset = built.sp.as_usize() - (TOP - stack.len());
+        (built, stack[offset..].to_vec())
+    }
+
+    #[test_case]
+    fn test_user_stack_layouts() {
+        // 空 argv：argc, NULL, NULL, AT_NULL, 8 字节填充
+        let (stack, bytes) = layout(&[], &[]);
+        assert_eq!(stack.sp.as_usize(), TOP - 48);
+        assert_eq!(bytes, fixture(&[0, 0, 0, 0, 0, 0], &[]));
+
+        // 一个参数："ls\0" 在 TOP-3，指针区 48 字节，sp = TOP-64
+        let (stack, bytes) = layout(&["ls"], &[]);
+        assert_eq!(stack.sp.as_usize(), TOP - 64);
+        assert_eq!(stack.argv.as_usize(), TOP - 56);
+        assert_eq!(stack.envp.as_usize(), TOP - 40);
+        let mut tail = vec![0u8; 13];
+        tail.extend_from_slice(b"ls\0");
+        assert_eq!(bytes, fixture(&[1, TOP - 3, 0, 0, 0, 0], &tail));
+
+        // 参数和环境变量："a\0bc\0X=1\0" 从 TOP-9 开始，sp = TOP-80
+        let (stack, bytes) = layout(&["a", "bc"], &["X=1"]);
+        assert_eq!(stack.sp.as_usize(), TOP - 80);
+        assert_eq!(stack.argc, 2);
+        let mut tail = vec![0u8; 7];
+        tail.extend_from_slice(b"a\0bc\0X=1\0");
+        let words = [2, TOP - 9, TOP - 7, 0, TOP - 4, 0, 0, 0];
+        assert_eq!(bytes, fixture(&words, &tail));
+    }
+
+    #[test_case]
+    fn test_user_stack_errors() {
+        let mut stack = vec![0u8; 2 * PAGE_SIZE];
+        let top = VirtAddr::new(TOP);
+
+        // 最长的字符串正好放得下
+        let longest = "x".repeat(MAX_ARG_STRLEN - 1);
+        let argv = [longest.as_str()];
+        let built = UserStackBuilder::new(&argv, &[]).build(&mut stack, top).unwrap();
+        assert!(built.sp.is_aligned(STACK_ALIGN));
+        assert_eq!(&stack[2 * PAGE_SIZE - MAX_ARG_STRLEN..][..3], b"xxx");
+
+        let too_long = "x".repeat(MAX_ARG_STRLEN);
+        let argv = [too_long.as_str()];
+        let builder = UserStackBuilder::new(&argv, &[]);
+        assert_eq!(builder.build(&mut stack, top), Err(UserStackError::BadString));
+
+        let builder = UserStackBuilder::new(&["ls"], &[]);
+        assert_eq!(builder.build(&mut stack, top + 8), Err(UserStackError::MisalignedTop));
+        assert_eq!(builder.build(&mut stack[..40], top), Err(UserStackError::Overflow));
+
+        // 字符串放得下但指针区放不下
+        let mut small = [0u8; 64];
+        let builder = UserStackBuilder::new(&["abcdefghijklmnopqrstuvwxyz"], &[]);
+        assert_eq!(builder.build(&mut small, top), Err(UserStackError::Overflow));
+    }
+}
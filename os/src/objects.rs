@@ -0,0 +1,306 @@
+/*
+ * ============================================
+ * 内核对象登记（泄漏检测）
+ * ============================================
+ * 功能：按类型统计存活的内核对象数量
+ *
+ * 用法：
+ * 1. 为类型实现 Registered：registered_object!(Task);
+ * 2. 在结构体中加入字段 `_tracked: Tracked<Task>`，
+ *    创建时 `Tracked::new()`，析构时自动减少计数
+ * 3. 测试中用 assert_objects_balanced! 包住一个场景，
+ *    检查场景结束后各类型的对象数回到初始值
+ *
+ * 计数只在启用 `obj_debug` feature 时进行，
+ * 否则 Tracked 是零大小类型，没有任何开销
+ * ============================================
+ */
+
+use crate::fmt::Elided;
+use crate::println;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// 最多登记的对象类型数
+pub const MAX_OBJECT_TYPES: usize = 16;
+
+/// 单个类型的对象计数器
+pub struct ObjectCounter {
+    /// 类型名称
+    name: &'static str,
+    /// 当前存活数量
+    live: AtomicUsize,
+    /// 累计创建数量
+    created: AtomicUsize,
+    /// 最近一次创建时的时钟中断计数
+    last_created_tick: AtomicU64,
+    /// 是否已加入登记表
+    #[cfg_attr(not(feature = "obj_debug"), allow(dead_code))]
+    registered: AtomicBool,
+}
+
+impl ObjectCounter {
+    /// 创建计数器（由 registered_object! 生成的静态变量使用）
+    pub const fn new(name: &'static str) -> Self {
+        ObjectCounter {
+            name,
+            live: AtomicUsize::new(0),
+            created: AtomicUsize::new(0),
+            last_created_tick: AtomicU64::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    /// 类型名称
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// 当前存活数量
+    pub fn live(&self) -> usize {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    /// 累计创建数量
+    pub fn created(&self) -> usize {
+        self.created.load(Ordering::Relaxed)
+    }
+
+    /// 最近一次创建时的时钟中断计数
+    pub fn last_created_tick(&self) -> u64 {
+        self.last_created_tick.load(Ordering::Relaxed)
+    }
+}
+
+/// 可登记的内核对象类型
+///
+/// 使用 `registered_object!` 宏实现
+pub trait Registered {
+    /// 该类型的计数器
+    fn counter() -> &'static ObjectCounter;
+}
+
+/// 为类型实现 `Registered`
+///
+/// # 示例
+/// ```ignore
+/// registered_object!(Task);
+/// ```
+#[macro_export]
+macro_rules! registered_object {
+    ($ty:ty) => {
+        impl $crate::objects::Registered for $ty {
+            fn counter() -> &'static $crate::objects::ObjectCounter {
+                static COUNTER: $crate::objects::ObjectCounter =
+                    $crate::objects::ObjectCounter::new(stringify!($ty));
+                &COUNTER
+            }
+        }
+    };
+}
+
+/// 已登记的计数器
+static REGISTRY: Mutex<[Option<&'static ObjectCounter>; MAX_OBJECT_TYPES]> =
+    Mutex::new([None; MAX_OBJECT_TYPES]);
+
+/// 把计数器加入登记表（只在第一次创建对象时发生）
+#[cfg(feature = "obj_debug")]
+fn register(counter: &'static ObjectCounter) {
+    if counter.registered.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    crate::interrupts::without_interrupts(|| {
+        let mut registry = REGISTRY.lock();
+        match registry.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(counter),
+            None => crate::klog!("[OBJECTS] Registry full, {} not tracked", counter.name),
+        }
+    });
+}
+
+/// 对象计数标记
+///
+/// 作为字段放进需要统计的结构体中：创建时计数加一，析构时减一
+pub struct Tracked<T: Registered> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Registered> Tracked<T> {
+    /// 登记一个新对象
+    pub fn new() -> Self {
+        #[cfg(feature = "obj_debug")]
+        {
+            let counter = T::counter();
+            register(counter);
+            counter.live.fetch_add(1, Ordering::Relaxed);
+            counter.created.fetch_add(1, Ordering::Relaxed);
+            counter
+                .last_created_tick
+                .store(crate::interrupts::uptime_ticks(), Ordering::Relaxed);
+        }
+
+        Tracked {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Registered> Default for Tracked<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Registered> Clone for Tracked<T> {
+    /// 克隆出的对象同样计数
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Registered> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "obj_debug")]
+        T::counter().live.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// ============================================
+// 快照与报告
+// ============================================
+
+/// 某一时刻各类型的存活对象数
+#[derive(Clone, Copy)]
+pub struct ObjectSnapshot {
+    entries: [(&'static str, usize); MAX_OBJECT_TYPES],
+    len: usize,
+}
+
+impl ObjectSnapshot {
+    /// 快照中的 (类型名, 存活数量)
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        self.entries[..self.len].iter().copied()
+    }
+
+    /// 某个类型的存活数量（快照时尚未登记的类型为 0）
+    pub fn live(&self, name: &str) -> usize {
+        self.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, live)| live)
+            .unwrap_or(0)
+    }
+}
+
+/// 记录当前各类型的存活对象数
+pub fn snapshot() -> ObjectSnapshot {
+    let mut snapshot = ObjectSnapshot {
+        entries: [("", 0); MAX_OBJECT_TYPES],
+        len: 0,
+    };
+
+    crate::interrupts::without_interrupts(|| {
+        for counter in REGISTRY.lock().iter().flatten() {
+            snapshot.entries[snapshot.len] = (counter.name, counter.live());
+            snapshot.len += 1;
+        }
+    });
+
+    snapshot
+}
+
+/// 检查两次快照之间各类型的对象数是否一致
+///
+/// # 返回
+/// - `Ok(())`: 对象数一致
+/// - `Err(name)`: 第一个数量不一致的类型
+pub fn check_balanced(before: &ObjectSnapshot, after: &ObjectSnapshot) -> Result<(), &'static str> {
+    for (name, live) in after.iter() {
+        if live != before.live(name) {
+            return Err(name);
+        }
+    }
+    Ok(())
+}
+
+/// 执行一段代码，并断言其前后各类型的对象数相同
+///
+/// # 示例
+/// ```ignore
+/// assert_objects_balanced!({
+///     let task = Task::new(async {});
+///     drop(task);
+/// });
+/// ```
+#[macro_export]
+macro_rules! assert_objects_balanced {
+    ($body:block) => {{
+        let before = $crate::objects::snapshot();
+        let ret = $body;
+        let after = $crate::objects::snapshot();
+        if let Err(name) = $crate::objects::check_balanced(&before, &after) {
+            panic!(
+                "object leak: {} {} -> {}",
+                name,
+                before.live(name),
+                after.live(name)
+            );
+        }
+        ret
+    }};
+}
+
+/// 打印对象统计表
+pub fn print_objects() {
+    println!("┌──────────────────┬──────────┬──────────┬──────────────┐");
+    println!("│ 类型             │ 存活     │ 累计创建 │ 最近创建tick │");
+    println!("├──────────────────┼──────────┼──────────┼──────────────┤");
+    crate::interrupts::without_interrupts(|| {
+        for counter in REGISTRY.lock().iter().flatten() {
+            println!(
+                "│ {:<16} │ {:>8} │ {:>8} │ {:>12} │",
+                counter.name,
+                counter.live(),
+                counter.created(),
+                Elided(counter.last_created_tick())
+            );
+        }
+    });
+    println!("└──────────────────┴──────────┴──────────┴──────────────┘");
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "obj_debug")]
+    use super::*;
+    use crate::task::{simple_executor::SimpleExecutor, Task};
+
+    #[test_case]
+    fn test_task_lifecycle_balanced() {
+        assert_objects_balanced!({
+            let mut executor = SimpleExecutor::new();
+            for _ in 0..4 {
+                executor.spawn(Task::new(async {}));
+            }
+            executor.run();
+        });
+    }
+
+    #[cfg(feature = "obj_debug")]
+    #[test_case]
+    fn test_leak_is_detected() {
+        let before = snapshot();
+        let task = Task::new(async {});
+        let during = snapshot();
+        assert_eq!(during.live("Task"), before.live("Task") + 1);
+        assert_eq!(check_balanced(&before, &during), Err("Task"));
+
+        drop(task);
+        assert_eq!(check_balanced(&before, &snapshot()), Ok(()));
+    }
+}
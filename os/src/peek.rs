@@ -0,0 +1,403 @@
+/*
+ * ============================================
+ * 内存查看命令（peek / poke）
+ * ============================================
+ * 功能：教学用，读写任意物理地址观察 MMIO 和内存布局
+ *
+ * 命令：
+ *   peek <addr> [len] [b|h|w|d]    按宽度显示 len 字节（默认 16 字节，b）
+ *   poke <addr> <value> [b|h|w|d]  写入一个值（默认 w）
+ * 地址可以是数字（0x 开头为十六进制）或下面列出的符号名
+ *
+ * 安全措施：
+ * - 读写都通过异常表保护：访问不存在的地址不会让内核崩溃，
+ *   peek 显示 unmapped，poke 返回错误
+ * - poke 写入前检查策略：内核代码段和固定的页帧（页表等）总是拒绝；
+ *   MMIO 区域允许；其他内存需要命令行参数 unsafe_poke=1（或 sysctl debug.unsafe_poke=1）
+ *
+ * 内核还没有 shell 和符号表：命令由 run_command 解析，
+ * 符号只支持链接脚本导出的几个边界和 trap_handler
+ * ============================================
+ */
+
+use crate::memory::{
+    is_pinned, kernel_end_addr, memory_end, probe_readable, PhysAddr, PhysFrame, MEMORY_START,
+};
+use crate::sysctl::{tunable, Kind, Tunable};
+use crate::{print, println};
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// 访问不存在的地址时的错误（peek 同样显示这个词）
+pub const UNMAPPED: &str = "unmapped";
+
+/// peek 一次最多显示的字节数
+pub const PEEK_MAX: usize = 4096;
+
+/// peek 默认显示的字节数
+const PEEK_DEFAULT_LEN: usize = 16;
+
+/// 是否允许 poke 写入 MMIO 以外的内存（命令行参数 unsafe_poke=1）
+static UNSAFE_POKE: AtomicBool = AtomicBool::new(false);
+
+tunable!(UNSAFE_POKE_TUNABLE = Tunable {
+    name: "debug.unsafe_poke",
+    kind: Kind::Bool,
+    min: 0,
+    max: 1,
+    get: || UNSAFE_POKE.load(Ordering::Relaxed) as u64,
+    set: |value| UNSAFE_POKE.store(value != 0, Ordering::Relaxed),
+});
+
+/// 根据内核命令行设置 poke 策略
+///
+/// # 说明
+/// 命令行中出现 `unsafe_poke=1` 时允许写普通内存，`unsafe_poke=0` 时禁止，其余参数忽略
+pub fn apply_cmdline(cmdline: &str) {
+    for arg in cmdline.split_whitespace() {
+        match arg {
+            "unsafe_poke=1" => UNSAFE_POKE.store(true, Ordering::Relaxed),
+            "unsafe_poke=0" => UNSAFE_POKE.store(false, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+}
+
+// ============================================
+// 访问宽度
+// ============================================
+
+/// 访问宽度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    /// 1 字节（b）
+    Byte,
+    /// 2 字节（h）
+    Half,
+    /// 4 字节（w）
+    Word,
+    /// 8 字节（d）
+    Double,
+}
+
+impl Width {
+    /// 解析宽度后缀
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        match s {
+            "b" => Ok(Width::Byte),
+            "h" => Ok(Width::Half),
+            "w" => Ok(Width::Word),
+            "d" => Ok(Width::Double),
+            _ => Err("Invalid width (expected b, h, w or d)"),
+        }
+    }
+
+    /// 字节数
+    pub const fn bytes(self) -> usize {
+        match self {
+            Width::Byte => 1,
+            Width::Half => 2,
+            Width::Word => 4,
+            Width::Double => 8,
+        }
+    }
+
+    /// 此宽度能表示的最大值
+    const fn max_value(self) -> u64 {
+        match self {
+            Width::Double => u64::MAX,
+            _ => (1 << (self.bytes() * 8)) - 1,
+        }
+    }
+}
+
+// ============================================
+// 受保护的访问
+// ============================================
+
+/// 执行一条受异常表保护的 load，出错时得到 None
+macro_rules! protected_load {
+    ($insn:literal, $addr:expr) => {{
+        let value: u64;
+        let faulted: usize;
+        core::arch::asm!(
+            "li {faulted}, 0",
+            concat!("7: ", $insn, " {value}, 0({addr})"),
+            "j 9f",
+            "8: li {faulted}, 1",
+            "9:",
+            ".pushsection __ex_table, \"a\"",
+            ".balign 8",
+            ".dword 7b, 8b",
+            ".popsection",
+            faulted = out(reg) faulted,
+            value = out(reg) value,
+            addr = in(reg) $addr,
+        );
+        (faulted == 0).then_some(value)
+    }};
+}
+
+/// 执行一条受异常表保护的 store，返回是否成功
+macro_rules! protected_store {
+    ($insn:literal, $addr:expr, $value:expr) => {{
+        let faulted: usize;
+        core::arch::asm!(
+            "li {faulted}, 0",
+            concat!("7: ", $insn, " {value}, 0({addr})"),
+            "j 9f",
+            "8: li {faulted}, 1",
+            "9:",
+            ".pushsection __ex_table, \"a\"",
+            ".balign 8",
+            ".dword 7b, 8b",
+            ".popsection",
+            faulted = out(reg) faulted,
+            value = in(reg) $value,
+            addr = in(reg) $addr,
+        );
+        faulted == 0
+    }};
+}
+
+/// 读取一个值（受异常表保护）
+///
+/// # 返回
+/// - `Err(UNMAPPED)`: 地址不存在或没有映射
+/// - `Err`: 地址没有按宽度对齐
+pub fn read(addr: usize, width: Width) -> Result<u64, &'static str> {
+    if !addr.is_multiple_of(width.bytes()) {
+        return Err("Address not aligned");
+    }
+    let value = unsafe {
+        match width {
+            Width::Byte => protected_load!("lbu", addr),
+            Width::Half => protected_load!("lhu", addr),
+            Width::Word => protected_load!("lwu", addr),
+            Width::Double => protected_load!("ld", addr),
+        }
+    };
+    value.ok_or(UNMAPPED)
+}
+
+/// 写入一个值（受异常表保护，不检查 poke 策略）
+///
+/// # Safety
+/// 调用者保证写入不会破坏内核正在使用的数据（见 `check_poke`）
+pub unsafe fn write(addr: usize, value: u64, width: Width) -> Result<(), &'static str> {
+    if !addr.is_multiple_of(width.bytes()) {
+        return Err("Address not aligned");
+    }
+    let ok = match width {
+        Width::Byte => protected_store!("sb", addr, value),
+        Width::Half => protected_store!("sh", addr, value),
+        Width::Word => protected_store!("sw", addr, value),
+        Width::Double => protected_store!("sd", addr, value),
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(UNMAPPED)
+    }
+}
+
+// ============================================
+// 符号与 poke 策略
+// ============================================
+
+/// 内核代码段的地址范围 [kernel_start, text_end)
+pub fn kernel_text() -> Range<usize> {
+    extern "C" {
+        static kernel_start: u8;
+        static text_end: u8;
+    }
+    unsafe { (&kernel_start as *const u8 as usize)..(&text_end as *const u8 as usize) }
+}
+
+/// 按名字查找符号地址
+///
+/// # 说明
+/// 内核没有完整的符号表，只认识链接脚本导出的边界和 trap_handler
+pub fn symbol_addr(name: &str) -> Option<usize> {
+    match name {
+        "kernel_start" => Some(kernel_text().start),
+        "text_end" => Some(kernel_text().end),
+        "kernel_end" => Some(kernel_end_addr()),
+        "trap_handler" => Some(crate::interrupts::trap_handler as *const () as usize),
+        _ => None,
+    }
+}
+
+/// 解析数字（0x 开头为十六进制，否则为十进制）
+pub(crate) fn parse_number(s: &str) -> Result<u64, &'static str> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| "Invalid number")
+}
+
+/// 解析地址（符号名或数字）
+pub fn resolve(s: &str) -> Result<usize, &'static str> {
+    match symbol_addr(s) {
+        Some(addr) => Ok(addr),
+        None => parse_number(s).map(|addr| addr as usize).map_err(|_| "Unknown symbol or address"),
+    }
+}
+
+/// 地址是否位于 MMIO 区域（已知的设备范围）
+fn is_mmio(addr: usize) -> bool {
+    !(MEMORY_START..memory_end()).contains(&addr) && probe_readable(PhysAddr::new(addr))
+}
+
+/// 检查 poke 能否写入 `[addr, addr + len)`
+///
+/// # 返回
+/// - `Ok(())`: MMIO 区域，或开启了 unsafe_poke 的普通内存
+/// - `Err`: 拒绝的原因（内核代码段、固定的页帧总是拒绝）
+pub fn check_poke(addr: usize, len: usize) -> Result<(), &'static str> {
+    let text = kernel_text();
+    if addr < text.end && text.start < addr.saturating_add(len) {
+        return Err("Refused: kernel text is never writable");
+    }
+    if is_pinned(PhysFrame::from_addr(PhysAddr::new(addr))) {
+        return Err("Refused: frame is pinned (page table, kernel stack or DMA buffer)");
+    }
+    if is_mmio(addr) || UNSAFE_POKE.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    Err("Refused: not an MMIO region (boot with unsafe_poke=1 to allow)")
+}
+
+// ============================================
+// 命令
+// ============================================
+
+/// 显示一段内存，每行 16 字节，按宽度分组（小端值）
+///
+/// # 说明
+/// 遇到不能访问的地址时该行显示 unmapped 并停止
+pub fn peek(addr: usize, len: usize, width: Width) -> Result<(), &'static str> {
+    if len == 0 || len > PEEK_MAX {
+        return Err("Length out of range");
+    }
+    if !addr.is_multiple_of(width.bytes()) {
+        return Err("Address not aligned");
+    }
+    let end = addr.checked_add(len).ok_or("Length out of range")?;
+
+    for line in (addr..end).step_by(16) {
+        print!("{:#012x}:", line);
+        for item in (line..end.min(line + 16)).step_by(width.bytes()) {
+            match read(item, width) {
+                Ok(value) => {
+                    print!(" {:0digits$x}", value, digits = width.bytes() * 2);
+                }
+                Err(_) => {
+                    println!(" {}", UNMAPPED);
+                    return Ok(());
+                }
+            }
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// 按策略检查后写入一个值
+pub fn poke(addr: usize, value: u64, width: Width) -> Result<(), &'static str> {
+    if value > width.max_value() {
+        return Err("Value does not fit the width");
+    }
+    check_poke(addr, width.bytes())?;
+    unsafe { write(addr, value, width) }
+}
+
+/// 执行一行 peek / poke 命令
+///
+/// # 返回
+/// - `Err`: 不是 peek / poke、参数错误，或 poke 被拒绝
+pub fn run_command(line: &str) -> Result<(), &'static str> {
+    let mut args = line.split_whitespace();
+    match args.next() {
+        Some("peek") => {
+            let addr = resolve(args.next().ok_or("usage: peek <addr> [len] [b|h|w|d]")?)?;
+            let mut len = PEEK_DEFAULT_LEN;
+            let mut width = Width::Byte;
+            for arg in args {
+                match Width::parse(arg) {
+                    Ok(w) => width = w,
+                    Err(_) => len = parse_number(arg)? as usize,
+                }
+            }
+            peek(addr, len, width)
+        }
+        Some("poke") => {
+            let usage = "usage: poke <addr> <value> [b|h|w|d]";
+            let addr = resolve(args.next().ok_or(usage)?)?;
+            let value = parse_number(args.next().ok_or(usage)?)?;
+            let width = args.next().map_or(Ok(Width::Word), Width::parse)?;
+            if args.next().is_some() {
+                return Err(usage);
+            }
+            poke(addr, value, width)
+        }
+        _ => Err("Unknown command"),
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{pin_frame, test_frame_allocator, unpin_frame};
+
+    static PATTERN: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+
+    #[test_case]
+    fn test_peek_reads_static() {
+        let addr = PATTERN.as_ptr() as usize;
+        let handler = crate::interrupts::trap_handler as *const () as usize;
+        assert_eq!(read(addr, Width::Byte), Ok(0x11));
+        assert_eq!(read(addr + 2, Width::Half), Ok(0x4433));
+        assert_eq!(read(addr + 4, Width::Word), Ok(0x8877_6655));
+        assert_eq!(read(addr, Width::Double), Ok(u64::from_le_bytes(PATTERN)));
+        assert_eq!(read(addr + 1, Width::Half), Err("Address not aligned"));
+
+        assert_eq!(resolve("trap_handler"), Ok(handler));
+        assert_eq!(run_command("peek trap_handler 32"), Ok(()));
+        assert_eq!(run_command("peek 0x80200000 d"), Ok(()));
+        assert_eq!(run_command("peek nowhere"), Err("Unknown symbol or address"));
+    }
+
+    #[test_case]
+    fn test_poke_policy() {
+        let mut allocator = test_frame_allocator();
+        let frame = allocator.allocate().expect("Out of memory");
+        let scratch = frame.start_address().as_usize();
+
+        // 普通内存需要 unsafe_poke=1
+        assert_eq!(
+            poke(scratch, 0xab, Width::Byte),
+            Err("Refused: not an MMIO region (boot with unsafe_poke=1 to allow)")
+        );
+        apply_cmdline("quiet unsafe_poke=1");
+        assert_eq!(poke(scratch + 8, 0x1234_5678, Width::Word), Ok(()));
+        assert_eq!(read(scratch + 8, Width::Word), Ok(0x1234_5678));
+        assert_eq!(poke(scratch, 0x100, Width::Byte), Err("Value does not fit the width"));
+
+        // 内核代码段和固定的页帧即使开启 unsafe_poke 也拒绝
+        let text = Err("Refused: kernel text is never writable");
+        assert_eq!(run_command("poke trap_handler 0"), text);
+        assert!(read(crate::interrupts::trap_handler as *const () as usize, Width::Byte).is_ok());
+        pin_frame(frame);
+        assert!(poke(scratch, 0, Width::Byte).is_err());
+        unpin_frame(frame);
+
+        apply_cmdline("unsafe_poke=0");
+        allocator.deallocate(frame);
+    }
+}
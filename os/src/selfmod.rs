@@ -0,0 +1,141 @@
+/*
+ * ============================================
+ * 自修改代码演示（fence.i）
+ * ============================================
+ * 功能：教学演示：为什么写入指令之后必须执行 fence.i
+ *
+ * RISC-V 的指令获取和数据访问不保证一致：
+ * 用 store 写进内存的指令，可能还没有出现在取指路径
+ * （指令缓存、预取缓冲）中。fence.i 保证本 hart 之后取到的指令
+ * 能看到之前所有的 store
+ *
+ * 演示步骤：
+ * 1. 在一个可写可执行的页面中写入 `li a0, 1; ret`，fence.i 后调用 → 1
+ * 2. 改写为 `li a0, 2; ret`，不执行 fence.i 就调用 → 结果与平台有关
+ *    （QEMU 会检测自修改代码，通常得到 2；真实硬件可能仍得到 1）
+ * 3. 执行 fence.i 后再次调用 → 一定是 2
+ *
+ * 需要可写又可执行的页面（违反 W^X），因此只在 `selfmod_demo`
+ * feature 下编译
+ * ============================================
+ */
+
+use crate::memory::{PhysFrame, SimpleFrameAllocator};
+use crate::println;
+
+/// `ret`（jalr x0, 0(ra)）
+const RET: u32 = 0x0000_8067;
+
+/// `li a0, value`（addi a0, x0, value）的编码
+///
+/// # 返回
+/// - `Err`: `value` 超出 12 位有符号立即数的范围
+pub fn encode_li_a0(value: i32) -> Result<u32, &'static str> {
+    if !(-2048..=2047).contains(&value) {
+        return Err("Immediate out of range");
+    }
+    // imm[11:0] | rs1=x0 | funct3=000 | rd=a0(x10) | opcode=OP-IMM
+    Ok(((value as u32 & 0xFFF) << 20) | (10 << 7) | 0x13)
+}
+
+/// 存放生成代码的页面（可写可执行）
+pub struct CodePage {
+    frame: PhysFrame,
+}
+
+impl CodePage {
+    /// 使用一个物理页帧（依赖恒等映射，且该页可执行）
+    pub fn new(frame: PhysFrame) -> Self {
+        CodePage { frame }
+    }
+
+    /// 页面起始地址
+    pub fn addr(&self) -> usize {
+        self.frame.start_address().as_usize()
+    }
+
+    /// 写入 `li a0, value; ret`（只写数据，不执行 fence.i）
+    pub fn write_return(&mut self, value: i32) -> Result<(), &'static str> {
+        let code = [encode_li_a0(value)?, RET];
+        let base = self.addr() as *mut u32;
+        for (i, insn) in code.into_iter().enumerate() {
+            unsafe { base.add(i).write_volatile(insn) };
+        }
+        Ok(())
+    }
+
+    /// 同步指令获取（fence.i）
+    pub fn sync(&self) {
+        unsafe { core::arch::asm!("fence.i") };
+    }
+
+    /// 调用页面中的代码
+    ///
+    /// # 说明
+    /// 调用前没有 `sync` 时执行的可能是旧的指令
+    pub fn call(&self) -> usize {
+        let entry: extern "C" fn() -> usize = unsafe { core::mem::transmute(self.addr()) };
+        entry()
+    }
+}
+
+/// 运行演示并打印结果
+///
+/// # 参数
+/// - `allocator`: 提供代码页面的帧分配器（演示结束后归还）
+pub fn run_demo(allocator: &mut SimpleFrameAllocator) -> Result<(), &'static str> {
+    let frame = allocator.allocate().ok_or("Out of memory")?;
+    let mut page = CodePage::new(frame);
+
+    page.write_return(1)?;
+    page.sync();
+    let first = page.call();
+
+    page.write_return(2)?;
+    let without_fence = page.call();
+
+    page.sync();
+    let with_fence = page.call();
+
+    allocator.deallocate(frame);
+
+    println!("┌──────────────────────────────────────────────┐");
+    println!("│ 自修改代码演示  代码页: {:#x}", page.addr());
+    println!("├────────────────────────────────┬─────────────┤");
+    println!("│ 写入 li a0, 1 + fence.i        │ 返回 {:<6} │", first);
+    println!("│ 改写 li a0, 2，不执行 fence.i  │ 返回 {:<6} │", without_fence);
+    println!("│ 执行 fence.i 后                │ 返回 {:<6} │", with_fence);
+    println!("└────────────────────────────────┴─────────────┘");
+    if without_fence != with_fence {
+        println!("没有 fence.i 时执行了旧的指令");
+    } else {
+        println!("本平台没有 fence.i 也看到了新指令，但这不受架构保证");
+    }
+    Ok(())
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::test_frame_allocator;
+
+    #[test_case]
+    fn test_generated_code_runs_after_fence_i() {
+        let mut allocator = test_frame_allocator();
+        let frame = allocator.allocate().expect("Out of memory");
+        let mut page = CodePage::new(frame);
+
+        for value in [1, -1, 2047, 0] {
+            page.write_return(value).unwrap();
+            page.sync();
+            assert_eq!(page.call() as isize, value as isize);
+        }
+
+        assert_eq!(encode_li_a0(1), Ok(0x0010_0513));
+        assert_eq!(page.write_return(2048), Err("Immediate out of range"));
+    }
+}
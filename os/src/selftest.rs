@@ -0,0 +1,344 @@
+/*
+ * ============================================
+ * 内置测试（selftest）
+ * ============================================
+ * 功能：在普通内核镜像中运行单元测试，用于部署后的冒烟检查
+ *
+ * 注册：需要在内核镜像中运行的测试用 builtin_tests! 包住
+ *       （其余测试留在 #[cfg(test)] 模块中，只在 cargo test 中运行）
+ * - cargo test 构建中每个函数照常是 #[test_case]
+ * - builtin_tests 特性下每个函数另外登记到 .selftests 段（SelfTest），
+ *   名字是 模块路径::函数名，例如 os::memory::paging::selftests::test_map_range
+ *
+ * 运行：命令行 run_tests=1 时，init_late 之后、memory::init 之前运行全部测试，
+ * 然后照常启动。测试使用 test_frame_allocator（堆之后的物理内存），
+ * 与 memory::init 的帧分配器重叠，因此只能在启动过程中运行，没有启动之后的入口
+ *
+ * 失败的测试不会停机：panic 处理函数调用 fail_current，打印失败信息后
+ * 跳回 selftest_call 的调用处（恢复被调用者保存的寄存器和栈指针）。
+ * 恢复时中断守卫层数、中断开关和故障注入表还原为测试开始前的状态，
+ * 但失败的测试泄漏的堆内存和没有释放的锁无法回收：之后的测试可能在这些锁上死锁，
+ * 因此第一个失败的测试结束本次运行，剩下的测试记为跳过，之后也不能再运行
+ * ============================================
+ */
+
+use crate::{serial_print, serial_println};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+/// 一个内置测试（由 builtin_tests! 放进 .selftests 段）
+pub struct SelfTest {
+    /// 模块路径::函数名
+    pub name: &'static str,
+    /// 测试函数
+    pub run: fn(),
+}
+
+/// 声明测试函数，并在 builtin_tests 特性下登记为内置测试
+///
+/// # 示例
+/// ```ignore
+/// builtin_tests! {
+///     #[test_case]
+///     fn test_heap_allocation() {
+///         assert_eq!(*Box::new(41), 41);
+///     }
+/// }
+/// ```
+#[cfg(any(test, feature = "builtin_tests"))]
+macro_rules! builtin_tests {
+    ($(#[test_case] $(#[$attr:meta])* fn $name:ident() $body:block)*) => {
+        $(
+            $(#[$attr])*
+            #[cfg_attr(test, test_case)]
+            fn $name() $body
+
+            #[cfg(feature = "builtin_tests")]
+            const _: () = {
+                #[used]
+                #[link_section = ".selftests"]
+                static TEST: $crate::selftest::SelfTest = $crate::selftest::SelfTest {
+                    name: concat!(module_path!(), "::", stringify!($name)),
+                    run: $name,
+                };
+            };
+        )*
+    };
+}
+#[cfg(any(test, feature = "builtin_tests"))]
+pub(crate) use builtin_tests;
+
+/// 全部内置测试（顺序由链接器决定；没有启用 builtin_tests 特性时为空）
+pub fn tests() -> &'static [SelfTest] {
+    extern "C" {
+        static __selftests_start: u8;
+        static __selftests_end: u8;
+    }
+    unsafe {
+        let start = &__selftests_start as *const u8 as *const SelfTest;
+        let end = &__selftests_end as *const u8 as *const SelfTest;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+// ============================================
+// 命令行
+// ============================================
+
+/// 命令行中是否出现了 run_tests=1
+static RUN_AT_BOOT: AtomicBool = AtomicBool::new(false);
+
+/// 根据内核命令行决定启动时是否运行内置测试
+///
+/// # 说明
+/// 出现 `run_tests=1` 时运行，`run_tests=0` 时不运行，其余参数忽略
+pub fn apply_cmdline(cmdline: &str) {
+    for arg in cmdline.split_whitespace() {
+        match arg {
+            "run_tests=1" => RUN_AT_BOOT.store(true, Ordering::Relaxed),
+            "run_tests=0" => RUN_AT_BOOT.store(false, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+}
+
+/// 启动时是否运行内置测试
+pub fn run_at_boot() -> bool {
+    RUN_AT_BOOT.load(Ordering::Relaxed)
+}
+
+// ============================================
+// 运行
+// ============================================
+
+/// 一次运行的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Summary {
+    /// 通过的测试数
+    pub passed: usize,
+    /// 失败的测试数
+    pub failed: usize,
+    /// 名字不匹配、没有运行的测试数
+    pub filtered: usize,
+    /// 名字匹配，但因为之前的测试失败而没有运行的测试数
+    pub skipped: usize,
+}
+
+/// selftest_call 保存的寄存器：ra、sp、s0 - s11
+#[repr(C)]
+struct JumpBuffer([usize; 14]);
+
+core::arch::global_asm!(
+    ".pushsection .text",
+    ".globl selftest_call",
+    ".globl selftest_resume",
+    ".p2align 2",
+    // a0 = 入口，a1 = 参数，a2 = JumpBuffer；正常返回 0
+    "selftest_call:",
+    "   sd ra, 0(a2)",
+    "   sd sp, 8(a2)",
+    "   sd s0, 16(a2)",
+    "   sd s1, 24(a2)",
+    "   sd s2, 32(a2)",
+    "   sd s3, 40(a2)",
+    "   sd s4, 48(a2)",
+    "   sd s5, 56(a2)",
+    "   sd s6, 64(a2)",
+    "   sd s7, 72(a2)",
+    "   sd s8, 80(a2)",
+    "   sd s9, 88(a2)",
+    "   sd s10, 96(a2)",
+    "   sd s11, 104(a2)",
+    "   mv s0, a2",
+    "   mv t0, a0",
+    "   mv a0, a1",
+    "   jalr t0",
+    "   mv a0, s0",
+    "   li a1, 0",
+    // a0 = JumpBuffer，a1 = 返回值：恢复寄存器，从 selftest_call 返回
+    "selftest_resume:",
+    "   ld ra, 0(a0)",
+    "   ld sp, 8(a0)",
+    "   ld s0, 16(a0)",
+    "   ld s1, 24(a0)",
+    "   ld s2, 32(a0)",
+    "   ld s3, 40(a0)",
+    "   ld s4, 48(a0)",
+    "   ld s5, 56(a0)",
+    "   ld s6, 64(a0)",
+    "   ld s7, 72(a0)",
+    "   ld s8, 80(a0)",
+    "   ld s9, 88(a0)",
+    "   ld s10, 96(a0)",
+    "   ld s11, 104(a0)",
+    "   mv a0, a1",
+    "   ret",
+    ".popsection",
+);
+
+extern "C" {
+    fn selftest_call(entry: extern "C" fn(usize), arg: usize, buffer: *mut JumpBuffer) -> usize;
+    fn selftest_resume(buffer: *mut JumpBuffer, value: usize) -> !;
+}
+
+/// 正在运行的测试的恢复点（没有测试在运行时为空）
+static RECOVERY: AtomicPtr<JumpBuffer> = AtomicPtr::new(core::ptr::null_mut());
+
+/// selftest_call 调用的入口：参数是 &SelfTest
+extern "C" fn invoke(test: usize) {
+    let test = unsafe { &*(test as *const SelfTest) };
+    (test.run)();
+}
+
+/// 运行一个测试
+///
+/// # 返回
+/// 是否通过
+fn run_one(test: &SelfTest) -> bool {
+    use riscv::register::sstatus;
+
+    let depth = crate::interrupts::guard_depth();
+    let enabled = sstatus::read().sie();
+    let mut buffer = JumpBuffer([0; 14]);
+    serial_print!("{}...\t", test.name);
+    RECOVERY.store(&mut buffer, Ordering::Relaxed);
+    let failed = unsafe { selftest_call(invoke, test as *const SelfTest as usize, &mut buffer) };
+    RECOVERY.store(core::ptr::null_mut(), Ordering::Relaxed);
+    if failed != 0 {
+        crate::interrupts::abandon_guards(depth, enabled);
+        #[cfg(any(test, feature = "faultinject"))]
+        crate::faultinject::disarm_all();
+        return false;
+    }
+    serial_println!("[ok]");
+    true
+}
+
+/// panic 发生在内置测试中时报告失败，并回到测试运行器
+///
+/// # 说明
+/// 由内核的 panic 处理函数最先调用；没有测试在运行时直接返回
+pub fn fail_current(info: &PanicInfo) {
+    let buffer = RECOVERY.swap(core::ptr::null_mut(), Ordering::Relaxed);
+    if buffer.is_null() {
+        return;
+    }
+    serial_println!("[failed]\n");
+    serial_println!("Error: {}\n", info);
+    unsafe { selftest_resume(buffer, 1) }
+}
+
+/// 是否有测试失败过（失败的测试可能留下没有释放的锁，之后不再运行测试）
+static POISONED: AtomicBool = AtomicBool::new(false);
+
+/// 运行名字中包含 `filter` 的内置测试（空串匹配全部）
+///
+/// # 返回
+/// - `Err`: memory::init 之后（测试的页帧会覆盖帧分配器管理的内存），
+///   或者之前的运行中有测试失败
+pub fn run(filter: &str) -> Result<Summary, &'static str> {
+    if crate::memory::frames_claimed() {
+        return Err("Built-in tests must run before memory::init");
+    }
+    run_tests(tests(), filter)
+}
+
+/// 运行全部内置测试
+pub fn run_full() -> Result<Summary, &'static str> {
+    run("")
+}
+
+/// `run` 的运行部分
+fn run_tests(tests: &[SelfTest], filter: &str) -> Result<Summary, &'static str> {
+    if POISONED.load(Ordering::Relaxed) {
+        return Err("A built-in test failed earlier, reboot to run tests again");
+    }
+    let mut summary = Summary::default();
+    let selected = tests.iter().filter(|test| test.name.contains(filter));
+    serial_println!("Running {} built-in tests", selected.clone().count());
+    for test in selected {
+        if POISONED.load(Ordering::Relaxed) {
+            summary.skipped += 1;
+        } else if run_one(test) {
+            summary.passed += 1;
+        } else {
+            summary.failed += 1;
+            POISONED.store(true, Ordering::Relaxed);
+        }
+    }
+    summary.filtered = tests.len() - summary.passed - summary.failed - summary.skipped;
+    serial_println!(
+        "[SELFTEST] {} passed, {} failed, {} skipped, {} filtered out",
+        summary.passed,
+        summary.failed,
+        summary.skipped,
+        summary.filtered
+    );
+    Ok(summary)
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::faultinject::{arm, check, Policy};
+    use crate::interrupts::{guard_depth, InterruptGuard};
+
+    #[test_case]
+    fn test_failing_test_is_recovered() {
+        fn failing() {
+            let _irq = InterruptGuard::new();
+            let _fault = arm("user_copy", Policy::Always);
+            panic!("expected failure");
+        }
+
+        let depth = guard_depth();
+        let enabled = riscv::register::sstatus::read().sie();
+        let output = crate::console::capture();
+        let passed = run_one(&SelfTest { name: "selftest::failing", run: failing });
+        let text = output.take();
+        assert!(!passed);
+        assert!(text.contains("selftest::failing...\t[failed]"));
+        assert!(text.contains("expected failure"));
+
+        // 守卫层数、中断开关、故障注入表都恢复了，之后的测试照常运行
+        assert_eq!(guard_depth(), depth);
+        assert_eq!(riscv::register::sstatus::read().sie(), enabled);
+        assert!(!check("user_copy"));
+        let output = crate::console::capture();
+        assert!(run_one(&SelfTest { name: "selftest::passing", run: || {} }));
+        assert!(output.take().contains("selftest::passing...\t[ok]"));
+    }
+
+    #[test_case]
+    fn test_failure_ends_the_run() {
+        let tests = [
+            SelfTest { name: "selftest::first", run: || {} },
+            SelfTest { name: "selftest::failing", run: || panic!("expected failure") },
+            SelfTest { name: "selftest::after", run: || {} },
+            SelfTest { name: "other::after", run: || {} },
+        ];
+        let output = crate::console::capture();
+        let summary = run_tests(&tests, "selftest::");
+        let text = output.take();
+        let expected = Summary { passed: 1, failed: 1, filtered: 1, skipped: 1 };
+        assert_eq!(summary, Ok(expected));
+        assert!(!text.contains("selftest::after..."));
+        assert!(text.contains("1 passed, 1 failed, 1 skipped, 1 filtered out"));
+
+        // 失败的测试可能留下没有释放的锁：之后不再运行测试
+        assert!(run_tests(&tests, "first").is_err());
+        POISONED.store(false, Ordering::Relaxed);
+    }
+
+    #[test_case]
+    fn test_cmdline() {
+        apply_cmdline("console=ttyS0 run_tests=1");
+        assert!(run_at_boot());
+        apply_cmdline("run_tests=0");
+        assert!(!run_at_boot());
+    }
+}
@@ -5,6 +5,10 @@
  * 功能：提供 UART 16550 串口输出功能
  * 用途：调试输出、日志记录、与 QEMU 通信
  *
+ * serial_print! / serial_println! 是最底层的输出通道，一般代码应该用
+ * println!（控制台）或 klog!（内核日志），分工见 klog 模块。
+ * panic 报告走单独的 _print_panic（不等锁）
+ *
  * RISC-V QEMU virt 机器的串口地址：0x10000000
  * ============================================
  */
@@ -13,6 +17,7 @@ use core::fmt;
 use spin::Mutex;
 use lazy_static::lazy_static;
 use volatile::Volatile;
+use crate::device::{driver, DriverSpec, DtNode};
 
 // RISC-V QEMU virt 机器的 UART 基地址
 const UART_BASE_ADDRESS: usize = 0x1000_0000;
@@ -35,6 +40,11 @@ impl SerialPort {
         SerialPort { base_address }
     }
 
+    /// 更换基地址（设备树探测到 UART 之后调用）
+    pub fn set_base_address(&mut self, base_address: usize) {
+        self.base_address = base_address;
+    }
+
     /// 初始化串口
     pub fn init(&mut self) {
         // QEMU 的 UART 默认已初始化，无需额外配置
@@ -82,6 +92,26 @@ lazy_static! {
     };
 }
 
+/// 设备树中的 UART：改用节点给出的基地址，并通过 PLIC 使能它的中断
+///
+/// # 说明
+/// 探测之前串口使用 UART_BASE_ADDRESS（QEMU virt 的地址），保证启动早期就能输出
+fn probe_uart(node: &DtNode) -> Result<(), &'static str> {
+    let (base, _) = node.reg().ok_or("UART node has no reg")?;
+    crate::interrupts::without_interrupts(|| SERIAL1.lock().set_base_address(base));
+    if let Some(irq) = node.prop_u32("interrupts") {
+        crate::interrupts::register_irq(irq, UART_DRIVER.name)?;
+    }
+    Ok(())
+}
+
+driver!(UART_DRIVER = DriverSpec {
+    name: "uart16550",
+    compatibles: &["ns16550a"],
+    needs: &["plic"],
+    probe: probe_uart,
+});
+
 /// 底层打印函数
 ///
 /// # 功能
@@ -94,14 +124,45 @@ lazy_static! {
 pub fn _print(args: ::core::fmt::Arguments) {
     use core::fmt::Write;
 
-    // RISC-V 中断禁用/启用
-    // 使用自旋锁时禁用中断，防止死锁
-    crate::interrupts::without_interrupts(|| {
-        SERIAL1
-            .lock()
-            .write_fmt(args)
-            .expect("Printing to serial failed");
-    });
+    // 使用自旋锁时禁用中断，防止死锁（守卫在锁之后释放）
+    let _irq = crate::interrupts::InterruptGuard::new();
+    crate::console::record(args);
+    SERIAL1
+        .lock()
+        .write_fmt(args)
+        .expect("Printing to serial failed");
+}
+
+/// panic 报告专用的打印函数（只由 `report_panic` 调用）
+///
+/// # 说明
+/// panic 可能发生在持有 SERIAL1 锁的输出途中，这时等锁会死锁：
+/// 锁空闲时照常经过锁输出，锁已被持有时直接写串口寄存器（见 `_print_raw`），
+/// 输出可能与被打断的那一行交错。其他输出都用 `_print`，每次输出保持完整
+#[doc(hidden)]
+pub fn _print_panic(args: ::core::fmt::Arguments) {
+    use core::fmt::Write;
+
+    let _irq = crate::interrupts::InterruptGuard::new();
+    match SERIAL1.try_lock() {
+        Some(mut port) => {
+            let _ = port.write_fmt(args);
+        }
+        None => _print_raw(args),
+    }
+}
+
+/// 不经过 SERIAL1 的锁和输出捕获直接写串口
+///
+/// # 说明
+/// 用于不能分配内存、也不能等锁的场合（例如堆初始化之前的分配）；
+/// 与其他输出可能交错
+#[doc(hidden)]
+pub fn _print_raw(args: ::core::fmt::Arguments) {
+    use core::fmt::Write;
+
+    let base = SERIAL1.try_lock().map_or(UART_BASE_ADDRESS, |port| port.base_address);
+    let _ = unsafe { SerialPort::new(base) }.write_fmt(args);
 }
 
 /// 串口打印宏
@@ -0,0 +1,320 @@
+/*
+ * ============================================
+ * 系统调用结构体布局（RV64 Linux ABI）
+ * ============================================
+ * 功能：系统调用复制到用户缓冲区的固定布局结构体
+ * 包括：Timespec、Timeval、Stat、Utsname、Sysinfo
+ *
+ * 布局与 Linux riscv64（asm-generic）一致，
+ * 大小和关键字段偏移在编译期断言，改动布局会直接编译失败：
+ *
+ *   结构体     大小   关键偏移
+ *   timespec    16    tv_nsec 8
+ *   timeval     16    tv_usec 8
+ *   stat       128    st_mode 16, st_size 48, st_blksize 56, st_mtime 88
+ *   utsname    390    每个字段 65 字节，machine 260
+ *   sysinfo    112    totalram 32, procs 80, mem_unit 104
+ *
+ * 系统调用实现和以后的用户程序都应使用这里的定义，不要各自手写
+ * ============================================
+ */
+
+use core::mem::{offset_of, size_of};
+
+// ============================================
+// timespec
+// ============================================
+
+/// 每秒的纳秒数
+pub const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// `struct timespec`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timespec {
+    /// 秒
+    pub tv_sec: i64,
+    /// 纳秒（0 ~ 999_999_999）
+    pub tv_nsec: i64,
+}
+
+impl Timespec {
+    /// 由纳秒数构造
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Timespec {
+            tv_sec: (nanos / NSEC_PER_SEC) as i64,
+            tv_nsec: (nanos % NSEC_PER_SEC) as i64,
+        }
+    }
+
+    /// 转换为纳秒数
+    ///
+    /// # 返回
+    /// - `None`: 字段为负、tv_nsec 超出范围或结果溢出
+    pub fn as_nanos(&self) -> Option<u64> {
+        if self.tv_sec < 0 || !(0..NSEC_PER_SEC as i64).contains(&self.tv_nsec) {
+            return None;
+        }
+        (self.tv_sec as u64)
+            .checked_mul(NSEC_PER_SEC)?
+            .checked_add(self.tv_nsec as u64)
+    }
+}
+
+// ============================================
+// timeval
+// ============================================
+
+/// 每秒的微秒数
+pub const USEC_PER_SEC: u64 = 1_000_000;
+
+/// `struct timeval`（gettimeofday）
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeval {
+    /// 秒
+    pub tv_sec: i64,
+    /// 微秒（0 ~ 999_999）
+    pub tv_usec: i64,
+}
+
+impl Timeval {
+    /// 由微秒数构造
+    pub const fn from_micros(micros: u64) -> Self {
+        Timeval {
+            tv_sec: (micros / USEC_PER_SEC) as i64,
+            tv_usec: (micros % USEC_PER_SEC) as i64,
+        }
+    }
+}
+
+// ============================================
+// stat
+// ============================================
+
+/// 普通文件（st_mode 的类型位）
+pub const S_IFREG: u32 = 0o100_000;
+
+/// 字符设备
+pub const S_IFCHR: u32 = 0o020_000;
+
+/// 目录
+pub const S_IFDIR: u32 = 0o040_000;
+
+/// `struct stat`（asm-generic 布局）
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub __pad1: u64,
+    pub st_size: i64,
+    pub st_blksize: i32,
+    pub __pad2: i32,
+    pub st_blocks: i64,
+    pub st_atime: i64,
+    pub st_atime_nsec: u64,
+    pub st_mtime: i64,
+    pub st_mtime_nsec: u64,
+    pub st_ctime: i64,
+    pub st_ctime_nsec: u64,
+    pub __unused4: u32,
+    pub __unused5: u32,
+}
+
+impl Stat {
+    /// 按文件类型、大小和块大小构造（其余字段为 0）
+    pub fn new(mode: u32, size: i64, blksize: i32) -> Self {
+        Stat {
+            st_mode: mode,
+            st_nlink: 1,
+            st_size: size,
+            st_blksize: blksize,
+            st_blocks: (size.max(0) as u64).div_ceil(512) as i64,
+            ..Stat::default()
+        }
+    }
+
+    /// 设置三个时间戳
+    pub fn set_times(&mut self, time: Timespec) {
+        (self.st_atime, self.st_atime_nsec) = (time.tv_sec, time.tv_nsec as u64);
+        (self.st_mtime, self.st_mtime_nsec) = (time.tv_sec, time.tv_nsec as u64);
+        (self.st_ctime, self.st_ctime_nsec) = (time.tv_sec, time.tv_nsec as u64);
+    }
+}
+
+// ============================================
+// utsname
+// ============================================
+
+/// utsname 每个字段的长度（包括结尾的 NUL）
+pub const UTSNAME_LEN: usize = 65;
+
+/// `struct utsname`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utsname {
+    pub sysname: [u8; UTSNAME_LEN],
+    pub nodename: [u8; UTSNAME_LEN],
+    pub release: [u8; UTSNAME_LEN],
+    pub version: [u8; UTSNAME_LEN],
+    pub machine: [u8; UTSNAME_LEN],
+    pub domainname: [u8; UTSNAME_LEN],
+}
+
+/// 把字符串复制为以 NUL 结尾的定长字段（过长时截断）
+const fn uts_field(s: &str) -> [u8; UTSNAME_LEN] {
+    let bytes = s.as_bytes();
+    let mut field = [0; UTSNAME_LEN];
+    let mut i = 0;
+    while i < bytes.len() && i < UTSNAME_LEN - 1 {
+        field[i] = bytes[i];
+        i += 1;
+    }
+    field
+}
+
+impl Utsname {
+    /// 本内核的 uname 信息
+    pub const fn kernel() -> Self {
+        Utsname {
+            sysname: uts_field("ErrorOS"),
+            nodename: uts_field("erroros"),
+            release: uts_field(env!("CARGO_PKG_VERSION")),
+            version: uts_field("#1"),
+            machine: uts_field("riscv64"),
+            domainname: uts_field("(none)"),
+        }
+    }
+
+    /// 字段内容（到第一个 NUL 为止）
+    pub fn field_str(field: &[u8; UTSNAME_LEN]) -> &str {
+        let len = field.iter().position(|&b| b == 0).unwrap_or(UTSNAME_LEN);
+        core::str::from_utf8(&field[..len]).unwrap_or("")
+    }
+}
+
+// ============================================
+// sysinfo
+// ============================================
+
+/// `struct sysinfo`（64 位布局，_f 填充为 0 字节）
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sysinfo {
+    /// 启动以来的秒数
+    pub uptime: i64,
+    /// 1、5、15 分钟平均负载（乘以 65536）
+    pub loads: [u64; 3],
+    pub totalram: u64,
+    pub freeram: u64,
+    pub sharedram: u64,
+    pub bufferram: u64,
+    pub totalswap: u64,
+    pub freeswap: u64,
+    /// 进程数
+    pub procs: u16,
+    pub pad: u16,
+    pub totalhigh: u64,
+    pub freehigh: u64,
+    /// 以上内存字段的单位（字节）
+    pub mem_unit: u32,
+}
+
+impl Sysinfo {
+    /// 由物理页帧统计构造（内存字段以页为单位）
+    pub fn from_frames(uptime_secs: i64, total_frames: usize, free_frames: usize) -> Self {
+        Sysinfo {
+            uptime: uptime_secs,
+            totalram: total_frames as u64,
+            freeram: free_frames as u64,
+            procs: 1,
+            mem_unit: crate::memory::PAGE_SIZE as u32,
+            ..Sysinfo::default()
+        }
+    }
+}
+
+// ============================================
+// 编译期布局检查
+// ============================================
+
+const _: () = {
+    assert!(size_of::<Timespec>() == 16);
+    assert!(offset_of!(Timespec, tv_nsec) == 8);
+
+    assert!(size_of::<Timeval>() == 16);
+    assert!(offset_of!(Timeval, tv_usec) == 8);
+
+    assert!(size_of::<Stat>() == 128);
+    assert!(offset_of!(Stat, st_mode) == 16);
+    assert!(offset_of!(Stat, st_rdev) == 32);
+    assert!(offset_of!(Stat, st_size) == 48);
+    assert!(offset_of!(Stat, st_blksize) == 56);
+    assert!(offset_of!(Stat, st_blocks) == 64);
+    assert!(offset_of!(Stat, st_mtime) == 88);
+    assert!(offset_of!(Stat, st_ctime_nsec) == 112);
+
+    assert!(size_of::<Utsname>() == 390);
+    assert!(offset_of!(Utsname, release) == 130);
+    assert!(offset_of!(Utsname, machine) == 260);
+
+    assert!(size_of::<Sysinfo>() == 112);
+    assert!(offset_of!(Sysinfo, totalram) == 32);
+    assert!(offset_of!(Sysinfo, procs) == 80);
+    assert!(offset_of!(Sysinfo, totalhigh) == 88);
+    assert!(offset_of!(Sysinfo, mem_unit) == 104);
+};
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_abi_layouts() {
+        // 参考值：Linux riscv64 头文件中的布局
+        let layouts = [
+            (size_of::<Timespec>(), 16),
+            (size_of::<Stat>(), 128),
+            (offset_of!(Stat, st_size), 48),
+            (offset_of!(Stat, st_atime_nsec), 80),
+            (size_of::<Utsname>(), 390),
+            (offset_of!(Utsname, domainname), 325),
+            (size_of::<Sysinfo>(), 112),
+            (offset_of!(Sysinfo, freeram), 40),
+            (offset_of!(Sysinfo, mem_unit), 104),
+        ];
+        for (actual, expected) in layouts {
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test_case]
+    fn test_abi_conversions() {
+        let ts = Timespec::from_nanos(3 * NSEC_PER_SEC + 7);
+        assert_eq!((ts.tv_sec, ts.tv_nsec), (3, 7));
+        assert_eq!(ts.as_nanos(), Some(3 * NSEC_PER_SEC + 7));
+        let bad = Timespec { tv_sec: 0, tv_nsec: NSEC_PER_SEC as i64 };
+        assert_eq!(bad.as_nanos(), None);
+
+        let mut stat = Stat::new(S_IFREG | 0o644, 1000, 4096);
+        stat.set_times(ts);
+        assert_eq!((stat.st_blocks, stat.st_mtime, stat.st_ctime_nsec), (2, 3, 7));
+
+        let uts = Utsname::kernel();
+        assert_eq!(Utsname::field_str(&uts.machine), "riscv64");
+        assert_eq!(Utsname::field_str(&uts_field(&"x".repeat(80))).len(), UTSNAME_LEN - 1);
+
+        let info = Sysinfo::from_frames(5, 100, 40);
+        assert_eq!((info.totalram, info.freeram, info.mem_unit), (100, 40, 4096));
+    }
+}
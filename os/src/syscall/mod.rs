@@ -0,0 +1,394 @@
+/*
+ * ============================================
+ * 系统调用
+ * ============================================
+ * 功能：系统调用分发与返回值编码
+ * 包括：
+ * - 系统调用号（与 Linux RISC-V 一致）
+ * - Errno：错误码
+ * - SyscallResult：系统调用的返回值
+ * - syscall_dispatcher：按调用号分发到 syscall_impl 中的实现
+ * - abi：复制给用户的结构体布局（timespec、stat 等）
+ *
+ * 返回值的 ABI 编码（a0 寄存器）：
+ * - 成功：非负数，即返回值本身
+ * - 失败：错误码的相反数（例如 -EBADF = -9）
+ *
+ * 实现函数内部只使用 SyscallResult，
+ * 只有 syscall_dispatcher 在边界处转换为 isize
+ * ============================================
+ */
+
+pub mod abi;
+pub mod syscall_impl;
+
+use core::fmt;
+
+// ============================================
+// 系统调用号
+// ============================================
+
+/// read(fd, buf, len)
+pub const SYS_READ: usize = 63;
+
+/// write(fd, buf, len)
+pub const SYS_WRITE: usize = 64;
+
+/// exit(code)
+pub const SYS_EXIT: usize = 93;
+
+/// set_tid_address(tidptr)
+pub const SYS_SET_TID_ADDRESS: usize = 96;
+
+/// nanosleep(nanos)（参数直接是纳秒数，不是 timespec）
+pub const SYS_NANOSLEEP: usize = 101;
+
+/// sched_yield()
+pub const SYS_YIELD: usize = 124;
+
+/// gettimeofday(tv, tz)
+pub const SYS_GETTIMEOFDAY: usize = 169;
+
+/// getpid()
+pub const SYS_GETPID: usize = 172;
+
+// ============================================
+// 错误码
+// ============================================
+
+/// 系统调用错误码（数值与 Linux 一致）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(isize)]
+pub enum Errno {
+    /// 操作不允许
+    EPERM = 1,
+    /// 文件或目录不存在
+    ENOENT = 2,
+    /// 无效的文件描述符
+    EBADF = 9,
+    /// 无效的地址
+    EFAULT = 14,
+    /// 无效的参数
+    EINVAL = 22,
+    /// 系统调用未实现
+    ENOSYS = 38,
+}
+
+impl Errno {
+    /// 所有错误码
+    const ALL: [Errno; 6] = [
+        Errno::EPERM,
+        Errno::ENOENT,
+        Errno::EBADF,
+        Errno::EFAULT,
+        Errno::EINVAL,
+        Errno::ENOSYS,
+    ];
+
+    /// 错误码的数值（正数）
+    pub const fn code(self) -> isize {
+        self as isize
+    }
+
+    /// 写回 a0 的值（错误码的相反数，例如 EBADF → -9）
+    pub const fn to_isize(self) -> isize {
+        -self.code()
+    }
+
+    /// 由数值查找错误码
+    pub fn from_code(code: isize) -> Option<Errno> {
+        Self::ALL.into_iter().find(|errno| errno.code() == code)
+    }
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}({})", self, self.code())
+    }
+}
+
+// ============================================
+// 返回值
+// ============================================
+
+/// 系统调用的返回值
+///
+/// # 说明
+/// 成功的返回值不能超过 `isize::MAX`，否则编码后会被当成错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallResult(Result<usize, Errno>);
+
+impl SyscallResult {
+    /// 成功
+    pub const fn ok(value: usize) -> Self {
+        SyscallResult(Ok(value))
+    }
+
+    /// 失败
+    pub const fn err(errno: Errno) -> Self {
+        SyscallResult(Err(errno))
+    }
+
+    /// 转换为 `Result`
+    pub const fn into_result(self) -> Result<usize, Errno> {
+        self.0
+    }
+
+    /// 编码为 a0 寄存器中的值
+    ///
+    /// # 返回
+    /// - `Ok(n)` → `n`
+    /// - `Err(e)` → `-e`
+    pub fn into_raw(self) -> isize {
+        match self.0 {
+            Ok(value) => {
+                debug_assert!(value <= isize::MAX as usize, "syscall return value too large");
+                value as isize
+            }
+            Err(errno) => errno.to_isize(),
+        }
+    }
+
+    /// 从 a0 寄存器中的值解码
+    ///
+    /// # 返回
+    /// - `Err`: 负数不是已知的错误码
+    pub fn from_raw(raw: isize) -> Result<Self, &'static str> {
+        if raw >= 0 {
+            return Ok(SyscallResult::ok(raw as usize));
+        }
+        raw.checked_neg()
+            .and_then(Errno::from_code)
+            .map(SyscallResult::err)
+            .ok_or("Unknown errno")
+    }
+}
+
+impl From<Result<usize, Errno>> for SyscallResult {
+    fn from(result: Result<usize, Errno>) -> Self {
+        SyscallResult(result)
+    }
+}
+
+impl From<SyscallResult> for isize {
+    fn from(result: SyscallResult) -> Self {
+        result.into_raw()
+    }
+}
+
+// ============================================
+// 分发
+// ============================================
+
+/// 系统调用分发
+///
+/// # 参数
+/// - `id`: 系统调用号（a7）
+/// - `args`: 参数（a0 ~ a2）
+///
+/// # 返回
+/// 写回 a0 的值（见 `SyscallResult::into_raw`）
+pub fn syscall_dispatcher(id: usize, args: [usize; 3]) -> isize {
+    let result = match id {
+        SYS_READ => syscall_impl::sys_read(args[0], args[1], args[2]),
+        SYS_WRITE => syscall_impl::sys_write(args[0], args[1], args[2]),
+        SYS_EXIT => syscall_impl::sys_exit(args[0] as i32),
+        SYS_SET_TID_ADDRESS => syscall_impl::sys_set_tid_address(args[0]),
+        SYS_NANOSLEEP => syscall_impl::sys_nanosleep(args[0]),
+        SYS_YIELD => syscall_impl::sys_yield(),
+        SYS_GETTIMEOFDAY => syscall_impl::sys_gettimeofday(args[0], args[1]),
+        SYS_GETPID => syscall_impl::sys_getpid(),
+        _ => SyscallResult::err(Errno::ENOSYS),
+    };
+    result.into_raw()
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_syscall_result_round_trip() {
+        for value in [0, 1, 4096, isize::MAX as usize] {
+            let raw = SyscallResult::ok(value).into_raw();
+            assert_eq!(raw, value as isize);
+            assert_eq!(SyscallResult::from_raw(raw), Ok(SyscallResult::ok(value)));
+        }
+        for errno in Errno::ALL {
+            let raw: isize = SyscallResult::err(errno).into();
+            assert_eq!(raw, -errno.code());
+            assert_eq!(SyscallResult::from_raw(raw), Ok(SyscallResult::err(errno)));
+        }
+
+        assert_eq!(SyscallResult::from_raw(-1000), Err("Unknown errno"));
+        assert_eq!(SyscallResult::from_raw(isize::MIN), Err("Unknown errno"));
+    }
+
+    #[test_case]
+    fn test_dispatcher_encodes_at_boundary() {
+        assert_eq!(syscall_dispatcher(SYS_GETPID, [0; 3]), 0);
+        assert_eq!(syscall_dispatcher(SYS_WRITE, [1, 0, 0]), -Errno::EFAULT.code());
+        assert_eq!(syscall_dispatcher(usize::MAX, [0; 3]), -Errno::ENOSYS.code());
+    }
+
+    #[test_case]
+    fn test_write_errors_are_distinguishable() {
+        let message = b"x";
+        let ptr = message.as_ptr() as usize;
+
+        assert_eq!(syscall_dispatcher(SYS_WRITE, [1, 0, 1]), Errno::EFAULT.to_isize());
+        assert_eq!(syscall_dispatcher(SYS_WRITE, [0, ptr, 1]), Errno::EBADF.to_isize());
+        assert_eq!(syscall_dispatcher(SYS_WRITE, [7, ptr, 1]), Errno::EBADF.to_isize());
+        assert_eq!(Errno::EFAULT.to_isize(), -14);
+        assert_eq!(Errno::EBADF.to_isize(), -9);
+        assert_ne!(Errno::EFAULT.to_isize(), Errno::EBADF.to_isize());
+    }
+
+    #[test_case]
+    fn test_yield_asks_executor_to_switch() {
+        use crate::interrupts::without_interrupts;
+        use crate::task::budget;
+
+        // 不在执行器的 poll 中：直接返回
+        assert_eq!(syscall_dispatcher(SYS_YIELD, [0; 3]), 0);
+        assert!(!budget::budget_exceeded());
+
+        // 在 poll 中：当前任务在下一个检查点让出
+        without_interrupts(|| {
+            budget::begin_poll();
+            assert!(!budget::budget_exceeded());
+            assert_eq!(syscall_dispatcher(SYS_YIELD, [0; 3]), 0);
+            assert!(budget::budget_exceeded());
+            budget::end_poll();
+        });
+    }
+
+    #[test_case]
+    fn test_set_tid_address_records_pointer() {
+        use syscall_impl::clear_child_tid;
+
+        let mut tid = -1i32;
+        let tidptr = &mut tid as *mut i32 as usize;
+        let pid = syscall_dispatcher(SYS_GETPID, [0; 3]);
+        assert_eq!(syscall_dispatcher(SYS_SET_TID_ADDRESS, [tidptr, 0, 0]), pid);
+        assert_eq!(clear_child_tid(), tidptr);
+        // 只记录地址，现在不写入
+        assert_eq!(tid, -1);
+
+        // 空指针清除记录，同样不会失败
+        assert_eq!(syscall_dispatcher(SYS_SET_TID_ADDRESS, [0; 3]), pid);
+        assert_eq!(clear_child_tid(), 0);
+    }
+
+    #[test_case]
+    fn test_nanosleep_waits_on_time_csr() {
+        use riscv::register::time;
+
+        let start = time::read64();
+        assert_eq!(syscall_dispatcher(SYS_NANOSLEEP, [0; 3]), 0);
+
+        // 2ms = 20,000 个计数（10MHz），比时钟中断间隔短：最后一段忙等，不等下一次中断
+        assert_eq!(syscall_dispatcher(SYS_NANOSLEEP, [2_000_000, 0, 0]), 0);
+        let elapsed = time::read64() - start;
+        assert!(elapsed >= crate::clock::nanos_to_ticks(2_000_000));
+        assert!(elapsed < crate::clock::TICK_INTERVAL);
+    }
+
+    #[test_case]
+    fn test_gettimeofday() {
+        let mut first = abi::Timeval::default();
+        let mut second = abi::Timeval { tv_sec: -1, tv_usec: -1 };
+        let call = |tv: &mut abi::Timeval| {
+            syscall_dispatcher(SYS_GETTIMEOFDAY, [tv as *mut _ as usize, 0, 0])
+        };
+        assert_eq!(call(&mut first), 0);
+        assert_eq!(call(&mut second), 0);
+        for tv in [first, second] {
+            assert!((0..1_000_000).contains(&tv.tv_usec));
+        }
+        assert!(second.tv_sec >= first.tv_sec);
+        assert!((second.tv_sec, second.tv_usec) >= (first.tv_sec, first.tv_usec));
+
+        // 空指针和未对齐的指针
+        assert_eq!(syscall_dispatcher(SYS_GETTIMEOFDAY, [0; 3]), -Errno::EFAULT.code());
+        let misaligned = &mut first as *mut _ as usize + 1;
+        let args = [misaligned, 0, 0];
+        assert_eq!(syscall_dispatcher(SYS_GETTIMEOFDAY, args), -Errno::EFAULT.code());
+    }
+
+    #[test_case]
+    fn test_write_reports_copy_failure() {
+        use crate::faultinject::{arm, Policy};
+
+        let message = b"!";
+        let args = [1, message.as_ptr() as usize, message.len()];
+        let _fault = arm("user_copy", Policy::EveryN(2));
+        let output = crate::console::capture();
+        assert_eq!(syscall_dispatcher(SYS_WRITE, args), 1);
+        assert_eq!(syscall_dispatcher(SYS_WRITE, args), -Errno::EFAULT.code());
+        // 失败的那次什么也没有输出
+        assert_eq!(output.take(), "!");
+    }
+
+    #[test_case]
+    fn test_read_takes_keyboard_input() {
+        use crate::task::keyboard::{add_scancode, read_available, ScancodeStream};
+
+        // 初始化队列并清空之前的输入
+        let _stream = ScancodeStream::new();
+        while read_available(&mut [0; 16]) > 0 {}
+
+        for &byte in b"hi\n" {
+            add_scancode(byte);
+        }
+        let mut buf = [0u8; 8];
+        let args = [0, buf.as_mut_ptr() as usize, buf.len()];
+        assert_eq!(syscall_dispatcher(SYS_READ, args), 3);
+        assert_eq!(&buf[..3], b"hi\n");
+
+        // 没有输入时返回 0；缓冲区较小时只取走能放下的部分
+        assert_eq!(syscall_dispatcher(SYS_READ, args), 0);
+        add_scancode(b'a');
+        add_scancode(b'b');
+        assert_eq!(syscall_dispatcher(SYS_READ, [0, buf.as_mut_ptr() as usize, 1]), 1);
+        assert_eq!(syscall_dispatcher(SYS_READ, args), 1);
+        assert_eq!(buf[0], b'b');
+
+        assert_eq!(syscall_dispatcher(SYS_READ, [1, args[1], args[2]]), -Errno::EBADF.code());
+        assert_eq!(syscall_dispatcher(SYS_READ, [0, 0, 8]), -Errno::EFAULT.code());
+    }
+
+    #[test_case]
+    fn test_large_write_is_chunked_and_resumable() {
+        use crate::faultinject::{arm, Policy};
+        use alloc::string::String;
+        use syscall_impl::IO_CHUNK;
+
+        // 第一块的末尾正好落在一个两字节字符中间
+        let mut text = String::from("a").repeat(IO_CHUNK - 1);
+        text.push('é');
+        text.push_str(&"b".repeat(2 * IO_CHUNK));
+        let len = text.len();
+        let base = text.as_ptr() as usize;
+
+        // 第三块之前读取失败：返回前两块写入的字节数
+        let output = crate::console::capture();
+        let first = {
+            let _fault = arm("user_copy", Policy::NthCall(3));
+            syscall_dispatcher(SYS_WRITE, [1, base, len])
+        };
+        assert_eq!(first, (2 * IO_CHUNK - 1) as isize);
+
+        // 从短写处重试，输出既不重复也不缺失
+        let first = first as usize;
+        let second = syscall_dispatcher(SYS_WRITE, [1, base + first, len - first]);
+        assert_eq!(first + second as usize, len);
+        let written = output.take();
+        assert!(!written.contains(char::REPLACEMENT_CHARACTER));
+        assert_eq!(written, text);
+    }
+}
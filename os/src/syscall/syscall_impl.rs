@@ -0,0 +1,236 @@
+/*
+ * ============================================
+ * 系统调用实现
+ * ============================================
+ * 功能：各个系统调用的具体实现
+ *
+ * 所有实现都返回 SyscallResult，
+ * 由 syscall_dispatcher 统一编码为 isize
+ *
+ * 目前还没有用户态进程，调用者即内核本身：
+ * - 缓冲区地址按内核地址直接访问
+ * - getpid 总是返回 0
+ * - gettimeofday 返回启动以来的时间（没有实时时钟）
+ * - read 从标准输入读取时取键盘队列中已有的字符，没有输入时返回 0 而不等待
+ * - yield 要求执行器中正在 poll 的任务在下一个预算检查点让出（见 task::budget）
+ * - nanosleep 没有别的进程可以切换，调用者原地等到期限（见 sys_nanosleep）
+ * - set_tid_address 没有 PCB，地址记在全局变量里，返回值和 getpid 相同
+ *
+ * 大块写入按 IO_CHUNK 分块处理，每块之前重新检查剩余的用户缓冲区；
+ * 中途失败时按短写语义返回已经写入的字节数，调用者从那里重试。
+ * 以后有了信号和抢占式调度，块之间也是检查待处理信号、让出时间片的位置
+ * ============================================
+ */
+
+use super::abi::Timeval;
+use super::{Errno, SyscallResult};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::{klog, print};
+
+/// 标准输入
+const STDIN: usize = 0;
+
+/// 标准输出
+const STDOUT: usize = 1;
+
+/// 标准错误
+const STDERR: usize = 2;
+
+/// 大块读写每次处理的字节数
+pub const IO_CHUNK: usize = 4096;
+
+/// 读取文件描述符
+///
+/// # 参数
+/// - `fd`: 文件描述符，目前只支持标准输入（键盘队列）
+/// - `buf`: 缓冲区地址
+/// - `len`: 缓冲区的字节数
+///
+/// # 返回
+/// - 成功：读到的字节数，没有可用的输入时为 0（不阻塞）
+/// - `EBADF`: 不支持的文件描述符
+/// - `EFAULT`: 缓冲区地址为空，或无法写入
+pub fn sys_read(fd: usize, buf: usize, len: usize) -> SyscallResult {
+    if fd != STDIN {
+        return SyscallResult::err(Errno::EBADF);
+    }
+    if buf == 0 || crate::fault_point!("user_copy") {
+        return SyscallResult::err(Errno::EFAULT);
+    }
+
+    let bytes = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len.min(IO_CHUNK)) };
+    SyscallResult::ok(crate::task::keyboard::read_available(bytes))
+}
+
+/// 写入文件描述符
+///
+/// # 参数
+/// - `fd`: 文件描述符，目前只支持标准输出和标准错误
+/// - `buf`: 缓冲区地址
+/// - `len`: 字节数
+///
+/// # 返回
+/// - 成功：写入的字节数；按块写入时后面的块失败，返回已经写入的部分（短写）
+/// - `EBADF`: 不支持的文件描述符
+/// - `EFAULT`: 缓冲区地址为空，或第一块就无法读取
+pub fn sys_write(fd: usize, buf: usize, len: usize) -> SyscallResult {
+    if fd != STDOUT && fd != STDERR {
+        return SyscallResult::err(Errno::EBADF);
+    }
+    if buf == 0 {
+        return SyscallResult::err(Errno::EFAULT);
+    }
+
+    let bytes = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+    let mut written = 0;
+    while written < len {
+        // 每块之前重新检查剩余的缓冲区
+        if crate::fault_point!("user_copy") {
+            return match written {
+                0 => SyscallResult::err(Errno::EFAULT),
+                _ => SyscallResult::ok(written),
+            };
+        }
+        let end = chunk_end(bytes, written);
+        write_text(&bytes[written..end]);
+        written = end;
+    }
+    SyscallResult::ok(len)
+}
+
+/// 从 `start` 开始的一块的结束位置
+///
+/// # 说明
+/// 块最长 IO_CHUNK 字节；不在缓冲区末尾结束时退回到 UTF-8 字符边界，
+/// 多字节字符不会被拆到两块里（拆开后两半都会输出为替换字符）
+fn chunk_end(bytes: &[u8], start: usize) -> usize {
+    let end = (start + IO_CHUNK).min(bytes.len());
+    if end == bytes.len() {
+        return end;
+    }
+    // UTF-8 字符最长 4 字节：最多退回 3 个后续字节（10xxxxxx）
+    let is_continuation = |index: usize| bytes[index] & 0xc0 == 0x80;
+    (end - 3..=end).rev().find(|&index| !is_continuation(index)).unwrap_or(end)
+}
+
+/// 输出一块文本（无效的 UTF-8 字节按替换字符输出）
+fn write_text(bytes: &[u8]) {
+    for chunk in bytes.utf8_chunks() {
+        print!("{}", chunk.valid());
+        if !chunk.invalid().is_empty() {
+            print!("{}", char::REPLACEMENT_CHARACTER);
+        }
+    }
+}
+
+/// 退出
+///
+/// # 说明
+/// 还没有进程可以结束，打印退出码后停机
+pub fn sys_exit(code: i32) -> ! {
+    klog!("[SYSCALL] exit({})", code);
+    crate::hlt_loop();
+}
+
+/// 让出 CPU
+///
+/// # 说明
+/// 调用者在执行器的任务中时，本次 poll 视为超出预算：
+/// 下一个 `budget_exceeded` / `budget::consume` 检查点返回 Pending，执行器先运行其他任务。
+/// 不在任务中时什么也不做
+pub fn sys_yield() -> SyscallResult {
+    crate::task::budget::request_yield();
+    SyscallResult::ok(0)
+}
+
+/// 睡眠
+///
+/// # 参数
+/// - `nanos`: 纳秒数（按 time 计数器的频率换算，向上取整）
+///
+/// # 返回
+/// 到期后返回 0
+///
+/// # 说明
+/// 周期时钟中断不为睡眠重新设置：下一次中断在期限之前时用 wfi 等它，
+/// 否则忙等，不会因为等中断而多睡一个时钟中断间隔
+pub fn sys_nanosleep(nanos: usize) -> SyscallResult {
+    use riscv::register::{sstatus, time};
+
+    let end = time::read64().saturating_add(crate::clock::nanos_to_ticks(nanos as u64));
+    loop {
+        let now = time::read64();
+        if now >= end {
+            break;
+        }
+        let deadline = crate::interrupts::timer_deadline();
+        if sstatus::read().sie() && (now..=end).contains(&deadline) {
+            riscv::asm::wfi();
+        } else {
+            core::hint::spin_loop();
+        }
+    }
+    SyscallResult::ok(0)
+}
+
+/// set_tid_address 记录的地址（0 表示没有）
+static CLEAR_CHILD_TID: AtomicUsize = AtomicUsize::new(0);
+
+/// 设置线程退出时清零的地址
+///
+/// # 参数
+/// - `tidptr`: 线程退出时写入 0 并唤醒等待者的地址，0 表示取消
+///
+/// # 返回
+/// 当前线程号（只有一个线程，等于进程号）
+///
+/// # 说明
+/// C 运行时（如 musl）启动时调用，不能失败。
+/// 还没有线程退出，只记录地址；有了进程之后应改为记在 PCB 中
+pub fn sys_set_tid_address(tidptr: usize) -> SyscallResult {
+    CLEAR_CHILD_TID.store(tidptr, Ordering::Relaxed);
+    sys_getpid()
+}
+
+/// set_tid_address 记录的地址
+pub fn clear_child_tid() -> usize {
+    CLEAR_CHILD_TID.load(Ordering::Relaxed)
+}
+
+/// 获取进程号
+pub fn sys_getpid() -> SyscallResult {
+    SyscallResult::ok(0)
+}
+
+/// 获取当前时间（启动以来的时间，没有实时时钟）
+///
+/// # 参数
+/// - `tv`: `Timeval` 的地址
+/// - `tz`: 时区，忽略（时间总是 UTC）
+///
+/// # 返回
+/// - 成功：0
+/// - `EFAULT`: `tv` 为空、未对齐或无法写入
+pub fn sys_gettimeofday(tv: usize, _tz: usize) -> SyscallResult {
+    let tv = match user_ptr::<Timeval>(tv) {
+        Ok(tv) => tv,
+        Err(errno) => return SyscallResult::err(errno),
+    };
+    let micros = crate::clock::ticks_to_micros(crate::clock::now());
+    unsafe { tv.write(Timeval::from_micros(micros)) };
+    SyscallResult::ok(0)
+}
+
+/// 检查要写入的用户指针
+///
+/// # 返回
+/// - `EFAULT`: 空指针、没有按 `T` 对齐，或复制失败（故障注入点 "user_copy"）
+fn user_ptr<T>(addr: usize) -> Result<*mut T, Errno> {
+    if addr == 0 || !addr.is_multiple_of(core::mem::align_of::<T>()) {
+        return Err(Errno::EFAULT);
+    }
+    if crate::fault_point!("user_copy") {
+        return Err(Errno::EFAULT);
+    }
+    Ok(addr as *mut T)
+}
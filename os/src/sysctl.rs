@@ -0,0 +1,294 @@
+/*
+ * ============================================
+ * 运行时内核参数（sysctl）
+ * ============================================
+ * 功能：在运行时读写内核的可调参数，不用重新编译
+ *
+ * 设计：
+ * - 每个参数由所属模块用 tunable! 宏声明一个 Tunable（名字、类型、范围、读写函数），
+ *   放进 .tunables 段（链接脚本用 __tunables_start / __tunables_end 标出范围）
+ * - 参数名为 "子系统.名字"，对应路径 /proc/sys/子系统/名字；两种写法都接受
+ * - 写入超出范围或无法解析的值返回 EINVAL，不存在的参数返回 ENOENT
+ *
+ * 内核还没有 VFS 和 shell：
+ * - read_file / write_file 提供 /proc/sys 文件的读写语义（内容为一行文本）
+ * - 命令 sysctl [name[=value]] 由 run_command 解析
+ * - 启动时命令行中的 sysctl.name=value 由 apply_cmdline 应用
+ * ============================================
+ */
+
+use crate::{klog, println};
+use crate::syscall::Errno;
+use alloc::format;
+use alloc::string::String;
+
+/// /proc/sys 路径前缀
+pub const PROC_SYS: &str = "/proc/sys/";
+
+/// 命令行参数前缀
+const CMDLINE_PREFIX: &str = "sysctl.";
+
+/// 参数类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// 开关（0 / 1，也接受 on / off、true / false）
+    Bool,
+    /// 无符号整数（0x 开头为十六进制）
+    U64,
+}
+
+/// 可调参数
+pub struct Tunable {
+    /// 参数名（"子系统.名字"）
+    pub name: &'static str,
+    /// 类型
+    pub kind: Kind,
+    /// 允许的最小值
+    pub min: u64,
+    /// 允许的最大值
+    pub max: u64,
+    /// 读取当前值
+    pub get: fn() -> u64,
+    /// 设置新值（只会收到范围内的值）
+    pub set: fn(u64),
+}
+
+impl Tunable {
+    /// 在 /proc/sys 下的路径
+    pub fn path(&self) -> String {
+        format!("{}{}", PROC_SYS, self.name.replace('.', "/"))
+    }
+
+    /// 解析文本形式的值
+    ///
+    /// # 返回
+    /// - `Err(EINVAL)`: 无法解析或超出范围
+    pub fn parse(&self, text: &str) -> Result<u64, Errno> {
+        let value = match (self.kind, text) {
+            (Kind::Bool, "on" | "true") => 1,
+            (Kind::Bool, "off" | "false") => 0,
+            _ => crate::peek::parse_number(text).map_err(|_| Errno::EINVAL)?,
+        };
+        if value < self.min || value > self.max {
+            return Err(Errno::EINVAL);
+        }
+        Ok(value)
+    }
+}
+
+/// 注册可调参数（放进 .tunables 段，由 tunables 找到）
+///
+/// # 示例
+/// ```ignore
+/// tunable!(QUANTUM_TUNABLE = Tunable { name: "sched.quantum", ... });
+/// ```
+macro_rules! tunable {
+    ($ident:ident = $spec:expr) => {
+        #[used]
+        #[link_section = ".tunables"]
+        static $ident: $crate::sysctl::Tunable = $spec;
+    };
+}
+pub(crate) use tunable;
+
+/// 全部注册的参数（顺序由链接器决定）
+pub fn tunables() -> &'static [Tunable] {
+    extern "C" {
+        static __tunables_start: u8;
+        static __tunables_end: u8;
+    }
+    unsafe {
+        let start = &__tunables_start as *const u8 as *const Tunable;
+        let end = &__tunables_end as *const u8 as *const Tunable;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// 按名字或 /proc/sys 路径查找参数
+pub fn find(name: &str) -> Option<&'static Tunable> {
+    match name.strip_prefix(PROC_SYS) {
+        Some(path) => tunables().iter().find(|t| t.name.split('.').eq(path.split('/'))),
+        None => tunables().iter().find(|t| t.name == name),
+    }
+}
+
+/// 读取参数
+///
+/// # 返回
+/// - `Err(ENOENT)`: 参数不存在
+pub fn get(name: &str) -> Result<u64, Errno> {
+    find(name).map(|t| (t.get)()).ok_or(Errno::ENOENT)
+}
+
+/// 设置参数
+///
+/// # 返回
+/// - `Err(ENOENT)`: 参数不存在
+/// - `Err(EINVAL)`: 无法解析或超出范围（参数保持不变）
+pub fn set(name: &str, text: &str) -> Result<(), Errno> {
+    let tunable = find(name).ok_or(Errno::ENOENT)?;
+    let value = tunable.parse(text)?;
+    (tunable.set)(value);
+    Ok(())
+}
+
+/// 读取 /proc/sys 文件的内容（一行文本）
+pub fn read_file(path: &str) -> Result<String, Errno> {
+    let tunable = path.strip_prefix(PROC_SYS).and_then(|_| find(path)).ok_or(Errno::ENOENT)?;
+    Ok(format!("{}\n", (tunable.get)()))
+}
+
+/// 写入 /proc/sys 文件
+///
+/// # 返回
+/// 写入的字节数（整个缓冲区）
+pub fn write_file(path: &str, data: &[u8]) -> Result<usize, Errno> {
+    if !path.starts_with(PROC_SYS) {
+        return Err(Errno::ENOENT);
+    }
+    let text = core::str::from_utf8(data).map_err(|_| Errno::EINVAL)?;
+    set(path, text.trim())?;
+    Ok(data.len())
+}
+
+/// 应用内核命令行中的 sysctl.name=value 参数
+///
+/// # 返回
+/// 成功应用的参数个数（失败的参数打印警告后忽略）
+pub fn apply_cmdline(cmdline: &str) -> usize {
+    let mut applied = 0;
+    for arg in cmdline.split_whitespace() {
+        let Some((name, value)) = arg.strip_prefix(CMDLINE_PREFIX).and_then(|a| a.split_once('='))
+        else {
+            continue;
+        };
+        match set(name, value) {
+            Ok(()) => applied += 1,
+            Err(e) => {
+                klog!("[SYSCTL] ignoring {}: {}", arg, e);
+            }
+        }
+    }
+    applied
+}
+
+/// 打印一个参数
+fn show(tunable: &Tunable) {
+    println!("{} = {}", tunable.name, (tunable.get)());
+}
+
+/// 解析并执行一条 sysctl 命令
+///
+/// # 命令
+/// - `sysctl`：列出全部参数
+/// - `sysctl name`：显示一个参数
+/// - `sysctl name=value`：设置参数并显示新值
+pub fn run_command(line: &str) -> Result<(), &'static str> {
+    let mut args = line.split_whitespace();
+    if args.next() != Some("sysctl") {
+        return Err("Unknown command");
+    }
+    let Some(arg) = args.next() else {
+        tunables().iter().for_each(show);
+        return Ok(());
+    };
+    if args.next().is_some() {
+        return Err("usage: sysctl [name[=value]]");
+    }
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (arg, None),
+    };
+    let tunable = find(name).ok_or("Unknown parameter")?;
+    if let Some(value) = value {
+        set(name, value).map_err(|_| "Invalid value")?;
+    }
+    show(tunable);
+    Ok(())
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use crate::interrupts::without_interrupts;
+    use crate::task::budget;
+
+    #[test_case]
+    fn test_registry_and_paths() {
+        for name in [
+            "sched.quantum",
+            "sched.overrun_report",
+            "irq.latency_warn",
+            "irq.fault_storm",
+            "kernel.canon",
+            "debug.unsafe_poke",
+        ] {
+            let tunable = find(name).expect("tunable missing");
+            assert!(core::ptr::eq(find(&tunable.path()).unwrap(), tunable));
+        }
+        assert_eq!(find("sched.quantum").unwrap().path(), "/proc/sys/sched/quantum");
+        assert!(find("/proc/sys/sched").is_none());
+
+        assert_eq!(get("no.such"), Err(Errno::ENOENT));
+        assert_eq!(read_file("sched.quantum"), Err(Errno::ENOENT));
+        assert_eq!(write_file("/proc/sys/no/such", b"1"), Err(Errno::ENOENT));
+    }
+
+    #[test_case]
+    fn test_quantum_write_changes_budget() {
+        const PATH: &str = "/proc/sys/sched/quantum";
+        let default = read_file(PATH).unwrap();
+        assert_eq!(default, format!("{}\n", budget::POLL_BUDGET));
+
+        // 极短的时间片：等过 2000 个计数后本次 poll 即超出预算
+        assert_eq!(write_file(PATH, b"2000\n"), Ok(5));
+        assert_eq!(read_file(PATH).unwrap(), "2000\n");
+        without_interrupts(|| {
+            budget::begin_poll();
+            let start = clock::now();
+            while clock::now() <= start + 2000 {}
+            assert!(budget::budget_exceeded());
+            budget::end_poll();
+        });
+
+        // 恢复默认值后，同样的等待没有超出预算
+        assert_eq!(write_file(PATH, default.as_bytes()), Ok(default.len()));
+        without_interrupts(|| {
+            budget::begin_poll();
+            let start = clock::now();
+            while clock::now() <= start + 2000 {}
+            assert!(!budget::budget_exceeded());
+            budget::end_poll();
+        });
+    }
+
+    #[test_case]
+    fn test_rejects_out_of_bounds() {
+        let quantum = get("sched.quantum").unwrap();
+        assert_eq!(set("sched.quantum", "0"), Err(Errno::EINVAL));
+        assert_eq!(set("sched.quantum", "abc"), Err(Errno::EINVAL));
+        assert_eq!(write_file("/proc/sys/sched/quantum", b"99999999999"), Err(Errno::EINVAL));
+        assert_eq!(get("sched.quantum"), Ok(quantum));
+
+        assert_eq!(set("kernel.canon", "2"), Err(Errno::EINVAL));
+        let canon = get("kernel.canon").unwrap();
+        assert_eq!(set("kernel.canon", "on"), Ok(()));
+        assert!(crate::fmt::is_canonical());
+        set("kernel.canon", &format!("{}", canon)).unwrap();
+
+        // 命令行：只应用 sysctl. 前缀的参数，坏值被忽略
+        let cmdline = "canon=1 sysctl.irq.fault_storm=500 sysctl.irq.latency_warn=0";
+        assert_eq!(apply_cmdline(cmdline), 1);
+        assert_eq!(get("/proc/sys/irq/fault_storm"), Ok(500));
+        set("irq.fault_storm", "1000").unwrap();
+
+        assert!(run_command("sysctl irq.fault_storm").is_ok());
+        assert_eq!(run_command("sysctl irq.fault_storm=0"), Err("Invalid value"));
+        assert_eq!(run_command("sysctl no.such"), Err("Unknown parameter"));
+    }
+}
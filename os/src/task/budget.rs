@@ -0,0 +1,136 @@
+/*
+ * ============================================
+ * 轮询预算（协作式时间片）
+ * ============================================
+ * 功能：防止一个任务在一次 poll 中长时间不返回
+ *
+ * 原理：
+ * - 执行器在 poll 任务前设置本次 poll 的截止时间
+ * - 时钟中断到来（或任务调用 sys_yield）时设置 should_yield 标志
+ * - 长时间运行的操作在合适的位置调用 budget_exceeded()，
+ *   超出预算时唤醒自己并返回 Pending，把 CPU 让给其他任务
+ *
+ * 计算密集的循环可以在循环体中 `budget::consume().await`
+ *
+ * 目前只有一个 hart，因此"每个 hart 一份"的状态用全局变量表示
+ * ============================================
+ */
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Context, Poll};
+use crate::clock;
+use crate::sysctl::{tunable, Kind, Tunable};
+
+/// 每次 poll 的默认预算（time 计数，10MHz 下为 10ms；时间来自 clock::now()）
+pub const POLL_BUDGET: u64 = 100_000;
+
+/// 当前的每次 poll 预算（sysctl sched.quantum）
+static QUANTUM: AtomicU64 = AtomicU64::new(POLL_BUDGET);
+
+tunable!(QUANTUM_TUNABLE = Tunable {
+    name: "sched.quantum",
+    kind: Kind::U64,
+    min: 1_000,
+    max: 10_000_000,
+    get: quantum,
+    set: |value| QUANTUM.store(value, Ordering::Relaxed),
+});
+
+/// 当前的每次 poll 预算
+pub fn quantum() -> u64 {
+    QUANTUM.load(Ordering::Relaxed)
+}
+
+/// 本次 poll 的截止时间（不在 poll 中时为 u64::MAX）
+static DEADLINE: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// 时钟中断设置的让出标志
+static SHOULD_YIELD: AtomicBool = AtomicBool::new(false);
+
+/// 开始一次 poll（由执行器调用）
+pub(crate) fn begin_poll() {
+    SHOULD_YIELD.store(false, Ordering::Relaxed);
+    DEADLINE.store(clock::now().saturating_add(quantum()), Ordering::Relaxed);
+}
+
+/// 结束一次 poll（由执行器调用）
+///
+/// # 返回
+/// 本次 poll 是否超出了预算
+pub(crate) fn end_poll() -> bool {
+    let deadline = DEADLINE.swap(u64::MAX, Ordering::Relaxed);
+    SHOULD_YIELD.store(false, Ordering::Relaxed);
+    clock::now() > deadline
+}
+
+/// 时钟中断和 sys_yield 调用：要求当前任务尽快让出
+pub(crate) fn request_yield() {
+    if DEADLINE.load(Ordering::Relaxed) != u64::MAX {
+        SHOULD_YIELD.store(true, Ordering::Relaxed);
+    }
+}
+
+/// 当前 poll 是否已超出预算
+///
+/// # 说明
+/// 不在执行器的 poll 中时总是返回 false
+pub fn budget_exceeded() -> bool {
+    SHOULD_YIELD.load(Ordering::Relaxed) || clock::now() > DEADLINE.load(Ordering::Relaxed)
+}
+
+/// 超出预算时让出一次的 Future（见 `consume`）
+pub struct Consume {
+    yielded: bool,
+}
+
+impl Future for Consume {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded || !budget_exceeded() {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// 检查预算：未超出时立即完成，超出时让出一次
+///
+/// # 示例
+/// ```ignore
+/// for chunk in data.chunks(64) {
+///     process(chunk);
+///     budget::consume().await;
+/// }
+/// ```
+pub fn consume() -> Consume {
+    Consume { yielded: false }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn test_budget_only_applies_inside_poll() {
+        assert!(!budget_exceeded());
+
+        begin_poll();
+        assert!(!budget_exceeded());
+        request_yield();
+        assert!(budget_exceeded());
+        assert!(!end_poll());
+
+        // poll 结束后时钟中断不再设置标志
+        request_yield();
+        assert!(!budget_exceeded());
+    }
+}
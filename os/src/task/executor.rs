@@ -1,12 +1,39 @@
 use super::{Task, TaskId};
 use alloc::{collections::BTreeMap, sync::Arc};
+use core::sync::atomic::{AtomicU64, Ordering};
 use core::task::Waker;
+use crate::sysctl::{tunable, Kind, Tunable};
 use crossbeam_queue::ArrayQueue;
 
 pub struct Executor {
     tasks: BTreeMap<TaskId, Task>,
     task_queue: Arc<ArrayQueue<TaskId>>,
     waker_cache: BTreeMap<TaskId, Waker>,
+    budget_overruns: BTreeMap<TaskId, BudgetOverrun>,
+}
+
+/// 超出轮询预算达到该次数的任务会出现在统计报告中（默认值）
+pub const OVERRUN_REPORT_THRESHOLD: u64 = 3;
+
+/// 当前的报告阈值（sysctl sched.overrun_report）
+static OVERRUN_REPORT: AtomicU64 = AtomicU64::new(OVERRUN_REPORT_THRESHOLD);
+
+tunable!(OVERRUN_REPORT_TUNABLE = Tunable {
+    name: "sched.overrun_report",
+    kind: Kind::U64,
+    min: 1,
+    max: 1_000_000,
+    get: || OVERRUN_REPORT.load(Ordering::Relaxed),
+    set: |value| OVERRUN_REPORT.store(value, Ordering::Relaxed),
+});
+
+/// 任务超出轮询预算的记录
+#[derive(Debug, Clone, Copy)]
+pub struct BudgetOverrun {
+    /// 任务名称
+    pub name: &'static str,
+    /// 超出预算的 poll 次数
+    pub count: u64,
 }
 
 impl Executor {
@@ -15,6 +42,7 @@ impl Executor {
             tasks: BTreeMap::new(),
             task_queue: Arc::new(ArrayQueue::new(100)),
             waker_cache: BTreeMap::new(),
+            budget_overruns: BTreeMap::new(),
         }
     }
 }
@@ -38,6 +66,7 @@ impl Executor {
             tasks,
             task_queue,
             waker_cache,
+            budget_overruns,
         } = self;
 
         while let Some(task_id) = task_queue.pop() {
@@ -49,7 +78,18 @@ impl Executor {
                 .entry(task_id)
                 .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()));
             let mut context = Context::from_waker(waker);
-            match task.poll(&mut context) {
+            super::budget::begin_poll();
+            let result = task.poll(&mut context);
+            if super::budget::end_poll() {
+                budget_overruns
+                    .entry(task_id)
+                    .or_insert(BudgetOverrun {
+                        name: task.name(),
+                        count: 0,
+                    })
+                    .count += 1;
+            }
+            match result {
                 Poll::Ready(()) => {
                     // 任务完成 -> 移除它和它缓存的唤醒器
                     tasks.remove(&task_id);
@@ -91,6 +131,36 @@ impl TaskWaker {
     }
 }
 impl Executor {
+    /// 运行任务直到没有就绪的任务
+    pub fn run_until_idle(&mut self) {
+        self.run_ready_tasks();
+    }
+
+    /// 超出轮询预算的任务（包括已结束的任务）
+    pub fn budget_overruns(&self) -> impl Iterator<Item = BudgetOverrun> + '_ {
+        self.budget_overruns.values().copied()
+    }
+
+    /// 打印执行器统计：任务数和反复超出预算的任务
+    pub fn print_stats(&self) {
+        use crate::fmt::Elided;
+        use crate::println;
+
+        println!("┌────────────────────────────────────────┐");
+        println!("│ Executor                               │");
+        println!("├────────────────────────────────────────┤");
+        println!("│ Tasks:          {:>8}               │", self.tasks.len());
+        println!("│ Ready:          {:>8}               │", self.task_queue.len());
+        println!("├────────────────────────────────────────┤");
+        let threshold = OVERRUN_REPORT.load(Ordering::Relaxed);
+        println!("│ Budget overruns (>= {})                 │", threshold);
+        for overrun in self.budget_overruns().filter(|o| o.count >= threshold)
+        {
+            println!("│   {:<24} {:>8}    │", overrun.name, Elided(overrun.count));
+        }
+        println!("└────────────────────────────────────────┘");
+    }
+
     pub fn run(&mut self) -> ! {
         loop {
             self.run_ready_tasks();
@@ -111,4 +181,79 @@ fn sleep_if_idle(&self) {
             interrupts::enable_interrupts();
         }
     }
-}
\ No newline at end of file
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::uptime_ticks;
+    use crate::task::budget;
+    use core::future::Future;
+    use core::pin::Pin;
+    use crate::clock::{self, ClockSource, TestClock, TICK_INTERVAL};
+    use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    /// 让出一次
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test_case]
+    fn test_compute_task_does_not_starve_others() {
+        static CLOCK: TestClock = TestClock::new();
+        static COMPUTE_DONE: AtomicBool = AtomicBool::new(false);
+        static MAX_GAP: AtomicU64 = AtomicU64::new(0);
+
+        let _clock = clock::install(&CLOCK);
+        let mut executor = Executor::new();
+
+        // 计算任务：运行 5 个时钟中断间隔（每轮循环推进一个），只在循环中检查预算
+        executor.spawn(Task::with_name("compute", async {
+            let end = CLOCK.now() + 5 * TICK_INTERVAL;
+            while CLOCK.now() < end {
+                CLOCK.advance(1);
+                budget::consume().await;
+            }
+            COMPUTE_DONE.store(true, Ordering::Relaxed);
+        }));
+
+        // 探测任务：记录两次被 poll 之间相隔的时钟中断数
+        executor.spawn(Task::with_name("probe", async {
+            let mut last = uptime_ticks();
+            while !COMPUTE_DONE.load(Ordering::Relaxed) {
+                let now = uptime_ticks();
+                MAX_GAP.fetch_max(now - last, Ordering::Relaxed);
+                last = now;
+                YieldOnce(false).await;
+            }
+        }));
+
+        executor.run_until_idle();
+
+        assert!(COMPUTE_DONE.load(Ordering::Relaxed));
+        // 没有预算时探测任务要等 5 个 tick
+        assert!(MAX_GAP.load(Ordering::Relaxed) <= 1);
+
+        let compute = executor
+            .budget_overruns()
+            .find(|o| o.name == "compute")
+            .expect("compute task not reported");
+        assert!(compute.count >= OVERRUN_REPORT_THRESHOLD);
+        assert!(executor.budget_overruns().all(|o| o.name != "probe"));
+    }
+}
@@ -19,6 +19,10 @@ use futures_util::stream::Stream;
 use futures_util::task::AtomicWaker;
 
 /// 扫描码队列（用于存储输入字符）
+///
+/// # 说明
+/// 固定容量的无锁队列，在任务上下文中分配（`ScancodeStream::new`）；
+/// 中断处理函数只向其中 push，不会分配内存或等待分配器的锁
 static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
 
 /// 唤醒器
@@ -40,6 +44,30 @@ pub(crate) fn add_scancode(scancode: u8) {
     // 如果队列未初始化，静默忽略（在键盘任务启动前可能发生）
 }
 
+/// 从队列中取出已有的字符（不等待）
+///
+/// # 返回
+/// 取出的字节数；队列为空或尚未初始化时为 0
+///
+/// # 说明
+/// 与键盘任务共用同一个队列：两边都在读时，每个字符只会被其中一方取走
+pub(crate) fn read_available(buf: &mut [u8]) -> usize {
+    let Ok(queue) = SCANCODE_QUEUE.try_get() else {
+        return 0;
+    };
+    let mut count = 0;
+    while count < buf.len() {
+        match queue.pop() {
+            Some(scancode) => {
+                buf[count] = scancode;
+                count += 1;
+            }
+            None => break,
+        }
+    }
+    count
+}
+
 /// 扫描码流（实现 Stream trait）
 pub struct ScancodeStream {
     _private: (),
@@ -62,15 +90,21 @@ impl Stream for ScancodeStream {
             .try_get()
             .expect("scancode queue not initialized");
 
+        // 超出轮询预算时先让出，避免连续输入占满执行器
+        if super::budget::budget_exceeded() {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
         // 尝试从队列中读取
         if let Some(scancode) = queue.pop() {
             return Poll::Ready(Some(scancode));
         }
 
-        // 注册唤醒器
+        // 注册唤醒器并再次检查（防止竞争条件）；
+        // 关中断使输入中断不会落在两步之间，避免多余的唤醒
+        let _irq = crate::interrupts::InterruptGuard::new();
         WAKER.register(cx.waker());
-
-        // 再次检查（防止竞争条件）
         match queue.pop() {
             Some(scancode) => {
                 WAKER.take();
@@ -112,11 +146,16 @@ fn sbi_console_getchar() -> Option<u8> {
 /// - 应该在定时器中断中调用
 /// - 限制每次最多读取的字符数，防止阻塞
 pub fn poll_keyboard() {
+    poll_from(sbi_console_getchar);
+}
+
+/// 从输入源读取字符放入队列（不分配内存，可以在中断中调用）
+fn poll_from(mut getchar: impl FnMut() -> Option<u8>) {
     // 限制每次中断最多读取 10 个字符，防止无限循环
     const MAX_READS_PER_POLL: usize = 10;
 
     for _ in 0..MAX_READS_PER_POLL {
-        if let Some(ch) = sbi_console_getchar() {
+        if let Some(ch) = getchar() {
             add_scancode(ch);
         } else {
             // 没有更多字符可读，退出
@@ -132,7 +171,7 @@ pub fn poll_keyboard() {
 pub async fn print_keypresses() {
     use futures_util::stream::StreamExt;
 
-    crate::serial_println!("[KEYBOARD] Keyboard input task started (SBI console)");
+    crate::klog!("[KEYBOARD] Keyboard input task started (SBI console)");
     crate::println!("[KEYBOARD] Press keys to test...");
 
     let mut scancodes = ScancodeStream::new();
@@ -167,3 +206,33 @@ pub async fn print_keypresses() {
 pub fn keyboard_interrupt_handler() {
     poll_keyboard();
 }
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::{in_interrupt_context, process_tick, without_interrupts};
+
+    #[test_case]
+    fn test_intake_with_allocator_locked() {
+        // 在任务上下文中分配队列，并清空之前的输入
+        let _stream = ScancodeStream::new();
+        let queue = SCANCODE_QUEUE.try_get().unwrap();
+        while queue.pop().is_some() {}
+
+        // 被打断的代码持有分配器的锁：时钟中断的处理逻辑若分配内存就会死锁
+        without_interrupts(|| {
+            let _heap = crate::allocator::hold_lock();
+            let mut input = [b'k'].into_iter();
+            in_interrupt_context(|| {
+                poll_from(|| input.next());
+                process_tick();
+            });
+        });
+        assert_eq!(queue.pop(), Some(b'k'));
+        assert_eq!(queue.pop(), None);
+    }
+}
@@ -1,18 +1,34 @@
 use core::{future::Future, pin::Pin};
 use alloc::boxed::Box;
+use crate::objects::Tracked;
 
 pub struct Task {
     id:TaskId,
+    name: &'static str,
     future: Pin<Box<dyn Future<Output = ()>>>,
+    _tracked: Tracked<Task>,
 }
 impl Task {
     pub fn new(future: impl Future<Output = ()> + 'static) -> Task {
+        Self::with_name("task", future)
+    }
+
+    /// 创建带名称的任务（名称用于执行器统计）
+    pub fn with_name(name: &'static str, future: impl Future<Output = ()> + 'static) -> Task {
         Task {
             id:TaskId::new(),
+            name,
             future: Box::pin(future),
+            _tracked: Tracked::new(),
         }
     }
+
+    /// 任务名称
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
 }
+crate::registered_object!(Task);
 use core::task::{Context, Poll};
 
 impl Task {
@@ -21,7 +37,11 @@ impl Task {
     }
 }
 pub mod simple_executor;
+pub mod budget;
+pub mod timer;
+pub use timer::{sleep, timeout, Timeout};
 pub mod keyboard;
+pub mod readline;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct TaskId(u64);
 use core::sync::atomic::{AtomicU64, Ordering};
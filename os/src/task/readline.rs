@@ -0,0 +1,392 @@
+/*
+ * ============================================
+ * 行编辑器（readline）
+ * ============================================
+ * 功能：在串口输入上提供行编辑
+ * 包括：
+ * - 左右方向键移动光标，在光标处插入 / 删除
+ * - 上下方向键浏览历史记录
+ * - 固定大小的行缓冲区和历史记录（不分配堆内存）
+ *
+ * 方向键是多字节 ANSI 转义序列：
+ *   上 ESC [ A   下 ESC [ B   右 ESC [ C   左 ESC [ D
+ *   Delete ESC [ 3 ~
+ * ============================================
+ */
+
+/// 单行最大长度（字节）
+pub const LINE_MAX: usize = 128;
+
+/// 历史记录条数
+pub const HISTORY_DEPTH: usize = 16;
+
+/// 转义序列解析状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscState {
+    /// 普通字符
+    Normal,
+    /// 收到 ESC
+    Escape,
+    /// 收到 ESC [，参数为已读到的数字
+    Csi(u8),
+}
+
+/// `feed` 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEvent {
+    /// 无可见变化（转义序列未结束、缓冲区已满等）
+    None,
+    /// 行内容或光标发生变化，需要重绘
+    Changed,
+    /// 按下回车，`line()` 为提交的内容
+    Submitted,
+}
+
+/// 固定长度的一行
+#[derive(Clone, Copy)]
+struct Line {
+    bytes: [u8; LINE_MAX],
+    len: usize,
+}
+
+impl Line {
+    const fn new() -> Self {
+        Line {
+            bytes: [0; LINE_MAX],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // 只接受可打印 ASCII，一定是合法 UTF-8
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+/// 行编辑器
+pub struct LineEditor {
+    /// 当前编辑的行
+    line: Line,
+    /// 光标位置（0..=line.len）
+    cursor: usize,
+    /// 历史记录（环形缓冲区）
+    history: [Line; HISTORY_DEPTH],
+    /// 下一条历史记录写入的位置
+    history_next: usize,
+    /// 历史记录条数
+    history_len: usize,
+    /// 正在浏览的历史记录（0 = 最近一条）
+    browsing: Option<usize>,
+    /// 开始浏览历史前正在编辑的内容
+    draft: Line,
+    /// 转义序列解析状态
+    esc: EscState,
+    /// 上一行已提交，下一个字符开始新的一行
+    submitted: bool,
+}
+
+impl LineEditor {
+    /// 创建行编辑器
+    pub const fn new() -> Self {
+        LineEditor {
+            line: Line::new(),
+            cursor: 0,
+            history: [Line::new(); HISTORY_DEPTH],
+            history_next: 0,
+            history_len: 0,
+            browsing: None,
+            draft: Line::new(),
+            esc: EscState::Normal,
+            submitted: false,
+        }
+    }
+
+    /// 当前行内容（提交后为提交的内容）
+    pub fn line(&self) -> &str {
+        self.line.as_str()
+    }
+
+    /// 光标位置
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// 第 `index` 条历史记录（0 = 最近一条）
+    pub fn history(&self, index: usize) -> Option<&str> {
+        if index >= self.history_len {
+            return None;
+        }
+        let slot = (self.history_next + HISTORY_DEPTH - 1 - index) % HISTORY_DEPTH;
+        Some(self.history[slot].as_str())
+    }
+
+    /// 处理一个输入字节
+    ///
+    /// # 返回
+    /// 见 `LineEvent`
+    pub fn feed(&mut self, byte: u8) -> LineEvent {
+        if self.submitted {
+            self.submitted = false;
+            self.line = Line::new();
+            self.cursor = 0;
+        }
+
+        match self.esc {
+            EscState::Escape => {
+                self.esc = if byte == b'[' {
+                    EscState::Csi(0)
+                } else {
+                    EscState::Normal
+                };
+                LineEvent::None
+            }
+            EscState::Csi(param) => match byte {
+                b'0'..=b'9' => {
+                    self.esc = EscState::Csi(param.saturating_mul(10).saturating_add(byte - b'0'));
+                    LineEvent::None
+                }
+                // 终止字节
+                0x40..=0x7e => {
+                    self.esc = EscState::Normal;
+                    self.handle_csi(byte, param)
+                }
+                _ => LineEvent::None,
+            },
+            EscState::Normal => self.handle_byte(byte),
+        }
+    }
+
+    /// 处理普通字符
+    fn handle_byte(&mut self, byte: u8) -> LineEvent {
+        match byte {
+            0x1b => {
+                self.esc = EscState::Escape;
+                LineEvent::None
+            }
+            b'\r' | b'\n' => {
+                self.push_history();
+                self.browsing = None;
+                self.submitted = true;
+                LineEvent::Submitted
+            }
+            // Backspace
+            0x08 | 0x7f => {
+                if self.cursor == 0 {
+                    return LineEvent::None;
+                }
+                self.cursor -= 1;
+                self.remove_at_cursor();
+                LineEvent::Changed
+            }
+            0x20..=0x7e => {
+                if self.line.len == LINE_MAX {
+                    return LineEvent::None;
+                }
+                self.line
+                    .bytes
+                    .copy_within(self.cursor..self.line.len, self.cursor + 1);
+                self.line.bytes[self.cursor] = byte;
+                self.line.len += 1;
+                self.cursor += 1;
+                LineEvent::Changed
+            }
+            _ => LineEvent::None,
+        }
+    }
+
+    /// 处理 CSI 转义序列
+    fn handle_csi(&mut self, command: u8, param: u8) -> LineEvent {
+        match command {
+            b'A' => self.recall(true),
+            b'B' => self.recall(false),
+            b'C' if self.cursor < self.line.len => {
+                self.cursor += 1;
+                LineEvent::Changed
+            }
+            b'D' if self.cursor > 0 => {
+                self.cursor -= 1;
+                LineEvent::Changed
+            }
+            // Delete
+            b'~' if param == 3 && self.cursor < self.line.len => {
+                self.remove_at_cursor();
+                LineEvent::Changed
+            }
+            _ => LineEvent::None,
+        }
+    }
+
+    /// 删除光标处的字符
+    fn remove_at_cursor(&mut self) {
+        self.line
+            .bytes
+            .copy_within(self.cursor + 1..self.line.len, self.cursor);
+        self.line.len -= 1;
+    }
+
+    /// 浏览历史记录
+    ///
+    /// # 参数
+    /// - `older`: true 为上一条，false 为下一条
+    fn recall(&mut self, older: bool) -> LineEvent {
+        let target = match (self.browsing, older) {
+            (None, true) if self.history_len > 0 => Some(0),
+            (Some(i), true) if i + 1 < self.history_len => Some(i + 1),
+            (Some(i), false) if i > 0 => Some(i - 1),
+            // 回到开始浏览前的编辑内容
+            (Some(0), false) => None,
+            _ => return LineEvent::None,
+        };
+
+        if self.browsing.is_none() {
+            self.draft = self.line;
+        }
+        self.browsing = target;
+        self.line = match target {
+            Some(index) => {
+                let slot = (self.history_next + HISTORY_DEPTH - 1 - index) % HISTORY_DEPTH;
+                self.history[slot]
+            }
+            None => self.draft,
+        };
+        self.cursor = self.line.len;
+        LineEvent::Changed
+    }
+
+    /// 把当前行加入历史记录（空行和与上一条相同的行不记录）
+    fn push_history(&mut self) {
+        if self.line.len == 0 || self.history(0) == Some(self.line.as_str()) {
+            return;
+        }
+        self.history[self.history_next] = self.line;
+        self.history_next = (self.history_next + 1) % HISTORY_DEPTH;
+        self.history_len = (self.history_len + 1).min(HISTORY_DEPTH);
+    }
+
+    /// 在终端上重绘当前行
+    ///
+    /// # 参数
+    /// - `prompt`: 提示符
+    pub fn redraw(&self, prompt: &str) {
+        // 回到行首并清除整行
+        crate::print!("\r\x1b[K{}{}", prompt, self.line());
+        let back = self.line.len - self.cursor;
+        if back > 0 {
+            crate::print!("\x1b[{}D", back);
+        }
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 异步读取行的任务
+///
+/// # 功能
+/// - 从键盘输入流读取字符并进行行编辑
+/// - 每提交一行调用一次 `on_line`
+pub async fn read_lines<F>(prompt: &str, mut on_line: F)
+where
+    F: FnMut(&str),
+{
+    use super::keyboard::ScancodeStream;
+    use alloc::boxed::Box;
+    use futures_util::stream::StreamExt;
+
+    // 编辑器约 2.5KB，放在堆上避免占用栈
+    let mut editor = Box::new(LineEditor::new());
+    let mut scancodes = ScancodeStream::new();
+
+    editor.redraw(prompt);
+    while let Some(byte) = scancodes.next().await {
+        match editor.feed(byte) {
+            LineEvent::None => {}
+            LineEvent::Changed => editor.redraw(prompt),
+            LineEvent::Submitted => {
+                crate::println!();
+                on_line(editor.line());
+                crate::print!("{}", prompt);
+            }
+        }
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+
+    fn feed_str(editor: &mut LineEditor, input: &[u8]) -> LineEvent {
+        let mut last = LineEvent::None;
+        for &byte in input {
+            last = editor.feed(byte);
+        }
+        last
+    }
+
+    #[test_case]
+    fn test_up_arrow_recalls_previous_line() {
+        let mut editor = Box::new(LineEditor::new());
+
+        assert_eq!(feed_str(&mut editor, b"ls /proc\r"), LineEvent::Submitted);
+        assert_eq!(editor.line(), "ls /proc");
+        assert_eq!(feed_str(&mut editor, b"echo hi\r"), LineEvent::Submitted);
+
+        // 上箭头两次：先 echo hi，再 ls /proc
+        assert_eq!(feed_str(&mut editor, b"\x1b[A"), LineEvent::Changed);
+        assert_eq!(editor.line(), "echo hi");
+        feed_str(&mut editor, b"\x1b[A");
+        assert_eq!(editor.line(), "ls /proc");
+        assert_eq!(editor.cursor(), 8);
+
+        // 下箭头回到最近一条，再回到空的新行
+        feed_str(&mut editor, b"\x1b[B");
+        assert_eq!(editor.line(), "echo hi");
+        feed_str(&mut editor, b"\x1b[B");
+        assert_eq!(editor.line(), "");
+    }
+
+    #[test_case]
+    fn test_cursor_movement_and_insertion() {
+        let mut editor = Box::new(LineEditor::new());
+
+        // "ac"，左移一格插入 "b" → "abc"
+        feed_str(&mut editor, b"ac\x1b[Db");
+        assert_eq!(editor.line(), "abc");
+        assert_eq!(editor.cursor(), 2);
+
+        // 左移两格到行首，Delete 删除 "a"
+        feed_str(&mut editor, b"\x1b[D\x1b[D\x1b[3~");
+        assert_eq!(editor.line(), "bc");
+        // 右移一格，Backspace 删除 "b"
+        feed_str(&mut editor, b"\x1b[C\x7f");
+        assert_eq!(editor.line(), "c");
+    }
+
+    #[test_case]
+    fn test_buffers_are_bounded() {
+        let mut editor = Box::new(LineEditor::new());
+
+        for _ in 0..LINE_MAX + 10 {
+            editor.feed(b'x');
+        }
+        assert_eq!(editor.line().len(), LINE_MAX);
+        editor.feed(b'\r');
+
+        for i in 0..HISTORY_DEPTH + 4 {
+            editor.feed(b'0' + (i % 10) as u8);
+            editor.feed(b'a' + (i / 10) as u8);
+            editor.feed(b'\r');
+        }
+        // 只保留最近 HISTORY_DEPTH 条
+        assert!(editor.history(HISTORY_DEPTH).is_none());
+        assert_eq!(editor.history(0), Some("9b"));
+    }
+}
@@ -0,0 +1,254 @@
+/*
+ * ============================================
+ * 异步定时器
+ * ============================================
+ * 功能：基于时钟中断的睡眠与超时
+ * 包括：
+ * - sleep(duration)：在指定时间后完成的 Future
+ * - timeout(duration, future)：让 future 与 sleep 竞争，
+ *   先完成的一方决定结果，另一方随之被丢弃（取消）
+ *
+ * 实现：
+ * - 等待中的 Sleep 把截止时间和唤醒器登记到 SLEEPERS
+ * - 时钟中断调用 wake_expired() 唤醒到期的任务
+ * - 时钟中断间隔为 100ms，因此睡眠的精度也是 100ms
+ * - 时间来自 clock::now()，测试中可以用 TestClock 推进
+ * ============================================
+ */
+
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use crate::clock::{self, TIME_FREQ};
+use spin::Mutex;
+
+/// 等待中的睡眠
+struct Sleeper {
+    id: u64,
+    deadline: u64,
+    waker: Waker,
+}
+
+/// 所有等待中的睡眠（只在关中断时访问）
+static SLEEPERS: Mutex<Vec<Sleeper>> = Mutex::new(Vec::new());
+
+/// 把时长转换为 time 计数
+fn duration_to_ticks(duration: Duration) -> u64 {
+    let ticks = duration.as_nanos() * TIME_FREQ as u128 / 1_000_000_000;
+    ticks.min(u64::MAX as u128) as u64
+}
+
+/// 唤醒所有已到期的睡眠（由时钟中断调用）
+pub(crate) fn wake_expired() {
+    let now = clock::now();
+    SLEEPERS.lock().retain(|sleeper| {
+        if sleeper.deadline <= now {
+            sleeper.waker.wake_by_ref();
+            false
+        } else {
+            true
+        }
+    });
+}
+
+// ============================================
+// Sleep
+// ============================================
+
+/// 在截止时间后完成的 Future（见 `sleep`）
+pub struct Sleep {
+    /// 截止时间（time 计数）
+    deadline: u64,
+    /// 在 SLEEPERS 中登记的编号
+    id: Option<u64>,
+}
+
+/// 睡眠指定时长
+///
+/// # 示例
+/// ```ignore
+/// task::sleep(Duration::from_millis(500)).await;
+/// ```
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: clock::now().saturating_add(duration_to_ticks(duration)),
+        id: None,
+    }
+}
+
+impl Sleep {
+    /// 从 SLEEPERS 中移除登记
+    fn unregister(&mut self) {
+        if let Some(id) = self.id.take() {
+            crate::interrupts::without_interrupts(|| {
+                SLEEPERS.lock().retain(|sleeper| sleeper.id != id);
+            });
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if clock::now() >= self.deadline {
+            self.unregister();
+            return Poll::Ready(());
+        }
+
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let deadline = self.deadline;
+        let id = *self
+            .id
+            .get_or_insert_with(|| NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+        crate::interrupts::without_interrupts(|| {
+            let mut sleepers = SLEEPERS.lock();
+            match sleepers.iter_mut().find(|sleeper| sleeper.id == id) {
+                Some(sleeper) => {
+                    if !sleeper.waker.will_wake(cx.waker()) {
+                        sleeper.waker = cx.waker().clone();
+                    }
+                }
+                None => sleepers.push(Sleeper {
+                    id,
+                    deadline,
+                    waker: cx.waker().clone(),
+                }),
+            }
+        });
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+// ============================================
+// Timeout
+// ============================================
+
+/// 超时错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout;
+
+/// 带超时的 Future（见 `timeout`）
+pub struct TimeoutFuture<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+/// 在指定时长内等待 future 完成
+///
+/// # 返回
+/// - `Ok(output)`: future 先完成（同一次 poll 中两者都就绪时也算 future 先完成）
+/// - `Err(Timeout)`: 超时，future 被丢弃
+///
+/// # 示例
+/// ```ignore
+/// match task::timeout(Duration::from_secs(1), read_line()).await {
+///     Ok(line) => ...,
+///     Err(Timeout) => println!("timed out"),
+/// }
+/// ```
+pub fn timeout<F: Future>(duration: Duration, future: F) -> TimeoutFuture<F> {
+    TimeoutFuture {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+impl<F: Future> Future for TimeoutFuture<F> {
+    type Output = Result<F::Output, Timeout>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // 安全性：future 字段不会被移出 TimeoutFuture，
+        // sleep 是 Unpin 的，可以普通地借用
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        // 先 poll future，保证两者同时就绪时返回 future 的结果
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        if Pin::new(&mut this.sleep).poll(cx).is_ready() {
+            return Poll::Ready(Err(Timeout));
+        }
+        Poll::Pending
+    }
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{ClockSource, TestClock, TICK_INTERVAL};
+    use crate::task::{executor::Executor, Task};
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    /// 运行执行器直到 `done` 为 true，每次空闲时推进一个时钟中断
+    fn run_until(executor: &mut Executor, clock: &TestClock, done: impl Fn() -> bool) {
+        while !done() {
+            executor.run_until_idle();
+            if !done() {
+                clock.advance(1);
+            }
+        }
+    }
+
+    #[test_case]
+    fn test_timeout_expires_for_slow_future() {
+        static CLOCK: TestClock = TestClock::new();
+        let _clock = clock::install(&CLOCK);
+        let result = Rc::new(Cell::new(None));
+        let mut executor = Executor::new();
+
+        let slot = result.clone();
+        executor.spawn(Task::new(async move {
+            let slow = sleep(Duration::from_secs(2));
+            slot.set(Some(timeout(Duration::from_millis(200), slow).await));
+        }));
+        let start = CLOCK.now();
+        run_until(&mut executor, &CLOCK, || result.get().is_some());
+
+        assert_eq!(result.get(), Some(Err(Timeout)));
+        // 正好在第 2 个时钟中断超时，慢的 future 被丢弃，不再登记在 SLEEPERS 中
+        assert_eq!(CLOCK.now() - start, 2 * TICK_INTERVAL);
+        assert!(crate::interrupts::without_interrupts(|| SLEEPERS.lock().is_empty()));
+    }
+
+    #[test_case]
+    fn test_timeout_returns_value_of_fast_future() {
+        static CLOCK: TestClock = TestClock::new();
+        let _clock = clock::install(&CLOCK);
+        let result = Rc::new(Cell::new(None));
+        let mut executor = Executor::new();
+
+        let slot = result.clone();
+        executor.spawn(Task::new(async move {
+            let fast = async {
+                sleep(Duration::from_millis(100)).await;
+                42
+            };
+            slot.set(Some(timeout(Duration::from_secs(5), fast).await));
+
+            // 同时就绪时以 future 的结果为准
+            let ready = timeout(Duration::ZERO, async { 7 }).await;
+            assert_eq!(ready, Ok(7));
+        }));
+        run_until(&mut executor, &CLOCK, || result.get().is_some());
+
+        assert_eq!(result.get(), Some(Ok(42)));
+        assert_eq!(CLOCK.now(), TICK_INTERVAL);
+    }
+}
@@ -0,0 +1,159 @@
+/*
+ * ============================================
+ * 跳板页（trampoline）
+ * ============================================
+ * 功能：陷阱入口/出口代码，单独放在一页里（链接脚本的 .text.trampoline），
+ *       并映射在每个地址空间的同一个虚拟地址
+ *
+ * - 内核地址空间恒等映射了整个内核镜像，跳板页在物理地址处本来就可以执行；
+ *   另外再映射到 TRAMPOLINE
+ * - 用户地址空间只映射 TRAMPOLINE 这一页（R-X，不带 U 位，用户态不能访问）
+ *
+ * stvec 指向跳板页的物理地址（Bare 模式和内核地址空间中都有效）
+ *
+ * 入口保存全部通用寄存器、sstatus 和 sepc（TrapFrame），调用 trap_handler，
+ * 按 TrapFrame 恢复后 sret；处理函数通过修改 TrapFrame.sepc 决定返回地址，
+ * 因此处理过程中的嵌套陷阱不会弄丢外层的 sepc
+ *
+ * 入口使用陷阱发生时的 sp，也不切换 satp，因此只能处理内核中的陷阱；
+ * 从用户态进入（经 sscratch 换到内核栈、切换到内核地址空间）还没有实现
+ * ============================================
+ */
+
+use crate::memory::{PhysAddr, PhysFrame, PAGE_SIZE};
+use core::arch::global_asm;
+use core::mem::size_of;
+
+/// 跳板页的虚拟地址（所有地址空间相同）
+///
+/// # 说明
+/// 地址空间的倒数第二页：最后一页不用，区域的结束地址 start + size 不会溢出
+pub const TRAMPOLINE: usize = usize::MAX - 2 * PAGE_SIZE + 1;
+
+/// 陷阱入口保存的上下文
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapFrame {
+    /// 通用寄存器 x0 - x31（x0 不保存，x2 是陷阱发生时的 sp）
+    pub regs: [usize; 32],
+    /// 陷阱发生时的 sstatus
+    pub sstatus: usize,
+    /// 返回地址（sret 之前写回 sepc）
+    pub sepc: usize,
+}
+
+const _: () = assert!(size_of::<TrapFrame>() == 34 * 8);
+
+global_asm!(
+    ".altmacro",
+    ".macro SAVE_GP n",
+    "   sd x\\n, \\n*8(sp)",
+    ".endm",
+    ".macro LOAD_GP n",
+    "   ld x\\n, \\n*8(sp)",
+    ".endm",
+    "",
+    ".pushsection .text.trampoline, \"ax\"",
+    ".globl trap_entry",
+    ".align 2",
+    "trap_entry:",
+    "   addi sp, sp, -34*8",
+    "   sd x1, 1*8(sp)",
+    "   .set n, 3",
+    "   .rept 29",
+    "       SAVE_GP %n",
+    "       .set n, n+1",
+    "   .endr",
+    "   addi t0, sp, 34*8",
+    "   sd t0, 2*8(sp)",
+    "   csrr t0, sstatus",
+    "   sd t0, 32*8(sp)",
+    "   csrr t0, sepc",
+    "   sd t0, 33*8(sp)",
+    // 处理函数的绝对地址放在本页中，按 PC 相对寻址读取，
+    // 从物理地址和 TRAMPOLINE 执行时读到的是同一个值
+    "   mv a0, sp",
+    "   ld t0, trap_handler_ptr",
+    "   jalr t0",
+    "   ld t0, 32*8(sp)",
+    "   csrw sstatus, t0",
+    "   ld t0, 33*8(sp)",
+    "   csrw sepc, t0",
+    "   ld x1, 1*8(sp)",
+    "   .set n, 3",
+    "   .rept 29",
+    "       LOAD_GP %n",
+    "       .set n, n+1",
+    "   .endr",
+    "   addi sp, sp, 34*8",
+    "   sret",
+    "",
+    ".align 3",
+    "trap_handler_ptr:",
+    "   .dword trap_handler",
+    ".popsection",
+    ".noaltmacro",
+);
+
+extern "C" {
+    fn trap_entry();
+}
+
+/// 陷阱入口的物理地址（内核运行时写入 stvec）
+pub fn trap_entry_addr() -> usize {
+    trap_entry as *const () as usize
+}
+
+/// 跳板页的物理页帧
+pub fn frame() -> PhysFrame {
+    extern "C" {
+        static trampoline_start: u8;
+    }
+    PhysFrame::from_addr(PhysAddr::new(unsafe { &trampoline_start as *const u8 as usize }))
+}
+
+// ============================================
+// 测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{create_kernel_address_space, test_frame_allocator, VirtAddr};
+    use crate::memory::{AddressSpace, PteFlags};
+
+    #[test_case]
+    fn test_trampoline_shared_across_spaces() {
+        let mut allocator = test_frame_allocator();
+        let used_before = allocator.stats().used_frames;
+        let kernel = create_kernel_address_space(&mut allocator).expect("kernel space failed");
+        let user = AddressSpace::new_user(&mut allocator).expect("Out of memory");
+
+        // 两个地址空间中同一个虚拟地址映射到同一个物理页帧
+        let vaddr = VirtAddr::new(TRAMPOLINE);
+        let paddr = frame().start_address();
+        assert_eq!(kernel.translate(vaddr), Some(paddr));
+        assert_eq!(user.translate(vaddr), Some(paddr));
+        assert_eq!(
+            user.translate(vaddr + 0x10),
+            Some(PhysAddr::new(paddr.as_usize() + 0x10))
+        );
+
+        // R-X，用户地址空间中也不带 U 位
+        for space in [&kernel, &user] {
+            let (_, _, flags) = space.region_bounds(vaddr).expect("trampoline area missing");
+            assert_eq!(flags, PteFlags::READ | PteFlags::EXECUTE);
+        }
+
+        // 入口在跳板页内；内核运行时 stvec 指向它的物理地址
+        let offset = trap_entry_addr().wrapping_sub(paddr.as_usize());
+        assert!(offset < PAGE_SIZE);
+        let stvec = crate::csr::read_stvec().expect("stvec not readable");
+        assert_eq!(stvec, trap_entry_addr());
+
+        // 销毁地址空间只归还页表，跳板页属于内核镜像
+        user.destroy(&mut allocator);
+        kernel.destroy(&mut allocator);
+        assert_eq!(allocator.stats().used_frames, used_before);
+    }
+}
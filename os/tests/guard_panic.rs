@@ -0,0 +1,65 @@
+// InterruptGuard 测试：without_interrupts 的闭包中 panic
+// panic = "abort" 时守卫不会被丢弃，panic 处理函数看到的应当仍是关中断状态，
+// 并且 guard_depth 记录着没有结束的临界区
+
+#![no_std]
+#![no_main]
+
+use core::arch::global_asm;
+use core::panic::PanicInfo;
+use os::interrupts::{guard_depth, without_interrupts};
+use os::{QemuExitCode, exit_qemu, hlt_loop, serial_print, serial_println};
+use riscv::register::sstatus;
+
+// RISC-V 汇编入口点
+global_asm!(
+    ".section .text.entry",
+    ".globl _start",
+    "_start:",
+    "   la sp, stack_end",
+    "   la t0, bss_start",
+    "   la t1, bss_end",
+    "1:",
+    "   bgeu t0, t1, 2f",
+    "   sd zero, (t0)",
+    "   addi t0, t0, 8",
+    "   j 1b",
+    "2:",
+    "   call test_kernel_main",
+    "3:",
+    "   wfi",
+    "   j 3b",
+);
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    let sie = sstatus::read().sie();
+    let depth = guard_depth();
+    if !sie && depth == 1 {
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    } else {
+        serial_println!("[failed]\n");
+        serial_println!("Error: sie={} guard_depth={} after panic in critical section\n",
+            sie, depth);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    hlt_loop();
+}
+
+#[no_mangle]
+pub extern "C" fn test_kernel_main() -> ! {
+    os::init();
+    os::init_late();
+    assert!(sstatus::read().sie(), "interrupts not enabled by init_late");
+
+    serial_print!("guard_panic::panic_in_closure... ");
+    without_interrupts(|| {
+        without_interrupts(|| {});
+        panic!("panic inside a critical section");
+    });
+
+    serial_println!("[no panic]");
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop();
+}
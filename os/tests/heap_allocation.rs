@@ -41,17 +41,13 @@ pub extern "C" fn test_main_entry() -> ! {
 
     os::init();
 
-    // 获取内核结束地址
-    extern "C" {
-        static kernel_end: u8;
-    }
-    let kernel_end_addr = unsafe { &kernel_end as *const u8 as usize };
-
-    // 初始化内存管理
-    let mut memory_manager = memory::init(kernel_end_addr);
+    // 初始化内存管理（init_heap 使用的堆区域不交给帧分配器）
+    let heap = allocator::HEAP_START..allocator::HEAP_START + allocator::HEAP_SIZE;
+    let mut memory_manager = memory::init(&[memory::phys_range(heap)]);
 
     allocator::init_heap(&mut memory_manager.frame_allocator)
         .expect("heap initialization failed");
+    os::init_late();
 
     test_main();
     loop {
@@ -0,0 +1,66 @@
+// 堆初始化之前的分配：应当打印说明并交给内存不足处理函数，而不是访问未初始化的分配器
+// 只在 heap_uninit_test 特性下编译：cargo test --test heap_uninit --features heap_uninit_test
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::arch::global_asm;
+use core::panic::PanicInfo;
+use os::{QemuExitCode, allocator, exit_qemu, hlt_loop, serial_print, serial_println};
+
+// RISC-V 汇编入口点
+global_asm!(
+    ".section .text.entry",
+    ".globl _start",
+    "_start:",
+    "   la sp, stack_end",
+    "   la t0, bss_start",
+    "   la t1, bss_end",
+    "1:",
+    "   bgeu t0, t1, 2f",
+    "   sd zero, (t0)",
+    "   addi t0, t0, 8",
+    "   j 1b",
+    "2:",
+    "   call test_kernel_main",
+    "3:",
+    "   wfi",
+    "   j 3b",
+);
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    serial_println!("[failed]\n");
+    serial_println!("Error: {}\n", info);
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop();
+}
+
+/// 自定义处理函数：说明已经打印（"Allocation before heap init"），检查收到的 Layout
+fn on_oom(layout: Layout) -> ! {
+    if layout == Layout::new::<u64>() && !allocator::heap_ready() {
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    } else {
+        serial_println!("[failed]\n");
+        serial_println!("Error: unexpected layout {:?}\n", layout);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    hlt_loop();
+}
+
+#[no_mangle]
+pub extern "C" fn test_kernel_main() -> ! {
+    // 不调用 init_heap_simple
+    serial_print!("heap_uninit::allocation_before_init... ");
+    allocator::set_oom_handler(on_oom);
+    let value = Box::new(42u64);
+
+    serial_println!("[allocated] {:p}", value);
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop();
+}
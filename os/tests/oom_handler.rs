@@ -0,0 +1,75 @@
+// 内存不足测试：耗尽堆后应当调用 set_oom_handler 设置的处理函数，而不是 panic
+// 只在 oom_test 特性下编译：cargo test --test oom_handler --features oom_test
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::arch::global_asm;
+use core::panic::PanicInfo;
+use os::{QemuExitCode, allocator, exit_qemu, hlt_loop, memory, serial_print, serial_println};
+
+// RISC-V 汇编入口点
+global_asm!(
+    ".section .text.entry",
+    ".globl _start",
+    "_start:",
+    "   la sp, stack_end",
+    "   la t0, bss_start",
+    "   la t1, bss_end",
+    "1:",
+    "   bgeu t0, t1, 2f",
+    "   sd zero, (t0)",
+    "   addi t0, t0, 8",
+    "   j 1b",
+    "2:",
+    "   call test_kernel_main",
+    "3:",
+    "   wfi",
+    "   j 3b",
+);
+
+/// 请求的大小（远大于 1MB 的堆）
+const REQUEST: usize = 16 * 1024 * 1024;
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    serial_println!("[failed]\n");
+    serial_println!("Error: {}\n", info);
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop();
+}
+
+/// 自定义处理函数：检查收到的 Layout，打印堆使用统计后报告成功
+fn on_oom(layout: Layout) -> ! {
+    if layout.size() == REQUEST {
+        allocator::print_heap_stats();
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    } else {
+        serial_println!("[failed]\n");
+        serial_println!("Error: unexpected layout {:?}\n", layout);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    hlt_loop();
+}
+
+#[no_mangle]
+pub extern "C" fn test_kernel_main() -> ! {
+    os::init();
+
+    let kernel_end = memory::kernel_end_addr();
+    allocator::init_heap_simple(kernel_end).expect("heap initialization failed");
+    os::init_late();
+
+    serial_print!("oom_handler::custom_handler... ");
+    allocator::set_oom_handler(on_oom);
+    let buffer: Vec<u8> = Vec::with_capacity(REQUEST);
+
+    serial_println!("[no oom] capacity={}", buffer.capacity());
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop();
+}
@@ -0,0 +1,96 @@
+// 栈溢出测试：无限递归撞上内核栈的保护页，页错误处理应当报告 STACK OVERFLOW
+// 只在 stack_overflow_test 特性下编译：cargo test --test stack_overflow --features stack_overflow_test
+
+#![no_std]
+#![no_main]
+
+use core::arch::global_asm;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use os::{QemuExitCode, allocator, exit_qemu, hlt_loop, memory, serial_print, serial_println};
+
+// RISC-V 汇编入口点
+global_asm!(
+    ".section .text.entry",
+    ".globl _start",
+    "_start:",
+    "   la sp, stack_end",
+    "   la t0, bss_start",
+    "   la t1, bss_end",
+    "1:",
+    "   bgeu t0, t1, 2f",
+    "   sd zero, (t0)",
+    "   addi t0, t0, 8",
+    "   j 1b",
+    "2:",
+    "   call test_kernel_main",
+    "3:",
+    "   wfi",
+    "   j 3b",
+);
+
+/// 期望的 panic 信息开头
+const EXPECTED: &str = "STACK OVERFLOW in kernel stack, sp=";
+
+/// 只保留 panic 信息的开头（panic 时不使用堆）
+struct Prefix {
+    buf: [u8; 64],
+    len: usize,
+}
+
+impl Write for Prefix {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let mut prefix = Prefix { buf: [0; 64], len: 0 };
+    let _ = write!(prefix, "{}", info.message());
+    if prefix.buf[..prefix.len].starts_with(EXPECTED.as_bytes()) {
+        serial_println!("[ok]");
+        exit_qemu(QemuExitCode::Success);
+    } else {
+        serial_println!("[failed]\n");
+        serial_println!("Error: {}\n", info);
+        exit_qemu(QemuExitCode::Failed);
+    }
+    hlt_loop();
+}
+
+#[no_mangle]
+pub extern "C" fn test_kernel_main() -> ! {
+    os::init();
+
+    let kernel_end = memory::kernel_end_addr();
+    allocator::init_heap_simple(kernel_end).expect("heap initialization failed");
+    os::init_late();
+
+    // 启用内核地址空间（内核栈下方留有保护页），并登记给页错误处理函数
+    let heap = memory::phys_range(allocator::simple_heap_range(kernel_end));
+    let mut memory_manager = memory::init(&[heap]);
+    let frames = &mut memory_manager.frame_allocator;
+    let mut space = memory::create_kernel_address_space(frames)
+        .expect("failed to create kernel address space");
+    space.activate();
+    let _active = space.set_active(frames);
+
+    serial_print!("stack_overflow::recurse... ");
+    recurse(0);
+
+    serial_println!("[no overflow]");
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop();
+}
+
+/// 每层占用 1KB 栈的无限递归
+#[allow(unconditional_recursion)]
+#[inline(never)]
+fn recurse(depth: usize) -> usize {
+    let frame = core::hint::black_box([depth as u8; 1024]);
+    recurse(depth + 1) + frame[depth % frame.len()] as usize
+}